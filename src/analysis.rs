@@ -0,0 +1,149 @@
+use crate::{CancelToken, Field, MinesError};
+use crate::solver::{self, Deduction, SolverCache};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// What the solver makes of a position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub deductions: Vec<Deduction>,
+    /// Mine probability of every closed, unflagged cell, see
+    /// `solver::mine_probabilities`.
+    pub probabilities: Vec<((u16, u16), f64)>,
+}
+
+impl Analysis {
+    pub fn of(field: &Field) -> Self {
+        Self::until(field, &mut SolverCache::default(), &CancelToken::new()).expect("nothing cancels it")
+    }
+
+    /// Like `of`, reusing the enumerations in `cache`, but stops with
+    /// `MinesError::Cancelled` once `cancel` is cancelled.
+    pub fn until(field: &Field, cache: &mut SolverCache, cancel: &CancelToken) -> Result<Self, MinesError> {
+        Ok(Self {
+            deductions: solver::deductions(field),
+            probabilities: solver::mine_probabilities_until(field, &mut rand::thread_rng(), cache, cancel)?,
+        })
+    }
+
+    /// The closed cell least likely to hide a mine, with its probability.
+    pub fn safest(&self) -> Option<((u16, u16), f64)> {
+        self.probabilities.iter()
+            .copied()
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+    }
+}
+
+/// Runs the solver on a background thread so a frontend never waits for it.
+/// Every position is sent as an immutable snapshot; sending a new one
+/// cancels the analysis of the previous one.
+pub struct Analyst {
+    requests: Sender<(u64, Arc<Field>, CancelToken)>,
+    results: Receiver<(u64, Analysis)>,
+    latest: u64,
+    cancel: CancelToken,
+}
+
+impl Analyst {
+    pub fn spawn() -> Self {
+        let (requests, incoming) = mpsc::channel::<(u64, Arc<Field>, CancelToken)>();
+        let (outgoing, results) = mpsc::channel();
+        thread::spawn(move || {
+            // a move only changes the groups around it, so most are enumerated already
+            let mut cache = SolverCache::default();
+            while let Ok(mut request) = incoming.recv() {
+                // only the newest position matters
+                while let Ok(newer) = incoming.try_recv() {
+                    request = newer;
+                }
+                let (id, field, cancel) = request;
+                let analysis = match Analysis::until(&field, &mut cache, &cancel) {
+                    Ok(analysis) => analysis,
+                    Err(_) => continue,
+                };
+                if outgoing.send((id, analysis)).is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            requests,
+            results,
+            latest: 0,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    /// Starts analysing `field`, replacing the position analysed before.
+    pub fn analyse(&mut self, field: &Field) {
+        self.cancel();
+        self.cancel = CancelToken::new();
+        self.latest += 1;
+        // the thread only ends when this side is dropped
+        let _ = self.requests.send((self.latest, Arc::new(field.clone()), self.cancel.clone()));
+    }
+
+    /// Stops the pending analysis, for example when the player asks for it.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// The analysis of the latest position once it's done, unless it was
+    /// cancelled.
+    pub fn poll(&mut self) -> Option<Analysis> {
+        if self.cancel.is_cancelled() {
+            return None;
+        }
+        let mut found = None;
+        while let Ok((id, analysis)) = self.results.try_recv() {
+            if id == self.latest {
+                found = Some(analysis);
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Field;
+    use crate::analysis::{Analysis, Analyst};
+    use crate::solver::Deduction;
+    use std::thread;
+    use std::time::Duration;
+
+    fn wait(analyst: &mut Analyst) -> Analysis {
+        for _ in 0..500 {
+            if let Some(analysis) = analyst.poll() {
+                return analysis;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no analysis after 5 seconds");
+    }
+
+    #[test]
+    fn latest_position() {
+        let mut analyst = Analyst::spawn();
+        analyst.analyse(&Field::parse("o..\n.*.\n...").unwrap());
+        analyst.analyse(&Field::parse("o*\noo").unwrap());
+        let analysis = wait(&mut analyst);
+        assert_eq!(vec![Deduction::Mine(0, 1)], analysis.deductions);
+        assert_eq!(Some(((0, 1), 1.0)), analysis.safest());
+        assert_eq!(None, analyst.poll());
+    }
+
+    #[test]
+    fn cancelled() {
+        let mut analyst = Analyst::spawn();
+        analyst.analyse(&Field::parse("o*\noo").unwrap());
+        analyst.cancel();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(None, analyst.poll());
+    }
+}
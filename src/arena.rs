@@ -0,0 +1,291 @@
+//! Many games on boards of one size, held and played side by side for
+//! simulations, training data and the search for boards without guessing.
+//! The mines, opened cells and flags of all games are packed as bits into
+//! one buffer and games are played on a `Field` loaded from them, so a game
+//! brings no board of its own. The buffer and the `Field` are kept when the
+//! arena is cleared for the next batch.
+//!
+//! Every played game draws from its own stream, seeded by the game's seed
+//! the way a game played alone is, so it plays the same in an arena as on
+//! its own: the mines are drawn from it first, then the bot's guesses.
+
+use crate::{Cell, Field, Mark, MinesError};
+use crate::bot::{Bot, GameResult, Step};
+use crate::field::{check_size, draw_mines};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+/// The planes of bits kept for every game, a bit per cell each.
+const MINE: usize = 0;
+const OPENED: usize = 1;
+const FLAGGED: usize = 2;
+const PLANES: usize = 3;
+
+/// Called with the number of a game, its board before a move and the move.
+pub type ArenaWatch<'a> = &'a mut dyn FnMut(usize, &Field, Step);
+
+/// Games of `rows` by `cols` boards with `mines` mines, numbered from 0 in
+/// the order they were added.
+pub struct Arena {
+    rows: u16,
+    cols: u16,
+    mines: u16,
+    /// Words of a plane.
+    words: usize,
+    /// The planes of every game, one game after another.
+    bits: Vec<u64>,
+    games: Vec<Game>,
+    /// The board a game is loaded into to be played, and the one it is
+    /// copied to for a watch before every move.
+    field: Field,
+    before: Field,
+}
+
+struct Game {
+    /// The stream of a played game, `None` for a board drawn with `draw`,
+    /// which is only held.
+    rng: Option<StdRng>,
+    /// Whether the first move was made safe already.
+    started: bool,
+    over: bool,
+    result: GameResult,
+}
+
+impl Arena {
+    /// An empty arena for boards of `rows` rows and `cols` columns with
+    /// `mines` mines, failing for the boards `Field::generate` fails for.
+    pub fn new(rows: u16, cols: u16, mines: u16) -> Result<Self, MinesError> {
+        check_size(rows as usize, cols as usize, mines, 1)?;
+        let blank = Field::with_cells(vec![vec![Cell::water(); cols as usize]; rows as usize]);
+        Ok(Self {
+            rows,
+            cols,
+            mines,
+            words: (rows as usize * cols as usize).div_ceil(64),
+            bits: vec![],
+            games: vec![],
+            field: blank.clone(),
+            before: blank,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    /// Removes all games, keeping the memory for the next ones.
+    pub fn clear(&mut self) {
+        self.bits.clear();
+        self.games.clear();
+    }
+
+    /// Adds a game on the board `Field::generate` makes from a `StdRng`
+    /// seeded with `seed`, played on with the same stream. Returns its
+    /// number.
+    pub fn add(&mut self, seed: u64) -> usize {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let game = self.draw(&mut rng);
+        self.games[game].rng = Some(rng);
+        self.games[game].over = false;
+        game
+    }
+
+    /// Adds a board with mines drawn from `rng` like `Field::generate`
+    /// would. It is only held, to be looked at with `load` or `field`, and
+    /// isn't played. Returns its number.
+    pub fn draw<R: Rng>(&mut self, rng: &mut R) -> usize {
+        let start = self.bits.len();
+        self.bits.resize(start + PLANES * self.words, 0);
+        let (cols, mines) = (self.cols as usize, &mut self.bits[start + MINE * self.words..]);
+        draw_mines(rng, self.rows, self.cols, self.mines, |x, y| {
+            let (word, bit) = ((x * cols + y) / 64, (x * cols + y) % 64);
+            let free = mines[word] & 1 << bit == 0;
+            mines[word] |= 1 << bit;
+            free
+        });
+        self.games.push(Game { rng: None, started: false, over: true, result: GameResult::default() });
+        self.games.len() - 1
+    }
+
+    /// The planes of `game`.
+    fn planes(&self, game: usize) -> &[u64] {
+        &self.bits[game * PLANES * self.words..(game + 1) * PLANES * self.words]
+    }
+
+    /// Loads the board of `game` into `field`, a board of the arena's size
+    /// like one from `field`, without allocating.
+    pub fn load(&self, game: usize, field: &mut Field) {
+        unpack(self.planes(game), self.words, self.cols as usize, field);
+    }
+
+    /// The board of `game` as a `Field` of its own.
+    pub fn field(&self, game: usize) -> Field {
+        let mut field = self.field.clone();
+        self.load(game, &mut field);
+        field
+    }
+
+    /// The result of `game`, `None` while it is played or for a board that
+    /// is only held.
+    pub fn result(&self, game: usize) -> Option<GameResult> {
+        let game = &self.games[game];
+        (game.rng.is_some() && game.over).then_some(game.result)
+    }
+
+    /// The results of all games, played to the end with `play_all`.
+    pub fn results(&self) -> impl Iterator<Item = GameResult> + '_ {
+        self.games.iter().filter(|game| game.rng.is_some()).map(|game| game.result)
+    }
+
+    /// Lets `bot` make a move in every game that isn't over, calling
+    /// `watch` with the board before the move. The first move of a game is
+    /// made safe like with `Bot::play`. Returns the number of games still
+    /// played.
+    pub fn step_all(&mut self, bot: &Bot, mut watch: Option<ArenaWatch>) -> usize {
+        let Arena { cols, words, bits, games, field, before, .. } = self;
+        let (cols, words) = (*cols as usize, *words);
+        let mut playing = 0;
+        for (number, game) in games.iter_mut().enumerate() {
+            let rng = match game.rng.as_mut() {
+                Some(rng) if !game.over => rng,
+                _ => continue,
+            };
+            let planes = &mut bits[number * PLANES * words..(number + 1) * PLANES * words];
+            unpack(planes, words, cols, field);
+            let mut opening = None;
+            if !game.started {
+                game.started = true;
+                opening = bot.safe_opening(field, rng);
+            }
+            if watch.is_some() {
+                pack(field, planes);
+                unpack(planes, words, cols, before);
+            }
+            game.result.moves += 1;
+            let step = bot.step_guessing(field, rng, opening);
+            if let Some(watch) = watch.as_mut() {
+                watch(number, before, step);
+            }
+            match step {
+                Step::Won => {
+                    game.result.won = true;
+                    game.over = true;
+                },
+                Step::Lost(_, _) => {
+                    game.result.guesses += 1;
+                    game.over = true;
+                },
+                Step::Guessed(_, _) => game.result.guesses += 1,
+                _ => {},
+            }
+            pack(field, planes);
+            playing += !game.over as usize;
+        }
+        playing
+    }
+
+    /// Lets `bot` play all games to the end, a move in every game at a time.
+    pub fn play_all(&mut self, bot: &Bot, mut watch: Option<ArenaWatch>) {
+        loop {
+            let playing = match watch.as_mut() {
+                Some(watch) => self.step_all(bot, Some(&mut **watch)),
+                None => self.step_all(bot, None),
+            };
+            if playing == 0 {
+                break;
+            }
+        }
+    }
+}
+
+/// Puts the cells of the `planes` of a game on `field`.
+fn unpack(planes: &[u64], words: usize, cols: usize, field: &mut Field) {
+    let bit = |plane: usize, x: usize, y: usize| planes[plane * words + (x * cols + y) / 64] & 1 << ((x * cols + y) % 64) != 0;
+    field.refill(|x, y| Cell {
+        opened: bit(OPENED, x, y),
+        mark: if bit(FLAGGED, x, y) { Mark::Flag } else { Mark::None },
+        flags: bit(FLAGGED, x, y) as u8,
+        ..if bit(MINE, x, y) { Cell::mine() } else { Cell::water() }
+    });
+}
+
+/// Writes the cells of `field` into the `planes` of a game.
+fn pack(field: &Field, planes: &mut [u64]) {
+    planes.fill(0);
+    let words = planes.len() / PLANES;
+    for (i, cell) in field.cells.iter().flatten().enumerate() {
+        for (plane, set) in [(MINE, cell.is_mine()), (OPENED, cell.opened), (FLAGGED, cell.is_flagged())] {
+            planes[plane * words + i / 64] |= (set as u64) << (i % 64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Field, MinesError};
+    use crate::arena::Arena;
+    use crate::bot::{strategy, Bot, Opening, Step};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn boards() {
+        let mut arena = Arena::new(9, 30, 99).unwrap();
+        assert!(arena.is_empty());
+        for seed in 0..3 {
+            let game = arena.add(seed);
+            let field = Field::generate(&mut StdRng::seed_from_u64(seed), 9, 30, 99).unwrap();
+            assert_eq!(field.to_board_file(), arena.field(game).to_board_file());
+            assert_eq!(None, arena.result(game));
+        }
+        let mut rng = StdRng::seed_from_u64(5);
+        let game = arena.draw(&mut rng);
+        let mut field = Field::generate(&mut StdRng::seed_from_u64(5), 9, 30, 99).unwrap();
+        let drawn = field.to_board_file();
+        arena.load(game, &mut field);
+        assert_eq!(drawn, field.to_board_file());
+        assert_eq!(4, arena.len());
+        arena.clear();
+        assert!(arena.is_empty());
+
+        assert!(matches!(Arena::new(0, 9, 1), Err(MinesError::EmptyField)));
+        assert!(matches!(Arena::new(2, 2, 4), Err(MinesError::TooManyMines)));
+    }
+
+    #[test]
+    fn plays_like_alone() {
+        let bot = Bot::new(strategy("greedy").unwrap());
+        let mut arena = Arena::new(16, 16, 40).unwrap();
+        for seed in 0..20 {
+            arena.add(seed);
+        }
+        let mut moves = [0; 20];
+        arena.play_all(&bot, Some(&mut |game, _: &Field, _| moves[game] += 1));
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut field = Field::generate(&mut rng, 16, 16, 40).unwrap();
+            let alone = bot.play(&mut field, &mut rng, true);
+            assert_eq!(Some(alone), arena.result(seed as usize));
+            assert_eq!(alone.moves, moves[seed as usize]);
+        }
+        assert_eq!(20, arena.results().count());
+    }
+
+    #[test]
+    fn first_move_is_safe() {
+        let bot = Bot::new(strategy("random").unwrap()).with_opening(Opening::Random);
+        let mut arena = Arena::new(9, 9, 60).unwrap();
+        for seed in 0..50 {
+            arena.add(seed);
+        }
+        let mut first = vec![None; 50];
+        arena.play_all(&bot, Some(&mut |game, _: &Field, step| { first[game].get_or_insert(step); }));
+        for step in first {
+            assert!(matches!(step, Some(Step::Guessed(_, _) | Step::Won)), "{:?}", step);
+        }
+    }
+}
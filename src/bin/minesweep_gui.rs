@@ -0,0 +1,190 @@
+//! A window to play in with the mouse, on the same engine as the terminal:
+//! left click opens a cell, right click flags it and a click on an opened
+//! number, or a middle click, chords.
+
+extern crate eframe;
+extern crate minesweep_rs;
+extern crate rand;
+
+use eframe::egui;
+use minesweep_rs::Difficulty;
+use minesweep_rs::game::{Game, State};
+use minesweep_rs::timer::{format_time, Timer};
+use std::time::{Duration, Instant};
+
+/// Side of a cell in points.
+const CELL_SIZE: f32 = 24.0;
+
+const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Expert];
+
+struct App {
+    game: Game,
+    timer: Timer,
+    /// Width, height and mines of a custom board, as set in the menu.
+    custom: (u16, u16, u16),
+    error: Option<String>,
+}
+
+impl App {
+    fn new(difficulty: Difficulty) -> Result<Self, String> {
+        let game = Game::new(difficulty, rand::random()).map_err(|e| e.to_string())?;
+        Ok(Self { game, timer: Timer::new(), custom: difficulty.dimensions(), error: None })
+    }
+
+    /// Starts over on a new board of `difficulty`, keeping the current game
+    /// if the board can't be made.
+    fn restart(&mut self, difficulty: Difficulty) {
+        match Game::new(difficulty, rand::random()) {
+            Ok(game) => {
+                self.game = game;
+                self.timer = Timer::new();
+                self.error = None;
+            },
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    fn play(&mut self, action: &str, row: u16, col: u16) {
+        if self.game.state != State::Playing {
+            return;
+        }
+        let now = Instant::now();
+        if action != "flag" {
+            self.timer.start(now);
+        }
+        match self.game.play(action, row, col) {
+            Ok(State::Playing) => {},
+            Ok(_) => self.timer.stop(now),
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    fn menu(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let current = self.game.difficulty;
+            for difficulty in DIFFICULTIES.iter() {
+                if ui.selectable_label(current == *difficulty, difficulty.to_string()).clicked() {
+                    self.restart(*difficulty);
+                }
+            }
+            ui.separator();
+            let (width, height, mines) = &mut self.custom;
+            ui.add(egui::DragValue::new(width).range(1..=100).prefix("w "));
+            ui.add(egui::DragValue::new(height).range(1..=100).prefix("h "));
+            ui.add(egui::DragValue::new(mines).range(0..=9999).prefix("mines "));
+            let custom = Difficulty::Custom(self.custom.0, self.custom.1, self.custom.2);
+            if ui.selectable_label(current == custom, "custom").clicked() {
+                self.restart(custom);
+            }
+        });
+    }
+
+    fn status(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let field = &self.game.field;
+            let left = field.mine_count() as i64 - field.flag_count() as i64;
+            ui.monospace(format!("mines {:>4}", left));
+            ui.separator();
+            ui.monospace(format_time(self.timer.elapsed(Instant::now())));
+            ui.separator();
+            let face = match self.game.state {
+                State::Playing => ":)",
+                State::Lost => "X(",
+                State::Won => "B)",
+            };
+            if ui.button(face).on_hover_text("new game").clicked() {
+                self.restart(self.game.difficulty);
+            }
+            if let Some(error) = &self.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+    }
+
+    fn board(&mut self, ui: &mut egui::Ui) {
+        let (cols, rows) = self.game.field.size();
+        let mut action = None;
+        egui::Grid::new("board").spacing([1.0, 1.0]).show(ui, |ui| {
+            for row in 0..rows {
+                for col in 0..cols {
+                    let (text, color, opened) = self.label(row, col);
+                    let button = egui::Button::new(egui::RichText::new(text).color(color).strong())
+                        .min_size(egui::vec2(CELL_SIZE, CELL_SIZE))
+                        .fill(if opened { ui.visuals().extreme_bg_color } else { ui.visuals().widgets.inactive.bg_fill });
+                    let response = ui.add(button);
+                    if response.clicked() {
+                        action = Some((if opened { "chord" } else { "open" }, row, col));
+                    } else if response.secondary_clicked() {
+                        action = Some(("flag", row, col));
+                    } else if response.middle_clicked() {
+                        action = Some(("chord", row, col));
+                    }
+                }
+                ui.end_row();
+            }
+        });
+        if let Some((action, row, col)) = action {
+            self.play(action, row, col);
+        }
+    }
+
+    /// Text and colour of the cell at `row` and `col`, and whether it is
+    /// opened, showing what the terminal shows for it.
+    fn label(&self, row: u16, col: u16) -> (String, egui::Color32, bool) {
+        let cell = match self.game.field.cell(row, col) {
+            Ok(cell) => cell,
+            Err(_) => return (String::new(), egui::Color32::TRANSPARENT, false),
+        };
+        let lost = self.game.state == State::Lost;
+        if cell.is_hole() {
+            (String::new(), egui::Color32::TRANSPARENT, true)
+        } else if cell.is_mine() && (cell.is_opened() || lost) {
+            let color = if cell.is_opened() { egui::Color32::RED } else { egui::Color32::DARK_GRAY };
+            ("*".to_string(), color, cell.is_opened())
+        } else if cell.is_flagged() {
+            let color = if lost { egui::Color32::DARK_RED } else { egui::Color32::RED };
+            ("F".to_string(), color, false)
+        } else if cell.is_opened() {
+            match self.game.field.number(row, col).unwrap_or(0) {
+                0 => (String::new(), egui::Color32::TRANSPARENT, true),
+                number => (number.to_string(), number_color(number), true),
+            }
+        } else {
+            (String::new(), egui::Color32::TRANSPARENT, false)
+        }
+    }
+}
+
+fn number_color(number: u8) -> egui::Color32 {
+    match number {
+        1 => egui::Color32::from_rgb(60, 120, 255),
+        2 => egui::Color32::from_rgb(40, 160, 40),
+        3 => egui::Color32::from_rgb(220, 50, 50),
+        4 => egui::Color32::from_rgb(120, 60, 200),
+        5 => egui::Color32::from_rgb(160, 60, 40),
+        6 => egui::Color32::from_rgb(40, 160, 160),
+        _ => egui::Color32::GRAY,
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::TopBottomPanel::top("menu").show(ctx, |ui| {
+            self.menu(ui);
+            self.status(ui);
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::both().show(ui, |ui| self.board(ui));
+        });
+        if self.timer.is_running() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+}
+
+fn main() -> Result<(), String> {
+    let difficulty = Difficulty::from_args(&std::env::args().skip(1).collect::<Vec<_>>())?;
+    let app = App::new(difficulty)?;
+    let options = eframe::NativeOptions::default();
+    eframe::run_native("minesweep", options, Box::new(|_| Ok(Box::new(app)))).map_err(|e| e.to_string())
+}
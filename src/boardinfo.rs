@@ -0,0 +1,123 @@
+//! What a board is like to play, worked out from where its mines are: how
+//! they are spread over the board, its openings and its 3BV. It helps to
+//! tell whether a board was fair and to pick boards for puzzles.
+
+use crate::Field;
+
+/// The rows and columns of regions the board is split into for the mine
+/// density, fewer on boards with fewer rows or columns.
+pub const REGIONS: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoardInfo {
+    /// Cells of the board, holes left out.
+    pub cells: usize,
+    pub mines: usize,
+    pub three_bv: u32,
+    /// The cells opened by a click on every opening, the biggest first.
+    /// Numbers at the edge of two openings count for both.
+    pub openings: Vec<usize>,
+    /// The share of cells holding a mine in every region, rows of regions
+    /// from the top and regions from the left. `None` for a region that is
+    /// all holes.
+    pub density: Vec<Vec<Option<f64>>>,
+}
+
+impl BoardInfo {
+    pub fn of(field: &Field) -> Self {
+        let (cols, rows) = field.size();
+        let (region_rows, region_cols) = (REGIONS.min(rows as usize), REGIONS.min(cols as usize));
+        let mut regions = vec![vec![(0, 0); region_cols]; region_rows];
+        for (x, row) in field.cells.iter().enumerate() {
+            for (y, cell) in row.iter().enumerate() {
+                if cell.is_hole() {
+                    continue;
+                }
+                let region = &mut regions[x * region_rows / rows as usize][y * region_cols / cols as usize];
+                region.0 += cell.mine_count() as usize;
+                region.1 += 1;
+            }
+        }
+        let density = regions.iter()
+            .map(|row| row.iter().map(|&(mines, cells)| (cells > 0).then(|| mines as f64 / cells as f64)).collect())
+            .collect();
+        let mut openings = openings(field);
+        openings.sort_unstable_by(|a, b| b.cmp(a));
+        Self {
+            cells: field.cell_count(),
+            mines: field.mine_count(),
+            three_bv: field.three_bv(),
+            openings,
+            density,
+        }
+    }
+
+    /// The cells opened by a click on the biggest opening, 0 without any.
+    pub fn largest_opening(&self) -> usize {
+        self.openings.first().copied().unwrap_or(0)
+    }
+}
+
+/// The cells every opening of `field` opens: its zeros and the numbers
+/// around them.
+fn openings(field: &Field) -> Vec<usize> {
+    let safe = |x: usize, y: usize| !field.cells[x][y].is_mine() && !field.cells[x][y].is_hole();
+    let mut seen: Vec<Vec<bool>> = field.cells.iter().map(|row| vec![false; row.len()]).collect();
+    let mut sizes = vec![];
+    for (x, row) in field.numbers.iter().enumerate() {
+        for (y, &number) in row.iter().enumerate() {
+            if number != 0 || !safe(x, y) || seen[x][y] {
+                continue;
+            }
+            seen[x][y] = true;
+            let mut opened = vec![(x, y)];
+            let mut zeros = vec![(x as u16, y as u16)];
+            while let Some((zx, zy)) = zeros.pop() {
+                for (nx, ny) in field.neighbours(zx, zy) {
+                    let (nx, ny) = (nx as usize, ny as usize);
+                    if !safe(nx, ny) || opened.contains(&(nx, ny)) {
+                        continue;
+                    }
+                    opened.push((nx, ny));
+                    if field.numbers[nx][ny] == 0 {
+                        seen[nx][ny] = true;
+                        zeros.push((nx as u16, ny as u16));
+                    }
+                }
+            }
+            sizes.push(opened.len());
+        }
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Field;
+    use crate::boardinfo::BoardInfo;
+
+    #[test]
+    fn openings() {
+        // two openings, the 1 below the mine opened by neither
+        let info = BoardInfo::of(&Field::parse("..*..\n.....\n").unwrap());
+        assert_eq!((10, 1, 3), (info.cells, info.mines, info.three_bv));
+        assert_eq!(vec![4, 4], info.openings);
+        assert_eq!(4, info.largest_opening());
+
+        let info = BoardInfo::of(&Field::parse("*.*\n.*.\n*.*\n").unwrap());
+        assert!(info.openings.is_empty());
+        assert_eq!(0, info.largest_opening());
+    }
+
+    #[test]
+    fn density() {
+        let field = Field::parse("**....\n......\n-....*\n").unwrap();
+        let info = BoardInfo::of(&field);
+        assert_eq!(vec![
+            vec![Some(1.0), Some(0.0), Some(0.0)],
+            vec![Some(0.0), Some(0.0), Some(0.0)],
+            vec![Some(0.0), Some(0.0), Some(0.5)],
+        ], info.density);
+        assert_eq!(vec![vec![None, Some(1.0), Some(0.0)]], BoardInfo::of(&Field::parse("-*.").unwrap()).density);
+    }
+}
@@ -0,0 +1,572 @@
+use crate::{Difficulty, Field, MinesError};
+use crate::arena::Arena;
+use crate::solver::{constraint_deductions, deductions, mine_estimates, mine_probabilities_cached, Deduction, SolverCache};
+use rand::{Rng, RngCore};
+use std::cell::RefCell;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Decides which closed cell a bot opens when nothing follows from the board.
+/// Implement this to plug a new strategy into bots and comparisons.
+pub trait Strategy {
+    fn name(&self) -> &str;
+
+    /// Picks the cell to open, or `None` if no closed cell is left.
+    fn guess(&self, field: &Field, rng: &mut dyn RngCore) -> Option<(u16, u16)>;
+
+    /// Tells why the strategy can't play boards of `rows` by `cols`, if it
+    /// can't. Most play any board.
+    fn check(&self, _rows: u16, _cols: u16) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Names accepted by `strategy`.
+pub const STRATEGIES: [&str; 5] = ["first", "random", "basic", "greedy", "info"];
+
+/// Looks up one of the built-in strategies by name, or loads a learned one
+/// from an ONNX model with `onnx:<file>`.
+pub fn strategy(name: &str) -> Result<Box<dyn Strategy>, String> {
+    match name {
+        #[cfg(feature = "onnx")]
+        _ if name.starts_with("onnx:") => {
+            let path = &name["onnx:".len()..];
+            let bytes = std::fs::read(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+            let model = crate::onnx::Model::parse(&bytes).map_err(|e| format!("{}: {}", path, e))?;
+            Ok(Box::new(crate::onnx::LearnedPolicy::new(name.to_string(), model)))
+        },
+        #[cfg(not(feature = "onnx"))]
+        _ if name.starts_with("onnx:") => Err("onnx strategies need a build with the onnx feature, cargo build --features onnx".into()),
+        "first" => Ok(Box::new(FirstCell)),
+        "random" => Ok(Box::new(RandomCell)),
+        "basic" => Ok(Box::new(EstimateGreedy)),
+        "greedy" => Ok(Box::new(ProbabilityGreedy::default())),
+        "info" => Ok(Box::new(InformationGain::default())),
+        _ => Err(format!("Unknown strategy '{}', expected one of {}", name, STRATEGIES.join(", "))),
+    }
+}
+
+/// Opens the first closed cell in reading order.
+pub struct FirstCell;
+
+impl Strategy for FirstCell {
+    fn name(&self) -> &str {
+        "first"
+    }
+
+    fn guess(&self, field: &Field, _rng: &mut dyn RngCore) -> Option<(u16, u16)> {
+        mine_estimates(field).first().map(|(cell, _)| *cell)
+    }
+}
+
+/// Opens a random closed cell.
+pub struct RandomCell;
+
+impl Strategy for RandomCell {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn guess(&self, field: &Field, rng: &mut dyn RngCore) -> Option<(u16, u16)> {
+        let estimates = mine_estimates(field);
+        if estimates.is_empty() {
+            return None;
+        }
+        Some(estimates[rng.gen_range(0, estimates.len())].0)
+    }
+}
+
+/// Opens the cell least likely to hide a mine, going by the quick estimate
+/// of `mine_estimates` from the numbers around every cell on its own.
+pub struct EstimateGreedy;
+
+impl Strategy for EstimateGreedy {
+    fn name(&self) -> &str {
+        "basic"
+    }
+
+    fn guess(&self, field: &Field, _rng: &mut dyn RngCore) -> Option<(u16, u16)> {
+        lowest(mine_estimates(field))
+    }
+}
+
+/// Opens the cell least likely to hide a mine, going by the probabilities
+/// of `mine_probabilities_cached`.
+#[derive(Default)]
+pub struct ProbabilityGreedy {
+    cache: RefCell<SolverCache>,
+}
+
+impl Strategy for ProbabilityGreedy {
+    fn name(&self) -> &str {
+        "greedy"
+    }
+
+    fn guess(&self, field: &Field, mut rng: &mut dyn RngCore) -> Option<(u16, u16)> {
+        lowest(mine_probabilities_cached(field, &mut rng, &mut self.cache.borrow_mut()))
+    }
+}
+
+fn lowest(probabilities: Vec<((u16, u16), f64)>) -> Option<(u16, u16)> {
+    probabilities.into_iter()
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(cell, _)| cell)
+}
+
+/// Opens the cell most likely to be safe and to open up the board, estimated
+/// as the chance that it and all its closed neighbours are free of mines.
+#[derive(Default)]
+pub struct InformationGain {
+    cache: RefCell<SolverCache>,
+}
+
+impl Strategy for InformationGain {
+    fn name(&self) -> &str {
+        "info"
+    }
+
+    fn guess(&self, field: &Field, mut rng: &mut dyn RngCore) -> Option<(u16, u16)> {
+        let estimates = mine_probabilities_cached(field, &mut rng, &mut self.cache.borrow_mut());
+        let estimate_of = |cell: (u16, u16)| estimates.iter().find(|(c, _)| *c == cell).map(|(_, p)| *p);
+        estimates.iter()
+            .map(|&((x, y), p)| {
+                let score = field.neighbours(x, y).into_iter()
+                    .filter_map(estimate_of)
+                    .fold(1.0 - p, |score, n| score * (1.0 - n));
+                ((x, y), score)
+            })
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(cell, _)| cell)
+    }
+}
+
+/// Where a bot opens on an untouched board.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Opening {
+    /// The top left cell.
+    Corner,
+    /// The middle of the board.
+    Center,
+    /// A random cell.
+    Random,
+    /// The best cell of a table of scores, stretched over the board.
+    Table(Vec<Vec<f64>>),
+}
+
+impl Opening {
+    /// Reads a table from rows of whitespace separated scores.
+    pub fn parse_table(text: &str) -> Result<Self, String> {
+        let table: Vec<Vec<f64>> = text.lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|line| line.split_whitespace()
+                .map(|score| score.parse().map_err(|_| format!("Invalid score '{}'", score)))
+                .collect())
+            .collect::<Result<_, _>>()?;
+        if table.is_empty() || table.iter().any(|row| row.len() != table[0].len() || row.is_empty()) {
+            return Err("Opening table needs equally long, non-empty rows".into());
+        }
+        Ok(Opening::Table(table))
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Opening::Corner => "corner",
+            Opening::Center => "center",
+            Opening::Random => "random",
+            Opening::Table(_) => "table",
+        }
+    }
+
+    /// The cell to open first on `field`.
+    pub fn cell(&self, field: &Field, rng: &mut dyn RngCore) -> (u16, u16) {
+        let (cols, rows) = field.size();
+        match self {
+            Opening::Corner => (0, 0),
+            Opening::Center => (rows / 2, cols / 2),
+            Opening::Random => (rng.gen_range(0, rows), rng.gen_range(0, cols)),
+            Opening::Table(table) => {
+                let mut best = ((0, 0), f64::MIN);
+                for x in 0..rows {
+                    for y in 0..cols {
+                        let tx = x as usize * table.len() / rows as usize;
+                        let ty = y as usize * table[0].len() / cols as usize;
+                        if table[tx][ty] > best.1 {
+                            best = ((x, y), table[tx][ty]);
+                        }
+                    }
+                }
+                best.0
+            },
+        }
+    }
+}
+
+impl FromStr for Opening {
+    type Err = String;
+
+    /// Parses `corner`, `center`, `random` or `table:<file>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "corner" => Ok(Opening::Corner),
+            "center" => Ok(Opening::Center),
+            "random" => Ok(Opening::Random),
+            _ if s.starts_with("table:") => {
+                let path = &s["table:".len()..];
+                let text = std::fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+                Opening::parse_table(&text)
+            },
+            _ => Err(format!("Unknown opening '{}'", s)),
+        }
+    }
+}
+
+/// Outcome of a single bot move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    Flagged(u16, u16),
+    Opened(u16, u16),
+    Guessed(u16, u16),
+    Lost(u16, u16),
+    Won,
+}
+
+/// Called with the board before every step of a watched game.
+pub type Watch<'a> = &'a mut dyn FnMut(&Field, Step);
+
+/// Called with the seed of a game of a simulation, the board before every
+/// step and the step.
+pub type SeedWatch<'a> = &'a mut dyn FnMut(u64, &Field, Step);
+
+/// Result of a game a bot played to the end.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GameResult {
+    pub won: bool,
+    pub moves: u32,
+    pub guesses: u32,
+}
+
+/// A bot that plays every move following from `deductions` or, failing
+/// those, from `constraint_deductions`, and guesses according to its
+/// strategy when none does.
+pub struct Bot {
+    pub strategy: Box<dyn Strategy>,
+    /// First move on an untouched board, left to the strategy if `None`.
+    pub opening: Option<Opening>,
+}
+
+impl Bot {
+    pub fn new(strategy: Box<dyn Strategy>) -> Self {
+        Self { strategy, opening: None }
+    }
+
+    pub fn with_opening(mut self, opening: Opening) -> Self {
+        self.opening = Some(opening);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.strategy.name()
+    }
+
+    pub(crate) fn guess(&self, field: &Field, rng: &mut dyn RngCore) -> Option<(u16, u16)> {
+        let opening = match self.opening.as_ref() {
+            Some(opening) if field.opened_count() == 0 => Some(opening.cell(field, rng)),
+            _ => None,
+        };
+        // the opening of a shaped board may fall into a hole
+        match opening {
+            Some((x, y)) if field.cell(x, y).is_ok_and(|cell| !cell.is_hole()) => opening,
+            _ => self.strategy.guess(field, rng),
+        }
+    }
+
+    /// Picks the cell the bot opens first on `field` and moves a mine below
+    /// it away, like for human players. Pass it to `step_guessing` so that
+    /// this very cell is opened.
+    pub fn safe_opening<R: Rng>(&self, field: &mut Field, rng: &mut R) -> Option<(u16, u16)> {
+        let (x, y) = self.guess(field, rng)?;
+        let _ = field.ensure_safe(x, y, rng);
+        Some((x, y))
+    }
+
+    /// Plays one move on `field`.
+    pub fn step(&self, field: &mut Field, rng: &mut dyn RngCore) -> Step {
+        self.step_guessing(field, rng, None)
+    }
+
+    /// Like `step`, opening `guess` instead of asking the strategy when
+    /// nothing follows from the board.
+    pub fn step_guessing(&self, field: &mut Field, rng: &mut dyn RngCore, guess: Option<(u16, u16)>) -> Step {
+        if field.is_won() {
+            return Step::Won;
+        }
+        let mut found = deductions(field);
+        if found.is_empty() {
+            found = constraint_deductions(field);
+        }
+        let step = if let Some(Deduction::Mine(x, y)) = found.iter().find(|d| matches!(d, Deduction::Mine(_, _))) {
+            let _ = field.flag(*x, *y);
+            Step::Flagged(*x, *y)
+        } else if let Some(Deduction::Safe(x, y)) = found.first() {
+            Step::Opened(*x, *y)
+        } else {
+            match guess.or_else(|| self.guess(field, rng)) {
+                Some((x, y)) => Step::Guessed(x, y),
+                None => return Step::Won,
+            }
+        };
+        match step {
+            Step::Opened(x, y) | Step::Guessed(x, y) => match field.open(x, y) {
+                Err(MinesError::MineOpened) => Step::Lost(x, y),
+                _ if field.is_won() => Step::Won,
+                _ => step,
+            },
+            _ => step,
+        }
+    }
+
+    /// Plays `field` to the end. With `safe_start` a mine below the first
+    /// opened cell is moved away, like for human players.
+    pub fn play<R: Rng>(&self, field: &mut Field, rng: &mut R, safe_start: bool) -> GameResult {
+        self.play_watched(field, rng, safe_start, None)
+    }
+
+    /// Like `play`, calling `watch` with the board as it was before every
+    /// move and the move made on it.
+    pub fn play_watched<R: Rng>(&self, field: &mut Field, rng: &mut R, safe_start: bool, mut watch: Option<Watch>) -> GameResult {
+        let mut result = GameResult::default();
+        let mut opening = if safe_start { self.safe_opening(field, rng) } else { None };
+        loop {
+            result.moves += 1;
+            let before = watch.is_some().then(|| field.clone());
+            let step = self.step_guessing(field, rng, opening.take());
+            if let (Some(watch), Some(before)) = (watch.as_mut(), before) {
+                watch(&before, step);
+            }
+            match step {
+                Step::Won => {
+                    result.won = true;
+                    return result;
+                },
+                Step::Lost(_, _) => {
+                    result.guesses += 1;
+                    return result;
+                },
+                Step::Guessed(_, _) => result.guesses += 1,
+                _ => {},
+            }
+        }
+    }
+}
+
+/// Results of a bot playing many boards, see `simulate`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Simulation {
+    pub results: Vec<GameResult>,
+    /// Time spent playing, without generating the boards.
+    pub time: Duration,
+}
+
+impl Simulation {
+    pub fn games(&self) -> u32 {
+        self.results.len() as u32
+    }
+
+    pub fn wins(&self) -> u32 {
+        self.results.iter().filter(|result| result.won).count() as u32
+    }
+
+    /// Average guesses of all games, or only of the won ones.
+    pub fn mean_guesses(&self, won_only: bool) -> f64 {
+        let guesses: Vec<u32> = self.results.iter()
+            .filter(|result| result.won || !won_only)
+            .map(|result| result.guesses)
+            .collect();
+        if guesses.is_empty() {
+            return 0.0;
+        }
+        guesses.iter().sum::<u32>() as f64 / guesses.len() as f64
+    }
+
+    pub fn max_guesses(&self) -> u32 {
+        self.results.iter().map(|result| result.guesses).max().unwrap_or(0)
+    }
+
+    /// Games and wins by the number of guesses, from none to `most`; games
+    /// with more guesses count towards `most`.
+    pub fn by_guesses(&self, most: u32) -> Vec<(u32, u32)> {
+        let mut counts = vec![(0, 0); most as usize + 1];
+        for result in &self.results {
+            let count = &mut counts[result.guesses.min(most) as usize];
+            count.0 += 1;
+            count.1 += result.won as u32;
+        }
+        counts
+    }
+}
+
+/// Games a simulation plays side by side in an `Arena`.
+const BATCH: usize = 1024;
+
+/// Lets `bot` play `games` boards of `difficulty`, seeded from `first_seed`
+/// on like the boards of a comparison, with a safe first move.
+pub fn simulate(bot: &Bot, difficulty: Difficulty, games: u32, first_seed: u64) -> Result<Simulation, MinesError> {
+    simulate_watched(bot, difficulty, games, first_seed, None)
+}
+
+/// Like `simulate`, calling `watch` with the seed of a game, its board
+/// before every move and the move made on it. The games are played in an
+/// `Arena` a batch at a time, so the moves of a batch come interleaved.
+pub fn simulate_watched(bot: &Bot, difficulty: Difficulty, games: u32, first_seed: u64, mut watch: Option<SeedWatch>) -> Result<Simulation, MinesError> {
+    let (width, height, mines) = difficulty.dimensions();
+    let mut arena = Arena::new(height, width, mines)?;
+    let mut simulation = Simulation::default();
+    let seeds: Vec<u64> = (first_seed..first_seed + games as u64).collect();
+    for batch in seeds.chunks(BATCH) {
+        arena.clear();
+        for &seed in batch {
+            arena.add(seed);
+        }
+        let start = Instant::now();
+        match watch.as_mut() {
+            Some(watch) => arena.play_all(bot, Some(&mut |game, field: &Field, step| watch(batch[game], field, step))),
+            None => arena.play_all(bot, None),
+        }
+        simulation.time += start.elapsed();
+        simulation.results.extend(arena.results());
+    }
+    Ok(simulation)
+}
+
+/// Lower and upper bound of the 95% Wilson score interval for a win rate.
+pub fn wilson_interval(wins: u32, games: u32) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+    let z = 1.96f64;
+    let n = games as f64;
+    let p = wins as f64 / n;
+    let center = p + z * z / (2.0 * n);
+    let margin = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt();
+    let denominator = 1.0 + z * z / n;
+    (((center - margin) / denominator).max(0.0), ((center + margin) / denominator).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Difficulty, Field};
+    use crate::bot::{simulate, strategy, wilson_interval, Bot, Opening, Step};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn bot(name: &str) -> Bot {
+        Bot::new(strategy(name).unwrap())
+    }
+
+    #[test]
+    fn flags_before_opening() {
+        let mut field = Field::parse("o*.\noo.").unwrap();
+        assert_eq!(Step::Flagged(0, 1), bot("first").step(&mut field, &mut rand::thread_rng()));
+    }
+
+    #[test]
+    fn compares_constraints() {
+        let mut field = Field::parse("*.*\nooo").unwrap();
+        let mut rng = rand::thread_rng();
+        let mut steps = vec![bot("first").step(&mut field, &mut rng), bot("first").step(&mut field, &mut rng)];
+        steps.sort_by_key(|step| format!("{:?}", step));
+        assert_eq!(vec![Step::Flagged(0, 0), Step::Flagged(0, 2)], steps);
+        assert_eq!(Step::Won, bot("first").step(&mut field, &mut rng));
+    }
+
+    #[test]
+    fn guesses_first_cell() {
+        let mut field = Field::parse("..\n.*").unwrap();
+        assert_eq!(Step::Guessed(0, 0), bot("first").step(&mut field, &mut rand::thread_rng()));
+    }
+
+    #[test]
+    fn greedy_avoids_likely_mines() {
+        let field = Field::parse("o..\n.*.\n...\n...\n...").unwrap();
+        for name in ["basic", "greedy"] {
+            let (x, y) = strategy(name).unwrap().guess(&field, &mut rand::thread_rng()).unwrap();
+            assert!(![(0, 1), (1, 0), (1, 1)].contains(&(x, y)));
+        }
+    }
+
+    #[test]
+    fn plays_to_the_end() {
+        let mut field = Field::parse("o*.\noo.\n...").unwrap();
+        let result = bot("info").play(&mut field, &mut rand::thread_rng(), false);
+        assert!(result.won);
+        assert_eq!(0, result.guesses);
+    }
+
+    #[test]
+    fn play_is_reproducible() {
+        let play = || {
+            let mut rng = StdRng::seed_from_u64(7);
+            let mut field = Field::generate(&mut rng, 9, 9, 8).unwrap();
+            bot("random").play(&mut field, &mut rng, true)
+        };
+        assert_eq!(play(), play());
+    }
+
+    #[test]
+    fn openings() {
+        let field = Field::parse(".....\n.....\n.....").unwrap();
+        let mut rng = rand::thread_rng();
+        assert_eq!((0, 0), Opening::Corner.cell(&field, &mut rng));
+        assert_eq!((1, 2), Opening::Center.cell(&field, &mut rng));
+        let table = Opening::parse_table("0 1\n0 3\n").unwrap();
+        assert_eq!((2, 3), table.cell(&field, &mut rng));
+        assert!(Opening::parse_table("0 1\n2").is_err());
+        assert!("table:/does/not/exist".parse::<Opening>().is_err());
+    }
+
+    #[test]
+    fn opening_is_first_guess() {
+        let mut field = Field::parse("*..\n...\n...").unwrap();
+        let bot = bot("first").with_opening(Opening::Center);
+        assert_eq!(Step::Guessed(1, 1), bot.step(&mut field, &mut rand::thread_rng()));
+    }
+
+    #[test]
+    fn first_move_is_safe() {
+        for bot in [bot("random"), bot("first").with_opening(Opening::Random)] {
+            for seed in 0..50 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut field = Field::generate(&mut rng, 9, 9, 60).unwrap();
+                let mut first = None;
+                bot.play_watched(&mut field, &mut rng, true, Some(&mut |_: &Field, step| { first.get_or_insert(step); }));
+                assert!(matches!(first, Some(Step::Guessed(_, _) | Step::Won)), "{:?}", first);
+            }
+        }
+    }
+
+    #[test]
+    fn simulation() {
+        let simulation = simulate(&bot("greedy"), Difficulty::Beginner, 20, 0).unwrap();
+        assert_eq!(20, simulation.games());
+        assert_eq!(simulation.results, simulate(&bot("greedy"), Difficulty::Beginner, 20, 0).unwrap().results);
+        let by_guesses = simulation.by_guesses(3);
+        assert_eq!(4, by_guesses.len());
+        assert_eq!(20, by_guesses.iter().map(|(games, _)| games).sum::<u32>());
+        assert_eq!(simulation.wins(), by_guesses.iter().map(|(_, wins)| wins).sum::<u32>());
+        assert!(simulation.mean_guesses(false) <= simulation.max_guesses() as f64);
+    }
+
+    #[test]
+    fn unknown_strategy() {
+        assert!(strategy("psychic").is_err());
+        assert_eq!("basic", strategy("basic").unwrap().name());
+    }
+
+    #[test]
+    fn wilson() {
+        let (low, high) = wilson_interval(50, 100);
+        assert!((low - 0.4038).abs() < 1e-3);
+        assert!((high - 0.5962).abs() < 1e-3);
+        assert_eq!((0.0, 1.0), wilson_interval(0, 0));
+    }
+}
@@ -0,0 +1,373 @@
+use crate::Difficulty;
+use crate::json::Json;
+use crate::timer::{format_time, parse_time};
+use rand::Rng;
+use std::cmp::Reverse;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How a player did on one board of a round.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Cleared(Duration),
+    Lost,
+}
+
+impl Outcome {
+    /// A cleared board beats a lost one, a faster time a slower one.
+    fn beats(self, other: Outcome) -> bool {
+        match (self, other) {
+            (Outcome::Cleared(time), Outcome::Cleared(other)) => time < other,
+            (Outcome::Cleared(_), Outcome::Lost) => true,
+            (Outcome::Lost, _) => false,
+        }
+    }
+
+    fn time(self) -> Duration {
+        match self {
+            Outcome::Cleared(time) => time,
+            Outcome::Lost => Duration::default(),
+        }
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lost" => Ok(Outcome::Lost),
+            _ => parse_time(s).map(Outcome::Cleared),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Cleared(time) => write!(f, "{}", format_time(*time)),
+            Outcome::Lost => write!(f, "lost"),
+        }
+    }
+}
+
+/// One side of a match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Entrant {
+    /// Index of a player in the seeding order.
+    Player(usize),
+    /// Nobody, the other side goes through without playing.
+    Bye,
+    /// The winner of a match of the previous round that isn't decided yet.
+    WinnerOf(usize),
+}
+
+/// Where a player stands in a bracket.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub player: usize,
+    /// Number of the furthest round the player got to, from 1, or one past
+    /// the last round for the champion.
+    pub reached: usize,
+    /// Whether the player lost a match.
+    pub out: bool,
+    pub games_won: usize,
+    /// Sum of the times of the cleared boards.
+    pub time: Duration,
+}
+
+/// A single elimination tournament where everybody in a round plays the
+/// same boards, so a club can run it offline with the seeds alone. Matches
+/// are decided by the games won against the opponent, then by the boards
+/// cleared, then by the total time and last by the seeding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bracket {
+    pub difficulty: Difficulty,
+    /// In seeding order, the favourite first.
+    pub players: Vec<String>,
+    /// The seeds of the boards of every round.
+    pub seeds: Vec<Vec<u64>>,
+    /// The outcomes entered so far by round and player.
+    results: Vec<(usize, usize, Vec<Outcome>)>,
+}
+
+impl Bracket {
+    /// Draws the seeds for a bracket of `players` with `games` boards in
+    /// every round.
+    pub fn new<R: Rng>(players: Vec<String>, games: usize, difficulty: Difficulty, rng: &mut R) -> Result<Self, String> {
+        if players.len() < 2 {
+            return Err("A bracket needs at least 2 players".into());
+        }
+        if let Some(name) = players.iter().enumerate().find(|(i, name)| players[..*i].contains(name)).map(|(_, name)| name) {
+            return Err(format!("Player '{}' is in the bracket twice", name));
+        }
+        if games == 0 {
+            return Err("Rounds need at least 1 game".into());
+        }
+        let seeds = (0..rounds_for(players.len()))
+            .map(|_| (0..games).map(|_| rng.gen()).collect())
+            .collect();
+        Ok(Self { difficulty, players, seeds, results: vec![] })
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.seeds.len()
+    }
+
+    /// Boards played in every round.
+    pub fn games(&self) -> usize {
+        self.seeds[0].len()
+    }
+
+    /// The player called `name`, or numbered `name` counting from 1.
+    pub fn player(&self, name: &str) -> Option<usize> {
+        self.players.iter().position(|player| player == name)
+            .or_else(|| name.parse::<usize>().ok().filter(|&n| n >= 1 && n <= self.players.len()).map(|n| n - 1))
+    }
+
+    /// The outcomes `player` entered for the 0-based `round`.
+    pub fn outcomes(&self, round: usize, player: usize) -> Option<&[Outcome]> {
+        self.results.iter()
+            .find(|(r, p, _)| *r == round && *p == player)
+            .map(|(_, _, outcomes)| outcomes.as_slice())
+    }
+
+    /// Enters the outcomes of `player` in the 0-based `round`, replacing
+    /// ones entered before.
+    pub fn record(&mut self, round: usize, player: usize, outcomes: Vec<Outcome>) -> Result<(), String> {
+        if round >= self.rounds() {
+            return Err(format!("There are only {} rounds", self.rounds()));
+        }
+        if outcomes.len() != self.games() {
+            return Err(format!("Every round has {} games, got {} results", self.games(), outcomes.len()));
+        }
+        let plays = self.matches(round).iter().any(|&(a, b)| a == Entrant::Player(player) || b == Entrant::Player(player));
+        if !plays {
+            return Err(format!("{} doesn't play in round {}", self.players[player], round + 1));
+        }
+        self.results.retain(|(r, p, _)| (*r, *p) != (round, player));
+        self.results.push((round, player, outcomes));
+        Ok(())
+    }
+
+    /// The pairs of every match of the 0-based `round`.
+    pub fn matches(&self, round: usize) -> Vec<(Entrant, Entrant)> {
+        let mut entrants: Vec<Entrant> = seeding(1 << self.rounds()).into_iter()
+            .map(|seed| if seed < self.players.len() { Entrant::Player(seed) } else { Entrant::Bye })
+            .collect();
+        for r in 0..round {
+            entrants = entrants.chunks(2)
+                .enumerate()
+                .map(|(i, pair)| self.winner(r, pair[0], pair[1]).map_or(Entrant::WinnerOf(i), Entrant::Player))
+                .collect();
+        }
+        entrants.chunks(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+
+    /// The player who won the final.
+    pub fn champion(&self) -> Option<usize> {
+        let last = self.rounds() - 1;
+        self.matches(last).first().and_then(|&(a, b)| self.winner(last, a, b))
+    }
+
+    /// The winner of a match of `a` and `b` in the 0-based `round`, `None`
+    /// while it is still open.
+    pub fn winner(&self, round: usize, a: Entrant, b: Entrant) -> Option<usize> {
+        match (a, b) {
+            (Entrant::Player(p), Entrant::Bye) | (Entrant::Bye, Entrant::Player(p)) => Some(p),
+            (Entrant::Player(p), Entrant::Player(q)) => {
+                let (ours, theirs) = (self.outcomes(round, p)?, self.outcomes(round, q)?);
+                let key = |ours: &[Outcome], theirs: &[Outcome]| (
+                    ours.iter().zip(theirs).filter(|(a, b)| a.beats(**b)).count(),
+                    ours.iter().filter(|o| **o != Outcome::Lost).count(),
+                    Reverse(ours.iter().map(|o| o.time()).sum::<Duration>()),
+                );
+                let (key_p, key_q) = (key(ours, theirs), key(theirs, ours));
+                Some(if key_p > key_q {
+                    p
+                } else if key_q > key_p {
+                    q
+                } else {
+                    p.min(q)
+                })
+            },
+            _ => None,
+        }
+    }
+
+    /// Every player with the furthest round they got to, best first.
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = (0..self.players.len())
+            .map(|player| Standing { player, reached: 1, out: false, games_won: 0, time: Duration::default() })
+            .collect();
+        for round in 0..self.rounds() {
+            for (a, b) in self.matches(round) {
+                let winner = match self.winner(round, a, b) {
+                    Some(winner) => winner,
+                    None => continue,
+                };
+                for (side, other) in [(a, b), (b, a)].iter() {
+                    if let Entrant::Player(p) = *side {
+                        let standing = &mut standings[p];
+                        if p == winner {
+                            standing.reached = round + 2;
+                        } else {
+                            standing.out = true;
+                        }
+                        if let (Some(ours), Entrant::Player(q)) = (self.outcomes(round, p), *other) {
+                            let theirs = self.outcomes(round, q).unwrap_or_default();
+                            standing.games_won += ours.iter().zip(theirs).filter(|(a, b)| a.beats(**b)).count();
+                            standing.time += ours.iter().map(|o| o.time()).sum::<Duration>();
+                        }
+                    }
+                }
+            }
+        }
+        standings.sort_by_key(|s| (Reverse(s.reached), s.out, Reverse(s.games_won), s.time, s.player));
+        standings
+    }
+
+    /// Writes the bracket as one JSON object. Rounds count from 1, times
+    /// are in milliseconds and lost games are `null`.
+    pub fn to_json(&self) -> String {
+        let results = self.results.iter().map(|(round, player, outcomes)| Json::Object(vec![
+            ("round".into(), Json::Number((round + 1) as f64)),
+            ("player".into(), Json::String(self.players[*player].clone())),
+            ("games".into(), Json::Array(outcomes.iter().map(|outcome| match outcome {
+                Outcome::Cleared(time) => Json::Number(time.as_millis() as f64),
+                Outcome::Lost => Json::Null,
+            }).collect())),
+        ]));
+        Json::Object(vec![
+            ("version".into(), Json::Number(1.0)),
+            ("difficulty".into(), Json::String(self.difficulty.to_string())),
+            ("players".into(), Json::Array(self.players.iter().map(|name| Json::String(name.clone())).collect())),
+            ("seeds".into(), Json::Array(self.seeds.iter()
+                .map(|round| Json::Array(round.iter().map(|seed| Json::String(seed.to_string())).collect()))
+                .collect())),
+            ("results".into(), Json::Array(results.collect())),
+        ]).to_string()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let json = Json::parse(text)?;
+        let field = |key: &str| json.get(key).ok_or_else(|| format!("missing '{}'", key));
+        let array = |key: &str| field(key)?.as_array().ok_or_else(|| format!("'{}' isn't an array", key));
+        if field("version")?.as_u64() != Some(1) {
+            return Err("unknown version, the bracket was written by a newer version".into());
+        }
+        let players: Vec<String> = array("players")?.iter()
+            .map(|name| name.as_str().map(String::from))
+            .collect::<Option<_>>()
+            .ok_or("'players' aren't all names")?;
+        let seeds: Vec<Vec<u64>> = array("seeds")?.iter()
+            .map(|round| round.as_array()?.iter().map(|seed| seed.as_str()?.parse().ok()).collect())
+            .collect::<Option<_>>()
+            .ok_or("'seeds' isn't an array of rounds of seeds")?;
+        if players.len() < 2 || seeds.len() != rounds_for(players.len()) || seeds.iter().any(|round| round.is_empty() || round.len() != seeds[0].len()) {
+            return Err("'seeds' don't match the players".into());
+        }
+        let mut bracket = Self {
+            difficulty: field("difficulty")?.as_str().ok_or("'difficulty' isn't a string")?.parse()?,
+            players,
+            seeds,
+            results: vec![],
+        };
+        for result in array("results")? {
+            let invalid = || "'results' has an invalid entry".to_string();
+            let round = result.get("round").and_then(|r| r.as_u64()).filter(|&r| r >= 1).ok_or_else(invalid)? as usize - 1;
+            let player = result.get("player").and_then(|p| p.as_str()).ok_or_else(invalid)?;
+            let player = bracket.player(player).ok_or_else(|| format!("unknown player '{}'", player))?;
+            let outcomes = result.get("games").and_then(|g| g.as_array()).ok_or_else(invalid)?.iter()
+                .map(|game| match game {
+                    Json::Null => Some(Outcome::Lost),
+                    _ => game.as_u64().map(|ms| Outcome::Cleared(Duration::from_millis(ms))),
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?;
+            bracket.record(round, player, outcomes)?;
+        }
+        Ok(bracket)
+    }
+}
+
+/// Rounds a single elimination bracket of `players` takes.
+pub fn rounds_for(players: usize) -> usize {
+    players.max(2).next_power_of_two().trailing_zeros() as usize
+}
+
+/// The 0-based seeds in bracket order for `size` places, a power of two,
+/// so the best seeds meet as late as possible: 0 7 3 4 1 6 2 5 for 8.
+fn seeding(size: usize) -> Vec<usize> {
+    let mut order = vec![0];
+    while order.len() < size {
+        let n = order.len() * 2;
+        order = order.iter().flat_map(|&seed| std::iter::once(seed).chain(std::iter::once(n - 1 - seed))).collect();
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::bracket::{rounds_for, seeding, Bracket, Entrant, Outcome};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use std::time::Duration;
+
+    fn bracket(players: usize) -> Bracket {
+        let names = (1..=players).map(|n| format!("p{}", n)).collect();
+        Bracket::new(names, 2, Difficulty::Beginner, &mut StdRng::seed_from_u64(7)).unwrap()
+    }
+
+    fn secs(s: u64) -> Outcome {
+        Outcome::Cleared(Duration::from_secs(s))
+    }
+
+    #[test]
+    fn draw() {
+        assert_eq!(vec![0, 7, 3, 4, 1, 6, 2, 5], seeding(8));
+        assert_eq!((1, 3, 3), (rounds_for(2), rounds_for(5), rounds_for(8)));
+        let bracket = bracket(6);
+        assert_eq!(3, bracket.seeds.len());
+        assert!(bracket.seeds.iter().all(|round| round.len() == 2));
+        let first = bracket.matches(0);
+        assert_eq!((Entrant::Player(0), Entrant::Bye), first[0]);
+        assert_eq!((Entrant::Player(3), Entrant::Player(4)), first[1]);
+        assert_eq!((Entrant::Player(0), Entrant::WinnerOf(1)), bracket.matches(1)[0]);
+        assert!(Bracket::new(vec!["a".into(), "a".into()], 1, Difficulty::Beginner, &mut StdRng::seed_from_u64(0)).is_err());
+    }
+
+    #[test]
+    fn results() {
+        let mut bracket = bracket(4);
+        assert!(bracket.record(1, 0, vec![secs(10), secs(10)]).is_err());
+        assert!(bracket.record(0, 0, vec![secs(10)]).is_err());
+        // one game each, p4 clears both boards
+        bracket.record(0, 0, vec![secs(10), Outcome::Lost]).unwrap();
+        bracket.record(0, 3, vec![secs(12), secs(40)]).unwrap();
+        bracket.record(0, 1, vec![secs(20), secs(20)]).unwrap();
+        bracket.record(0, 2, vec![secs(20), secs(20)]).unwrap();
+        assert_eq!((Entrant::Player(3), Entrant::Player(1)), bracket.matches(1)[0]);
+        bracket.record(1, 3, vec![secs(9), secs(9)]).unwrap();
+        bracket.record(1, 1, vec![Outcome::Lost, Outcome::Lost]).unwrap();
+        assert_eq!(Some(3), bracket.champion());
+        let places: Vec<(usize, usize, bool)> = bracket.standings().iter().map(|s| (s.player, s.reached, s.out)).collect();
+        assert_eq!(vec![(3, 3, false), (1, 2, true), (0, 1, true), (2, 1, true)], places);
+        assert_eq!(Some(3), bracket.player("4"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bracket = bracket(3);
+        bracket.record(0, 1, vec![secs(65), Outcome::Lost]).unwrap();
+        let json = bracket.to_json();
+        let loaded = Bracket::from_json(&json).unwrap();
+        assert_eq!(bracket, loaded);
+        assert_eq!(json, loaded.to_json());
+        assert!(Bracket::from_json(&json.replace("\"p3\"]", "\"p3\",\"p4\",\"p5\"]")).is_err());
+        assert_eq!(Ok(Outcome::Lost), "lost".parse());
+        assert_eq!("1:05.0", secs(65).to_string());
+    }
+}
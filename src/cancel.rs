@@ -0,0 +1,35 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Asks a long-running solver or generator call to stop early. Clones share
+/// the same flag, so the caller keeps one and hands another to the work.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CancelToken;
+
+    #[test]
+    fn shared_between_clones() {
+        let token = CancelToken::new();
+        let work = token.clone();
+        assert!(!work.is_cancelled());
+        token.cancel();
+        assert!(work.is_cancelled());
+    }
+}
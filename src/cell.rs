@@ -0,0 +1,245 @@
+use crate::MinesError;
+
+/// What is hidden below a cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellValue {
+    Mine,
+    Water,
+}
+
+/// How the player marked a closed cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mark {
+    None,
+    Flag,
+    /// Not sure yet. Unlike a flag it doesn't keep the cell from being
+    /// opened or count towards chording.
+    Question,
+}
+
+/// A single cell of a `Field`.
+#[derive(Clone, PartialEq)]
+pub struct Cell {
+    pub(crate) value: CellValue,
+    pub(crate) opened: bool,
+    pub(crate) mark: Mark,
+    /// Mines below a mine cell on multimine boards, see `mine_count`.
+    pub(crate) mines: u8,
+    /// Flags on a flagged cell on multimine boards, see `flags`.
+    pub(crate) flags: u8,
+    /// Not part of the board, see `hole`.
+    pub(crate) hole: bool,
+}
+
+impl Cell {
+    /// A closed cell hiding a mine.
+    pub fn mine() -> Self {
+        Self {
+            value: CellValue::Mine,
+            opened: false,
+            mark: Mark::None,
+            mines: 1,
+            flags: 0,
+            hole: false,
+        }
+    }
+
+    /// A closed cell hiding `mines` mines, water without any.
+    pub fn with_mines(mines: u8) -> Self {
+        match mines {
+            0 => Self::water(),
+            _ => Self { mines, ..Self::mine() },
+        }
+    }
+
+    /// A closed cell without a mine.
+    pub fn water() -> Self {
+        Self {
+            value: CellValue::Water,
+            opened: false,
+            mark: Mark::None,
+            mines: 0,
+            flags: 0,
+            hole: false,
+        }
+    }
+
+    /// A gap in a shaped board. It is open from the start, has no number
+    /// and doesn't touch any other cell.
+    pub fn hole() -> Self {
+        Self { opened: true, hole: true, ..Self::water() }
+    }
+
+    pub fn value(&self) -> CellValue {
+        self.value
+    }
+
+    pub fn is_hole(&self) -> bool {
+        self.hole
+    }
+
+    pub fn is_mine(&self) -> bool {
+        self.value == CellValue::Mine
+    }
+
+    /// Mines hidden below the cell: none for water, one for a mine unless
+    /// the board allows several per cell.
+    pub fn mine_count(&self) -> u8 {
+        match self.value {
+            CellValue::Mine => self.mines.max(1),
+            CellValue::Water => 0,
+        }
+    }
+
+    /// Flags placed on the cell, one for a flag unless the board allows
+    /// several mines per cell.
+    pub fn flags(&self) -> u8 {
+        if self.is_flagged() { self.flags.max(1) } else { 0 }
+    }
+
+    pub fn is_opened(&self) -> bool {
+        self.opened
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        self.mark == Mark::Flag
+    }
+
+    pub fn mark(&self) -> Mark {
+        self.mark
+    }
+
+    /// Opens the cell unless it is flagged. A mine stays closed and
+    /// `MinesError::MineOpened` is returned, `explode` opens it.
+    pub(crate) fn open(&mut self) -> Result<(), MinesError> {
+        if self.is_flagged() {
+            return Ok(());
+        }
+        if self.value == CellValue::Mine {
+            return Err(MinesError::MineOpened);
+        }
+        self.opened = true;
+        self.mark = Mark::None;
+        self.flags = 0;
+        Ok(())
+    }
+
+    /// Opens a mine, losing the game.
+    pub(crate) fn explode(&mut self) {
+        self.opened = true;
+        self.mark = Mark::None;
+        self.flags = 0;
+    }
+
+    /// Flags the cell or takes the flag away. A question mark becomes a
+    /// flag.
+    pub(crate) fn toggle_flag(&mut self) {
+        if !self.opened {
+            self.mark = if self.is_flagged() { Mark::None } else { Mark::Flag };
+            self.flags = self.is_flagged() as u8;
+        }
+    }
+
+    /// Places one more flag, up to `max` flags, after which they are all
+    /// taken away. A question mark becomes the first flag.
+    pub(crate) fn add_flag(&mut self, max: u8) {
+        if !self.opened {
+            self.flags = (self.flags() + 1) % (max + 1);
+            self.mark = if self.flags > 0 { Mark::Flag } else { Mark::None };
+        }
+    }
+
+    /// Goes from no mark to a flag, a question mark and back.
+    pub(crate) fn cycle_mark(&mut self) {
+        if !self.opened {
+            self.mark = match self.mark {
+                Mark::None => Mark::Flag,
+                Mark::Flag => Mark::Question,
+                Mark::Question => Mark::None,
+            };
+            self.flags = self.is_flagged() as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cell, Mark, MinesError};
+
+    #[test]
+    fn open_water() {
+        assert!(Cell::water().open().is_ok());
+    }
+
+    #[test]
+    fn open_mine() {
+        let mut cell = Cell::mine();
+        assert_eq!(Err(MinesError::MineOpened), cell.open());
+        assert!(!cell.is_opened());
+        cell.explode();
+        assert!(cell.is_opened());
+    }
+
+    #[test]
+    fn toggle_flag() {
+        let mut cell = Cell::mine();
+        assert!(!cell.is_flagged());
+        cell.toggle_flag();
+        assert!(cell.is_flagged());
+        cell.toggle_flag();
+        assert!(!cell.is_flagged());
+    }
+
+    #[test]
+    fn add_flag() {
+        let mut cell = Cell::with_mines(2);
+        assert_eq!((2, 0), (cell.mine_count(), cell.flags()));
+        cell.add_flag(3);
+        cell.add_flag(3);
+        assert_eq!(2, cell.flags());
+        cell.add_flag(3);
+        cell.add_flag(3);
+        assert_eq!((Mark::None, 0), (cell.mark(), cell.flags()));
+        cell.toggle_flag();
+        assert_eq!(1, cell.flags());
+    }
+
+    #[test]
+    fn cycle_mark() {
+        let mut cell = Cell::water();
+        cell.cycle_mark();
+        assert_eq!(Mark::Flag, cell.mark());
+        cell.cycle_mark();
+        assert_eq!(Mark::Question, cell.mark());
+        cell.cycle_mark();
+        assert_eq!(Mark::None, cell.mark());
+        cell.mark = Mark::Question;
+        cell.open().unwrap();
+        assert!(cell.is_opened());
+        assert_eq!(Mark::None, cell.mark());
+    }
+
+    #[test]
+    fn hole() {
+        let mut cell = Cell::hole();
+        assert!(cell.open().is_ok());
+        cell.toggle_flag();
+        assert_eq!((0, 0), (cell.mine_count(), cell.flags()));
+    }
+
+    #[test]
+    fn toggle_opened() {
+        let mut cell = Cell::water();
+        let _ = cell.open();
+        cell.toggle_flag();
+        assert!(!cell.is_flagged());
+    }
+
+    #[test]
+    fn open_flagged() {
+        let mut cell = Cell::water();
+        cell.mark = Mark::Flag;
+        cell.open().unwrap();
+        assert!(!cell.opened);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::Difficulty;
+use std::fmt;
+use std::str::FromStr;
+
+/// A board to share, written as a short code like `E-1234` or
+/// `30X20X120-1234-NG`: the difficulty, the seed and whether the board is
+/// one without guessing. Anyone playing it with `play --challenge <code>`
+/// gets the same mines as long as they start on the same cell.
+///
+/// Codes only use upper case letters, digits and dashes, so they are easy
+/// to read out and fit a small QR code.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Challenge {
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    pub no_guess: bool,
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.difficulty {
+            Difficulty::Beginner => write!(f, "B")?,
+            Difficulty::Intermediate => write!(f, "I")?,
+            Difficulty::Expert => write!(f, "E")?,
+            Difficulty::Custom(width, height, mines) => write!(f, "{}X{}X{}", width, height, mines)?,
+        }
+        write!(f, "-{}", self.seed)?;
+        if self.no_guess {
+            write!(f, "-NG")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Challenge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid challenge code '{}', expected one like E-1234", s);
+        let code = s.trim().to_uppercase();
+        let mut parts = code.split('-');
+        let difficulty = match parts.next().ok_or_else(invalid)? {
+            "B" => Difficulty::Beginner,
+            "I" => Difficulty::Intermediate,
+            "E" => Difficulty::Expert,
+            custom => {
+                let numbers = custom.split('X').map(|n| n.parse().map_err(|_| invalid())).collect::<Result<Vec<u16>, _>>()?;
+                match numbers[..] {
+                    [width, height, mines] => Difficulty::Custom(width, height, mines),
+                    _ => return Err(invalid()),
+                }
+            },
+        };
+        let seed = parts.next().and_then(|seed| seed.parse().ok()).ok_or_else(invalid)?;
+        let no_guess = match parts.next() {
+            Some("NG") => true,
+            None => false,
+            Some(_) => return Err(invalid()),
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self { difficulty, seed, no_guess })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::challenge::Challenge;
+
+    #[test]
+    fn codes() {
+        let expert = Challenge { difficulty: Difficulty::Expert, seed: 1234, no_guess: false };
+        assert_eq!("E-1234", expert.to_string());
+        assert_eq!(Ok(expert), " e-1234 ".parse());
+        let custom = Challenge { difficulty: Difficulty::Custom(30, 20, 120), seed: u64::MAX, no_guess: true };
+        assert_eq!(Ok(custom), custom.to_string().parse());
+        for invalid in ["", "E", "E-", "E-12-X", "E-12-NG-1", "30X20-5", "H-5"] {
+            assert!(invalid.parse::<Challenge>().is_err(), "{}", invalid);
+        }
+    }
+}
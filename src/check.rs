@@ -0,0 +1,319 @@
+use crate::{CellValue, Field};
+use crate::json::Json;
+use crate::solver::{apply_deductions, solvable_from};
+use std::collections::VecDeque;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    /// The board can't be played.
+    Error,
+    /// The board can be played, but probably not as meant.
+    Warning,
+}
+
+/// Something `check` found in a board file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// A short name for tools, like `row-length` or `opened-mine`.
+    pub kind: &'static str,
+    /// The 1-based line of the file, for problems with the text.
+    pub line: Option<usize>,
+    /// The cell, `x` being the row like on `Field`.
+    pub cell: Option<(u16, u16)>,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(kind: &'static str, line: usize, message: String) -> Self {
+        Self { severity: Severity::Error, kind, line: Some(line), cell: None, message }
+    }
+
+    fn warning(kind: &'static str, cell: Option<(u16, u16)>, message: String) -> Self {
+        Self { severity: Severity::Warning, kind, line: None, cell, message }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => write!(f, "{} in line {}: {}", severity, line, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// What `check` makes of a board file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub rows: u16,
+    pub cols: u16,
+    pub mines: usize,
+    pub issues: Vec<Issue>,
+    /// Whether the solver clears the board without guessing, from the
+    /// opened cells or else from the best first click. `None` when the
+    /// board couldn't be read.
+    pub solvable: Option<bool>,
+    /// The first click the solver cleared the board from.
+    pub start: Option<(u16, u16)>,
+}
+
+impl Report {
+    /// Whether the board can be played at all.
+    pub fn is_valid(&self) -> bool {
+        self.issues.iter().all(|issue| issue.severity != Severity::Error)
+    }
+
+    /// The report as one JSON object, with 0-based rows and columns.
+    pub fn to_json(&self) -> String {
+        let number = |n: usize| Json::Number(n as f64);
+        let cell = |(x, y): (u16, u16)| Json::Object(vec![
+            ("row".into(), number(x as usize)),
+            ("col".into(), number(y as usize)),
+        ]);
+        let issues = self.issues.iter().map(|issue| {
+            let mut pairs = vec![
+                ("severity".into(), Json::String(match issue.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                }.into())),
+                ("kind".into(), Json::String(issue.kind.into())),
+                ("message".into(), Json::String(issue.message.clone())),
+            ];
+            if let Some(line) = issue.line {
+                pairs.push(("line".into(), number(line)));
+            }
+            if let Some(at) = issue.cell {
+                pairs.push(("cell".into(), cell(at)));
+            }
+            Json::Object(pairs)
+        }).collect();
+        Json::Object(vec![
+            ("valid".into(), Json::Bool(self.is_valid())),
+            ("rows".into(), number(self.rows as usize)),
+            ("cols".into(), number(self.cols as usize)),
+            ("mines".into(), number(self.mines)),
+            ("solvable".into(), self.solvable.map_or(Json::Null, Json::Bool)),
+            ("start".into(), self.start.map_or(Json::Null, cell)),
+            ("issues".into(), Json::Array(issues)),
+        ]).to_string()
+    }
+}
+
+/// Checks a board file in the format of `Field::parse` for puzzle authors.
+/// Unlike `Field::parse` it goes on after the first problem with the text,
+/// and on boards that can be read it looks for cells holding several mines,
+/// wrong flags, areas that holes cut off from the rest and whether the
+/// solver gets through without guessing.
+pub fn check(text: &str) -> Report {
+    let mut issues = vec![];
+    let mut cols = None;
+    let mut rows = 0;
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let length = line.chars().count();
+        if rows == u16::MAX || length > u16::MAX as usize {
+            issues.push(Issue::error("too-big", line_no + 1, format!("boards have at most {} rows and columns", u16::MAX)));
+            break;
+        }
+        match cols {
+            None => cols = Some(length),
+            Some(cols) if cols != length => issues.push(Issue::error(
+                "row-length", line_no + 1, format!("expected {} cells like the first row, found {}", cols, length),
+            )),
+            _ => {},
+        }
+        for (col, c) in line.chars().enumerate() {
+            match c {
+                'X' => issues.push(Issue { cell: Some((rows, col as u16)), ..Issue::error(
+                    "opened-mine", line_no + 1, format!("the mine at {} {} is already opened", col + 1, rows + 1),
+                ) }),
+                '.' | '*' | 'o' | 'f' | 'F' | 'q' | 'Q' | '-' | '2'..='9' => {},
+                _ => issues.push(Issue::error("unknown-cell", line_no + 1, format!("unknown cell '{}'", c))),
+            }
+        }
+        rows += 1;
+    }
+    let mut report = Report {
+        rows,
+        cols: cols.unwrap_or(0).min(u16::MAX as usize) as u16,
+        mines: 0,
+        issues,
+        solvable: None,
+        start: None,
+    };
+    if rows == 0 {
+        report.issues.push(Issue::error("empty", 1, "the file has no rows".into()));
+    }
+    if !report.is_valid() {
+        return report;
+    }
+    let field = match Field::parse(text) {
+        Ok(field) => field,
+        Err(e) => {
+            report.issues.push(Issue::error("invalid", 1, e.to_string()));
+            return report;
+        },
+    };
+    report.mines = field.mine_count();
+    inspect(&field, &mut report.issues);
+    if field.cell_count() == field.mine_count() {
+        report.issues.push(Issue { line: None, ..Issue::error("too-many-mines", 0, "every cell is a mine".into()) });
+        return report;
+    }
+    if field.mines_per_cell() == 1 {
+        let (solvable, start) = solvability(&field);
+        report.solvable = Some(solvable);
+        report.start = start;
+    }
+    report
+}
+
+/// Looks for what a board file allows but a puzzle hardly means.
+fn inspect(field: &Field, issues: &mut Vec<Issue>) {
+    let mut any_opened = false;
+    for (x, row) in field.cells.iter().enumerate() {
+        for (y, cell) in row.iter().enumerate() {
+            let at = (x as u16, y as u16);
+            any_opened |= cell.opened && !cell.is_hole();
+            if cell.mine_count() > 1 {
+                issues.push(Issue::warning("stacked-mines", Some(at), format!(
+                    "{} holds {} mines, which only multimine boards allow", field.coords(at.0, at.1), cell.mine_count(),
+                )));
+            }
+            if cell.is_flagged() && cell.value == CellValue::Water {
+                issues.push(Issue::warning("wrong-flag", Some(at), format!("the flag at {} has no mine below it", field.coords(at.0, at.1))));
+            }
+        }
+    }
+    if field.mine_count() == 0 {
+        issues.push(Issue::warning("no-mines", None, "the board has no mines".into()));
+    }
+    // areas the player can open something in but no number leads into
+    let mut areas: Vec<(usize, (u16, u16), bool)> = areas(field).into_iter()
+        .filter(|area| area.iter().any(|&(x, y)| {
+            let cell = &field.cells[x as usize][y as usize];
+            !cell.opened && cell.value == CellValue::Water
+        }))
+        .map(|area| {
+            let opened = area.iter().any(|&(x, y)| field.cells[x as usize][y as usize].opened);
+            (area.len(), area[0], opened)
+        })
+        .collect();
+    if !any_opened {
+        // the player starts in the biggest one
+        areas.sort_by_key(|&(size, _, _)| std::cmp::Reverse(size));
+        areas.drain(..areas.len().min(1));
+    }
+    for (size, (x, y), opened) in areas {
+        if !opened {
+            issues.push(Issue::warning("unreachable-area", Some((x, y)), format!(
+                "holes cut off the {} cells around {}, the player has to guess to get in", size, field.coords(x, y),
+            )));
+        }
+    }
+}
+
+/// The cells that aren't holes, in groups of cells reaching each other as
+/// neighbours.
+fn areas(field: &Field) -> Vec<Vec<(u16, u16)>> {
+    let mut seen: Vec<Vec<bool>> = field.cells.iter().map(|row| row.iter().map(|cell| cell.is_hole()).collect()).collect();
+    let mut areas = vec![];
+    for x in 0..field.cells.len() {
+        for y in 0..field.cells[x].len() {
+            if seen[x][y] {
+                continue;
+            }
+            seen[x][y] = true;
+            let mut area = vec![];
+            let mut queue = VecDeque::from(vec![(x as u16, y as u16)]);
+            while let Some((cx, cy)) = queue.pop_front() {
+                area.push((cx, cy));
+                for (nx, ny) in field.neighbours(cx, cy) {
+                    if !seen[nx as usize][ny as usize] {
+                        seen[nx as usize][ny as usize] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+            areas.push(area);
+        }
+    }
+    areas
+}
+
+/// Whether the solver clears the board without guessing: from where it
+/// stands if something is opened, else from any opening or, on boards
+/// without one, any safe cell.
+fn solvability(field: &Field) -> (bool, Option<(u16, u16)>) {
+    if field.opened_count() > 0 {
+        let mut field = field.clone();
+        return (apply_deductions(&mut field) && field.is_won(), None);
+    }
+    let safe: Vec<(u16, u16)> = field.cells.iter().enumerate()
+        .flat_map(|(x, row)| row.iter().enumerate()
+            .filter(|(_, cell)| cell.value == CellValue::Water && !cell.is_hole() && !cell.is_flagged())
+            .map(move |(y, _)| (x as u16, y as u16)))
+        .collect();
+    let zeros: Vec<(u16, u16)> = safe.iter().copied().filter(|&(x, y)| field.numbers[x as usize][y as usize] == 0).collect();
+    let mut tried = field.clone();
+    for &(x, y) in if zeros.is_empty() { &safe } else { &zeros } {
+        // the cells of an opening all clear the same
+        if tried.cells[x as usize][y as usize].opened {
+            continue;
+        }
+        let _ = tried.open(x, y);
+        if solvable_from(field, x, y) {
+            return (true, Some((x, y)));
+        }
+    }
+    (false, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::check::{check, Severity};
+
+    #[test]
+    fn text_problems() {
+        let report = check("..*\n.X\n..a\n");
+        assert!(!report.is_valid());
+        let kinds: Vec<&str> = report.issues.iter().map(|issue| issue.kind).collect();
+        assert_eq!(vec!["row-length", "opened-mine", "unknown-cell"], kinds);
+        assert_eq!((Some(2), Some((1, 1))), (report.issues[1].line, report.issues[1].cell));
+        assert_eq!(None, report.solvable);
+        assert!(report.to_json().starts_with("{\"valid\":false,\"rows\":3,\"cols\":3,\"mines\":0,"));
+        assert!(!check("# nothing\n").is_valid());
+        assert_eq!("too-big", check(&".".repeat(70_000)).issues[0].kind);
+        assert_eq!("too-big", check(&"..\n".repeat(70_000)).issues[0].kind);
+    }
+
+    #[test]
+    fn boards() {
+        let report = check("....\n....\n...*\n");
+        assert!(report.is_valid() && report.issues.is_empty());
+        assert_eq!((3, 4, 1), (report.rows, report.cols, report.mines));
+        assert_eq!(Some(true), report.solvable);
+        assert!(report.start.is_some());
+
+        // two corners cut off by holes, and a flag without a mine
+        let report = check("..-..\n*.-.*\nf.-..\n");
+        let kinds: Vec<&str> = report.issues.iter().map(|issue| issue.kind).collect();
+        assert_eq!(vec!["wrong-flag", "unreachable-area"], kinds);
+        assert!(report.issues.iter().all(|issue| issue.severity == Severity::Warning));
+        assert_eq!(Some(false), report.solvable);
+
+        // a coin flip
+        assert_eq!(Some(false), check("*.\n..\n.*\n").solvable);
+        assert_eq!("too-many-mines", check("**\n").issues[0].kind);
+        assert_eq!("stacked-mines", check("3.\n..\n").issues[0].kind);
+    }
+}
@@ -0,0 +1,892 @@
+//! Two or more players clearing one board together over TCP. The host
+//! keeps the board that counts in a `sync::Authority`, which orders the
+//! moves as they arrive, and every player, the host included, shows the
+//! board of a `sync::Predictor`: own moves at once, the others' once the
+//! host ordered them. The board is built like a race board, see
+//! `race::board`. Lines of text go both ways:
+//!
+//! * `hello <name>` from a player joining,
+//! * `back <player> <seen> <token>` from a player coming back on their seat
+//!   after losing the connection, with the number of moves they saw ordered
+//!   and the token of the seat,
+//! * `welcome <player> <seed> <seq> <moves> <token> <difficulty>` back to
+//!   them, with the players numbered from 0 for the host, the last of the
+//!   player's own moves the host got and a token only the player is told,
+//!   followed by `<moves>` lines of the moves played since what they saw
+//!   and a `joined` line for everyone,
+//! * `joined <player> <name>`, `left <player>` and `forfeited <player>` to
+//!   everyone, the last once a player stayed away longer than `sync::GRACE`,
+//! * `move <seq> <seen> <action> <row> <col>` from a player, rows and
+//!   columns from 0, see `sync::Move`,
+//! * `played <order> <player> <seq> <seen> <outcome> <action> <row> <col>`
+//!   to everyone once the host ordered a move, see `sync::Applied`,
+//! * `rejected <reason>` to the player whose move can't be played, like
+//!   opening a cell somebody else flagged a moment ago, or who can't come
+//!   back on their seat,
+//! * `chat <player> <text>` from a player, passed on by the host to
+//!   everyone with the player it came from.
+//!
+//! Lines of more than `MAX_LINE` bytes end the connection. Players are
+//! numbered below `MAX_PLAYERS`, and control characters are dropped from
+//! names, chat lines and reasons so they can't reach the terminal.
+
+use crate::{Difficulty, Field};
+use crate::race;
+use crate::server::MAX_CELLS;
+use crate::replay::Action;
+use crate::sync::{Applied, Authority, Move, Outcome, Predictor, Snapshot, GRACE};
+use std::collections::HashMap;
+use std::fmt;
+use rand::Rng;
+use std::io::{self, BufRead, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a guest waits for the host to answer a greeting.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a guest who lost the host tries to get back.
+const RETRY: Duration = Duration::from_secs(1);
+/// Longest line read from the other side, the newline included.
+pub(crate) const MAX_LINE: usize = 4 << 10;
+/// Most players in a game, the host included.
+pub const MAX_PLAYERS: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Hello(String),
+    Back(usize, u64, String),
+    Welcome(usize, u64, u32, usize, String, Difficulty),
+    Joined(usize, String),
+    Left(usize),
+    Forfeited(usize),
+    Move(u32, u64, Action, u16, u16),
+    Played(Applied),
+    Rejected(String),
+    Chat(usize, String),
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Hello(name) => write!(f, "hello {}", name),
+            Message::Back(player, seen, token) => write!(f, "back {} {} {}", player, seen, token),
+            Message::Welcome(player, seed, seq, moves, token, difficulty) => {
+                write!(f, "welcome {} {} {} {} {} {}", player, seed, seq, moves, token, difficulty)
+            },
+            Message::Joined(player, name) => write!(f, "joined {} {}", player, name),
+            Message::Left(player) => write!(f, "left {}", player),
+            Message::Forfeited(player) => write!(f, "forfeited {}", player),
+            Message::Move(seq, seen, action, x, y) => write!(f, "move {} {} {} {} {}", seq, seen, action, x, y),
+            Message::Played(Applied { order, mv, outcome }) => write!(
+                f, "played {} {} {} {} {} {} {} {}", order, mv.player, mv.seq, mv.seen, outcome, mv.action, mv.x, mv.y,
+            ),
+            Message::Rejected(reason) => write!(f, "rejected {}", reason),
+            Message::Chat(player, text) => write!(f, "chat {} {}", player, text),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || format!("invalid message '{}'", s);
+        let (word, rest) = s.split_once(' ').ok_or_else(invalid)?;
+        let words: Vec<&str> = rest.split(' ').collect();
+        fn number<T: FromStr>(word: &str, invalid: impl Fn() -> String) -> Result<T, String> {
+            word.parse().map_err(|_| invalid())
+        }
+        let player = |word: &str| number(word, invalid).and_then(|player: usize| {
+            if player < MAX_PLAYERS { Ok(player) } else { Err(format!("players are numbered below {}", MAX_PLAYERS)) }
+        });
+        match (word, &words[..]) {
+            ("hello", _) => Ok(Message::Hello(printable(rest))),
+            ("back", [p, seen, token]) => Ok(Message::Back(player(p)?, number(seen, invalid)?, token.to_string())),
+            // custom difficulties take up several words
+            ("welcome", [p, seed, seq, moves, token, difficulty @ ..]) if !difficulty.is_empty() => Ok(Message::Welcome(
+                player(p)?,
+                number(seed, invalid)?,
+                number(seq, invalid)?,
+                number(moves, invalid)?,
+                token.to_string(),
+                difficulty.join(" ").parse()?,
+            )),
+            ("joined", [p, name @ ..]) if !name.is_empty() => Ok(Message::Joined(player(p)?, printable(&name.join(" ")))),
+            ("left", [p]) => Ok(Message::Left(player(p)?)),
+            ("forfeited", [p]) => Ok(Message::Forfeited(player(p)?)),
+            ("move", [seq, seen, action, x, y]) => Ok(Message::Move(
+                number(seq, invalid)?, number(seen, invalid)?, action.parse()?, number(x, invalid)?, number(y, invalid)?,
+            )),
+            ("played", [order, p, seq, seen, outcome, action, x, y]) => Ok(Message::Played(Applied {
+                order: number(order, invalid)?,
+                mv: Move {
+                    player: player(p)?,
+                    seq: number(seq, invalid)?,
+                    seen: number(seen, invalid)?,
+                    action: action.parse()?,
+                    x: number(x, invalid)?,
+                    y: number(y, invalid)?,
+                },
+                outcome: outcome.parse()?,
+            })),
+            ("rejected", _) => Ok(Message::Rejected(printable(rest))),
+            ("chat", [p, text @ ..]) if !text.is_empty() => Ok(Message::Chat(player(p)?, printable(&text.join(" ")))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// `text` without control characters, which could move the cursor or
+/// change the colors of a terminal it is printed on.
+fn printable(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// Who did what on the shared board.
+#[derive(Clone, Debug, Default)]
+pub struct Coop {
+    /// The player who placed each flag.
+    pub flag_owners: HashMap<(u16, u16), usize>,
+    /// Cells opened by each player.
+    pub opened: HashMap<usize, usize>,
+    /// The player who opened a mine.
+    pub hit_by: Option<usize>,
+}
+
+impl Coop {
+    pub fn is_over(&self, field: &Field) -> bool {
+        self.hit_by.is_some() || field.is_won()
+    }
+
+    /// Why `player` can't play `action` on the cell at `(x, y)` of `field`,
+    /// the board as the host has it with the moves of the others that came
+    /// first played.
+    pub fn check(&self, field: &Field, player: usize, action: Action, x: u16, y: u16) -> Result<(), String> {
+        if self.is_over(field) {
+            return Err("the game is over".into());
+        }
+        let cell = field.cell(x, y).map_err(|e| e.to_string())?;
+        // flags are only taken away by whoever placed them
+        let owner = self.flag_owners.get(&(x, y)).copied().filter(|&owner| owner != player && cell.is_flagged());
+        match (action, owner) {
+            (Action::Open, _) | (Action::Flag, _) if cell.opened => Err(format!("{} is open already", field.coords(x, y))),
+            (Action::Open, _) if cell.is_flagged() => Err(format!("{} is flagged", field.coords(x, y))),
+            (Action::Flag, Some(owner)) => Err(format!("{} was flagged by player {}", field.coords(x, y), owner + 1)),
+            (Action::Chord, _) if !cell.opened => Err(format!("{} isn't open yet", field.coords(x, y))),
+            (Action::Open, _) | (Action::Flag, _) | (Action::Chord, _) => Ok(()),
+            _ => Err("no hints or undo in a game for several players".into()),
+        }
+    }
+
+    /// Notes what a move ordered by the host did, with the number of cells
+    /// opened before it and the board after it.
+    pub fn record(&mut self, applied: &Applied, opened_before: usize, field: &Field) {
+        if applied.outcome != Outcome::Applied {
+            return;
+        }
+        let Move { player, action, x, y, .. } = applied.mv;
+        match action {
+            Action::Flag if field.cell(x, y).is_ok_and(|cell| cell.is_flagged()) => {
+                self.flag_owners.insert((x, y), player);
+            },
+            Action::Flag => {
+                self.flag_owners.remove(&(x, y));
+            },
+            _ if self.hit_by.is_none() && field.opened_mine().is_some() => self.hit_by = Some(player),
+            _ => *self.opened.entry(player).or_default() += field.opened_count().saturating_sub(opened_before),
+        }
+    }
+}
+
+/// What happened in a co-op game, for the players to see.
+#[derive(Clone, Debug, PartialEq)]
+pub enum News {
+    Joined(usize),
+    /// A player lost their connection and has `sync::GRACE` to come back.
+    Left(usize),
+    Back(usize),
+    Forfeited(usize),
+    /// A move of another player, or an own move another one got in first
+    /// with, as ordered by the host.
+    Played(Applied),
+    /// An own move the host turned down, with why.
+    NotPlayed(String),
+    /// A chat line of another player.
+    Chat(usize, String),
+    /// The connection to the host was lost and is being tried again.
+    Reconnecting,
+    Reconnected,
+}
+
+/// What reaches the host from the players, or a guest from the host, who
+/// is player 0.
+enum Event {
+    /// A player greeted the host, with `hello` or `back`.
+    Connected(Message, io::BufReader<TcpStream>),
+    Received(usize, Message),
+    /// The connection of a player was lost, by the number `listen` gave it.
+    Lost(usize, u64),
+    /// A guest tried to get back to the host.
+    Rejoined(Result<Rejoined, String>),
+}
+
+/// A connection to the host, just welcomed.
+type Rejoined = (TcpStream, io::BufReader<TcpStream>, Welcome);
+
+enum Link {
+    Host {
+        authority: Box<Authority>,
+        /// By player with the number of the connection, `None` while they
+        /// are away and for the host.
+        players: Vec<Option<(u64, TcpStream)>>,
+        /// Players who stayed away too long, announced already.
+        forfeited: Vec<usize>,
+        /// By player, what they come back on their seat with. Empty for
+        /// the host.
+        tokens: Vec<String>,
+    },
+    Guest {
+        address: String,
+        /// `None` while the connection is lost.
+        writer: Option<TcpStream>,
+        /// While the connection is lost, since when and how getting back
+        /// went last.
+        lost: Option<Lost>,
+    },
+}
+
+struct Lost {
+    since: Instant,
+    tried: Instant,
+    trying: bool,
+    /// Why the host couldn't be had the last time, told once it is too late.
+    failed: Option<String>,
+}
+
+/// A co-op game as one player sees it, with the connections to the others.
+pub struct Session {
+    pub player: usize,
+    /// By player, as far as they are known.
+    pub names: Vec<String>,
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    /// What this player comes back on their seat with, see `join`. Empty
+    /// on the host.
+    pub token: String,
+    pub coop: Coop,
+    predictor: Predictor,
+    events: Receiver<Event>,
+    sender: Sender<Event>,
+    /// Connections listened to so far.
+    connections: u64,
+    link: Link,
+}
+
+impl Session {
+    /// Hosts a game on the board of `difficulty` and `seed`, taking the
+    /// players who connect to `listener`.
+    pub fn host(listener: TcpListener, name: String, difficulty: Difficulty, seed: u64) -> Result<Self, String> {
+        let field = race::board(difficulty, seed).map_err(|e| e.to_string())?;
+        let (sender, events) = channel();
+        let connected = sender.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let connected = connected.clone();
+                // a slow greeting doesn't hold up the next player
+                thread::spawn(move || {
+                    let _ = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+                    let mut reader = io::BufReader::new(stream);
+                    let mut line = String::new();
+                    if read_line(&mut reader, &mut line).is_ok() && reader.get_ref().set_read_timeout(None).is_ok() {
+                        if let Ok(greeting @ (Message::Hello(_) | Message::Back(..))) = line.parse() {
+                            let _ = connected.send(Event::Connected(greeting, reader));
+                        }
+                    }
+                });
+            }
+        });
+        let link = Link::Host {
+            authority: Box::new(Authority::new(field.clone())),
+            players: vec![None],
+            forfeited: vec![],
+            tokens: vec![String::new()],
+        };
+        Ok(Self {
+            player: 0,
+            names: vec![name],
+            difficulty,
+            seed,
+            token: String::new(),
+            coop: Coop::default(),
+            predictor: Predictor::new(0, field),
+            events,
+            sender,
+            connections: 0,
+            link,
+        })
+    }
+
+    /// Joins the game hosted at `address` as a new player, or back on
+    /// `seat` with its token after this side lost everything it knew about
+    /// the game.
+    pub fn join(address: &str, name: String, seat: Option<(usize, String)>) -> Result<Self, String> {
+        let greeting = match seat {
+            Some((player, token)) => Message::Back(player, 0, token),
+            None => Message::Hello(name.clone()),
+        };
+        let (writer, reader, welcome) = handshake(address, &greeting)?;
+        let Welcome { player, seed, seq, token, difficulty, missed } = welcome;
+        if race::too_big(difficulty) {
+            return Err(format!("The host picked a board of more than {} cells", MAX_CELLS));
+        }
+        let field = race::board(difficulty, seed).map_err(|e| e.to_string())?;
+        let (sender, events) = channel();
+        let mut names: Vec<String> = (0..player).map(|player| format!("Player {}", player + 1)).collect();
+        names.push(name);
+        let mut session = Self {
+            player,
+            names,
+            difficulty,
+            seed,
+            token,
+            coop: Coop::default(),
+            // the moves so far are played on top, to know who did what
+            predictor: Predictor::resume(player, Snapshot { field, order: 0, seq }),
+            events,
+            sender,
+            connections: 0,
+            link: Link::Guest { address: address.to_string(), writer: Some(writer), lost: None },
+        };
+        for applied in missed {
+            session.confirm(&applied);
+        }
+        session.listen(0, reader);
+        Ok(session)
+    }
+
+    /// The board to show, with own moves on it before the host ordered them.
+    pub fn field(&self) -> &Field {
+        self.predictor.field()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.coop.is_over(self.predictor.confirmed())
+    }
+
+    /// Passes the lines of `player` on as events, until the connection
+    /// is lost. Returns the number of the connection.
+    fn listen(&mut self, player: usize, mut reader: io::BufReader<TcpStream>) -> u64 {
+        self.connections += 1;
+        let connection = self.connections;
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let mut line = String::new();
+            while let Ok(1..) = read_line(&mut reader, &mut line) {
+                if let Ok(message) = line.parse() {
+                    if sender.send(Event::Received(player, message)).is_err() {
+                        return;
+                    }
+                }
+                line.clear();
+            }
+            let _ = sender.send(Event::Lost(player, connection));
+        });
+        connection
+    }
+
+    /// Handles what came in from the others without waiting and, on a
+    /// guest who lost the host, tries to get back. Returns what happened.
+    /// An error ends the game.
+    pub fn poll(&mut self) -> Result<Vec<News>, String> {
+        let mut news = vec![];
+        while let Ok(event) = self.events.try_recv() {
+            news.extend(self.handle(event));
+        }
+        let now = Instant::now();
+        let mut gone = vec![];
+        let mut retry = None;
+        match &mut self.link {
+            Link::Host { authority, forfeited, .. } => {
+                gone = authority.forfeited(now).into_iter().filter(|player| !forfeited.contains(player)).collect();
+                forfeited.extend(&gone);
+            },
+            Link::Guest { address, lost: Some(lost), .. } => {
+                if now.duration_since(lost.since) > GRACE {
+                    return Err(lost.failed.take().unwrap_or_else(|| "Lost the host, the game is over".into()));
+                }
+                if !lost.trying && now.duration_since(lost.tried) >= RETRY {
+                    lost.tried = now;
+                    lost.trying = true;
+                    retry = Some(address.clone());
+                }
+            },
+            Link::Guest { .. } => {},
+        }
+        for player in gone {
+            self.broadcast(Message::Forfeited(player));
+            news.push(News::Forfeited(player));
+        }
+        if let Some(address) = retry {
+            // the host may take a while to answer, the game goes on meanwhile
+            let greeting = Message::Back(self.player, self.predictor.seen(), self.token.clone());
+            let sender = self.sender.clone();
+            thread::spawn(move || {
+                let _ = sender.send(Event::Rejoined(handshake(&address, &greeting)));
+            });
+        }
+        Ok(news)
+    }
+
+    /// Catches up on what was missed while away from the host, sending the
+    /// own moves the host never got once more.
+    fn rejoined(&mut self, (writer, reader, welcome): Rejoined) -> Vec<News> {
+        let mut news: Vec<News> = welcome.missed.iter().filter_map(|applied| self.confirm(applied)).collect();
+        if let Link::Guest { writer: slot, lost, .. } = &mut self.link {
+            *slot = Some(writer);
+            *lost = None;
+        }
+        for mv in self.predictor.pending().to_vec() {
+            self.send_move(mv);
+        }
+        self.listen(0, reader);
+        news.push(News::Reconnected);
+        news
+    }
+
+    fn handle(&mut self, event: Event) -> Vec<News> {
+        let host = matches!(self.link, Link::Host { .. });
+        match event {
+            Event::Connected(greeting, reader) if host => self.greet(greeting, reader).into_iter().collect(),
+            Event::Lost(player, connection) => match &mut self.link {
+                Link::Host { authority, players, .. } => {
+                    // a player back already may still hear of their old connection
+                    if !matches!(players[player], Some((current, _)) if current == connection) {
+                        return vec![];
+                    }
+                    players[player] = None;
+                    authority.leave(player, Instant::now());
+                    self.broadcast(Message::Left(player));
+                    vec![News::Left(player)]
+                },
+                Link::Guest { writer, lost, .. } => {
+                    *writer = None;
+                    let now = Instant::now();
+                    *lost = Some(Lost { since: now, tried: now - RETRY, trying: false, failed: None });
+                    vec![News::Reconnecting]
+                },
+            },
+            Event::Rejoined(Ok(rejoined)) if !host => self.rejoined(rejoined),
+            Event::Rejoined(Err(reason)) => {
+                // a host who didn't notice the old connection is gone yet
+                // turns us down too, so it is tried again until too late
+                if let Link::Guest { lost: Some(lost), .. } = &mut self.link {
+                    lost.trying = false;
+                    lost.failed = Some(reason);
+                }
+                vec![]
+            },
+            Event::Received(player, Message::Move(seq, seen, action, x, y)) if host => {
+                self.order(Move { player, seq, seen, action, x, y })
+            },
+            // the player is the one of the connection, whoever it claims to be
+            Event::Received(player, Message::Chat(_, text)) if host => {
+                self.broadcast(Message::Chat(player, text.clone()));
+                vec![News::Chat(player, text)]
+            },
+            Event::Received(_, message) if !host => match message {
+                Message::Played(applied) => self.confirm(&applied).into_iter().collect(),
+                Message::Joined(player, name) => {
+                    if self.names.len() <= player {
+                        self.names.resize_with(player + 1, String::new);
+                    }
+                    self.names[player] = name;
+                    // everybody there before this player came is greeted at once
+                    if player != self.player { vec![News::Joined(player)] } else { vec![] }
+                },
+                Message::Left(player) => vec![News::Left(player)],
+                Message::Forfeited(player) => vec![News::Forfeited(player)],
+                Message::Rejected(reason) => vec![News::NotPlayed(reason)],
+                // own lines come back from the host, and were shown already
+                Message::Chat(player, text) if player != self.player => vec![News::Chat(player, text)],
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Seats a player who greeted the host and sends them everything they
+    /// need to play along.
+    fn greet(&mut self, greeting: Message, reader: io::BufReader<TcpStream>) -> Option<News> {
+        let mut stream = reader.get_ref().try_clone().ok()?;
+        let (player, seen, news) = match greeting {
+            Message::Hello(_) if self.names.len() >= MAX_PLAYERS => {
+                let _ = writeln!(stream, "{}", Message::Rejected(format!("the game is full with {} players", MAX_PLAYERS)));
+                return None;
+            },
+            Message::Hello(name) => {
+                let player = self.names.len();
+                self.broadcast(Message::Joined(player, name.clone()));
+                self.names.push(name);
+                if let Link::Host { players, tokens, .. } = &mut self.link {
+                    players.push(None);
+                    tokens.push(format!("{:016x}", rand::thread_rng().gen::<u64>()));
+                }
+                (player, 0, News::Joined(player))
+            },
+            Message::Back(player, seen, token) => {
+                let refusal = match &mut self.link {
+                    Link::Host { tokens, .. } if player == 0 || tokens.get(player) != Some(&token) => {
+                        Some(format!("that isn't the token of seat {}", player + 1))
+                    },
+                    Link::Host { players, .. } if players[player].is_some() => Some(format!("seat {} is taken", player + 1)),
+                    Link::Host { authority, .. } => {
+                        if authority.rejoin(player, Instant::now()) { None } else { Some(format!("player {} stayed away too long", player + 1)) }
+                    },
+                    Link::Guest { .. } => None,
+                };
+                if let Some(reason) = refusal {
+                    let _ = writeln!(stream, "{}", Message::Rejected(reason));
+                    return None;
+                }
+                (player, seen, News::Back(player))
+            },
+            _ => return None,
+        };
+        let (authority, token) = match &self.link {
+            Link::Host { authority, tokens, .. } => (authority, tokens[player].clone()),
+            Link::Guest { .. } => return None,
+        };
+        let missed = match authority.since(seen) {
+            Some(missed) => missed,
+            None => {
+                let _ = writeln!(stream, "{}", Message::Rejected("you saw moves this game never had".into()));
+                return None;
+            },
+        };
+        let seq = authority.snapshot(player).seq;
+        let mut welcome = vec![Message::Welcome(player, self.seed, seq, missed.len(), token, self.difficulty)];
+        welcome.extend(missed.iter().map(|applied| Message::Played(*applied)));
+        welcome.extend(self.names.iter().enumerate().map(|(player, name)| Message::Joined(player, name.clone())));
+        for message in welcome {
+            writeln!(stream, "{}", message).ok()?;
+        }
+        if news == News::Back(player) {
+            self.broadcast(Message::Joined(player, self.names[player].clone()));
+        }
+        let connection = self.listen(player, reader);
+        if let Link::Host { players, .. } = &mut self.link {
+            players[player] = Some((connection, stream));
+        }
+        Some(news)
+    }
+
+    /// Orders a move on the host, checked against the rules of the game,
+    /// and passes it on.
+    fn order(&mut self, mv: Move) -> Vec<News> {
+        let authority = match &mut self.link {
+            Link::Host { authority, .. } => authority,
+            Link::Guest { .. } => return vec![],
+        };
+        let mut news = vec![];
+        let applied = match self.coop.check(authority.field(), mv.player, mv.action, mv.x, mv.y) {
+            Ok(()) => authority.apply(mv),
+            Err(reason) => {
+                let applied = authority.reject(mv);
+                if mv.player == self.player {
+                    news.push(News::NotPlayed(reason));
+                } else {
+                    self.send_to(mv.player, Message::Rejected(reason));
+                }
+                applied
+            },
+        };
+        self.broadcast(Message::Played(applied));
+        news.extend(self.confirm(&applied));
+        news
+    }
+
+    /// Takes a move ordered by the host, returning it as news if it is one.
+    fn confirm(&mut self, applied: &Applied) -> Option<News> {
+        let before = self.predictor.confirmed().opened_count();
+        let dropped = self.predictor.confirm(applied);
+        self.coop.record(applied, before, self.predictor.confirmed());
+        let others = applied.mv.player != self.player && applied.outcome == Outcome::Applied;
+        let overtaken = dropped && applied.outcome == Outcome::Conflict;
+        (others || overtaken).then_some(News::Played(*applied))
+    }
+
+    /// Plays a move of this player: shown at once and ordered right away
+    /// on the host, or sent to the host when joined.
+    pub fn submit(&mut self, action: Action, x: u16, y: u16) -> Vec<News> {
+        let mv = self.predictor.local(action, x, y);
+        match self.link {
+            Link::Host { .. } => self.order(mv),
+            Link::Guest { .. } => {
+                self.send_move(mv);
+                vec![]
+            },
+        }
+    }
+
+    /// Sends a chat line of this player to the others.
+    pub fn say(&mut self, text: &str) {
+        let message = Message::Chat(self.player, text.trim().to_string());
+        match &mut self.link {
+            Link::Host { .. } => self.broadcast(message),
+            // a line said while the host is away is lost
+            Link::Guest { writer: Some(writer), .. } => {
+                let _ = writeln!(writer, "{}", message);
+            },
+            Link::Guest { .. } => {},
+        }
+    }
+
+    fn send_move(&mut self, mv: Move) {
+        if let Link::Guest { writer: Some(writer), .. } = &mut self.link {
+            // a lost connection shows up on the reading side, and the move
+            // is sent again once back
+            let _ = writeln!(writer, "{}", Message::Move(mv.seq, mv.seen, mv.action, mv.x, mv.y));
+        }
+    }
+
+    /// Passes a message on to every player connected, on the host.
+    fn broadcast(&mut self, message: Message) {
+        if let Link::Host { players, .. } = &mut self.link {
+            // a player who can't be reached anymore leaves on their own
+            for (_, stream) in players.iter_mut().flatten() {
+                let _ = writeln!(stream, "{}", message);
+            }
+        }
+    }
+
+    fn send_to(&mut self, player: usize, message: Message) {
+        if let Link::Host { players, .. } = &mut self.link {
+            if let Some(Some((_, stream))) = players.get_mut(player) {
+                let _ = writeln!(stream, "{}", message);
+            }
+        }
+    }
+}
+
+/// What a guest learns from the host when greeting it.
+struct Welcome {
+    player: usize,
+    seed: u64,
+    seq: u32,
+    token: String,
+    difficulty: Difficulty,
+    /// The moves ordered since what the guest saw.
+    missed: Vec<Applied>,
+}
+
+/// Connects to the host at `address`, greets it and reads the welcome with
+/// the moves that come with it.
+fn handshake(address: &str, greeting: &Message) -> Result<Rejoined, String> {
+    let unreachable = |e: io::Error| format!("Can't reach the host at {}: {}", address, e);
+    let socket = address.to_socket_addrs().map_err(unreachable)?
+        .next()
+        .ok_or_else(|| format!("Can't find {}", address))?;
+    let stream = TcpStream::connect_timeout(&socket, HANDSHAKE_TIMEOUT).map_err(unreachable)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(unreachable)?;
+    let mut writer = stream.try_clone().map_err(unreachable)?;
+    writeln!(writer, "{}", greeting).map_err(unreachable)?;
+    let mut reader = io::BufReader::new(stream);
+    let mut read = || -> Result<Message, String> {
+        let mut line = String::new();
+        match read_line(&mut reader, &mut line) {
+            Ok(0) => Err("The host closed the connection".into()),
+            Ok(_) => line.parse(),
+            Err(e) => Err(unreachable(e)),
+        }
+    };
+    let (player, seed, seq, moves, token, difficulty) = match read()? {
+        Message::Welcome(player, seed, seq, moves, token, difficulty) => (player, seed, seq, moves, token, difficulty),
+        Message::Rejected(reason) => return Err(format!("The host turned us down: {}", reason)),
+        other => return Err(format!("Expected a welcome, got '{}'", other)),
+    };
+    let missed = (0..moves)
+        .map(|_| match read()? {
+            Message::Played(applied) => Ok(applied),
+            other => Err(format!("Expected a move, got '{}'", other)),
+        })
+        .collect::<Result<_, String>>()?;
+    reader.get_ref().set_read_timeout(None).map_err(unreachable)?;
+    Ok((writer, reader, Welcome { player, seed, seq, token, difficulty, missed }))
+}
+
+/// Reads a line of at most `MAX_LINE` bytes into `line`, so the other side
+/// can't fill up memory by never ending one. Races read their lines with it
+/// as well.
+pub(crate) fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> io::Result<usize> {
+    let read = Read::take(&mut *reader, MAX_LINE as u64).read_line(line)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::coop::{read_line, Coop, Link, Message, News, Session, MAX_LINE, MAX_PLAYERS};
+    use crate::replay::Action;
+    use crate::sync::{Applied, Authority, Move, Outcome};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn messages() {
+        let mv = Move { player: 2, seq: 5, seen: 9, action: Action::Flag, x: 0, y: 7 };
+        let messages = vec![
+            Message::Hello("Grace Hopper".into()),
+            Message::Back(1, 12, "0123456789abcdef".into()),
+            Message::Welcome(2, 99, 3, 4, "0123456789abcdef".into(), Difficulty::Custom(8, 8, 9)),
+            Message::Joined(1, "Ada Lovelace".into()),
+            Message::Left(1),
+            Message::Forfeited(1),
+            Message::Move(3, 8, Action::Chord, 3, 14),
+            Message::Played(Applied { order: 10, mv, outcome: Outcome::Conflict }),
+            Message::Rejected("3 4 is flagged".into()),
+            Message::Chat(2, "mine at 3 4,  careful".into()),
+        ];
+        for message in messages {
+            assert_eq!(Ok(message.clone()), message.to_string().parse());
+        }
+        assert!("move 1 2 dig 1 2".parse::<Message>().is_err());
+        assert!("played 1 open 2".parse::<Message>().is_err());
+        assert!("quit".parse::<Message>().is_err());
+        assert!("back 1 12".parse::<Message>().is_err());
+        assert!("chat 1".parse::<Message>().is_err());
+        // a seat far out would have the names grow without end
+        assert!("joined 18446744073709551615 eve".parse::<Message>().is_err());
+        assert!(format!("left {}", MAX_PLAYERS).parse::<Message>().is_err());
+        assert_eq!(Ok(Message::Joined(1, "[2Jeve".into())), "joined 1 \x1b[2Jeve".parse());
+        assert_eq!(Ok(Message::Chat(1, "hi".into())), "chat 1 h\x07i".parse());
+    }
+
+    #[test]
+    fn long_lines() {
+        let mut line = String::new();
+        let long = "hello ".to_string() + &"a".repeat(MAX_LINE);
+        assert!(read_line(&mut long.as_bytes(), &mut line).is_err());
+        line.clear();
+        let fits = "hello ".to_string() + &"a".repeat(MAX_LINE - 7) + "\n";
+        assert_eq!(MAX_LINE, read_line(&mut fits.as_bytes(), &mut line).unwrap());
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut authority = Authority::new(crate::race::board(Difficulty::Beginner, 3).unwrap());
+        let mut coop = Coop::default();
+        let mut play = |coop: &mut Coop, player: usize, action: Action, (x, y): (u16, u16)| {
+            let checked = coop.check(authority.field(), player, action, x, y);
+            let mv = Move { player, seq: 0, seen: u64::MAX, action, x, y };
+            let before = authority.field().opened_count();
+            let applied = if checked.is_ok() { authority.apply(mv) } else { authority.reject(mv) };
+            coop.record(&applied, before, authority.field());
+            checked
+        };
+        let field = crate::race::board(Difficulty::Beginner, 3).unwrap();
+        let cells = || (0..9).flat_map(|x| (0..9).map(move |y| (x, y)));
+        let closed = cells().find(|&(x, y)| !field.cell(x, y).unwrap().opened).unwrap();
+        play(&mut coop, 1, Action::Flag, closed).unwrap();
+        assert_eq!(Some(&1), coop.flag_owners.get(&closed));
+        assert!(play(&mut coop, 0, Action::Flag, closed).is_err());
+        assert!(play(&mut coop, 0, Action::Open, closed).is_err());
+        assert!(play(&mut coop, 0, Action::Chord, closed).is_err());
+        assert!(play(&mut coop, 0, Action::Hint, closed).is_err());
+        play(&mut coop, 1, Action::Flag, closed).unwrap();
+        assert!(coop.flag_owners.is_empty());
+
+        // the first of two players opening the same cell gets it
+        let safe = cells().find(|&(x, y)| !field.cell(x, y).unwrap().opened && !field.cell(x, y).unwrap().is_mine()).unwrap();
+        play(&mut coop, 0, Action::Open, safe).unwrap();
+        assert!(coop.opened[&0] > 0);
+        assert!(play(&mut coop, 1, Action::Open, safe).is_err());
+
+        let mine = cells().find(|&(x, y)| field.cell(x, y).unwrap().is_mine()).unwrap();
+        play(&mut coop, 1, Action::Open, mine).unwrap();
+        assert_eq!(Some(1), coop.hit_by);
+        assert!(play(&mut coop, 0, Action::Open, safe).is_err());
+    }
+
+    /// Polls `session` until `done` holds for it, collecting the news.
+    fn wait(session: &mut Session, done: impl Fn(&Session, &[News]) -> bool) -> Vec<News> {
+        let start = Instant::now();
+        let mut news = vec![];
+        while !done(session, &news) {
+            assert!(start.elapsed() < Duration::from_secs(10), "gave up waiting, got {:?}", news);
+            news.extend(session.poll().unwrap());
+            thread::sleep(Duration::from_millis(5));
+        }
+        news
+    }
+
+    #[test]
+    fn play_together() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut host = Session::host(listener, "ada".into(), Difficulty::Beginner, 5).unwrap();
+        let guest = thread::spawn(move || Session::join(&address, "bo".into(), None).unwrap());
+        wait(&mut host, |_, news| news.contains(&News::Joined(1)));
+        let mut guest = guest.join().unwrap();
+        wait(&mut guest, |guest, _| guest.names[0] == "ada");
+        assert_eq!(1, guest.player);
+
+        let closed: Vec<(u16, u16)> = (0..9).flat_map(|x| (0..9).map(move |y| (x, y)))
+            .filter(|&(x, y)| !host.field().cell(x, y).unwrap().opened)
+            .collect();
+        // shown at once, ordered by the host and passed on to everyone
+        guest.submit(Action::Flag, closed[0].0, closed[0].1);
+        assert!(guest.field().cell(closed[0].0, closed[0].1).unwrap().is_flagged());
+        let news = wait(&mut host, |host, _| host.field().cell(closed[0].0, closed[0].1).unwrap().is_flagged());
+        assert!(matches!(news[..], [News::Played(Applied { outcome: Outcome::Applied, .. })]));
+        assert!(host.submit(Action::Flag, closed[0].0, closed[0].1).iter().any(|news| matches!(news, News::NotPlayed(_))));
+        assert!(!host.field().cell(closed[0].0, closed[0].1).unwrap().is_flagged() || host.coop.flag_owners[&closed[0]] == 1);
+
+        // the guest loses the host, plays on and catches up once back
+        if let Link::Host { players, .. } = &mut host.link {
+            players[1].as_ref().unwrap().1.shutdown(std::net::Shutdown::Both).unwrap();
+        }
+        wait(&mut guest, |_, news| news.contains(&News::Reconnecting));
+        wait(&mut host, |_, news| news.contains(&News::Left(1)));
+        host.submit(Action::Flag, closed[1].0, closed[1].1);
+        guest.submit(Action::Flag, closed[2].0, closed[2].1);
+        // the host answers the guest as it polls
+        let start = Instant::now();
+        while !guest.poll().unwrap().contains(&News::Reconnected) {
+            assert!(start.elapsed() < Duration::from_secs(10), "the guest didn't get back");
+            host.poll().unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+        wait(&mut host, |host, _| host.field().cell(closed[2].0, closed[2].1).unwrap().is_flagged());
+        wait(&mut guest, |guest, _| guest.predictor.pending().is_empty());
+        assert!(guest.field().cell(closed[1].0, closed[1].1).unwrap().is_flagged());
+        assert_eq!(host.coop.flag_owners, guest.coop.flag_owners);
+        assert!(host.predictor.confirmed().cells == guest.predictor.confirmed().cells);
+
+        // chat goes through the host, as the player of the connection
+        guest.say("left side is clear");
+        wait(&mut host, |_, news| news.contains(&News::Chat(1, "left side is clear".into())));
+        host.say("thanks");
+        let news = wait(&mut guest, |_, news| news.iter().any(|news| matches!(news, News::Chat(0, _))));
+        assert_eq!(vec![News::Chat(0, "thanks".into())], news);
+
+        // a seat can't be had without its token, nor while it is taken
+        let address = match &guest.link {
+            Link::Guest { address, .. } => address.clone(),
+            _ => unreachable!(),
+        };
+        for (token, refusal) in [("guess".to_string(), "isn't the token of seat 2"), (guest.token.clone(), "seat 2 is taken")] {
+            let address = address.clone();
+            let intruder = thread::spawn(move || Session::join(&address, "cy".into(), Some((1, token))).err());
+            wait(&mut host, |_, _| intruder.is_finished());
+            assert!(intruder.join().unwrap().unwrap().contains(refusal));
+        }
+    }
+}
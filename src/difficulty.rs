@@ -0,0 +1,142 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// Board settings, either one of the classic presets or custom dimensions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    /// 9 x 9 with 10 mines.
+    Beginner,
+    /// 16 x 16 with 40 mines.
+    Intermediate,
+    /// 30 x 16 with 99 mines.
+    Expert,
+    /// Width, height and mines.
+    Custom(u16, u16, u16),
+}
+
+impl Difficulty {
+    /// Custom board of `width` x `height` with the default density of 10%.
+    pub fn custom(width: u16, height: u16) -> Self {
+        Difficulty::Custom(width, height, 0).with_density(10)
+    }
+
+    /// The same board size with exactly `mines` mines.
+    pub fn with_mines(self, mines: u16) -> Self {
+        let (width, height, _) = self.dimensions();
+        Difficulty::Custom(width, height, mines)
+    }
+
+    /// The same board size with `percent` of the cells being mines.
+    pub fn with_density(self, percent: u8) -> Self {
+        let (width, height, _) = self.dimensions();
+        // the cells of the biggest boards times 100 overflow a u32
+        let mines = width as u64 * height as u64 * percent as u64 / 100;
+        Difficulty::Custom(width, height, u16::try_from(mines).unwrap_or(u16::MAX))
+    }
+
+    /// Reads the board settings from command line arguments: a preset name,
+    /// `custom <width> <height> [mines]` or `<width> <height>`. Without
+    /// arguments the board is a beginner one.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let number = |i: usize| -> Result<u16, String> {
+            let arg = args.get(i).ok_or("custom boards need a width and a height")?;
+            arg.parse().map_err(|_| format!("Invalid number '{}'", arg))
+        };
+        let (difficulty, used) = match args.first().map(|a| a.as_str()) {
+            Some("custom") if args.len() > 3 => (Difficulty::Custom(number(1)?, number(2)?, number(3)?), 4),
+            Some("custom") => (Difficulty::custom(number(1)?, number(2)?), 3),
+            Some(name) if name.parse::<u16>().is_err() => (name.parse()?, 1),
+            Some(_) => (Difficulty::custom(number(0)?, number(1)?), 2),
+            None => (Difficulty::Beginner, 0),
+        };
+        if let Some(extra) = args.get(used) {
+            return Err(format!("Unexpected argument '{}'", extra));
+        }
+        Ok(difficulty)
+    }
+
+    /// Width, height and number of mines.
+    pub fn dimensions(self) -> (u16, u16, u16) {
+        match self {
+            Difficulty::Beginner => (9, 9, 10),
+            Difficulty::Intermediate => (16, 16, 40),
+            Difficulty::Expert => (30, 16, 99),
+            Difficulty::Custom(width, height, mines) => (width, height, mines),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "beginner" => Ok(Difficulty::Beginner),
+            "intermediate" => Ok(Difficulty::Intermediate),
+            "expert" => Ok(Difficulty::Expert),
+            // the way custom boards are displayed
+            _ if s.starts_with("custom ") => {
+                let invalid = || format!("Invalid custom board '{}', expected custom <width>x<height>/<mines>", s);
+                let (size, mines) = s["custom ".len()..].split_once('/').ok_or_else(invalid)?;
+                let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+                let number = |n: &str| n.parse::<u16>().map_err(|_| invalid());
+                Ok(Difficulty::Custom(number(width)?, number(height)?, number(mines)?))
+            },
+            _ => Err(format!("Unknown difficulty '{}', expected beginner, intermediate, expert or custom", s)),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difficulty::Beginner => write!(f, "beginner"),
+            Difficulty::Intermediate => write!(f, "intermediate"),
+            Difficulty::Expert => write!(f, "expert"),
+            Difficulty::Custom(width, height, mines) => write!(f, "custom {}x{}/{}", width, height, mines),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+
+    #[test]
+    fn presets() {
+        assert_eq!(Ok(Difficulty::Beginner), "beginner".parse());
+        assert_eq!((16, 16, 40), "intermediate".parse::<Difficulty>().unwrap().dimensions());
+        assert_eq!((30, 16, 99), Difficulty::Expert.dimensions());
+        assert!("nightmare".parse::<Difficulty>().is_err());
+    }
+
+    #[test]
+    fn from_args() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        assert_eq!(Ok(Difficulty::Beginner), Difficulty::from_args(&[]));
+        assert_eq!(Ok(Difficulty::Expert), Difficulty::from_args(&args("expert")));
+        assert_eq!(Ok(Difficulty::Custom(20, 10, 5)), Difficulty::from_args(&args("custom 20 10 5")));
+        assert_eq!(Ok(Difficulty::custom(20, 10)), Difficulty::from_args(&args("20 10")));
+        assert!(Difficulty::from_args(&args("20")).is_err());
+        assert!(Difficulty::from_args(&args("custom 20 ten")).is_err());
+        assert!(Difficulty::from_args(&args("expert 5")).is_err());
+    }
+
+    #[test]
+    fn custom() {
+        assert_eq!(Difficulty::Custom(20, 10, 20), Difficulty::custom(20, 10));
+        assert_eq!("custom 20x10/20", Difficulty::custom(20, 10).to_string());
+        assert_eq!(Ok(Difficulty::custom(20, 10)), "custom 20x10/20".parse());
+        assert!("custom 20x10".parse::<Difficulty>().is_err());
+        assert_eq!(Difficulty::Custom(9, 9, 30), Difficulty::Beginner.with_mines(30));
+        assert_eq!(Difficulty::Custom(30, 16, 120), Difficulty::Expert.with_density(25));
+    }
+
+    #[test]
+    fn large() {
+        assert_eq!(Difficulty::Custom(300, 300, 9000), Difficulty::custom(300, 300));
+        assert_eq!(Difficulty::Custom(1000, 1000, u16::MAX), Difficulty::custom(1000, 1000));
+        assert_eq!(Difficulty::Custom(u16::MAX, u16::MAX, u16::MAX), Difficulty::Custom(u16::MAX, u16::MAX, 0).with_density(100));
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{Difficulty, Field, MinesError};
+use crate::replay::Action;
+use crate::solver::{deductions, Deduction};
+use crate::training::visible_state;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+/// How a board is turned into numbers for a learning agent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    /// One value per cell, row by row, as `training::visible_state` has it:
+    /// the number of an opened cell, -1 closed, -2 flagged and -3 a hole.
+    Visible,
+    /// Eleven planes of one value per cell, each 1 where the cell is in the
+    /// plane's state and 0 elsewhere: closed, flagged and opened with 0 to
+    /// 8 neighbouring mines.
+    Planes,
+}
+
+impl Encoding {
+    /// Values of the planes per cell.
+    pub fn channels(self) -> usize {
+        match self {
+            Encoding::Visible => 1,
+            Encoding::Planes => 11,
+        }
+    }
+}
+
+/// What an agent sees after every step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    /// Plane by plane, row by row.
+    pub values: Vec<f32>,
+    pub channels: usize,
+    pub rows: u16,
+    pub cols: u16,
+    pub mines_left: isize,
+}
+
+/// Rewards handed out by `Environment::step`. Everything but winning and
+/// losing is there to shape learning and left at 0 for sparse rewards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rewards {
+    pub win: f64,
+    pub loss: f64,
+    /// For every cell a move opened.
+    pub opened: f64,
+    /// For opening a cell the solver couldn't tell is safe.
+    pub guess: f64,
+    /// For a flag placed on a mine, and taken off again when it is removed.
+    pub flag: f64,
+    /// For a move that changes nothing, like opening an opened cell.
+    pub wasted: f64,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self { win: 1.0, loss: -1.0, opened: 0.0, guess: 0.0, flag: 0.0, wasted: -0.1 }
+    }
+}
+
+/// One step of an episode, the way reinforcement learning libraries want it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    pub observation: Observation,
+    pub reward: f64,
+    /// Whether the game ended with this step.
+    pub done: bool,
+}
+
+/// Minesweeper as an environment for reinforcement learning agents: `reset`
+/// starts an episode on a board made from a seed, `step` plays a move and
+/// tells the agent what it sees, what the move earned and whether the game
+/// is over.
+pub struct Environment {
+    pub difficulty: Difficulty,
+    pub encoding: Encoding,
+    pub rewards: Rewards,
+    /// Whether a mine below the first opened cell is moved away.
+    pub safe_start: bool,
+    field: Field,
+    rng: StdRng,
+    moved_first: bool,
+    done: bool,
+}
+
+impl Environment {
+    /// An environment for boards of `difficulty`, seen as planes with the
+    /// default rewards. Call `reset` to start the first episode.
+    pub fn new(difficulty: Difficulty) -> Result<Self, MinesError> {
+        let mut environment = Self {
+            difficulty,
+            encoding: Encoding::Planes,
+            rewards: Rewards::default(),
+            safe_start: true,
+            field: Field::parse(".")?,
+            rng: StdRng::seed_from_u64(0),
+            moved_first: false,
+            done: false,
+        };
+        environment.reset(0)?;
+        Ok(environment)
+    }
+
+    /// Starts an episode on a new board, the same for the same seed.
+    pub fn reset(&mut self, seed: u64) -> Result<Observation, MinesError> {
+        let (width, height, mines) = self.difficulty.dimensions();
+        self.rng = StdRng::seed_from_u64(seed);
+        self.field = Field::generate(&mut self.rng, height, width, mines)?;
+        self.moved_first = false;
+        self.done = false;
+        Ok(self.observe())
+    }
+
+    /// The board of the episode.
+    pub fn field(&self) -> &Field {
+        &self.field
+    }
+
+    /// Whether the episode is over.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Cells that opening would change, row by row, for agents masking out
+    /// moves that make no sense.
+    pub fn openable(&self) -> Vec<bool> {
+        self.field.cells.iter().flatten().map(|cell| !cell.opened && !cell.is_flagged()).collect()
+    }
+
+    /// Plays `action` on the cell at `(x, y)`. Moves after the end of an
+    /// episode and actions that aren't moves on the board, like undo, earn
+    /// nothing and leave the board as it is.
+    pub fn step(&mut self, action: Action, x: u16, y: u16) -> Transition {
+        let reward = if self.done {
+            0.0
+        } else {
+            self.play(action, x, y)
+        };
+        Transition { observation: self.observe(), reward, done: self.done }
+    }
+
+    /// Like `step` with the action and cell taken from one number: the
+    /// cell's index row by row, plus the cells once for a flag and twice for
+    /// a chord.
+    pub fn step_index(&mut self, index: usize) -> Transition {
+        let (cols, rows) = self.field.size();
+        let cells = rows as usize * cols as usize;
+        let action = match index / cells.max(1) {
+            0 => Action::Open,
+            1 => Action::Flag,
+            _ => Action::Chord,
+        };
+        let cell = index % cells.max(1);
+        self.step(action, (cell / cols as usize) as u16, (cell % cols as usize) as u16)
+    }
+
+    fn play(&mut self, action: Action, x: u16, y: u16) -> f64 {
+        let rewards = self.rewards;
+        let cell = match self.field.cell(x, y) {
+            Ok(cell) => cell.clone(),
+            Err(_) => return rewards.wasted,
+        };
+        let opened_before = self.field.opened_count();
+        let result = match action {
+            Action::Open | Action::Chord => {
+                let guess = action == Action::Open && !cell.opened && rewards.guess != 0.0
+                    && !deductions(&self.field).contains(&Deduction::Safe(x, y));
+                if action == Action::Open && self.safe_start && !self.moved_first && !cell.opened {
+                    let _ = self.field.ensure_safe(x, y, &mut self.rng);
+                    self.moved_first = true;
+                }
+                // the first move is always a guess, but not one worth a penalty
+                let guess_reward = if guess && opened_before > 0 { rewards.guess } else { 0.0 };
+                let result = if action == Action::Open { self.field.open(x, y) } else { self.field.chord(x, y) };
+                result.map(|_| guess_reward)
+            },
+            Action::Flag => self.field.flag(x, y).map(|_| {
+                let flagged = self.field.cell(x, y).is_ok_and(|cell| cell.is_flagged());
+                match (cell.is_mine(), flagged) {
+                    (true, true) => rewards.flag,
+                    (true, false) => -rewards.flag,
+                    _ => 0.0,
+                }
+            }),
+            Action::Hint | Action::Undo | Action::Redo => return rewards.wasted,
+        };
+        match result {
+            Err(MinesError::MineOpened) => {
+                self.done = true;
+                rewards.loss
+            },
+            _ if self.field.is_won() => {
+                self.done = true;
+                rewards.win
+            },
+            Err(_) => rewards.wasted,
+            Ok(reward) => {
+                let opened = self.field.opened_count() - opened_before;
+                if opened == 0 && action != Action::Flag {
+                    rewards.wasted
+                } else {
+                    reward + rewards.opened * opened as f64
+                }
+            },
+        }
+    }
+
+    fn observe(&self) -> Observation {
+        let (cols, rows) = self.field.size();
+        let state = visible_state(&self.field);
+        let values = match self.encoding {
+            Encoding::Visible => state.iter().map(|&v| v as f32).collect(),
+            Encoding::Planes => {
+                let plane = |v: i8| match v {
+                    -1 => Some(0),
+                    -2 => Some(1),
+                    0..=8 => Some(2 + v as usize),
+                    _ => None,
+                };
+                let mut values = vec![0.0; 11 * state.len()];
+                for (i, &v) in state.iter().enumerate() {
+                    if let Some(plane) = plane(v) {
+                        values[plane * state.len() + i] = 1.0;
+                    }
+                }
+                values
+            },
+        };
+        Observation { values, channels: self.encoding.channels(), rows, cols, mines_left: self.field.mines_left() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::environment::{Encoding, Environment, Rewards};
+    use crate::replay::Action;
+
+    #[test]
+    fn episode() {
+        let mut environment = Environment::new(Difficulty::Custom(4, 3, 2)).unwrap();
+        environment.rewards = Rewards { opened: 0.01, ..Rewards::default() };
+        let observation = environment.reset(5).unwrap();
+        assert_eq!((11 * 12, 3, 4, 2), (observation.values.len(), observation.rows, observation.cols, observation.mines_left));
+        // every cell starts closed
+        assert_eq!(12.0, observation.values[..12].iter().sum::<f32>());
+        assert!(environment.openable().iter().all(|&open| open));
+
+        let first = environment.step(Action::Open, 1, 1);
+        assert!(first.reward > 0.0 || first.done);
+        assert_eq!(-0.1, environment.step(Action::Open, 1, 1).reward);
+        assert_eq!(-0.1, environment.step(Action::Undo, 0, 0).reward);
+        assert_eq!(-0.1, environment.step(Action::Open, 9, 9).reward);
+
+        // playing every safe cell wins
+        let mut last = first;
+        for index in 0..12 {
+            let (x, y) = ((index / 4) as u16, (index % 4) as u16);
+            if !environment.field().cell(x, y).unwrap().is_mine() && !environment.is_done() {
+                last = environment.step_index(index);
+            }
+        }
+        assert!(last.done);
+        assert_eq!(1.0, last.reward);
+        assert_eq!(0.0, environment.step(Action::Open, 0, 0).reward);
+
+        environment.encoding = Encoding::Visible;
+        let observation = environment.reset(5).unwrap();
+        assert_eq!(vec![-1.0; 12], observation.values);
+        assert!(!environment.is_done());
+    }
+
+    #[test]
+    fn losing() {
+        let mut environment = Environment::new(Difficulty::Custom(4, 3, 2)).unwrap();
+        environment.safe_start = false;
+        environment.rewards.flag = 0.5;
+        environment.reset(1).unwrap();
+        let mines: Vec<usize> = (0..12)
+            .filter(|&i| environment.field().cell((i / 4) as u16, (i % 4) as u16).unwrap().is_mine())
+            .collect();
+        assert_eq!(0.5, environment.step_index(12 + mines[0]).reward);
+        assert_eq!(-0.5, environment.step_index(12 + mines[0]).reward);
+        let last = environment.step_index(mines[1]);
+        assert_eq!((-1.0, true), (last.reward, last.done));
+    }
+}
@@ -0,0 +1,46 @@
+use std::fmt;
+
+/// Errors returned by the engine. Coordinates are `(x, y)` as passed to the
+/// failing call.
+#[derive(Debug, PartialEq)]
+pub enum MinesError {
+    /// A mine was opened, the game is lost.
+    MineOpened,
+    OutOfBounds(u16, u16),
+    EmptyField,
+    FieldTooSmall(u16, u16),
+    TooManyMines,
+    /// A board file could not be parsed, with the 1-based line and the reason.
+    InvalidBoard(usize, String),
+    /// The cell belongs to a stage that isn't unlocked yet.
+    ZoneLocked(u16, u16),
+    /// No board solvable without guessing was found within the given number of attempts.
+    NoGuessBoardNotFound(u32),
+    /// A board file starts with an opened mine at the given cell.
+    MineOpenedInBoard(u16, u16),
+    /// The work was stopped through its `CancelToken`.
+    Cancelled,
+    /// The rows of a board can't be split into the given number of layers.
+    UnevenLayers(u16, u16),
+}
+
+impl fmt::Display for MinesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // coordinates are shown the way players type them, column first and 1-based
+        match self {
+            MinesError::MineOpened => write!(f, "A mine was opened"),
+            MinesError::OutOfBounds(x, y) => write!(f, "{} {} is outside the board", y + 1, x + 1),
+            MinesError::EmptyField => write!(f, "The board needs at least one row and column"),
+            MinesError::FieldTooSmall(width, height) => write!(f, "A {} x {} board is too small", height, width),
+            MinesError::TooManyMines => write!(f, "Too many mines, at least one cell has to be free"),
+            MinesError::InvalidBoard(line, reason) => write!(f, "Invalid board in line {}: {}", line, reason),
+            MinesError::ZoneLocked(_, _) => write!(f, "Clear the previous stage first"),
+            MinesError::NoGuessBoardNotFound(attempts) => write!(f, "No board without guessing found in {} attempts", attempts),
+            MinesError::MineOpenedInBoard(x, y) => write!(f, "Board starts with an opened mine at {} {}", y + 1, x + 1),
+            MinesError::Cancelled => write!(f, "Cancelled"),
+            MinesError::UnevenLayers(rows, layers) => write!(f, "{} rows can't be split into {} layers", rows, layers),
+        }
+    }
+}
+
+impl std::error::Error for MinesError {}
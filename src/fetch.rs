@@ -0,0 +1,193 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// Largest download taken, far more than any puzzle pack needs.
+const MAX_DOWNLOAD: usize = 16 << 20;
+/// Redirects followed before giving up.
+const MAX_REDIRECTS: usize = 5;
+
+/// Host, port and path of an `http://` URL.
+pub fn parse_url(url: &str) -> Result<(String, u16, String), String> {
+    if url.starts_with("https://") {
+        return Err(format!("Can't download {}: only http:// is supported, the pinned checksum keeps the download honest", url));
+    }
+    let rest = url.strip_prefix("http://").ok_or_else(|| format!("Can't download {}: not an http:// URL", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| format!("Can't download {}: invalid port", url))?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(format!("Can't download {}: no host", url));
+    }
+    Ok((host.to_string(), port, path.to_string()))
+}
+
+/// An answer to a GET request, read until the server closed the connection.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    /// Headers with lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let end = bytes.windows(4).position(|w| w == b"\r\n\r\n").ok_or("incomplete HTTP response")?;
+        let head = String::from_utf8_lossy(&bytes[..end]);
+        let mut lines = head.lines();
+        let status = lines.next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|status| status.parse().ok())
+            .ok_or("not an HTTP response")?;
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        let mut body = bytes[end + 4..].to_vec();
+        let length = headers.iter().find(|(name, _)| name == "content-length").and_then(|(_, value)| value.parse().ok());
+        if let Some(length) = length {
+            if body.len() < length {
+                return Err("the download was cut off".into());
+            }
+            body.truncate(length);
+        }
+        Ok(Self { status, headers, body })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Downloads `url`, following redirects.
+pub fn get(url: &str) -> Result<Vec<u8>, String> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let (host, port, path) = parse_url(&url)?;
+        let error = |e: std::io::Error| format!("Can't download {}: {}", url, e);
+        let mut stream = TcpStream::connect((host.as_str(), port)).map_err(error)?;
+        stream.set_read_timeout(Some(Duration::from_secs(30))).map_err(error)?;
+        // HTTP/1.0 keeps the body in one piece, without chunks
+        write!(stream, "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: minesweep_rs\r\n\r\n", path, host).map_err(error)?;
+        let mut bytes = vec![];
+        stream.take(MAX_DOWNLOAD as u64 + 1).read_to_end(&mut bytes).map_err(error)?;
+        if bytes.len() > MAX_DOWNLOAD {
+            return Err(format!("Can't download {}: larger than {} MiB", url, MAX_DOWNLOAD >> 20));
+        }
+        let response = Response::parse(&bytes).map_err(|e| format!("Can't download {}: {}", url, e))?;
+        match response.status {
+            200 => return Ok(response.body),
+            301 | 302 | 303 | 307 | 308 => {
+                let location = response.header("location").ok_or_else(|| format!("Can't download {}: redirect without a location", url))?;
+                url = if location.starts_with('/') {
+                    format!("http://{}:{}{}", host, port, location)
+                } else {
+                    location.to_string()
+                };
+            },
+            status => return Err(format!("Can't download {}: the server answered {}", url, status)),
+        }
+    }
+    Err(format!("Can't download {}: too many redirects", url))
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The SHA-256 checksum of `data` in lowercase hex, as printed by
+/// `sha256sum`.
+pub fn sha256(data: &[u8]) -> String {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    h.iter().map(|word| format!("{:08x}", word)).collect()
+}
+
+/// Checks `data` against a checksum in hex, like the first word of a line
+/// written by `sha256sum`.
+pub fn verify(data: &[u8], checksum: &str) -> Result<(), String> {
+    let expected = checksum.split_whitespace().next().unwrap_or_default().to_lowercase();
+    let actual = sha256(data);
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(format!("Checksum mismatch: expected {}, got {}", expected, actual))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fetch::{parse_url, sha256, verify, Response};
+
+    #[test]
+    fn checksums() {
+        assert_eq!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", sha256(b""));
+        assert_eq!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad", sha256(b"abc"));
+        let long = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1", sha256(long));
+        assert!(verify(b"abc", "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD  pack.json\n").is_ok());
+        assert!(verify(b"abd", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad").is_err());
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(Ok(("example.com".into(), 80, "/packs/a.json".into())), parse_url("http://example.com/packs/a.json"));
+        assert_eq!(Ok(("localhost".into(), 8080, "/".into())), parse_url("http://localhost:8080"));
+        assert!(parse_url("https://example.com/a.json").is_err());
+        assert!(parse_url("ftp://example.com/a.json").is_err());
+        let response = Response::parse(b"HTTP/1.1 302 Found\r\nLocation: /b.json\r\nContent-Length: 2\r\n\r\nokextra").unwrap();
+        assert_eq!((302, Some("/b.json"), &b"ok"[..]), (response.status, response.header("location"), &response.body[..]));
+        assert!(Response::parse(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nshort").is_err());
+    }
+}
@@ -0,0 +1,1731 @@
+use crate::{CancelToken, Cell, CellValue, Mark, MinesError, Topology, solver};
+use crate::arena::Arena;
+use crate::grid::Grid;
+use rand::Rng;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How far `Field::generate_no_guess_until` got, reported after every attempt.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GenerationProgress {
+    pub attempts: u32,
+    pub max_attempts: u32,
+    /// Share of the safe cells that the best board so far opens without
+    /// guessing, from its start cell.
+    pub best_cleared: f64,
+}
+
+/// A minesweeper board. Cells are addressed as `(x, y)` where `x` selects
+/// the row and `y` the column.
+///
+/// A change that fails leaves the board as it was. The one exception is
+/// opening a mine with `open` or `chord`, which isn't a failed move but a
+/// lost game: the move is made in full and the mines it hit are opened to
+/// show them. `try_open` and `try_chord` leave the board untouched then too.
+#[derive(Clone)]
+pub struct Field {
+    pub(crate) cells: Grid<Cell>,
+    pub(crate) numbers: Grid<u8>,
+    /// Stage every cell belongs to. Cells of a stage stay locked until all
+    /// previous stages are cleared.
+    pub(crate) zones: Grid<u8>,
+    /// Mines and flags in each quadrant, see `quadrant`. Kept up to date so
+    /// the counters of remaining mines don't need to scan the board.
+    mines: [usize; 4],
+    flags: [usize; 4],
+    /// Whether the player is told the number of mines in every row and
+    /// column, which the solver then uses too.
+    pub(crate) line_counts: bool,
+    pub(crate) topology: Topology,
+    /// Whether opposite edges touch.
+    pub(crate) wrap: bool,
+    /// Boards stacked as rows for a three-dimensional board, 1 for a flat one.
+    pub(crate) layers: u16,
+    /// Most mines a cell may hold, more than 1 on multimine boards.
+    pub(crate) per_cell: u8,
+}
+
+/// How the mines are uncovered after a mine has been opened.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RevealStyle {
+    /// All mines at once.
+    Instant,
+    /// Column by column from left to right.
+    Sweep,
+    /// Ring by ring, starting at the mine that was hit.
+    Cascade,
+}
+
+impl FromStr for RevealStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "instant" => Ok(RevealStyle::Instant),
+            "sweep" => Ok(RevealStyle::Sweep),
+            "cascade" => Ok(RevealStyle::Cascade),
+            _ => Err(format!("Unknown reveal style '{}'", s)),
+        }
+    }
+}
+
+impl RevealStyle {
+    /// Delay between two frames of the reveal animation.
+    pub fn frame_delay(self) -> Duration {
+        match self {
+            RevealStyle::Instant => Duration::from_millis(0),
+            RevealStyle::Sweep => Duration::from_millis(40),
+            RevealStyle::Cascade => Duration::from_millis(120),
+        }
+    }
+}
+
+impl Field {
+    /// Builds a board from its cells and computes the neighbour counts.
+    pub fn with_cells(cells: Vec<Vec<Cell>>) -> Self {
+        Self::with_grid(Grid::from(cells))
+    }
+
+    pub(crate) fn with_grid(cells: Grid<Cell>) -> Self {
+        let numbers = compute_numbers(&cells, Topology::Square, false, 1);
+        let zones = numbers.map(|_| 0);
+        let (rows, cols) = (cells.len(), cells.first().map_or(0, |col| col.len()));
+        let mut mines = [0; 4];
+        let mut flags = [0; 4];
+        for (x, col) in cells.iter().enumerate() {
+            for (y, cell) in col.iter().enumerate() {
+                let quadrant = quadrant(rows, cols, x, y);
+                mines[quadrant] += cell.mine_count() as usize;
+                flags[quadrant] += cell.flags() as usize;
+            }
+        }
+        Self {
+            cells,
+            numbers,
+            zones,
+            mines,
+            flags,
+            line_counts: false,
+            topology: Topology::Square,
+            wrap: false,
+            layers: 1,
+            per_cell: 1,
+        }
+    }
+
+    /// Puts `cell` of every row and column in place of the cells of this
+    /// flat board of square cells, keeping its memory. `Arena` plays all its
+    /// games on a few boards this way.
+    pub(crate) fn refill(&mut self, cell: impl Fn(usize, usize) -> Cell) {
+        let (rows, cols) = (self.cells.len(), self.cells.first().map_or(0, |col| col.len()));
+        self.mines = [0; 4];
+        self.flags = [0; 4];
+        for x in 0..rows {
+            for y in 0..cols {
+                let cell = cell(x, y);
+                let quadrant = quadrant(rows, cols, x, y);
+                self.mines[quadrant] += cell.mine_count() as usize;
+                self.flags[quadrant] += cell.flags() as usize;
+                self.cells[x][y] = cell;
+            }
+        }
+        // the neighbours of a flat board of squares, without the allocations of `compute_numbers`
+        for x in 0..rows {
+            for y in 0..cols {
+                let mut number = 0;
+                for nx in x.saturating_sub(1)..(x + 2).min(rows) {
+                    for ny in y.saturating_sub(1)..(y + 2).min(cols) {
+                        number += self.cells[nx][ny].mine_count() as u32 * ((nx, ny) != (x, y)) as u32;
+                    }
+                }
+                self.numbers[x][y] = number.min(u8::MAX as u32) as u8;
+            }
+        }
+    }
+
+    /// The same board with every cell closed and unmarked again, to play it
+    /// once more.
+    pub fn closed(&self) -> Self {
+        let cells = self.cells.map(|cell| if cell.hole { Cell::hole() } else { Cell::with_mines(cell.mine_count()) });
+        let mut closed = Self {
+            zones: self.zones.clone(),
+            line_counts: self.line_counts,
+            ..Self::with_grid(cells)
+        };
+        closed.wrap = self.wrap;
+        closed.layers = self.layers;
+        closed.per_cell = self.per_cell;
+        closed.set_topology(self.topology);
+        closed
+    }
+
+    /// Moves the mine at `(x, y)`, if there is one, to a random closed cell
+    /// without a mine or flag so that opening `(x, y)` is safe.
+    pub fn ensure_safe<R: Rng>(&mut self, x: u16, y: u16, rng: &mut R) -> Result<(), MinesError> {
+        if get_2d(&self.cells, x, y)?.value == CellValue::Water {
+            return Ok(());
+        }
+        let mut free = vec![];
+        for (fx, col) in self.cells.iter().enumerate() {
+            for (fy, cell) in col.iter().enumerate() {
+                if cell.value == CellValue::Water && !cell.opened && !cell.is_flagged() {
+                    free.push((fx, fy));
+                }
+            }
+        }
+        if free.is_empty() {
+            return Err(MinesError::TooManyMines);
+        }
+        let (fx, fy) = free[rng.gen_range(0, free.len())];
+        let count = self.cells[x as usize][y as usize].mine_count();
+        self.cells[fx][fy] = Cell::with_mines(count);
+        self.cells[x as usize][y as usize].value = CellValue::Water;
+        self.mines[self.quadrant(fx as u16, fy as u16)] += count as usize;
+        self.mines[self.quadrant(x, y)] -= count as usize;
+        self.numbers = compute_numbers(&self.cells, self.topology, self.wrap, self.layers);
+        Ok(())
+    }
+
+    /// Generates boards until one can be solved from an opening without
+    /// guessing and returns it together with the cell to start at. Gives up
+    /// with `MinesError::NoGuessBoardNotFound` after `max_attempts` boards.
+    pub fn generate_no_guess<R: Rng>(rng: &mut R, width: u16, height: u16, mines: u16, max_attempts: u32) -> Result<(Self, (u16, u16)), MinesError> {
+        Self::generate_no_guess_until(rng, width, height, mines, max_attempts, &CancelToken::new(), |_| true)
+    }
+
+    /// Like `generate_no_guess`, but stops with `MinesError::Cancelled` once
+    /// `cancel` is cancelled and reports its progress after every attempt.
+    /// When `progress` returns `false` the best board found so far is
+    /// accepted even though it needs guessing.
+    pub fn generate_no_guess_until<R: Rng, P: FnMut(&GenerationProgress) -> bool>(rng: &mut R, width: u16, height: u16, mines: u16, max_attempts: u32, cancel: &CancelToken, progress: P) -> Result<(Self, (u16, u16)), MinesError> {
+        Self::generate_solvable(rng, width, height, mines, max_attempts, false, cancel, progress)
+    }
+
+    /// Like `generate_no_guess_until`, but for boards that come with the
+    /// number of mines in every row and column. They only have to be
+    /// solvable with the help of those counts.
+    pub fn generate_crosssweeper_until<R: Rng, P: FnMut(&GenerationProgress) -> bool>(rng: &mut R, width: u16, height: u16, mines: u16, max_attempts: u32, cancel: &CancelToken, progress: P) -> Result<(Self, (u16, u16)), MinesError> {
+        Self::generate_solvable(rng, width, height, mines, max_attempts, true, cancel, progress)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn generate_solvable<R: Rng, P: FnMut(&GenerationProgress) -> bool>(rng: &mut R, width: u16, height: u16, mines: u16, max_attempts: u32, line_counts: bool, cancel: &CancelToken, mut progress: P) -> Result<(Self, (u16, u16)), MinesError> {
+        let mut best: Option<(Self, (u16, u16))> = None;
+        let mut report = GenerationProgress {
+            attempts: 0,
+            max_attempts,
+            best_cleared: 0.0,
+        };
+        // the attempts are drawn into an arena and tried on the same two boards
+        let mut arena = Arena::new(width, height, mines)?;
+        let mut field = Self::with_cells(vec![vec![Cell::water(); height as usize]; width as usize]);
+        field.line_counts = line_counts;
+        let mut solved = field.clone();
+        let mut openings = vec![];
+        for attempt in 1..=max_attempts {
+            if cancel.is_cancelled() {
+                return Err(MinesError::Cancelled);
+            }
+            arena.clear();
+            let game = arena.draw(rng);
+            arena.load(game, &mut field);
+            openings.clear();
+            for (x, col) in field.numbers.iter().enumerate() {
+                for (y, &number) in col.iter().enumerate() {
+                    if number == 0 && field.cells[x][y].value == CellValue::Water {
+                        openings.push((x as u16, y as u16));
+                    }
+                }
+            }
+            report.attempts = attempt;
+            if !openings.is_empty() {
+                let (x, y) = openings[rng.gen_range(0, openings.len())];
+                arena.load(game, &mut solved);
+                let _ = solved.open(x, y);
+                let won = solver::apply_deductions_until(&mut solved, cancel)? && solved.is_won();
+                let water = field.cell_count() - field.mine_count();
+                let cleared = solved.opened_count() as f64 / water as f64;
+                if won || best.is_none() || cleared > report.best_cleared {
+                    let mut board = arena.field(game);
+                    board.line_counts = line_counts;
+                    if won {
+                        return Ok((board, (x, y)));
+                    }
+                    report.best_cleared = cleared;
+                    best = Some((board, (x, y)));
+                }
+            }
+            if !progress(&report) {
+                if let Some(best) = best {
+                    return Ok(best);
+                }
+            }
+        }
+        Err(MinesError::NoGuessBoardNotFound(max_attempts))
+    }
+
+    /// Splits the board into `stages` bands of columns from left to right.
+    pub fn split_stages(&mut self, stages: u8) {
+        let (cols, _) = self.size();
+        let stages = stages.max(1) as usize;
+        for col in self.zones.iter_mut() {
+            for (y, zone) in col.iter_mut().enumerate() {
+                *zone = (y * stages / cols.max(1) as usize) as u8;
+            }
+        }
+    }
+
+    /// The last stage that can currently be played in.
+    pub fn unlocked_zone(&self) -> u8 {
+        let mut first_uncleared = None;
+        for (col, zones) in self.cells.iter().zip(self.zones.iter()) {
+            for (cell, &zone) in col.iter().zip(zones.iter()) {
+                if cell.value == CellValue::Water && !cell.opened {
+                    first_uncleared = Some(first_uncleared.map_or(zone, |z: u8| z.min(zone)));
+                }
+            }
+        }
+        first_uncleared.unwrap_or(u8::MAX)
+    }
+
+    pub fn is_locked(&self, x: u16, y: u16) -> bool {
+        // the first stage is never locked, which spares the scan on boards without stages
+        get_2d(&self.zones, x, y).is_ok_and(|&zone| zone > 0 && zone > self.unlocked_zone())
+    }
+
+    /// Like `is_locked` with `unlocked` from `unlocked_zone`, to look at
+    /// many cells without scanning the board for every one of them.
+    pub fn is_locked_with(&self, x: u16, y: u16, unlocked: u8) -> bool {
+        get_2d(&self.zones, x, y).is_ok_and(|&zone| zone > unlocked)
+    }
+
+    /// Parses a board file. Every non-empty line that doesn't start with `#`
+    /// is a row of the board with one character per cell:
+    ///
+    /// * `.` closed water, `*` closed mine
+    /// * `o` opened water, `X` opened mine (rejected)
+    /// * `f` flagged water, `F` flagged mine
+    /// * `q` water and `Q` a mine marked with a question mark
+    /// * `2` to `9` a closed cell with that many mines, for multimine boards
+    /// * `-` a hole in a shaped board
+    pub fn parse(text: &str) -> Result<Self, MinesError> {
+        let mut rows: Vec<Vec<Cell>> = vec![];
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // cells are addressed by u16 rows and columns
+            if rows.len() == u16::MAX as usize || line.chars().count() > u16::MAX as usize {
+                return Err(MinesError::InvalidBoard(line_no + 1, format!("boards have at most {} rows and columns", u16::MAX)));
+            }
+            let mut row = vec![];
+            for c in line.chars() {
+                if let Some(mines) = c.to_digit(10).filter(|&mines| mines >= 2) {
+                    row.push(Cell::with_mines(mines as u8));
+                    continue;
+                }
+                let (value, opened, mark) = match c {
+                    '.' => (CellValue::Water, false, Mark::None),
+                    '*' => (CellValue::Mine, false, Mark::None),
+                    'o' => (CellValue::Water, true, Mark::None),
+                    'f' => (CellValue::Water, false, Mark::Flag),
+                    'F' => (CellValue::Mine, false, Mark::Flag),
+                    'q' => (CellValue::Water, false, Mark::Question),
+                    'Q' => (CellValue::Mine, false, Mark::Question),
+                    '-' => {
+                        row.push(Cell::hole());
+                        continue;
+                    },
+                    'X' => return Err(MinesError::MineOpenedInBoard(rows.len() as u16, row.len() as u16)),
+                    _ => return Err(MinesError::InvalidBoard(line_no + 1, format!("unknown cell '{}'", c))),
+                };
+                row.push(Cell { value, opened, mark, ..Cell::water() });
+            }
+            if let Some(first) = rows.first() {
+                if first.len() != row.len() {
+                    return Err(MinesError::InvalidBoard(line_no + 1, format!("expected {} cells, found {}", first.len(), row.len())));
+                }
+            }
+            rows.push(row);
+        }
+        if rows.is_empty() {
+            return Err(MinesError::EmptyField);
+        }
+        let per_cell = rows.iter().flatten().map(|cell| cell.mine_count()).max().unwrap_or(1).max(1);
+        Ok(Self { per_cell, ..Self::with_cells(rows) })
+    }
+
+    /// Writes the board in the format read by `parse`.
+    pub fn to_board_file(&self) -> String {
+        let mut text = String::new();
+        for col in self.cells.iter() {
+            for cell in col.iter() {
+                text.push(match (cell.value, cell.opened, cell.mark) {
+                    _ if cell.hole => '-',
+                    (CellValue::Water, true, _) => 'o',
+                    (CellValue::Mine, true, _) => 'X',
+                    (CellValue::Water, false, Mark::Flag) => 'f',
+                    (CellValue::Mine, false, Mark::Flag) => 'F',
+                    (CellValue::Water, false, Mark::Question) => 'q',
+                    (CellValue::Mine, false, Mark::Question) => 'Q',
+                    (CellValue::Water, false, Mark::None) => '.',
+                    (CellValue::Mine, false, Mark::None) if cell.mine_count() > 1 => (b'0' + cell.mine_count()) as char,
+                    (CellValue::Mine, false, Mark::None) => '*',
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Describes only what a player can see: the number of mines and the
+    /// shape of the board like in `export` (`grid <shape>`, `wrap`,
+    /// `layers <n>`, and `per-cell <n>` for several mines per cell), then one
+    /// row per line with the cells separated by spaces: `_` for closed cells,
+    /// `F` for a flag or `F<n>` for several, the number of every opened cell
+    /// and `-` for holes. Mine locations are left out, so the position can
+    /// be shared without spoiling the board.
+    pub fn position(&self) -> String {
+        let mut text = format!("mines {}\n", self.mine_count());
+        if self.topology != Topology::Square {
+            text.push_str(&format!("grid {}\n", self.topology));
+        }
+        if self.wrap {
+            text.push_str("wrap\n");
+        }
+        if self.layers > 1 {
+            text.push_str(&format!("layers {}\n", self.layers));
+        }
+        if self.per_cell > 1 {
+            text.push_str(&format!("per-cell {}\n", self.per_cell));
+        }
+        for (x, col) in self.cells.iter().enumerate() {
+            let row: Vec<String> = col.iter().enumerate().map(|(y, cell)| match cell.flags() {
+                1 => "F".to_string(),
+                flags if flags > 1 => format!("F{}", flags),
+                _ if cell.hole => "-".to_string(),
+                _ if cell.opened => self.numbers[x][y].to_string(),
+                _ => "_".to_string(),
+            }).collect();
+            text.push_str(&row.join(" "));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Loads a position written by `position`. The mines have to go
+    /// somewhere, so they are put under the flags first and then on the
+    /// closed cells in order, up to the mines a cell can hold; the numbers
+    /// come from the position. The board is meant for analysis and doesn't
+    /// know where the mines really are. Rows written without spaces, one
+    /// character per cell, are read as well.
+    pub fn from_position(text: &str) -> Result<Self, MinesError> {
+        let mut mines = None;
+        let (mut topology, mut wrap, mut layers, mut per_cell) = (Topology::Square, false, 1, 1);
+        let mut rows: Vec<Vec<Option<u8>>> = vec![];
+        let mut flags = vec![];
+        let mut holes = vec![];
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: String| MinesError::InvalidBoard(line_no + 1, reason);
+            let number = |word: &str| word.trim().parse::<u16>().map_err(|_| invalid(format!("invalid number '{}'", word.trim())));
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "mines" => {
+                    mines = Some(rest.trim().parse::<u16>().map_err(|_| invalid(format!("invalid mine count '{}'", rest.trim())))?);
+                    continue;
+                },
+                "grid" => {
+                    topology = rest.trim().parse().map_err(invalid)?;
+                    continue;
+                },
+                "wrap" => {
+                    wrap = true;
+                    continue;
+                },
+                "layers" => {
+                    layers = number(rest)?;
+                    continue;
+                },
+                "per-cell" => {
+                    per_cell = number(rest)?.clamp(1, 9) as u8;
+                    continue;
+                },
+                _ => {},
+            }
+            let tokens: Vec<String> = if line.contains(char::is_whitespace) {
+                line.split_whitespace().map(str::to_string).collect()
+            } else {
+                line.chars().map(String::from).collect()
+            };
+            let mut row = vec![];
+            for token in tokens {
+                row.push(match token.as_str() {
+                    "_" => None,
+                    "-" => {
+                        holes.push((rows.len(), row.len()));
+                        Some(0)
+                    },
+                    flag if flag.starts_with('F') => {
+                        let count = match &flag[1..] {
+                            "" => 1,
+                            count => count.parse::<u8>().ok().filter(|&count| count > 0).ok_or_else(|| invalid(format!("unknown cell '{}'", flag)))?,
+                        };
+                        flags.push(((rows.len(), row.len()), count));
+                        None
+                    },
+                    number => Some(number.parse::<u8>().map_err(|_| invalid(format!("unknown cell '{}'", number)))?),
+                });
+            }
+            if let Some(first) = rows.first() {
+                if first.len() != row.len() {
+                    return Err(invalid(format!("expected {} cells, found {}", first.len(), row.len())));
+                }
+            }
+            rows.push(row);
+        }
+        if rows.is_empty() {
+            return Err(MinesError::EmptyField);
+        }
+        let mines = mines.ok_or_else(|| MinesError::InvalidBoard(1, "missing 'mines' line".into()))?;
+        // the flags take their mines first, then the closed cells a mine each
+        // in turn until all are placed
+        let mut counts = vec![vec![0u8; rows[0].len()]; rows.len()];
+        let mut left = mines as usize;
+        for &((x, y), flags) in flags.iter() {
+            let placed = (flags.min(per_cell) as usize).min(left);
+            counts[x][y] = placed as u8;
+            left -= placed;
+        }
+        let closed: Vec<(usize, usize)> = rows.iter().enumerate()
+            .flat_map(|(x, row)| row.iter().enumerate().filter(|(_, number)| number.is_none()).map(move |(y, _)| (x, y)))
+            .collect();
+        while left > 0 {
+            let before = left;
+            for &(x, y) in closed.iter() {
+                if left > 0 && counts[x][y] < per_cell {
+                    counts[x][y] += 1;
+                    left -= 1;
+                }
+            }
+            if left == before {
+                return Err(MinesError::TooManyMines);
+            }
+        }
+        let mut cells: Vec<Vec<Cell>> = rows.iter().zip(counts.iter()).map(|(row, counts)| {
+            row.iter().zip(counts.iter()).map(|(number, &mines)| Cell {
+                opened: number.is_some(),
+                ..Cell::with_mines(mines)
+            }).collect()
+        }).collect();
+        for &((x, y), flags) in flags.iter() {
+            cells[x][y].mark = Mark::Flag;
+            cells[x][y].flags = flags;
+        }
+        for &(x, y) in holes.iter() {
+            cells[x][y] = Cell::hole();
+        }
+        let mut field = Self { per_cell, ..Self::with_cells(cells) };
+        field.topology = topology;
+        field.wrap = wrap;
+        field.set_layers(layers)?;
+        for (x, row) in rows.iter().enumerate() {
+            for (y, number) in row.iter().enumerate() {
+                field.numbers[x][y] = number.unwrap_or(0);
+            }
+        }
+        Ok(field)
+    }
+
+    /// Describes the whole board by keyed lines, for bug reports and test
+    /// fixtures: `size <cols> <rows>`, the shape of the board if it isn't
+    /// flat and square (`grid <shape>`, `wrap`, `layers <n>`,
+    /// `line-counts`), then the cells of `mines`, `opened`, `flags`, `marks`
+    /// and `holes` as comma separated `<col> <row>` pairs counted from 1. A
+    /// cell with several mines or flags is listed that many times.
+    pub fn export(&self) -> String {
+        let (cols, rows) = self.size();
+        let mut text = format!("size {} {}\n", cols, rows);
+        if self.topology != Topology::Square {
+            text.push_str(&format!("grid {}\n", self.topology));
+        }
+        if self.wrap {
+            text.push_str("wrap\n");
+        }
+        if self.layers > 1 {
+            text.push_str(&format!("layers {}\n", self.layers));
+        }
+        if self.line_counts {
+            text.push_str("line-counts\n");
+        }
+        // how many times a cell is listed
+        let times = |name: &str, cell: &Cell| match name {
+            "mines" => cell.mine_count() as usize,
+            "opened" => (cell.opened && !cell.hole) as usize,
+            "flags" => cell.flags() as usize,
+            "marks" => (cell.mark == Mark::Question) as usize,
+            _ => cell.hole as usize,
+        };
+        for name in ["mines", "opened", "flags", "marks", "holes"] {
+            let cells: Vec<String> = self.cells.iter().enumerate()
+                .flat_map(|(x, row)| row.iter().enumerate().flat_map(move |(y, cell)| {
+                    std::iter::repeat_n(format!("{} {}", y + 1, x + 1), times(name, cell))
+                }))
+                .collect();
+            if !cells.is_empty() {
+                text.push_str(&format!("{} {}\n", name, cells.join(", ")));
+            }
+        }
+        text
+    }
+
+    /// Loads a board written by `export`, turning down boards that would
+    /// take up more than `memory_cap` bytes. Lines starting with `#` are
+    /// comments.
+    pub fn import(text: &str, memory_cap: usize) -> Result<Self, MinesError> {
+        let mut cells: Option<Vec<Vec<Cell>>> = None;
+        let (mut topology, mut wrap, mut layers, mut line_counts) = (Topology::Square, false, 1, false);
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: String| MinesError::InvalidBoard(line_no + 1, reason);
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let number = |word: &str| word.parse::<u16>().map_err(|_| invalid(format!("invalid number '{}'", word)));
+            if key == "size" {
+                let (cols, rows) = rest.trim().split_once(' ').ok_or_else(|| invalid("expected size <cols> <rows>".into()))?;
+                let (cols, rows) = (number(cols.trim())?, number(rows.trim())?);
+                if cols == 0 || rows == 0 {
+                    return Err(MinesError::EmptyField);
+                }
+                // the size line alone picks how much is taken up, not the cells listed
+                let needed = Self::memory_estimate(rows, cols);
+                if needed > memory_cap {
+                    let mib = |bytes: usize| bytes.div_ceil(1 << 20);
+                    return Err(invalid(format!("a {}x{} board needs about {} MiB, more than the {} MiB allowed", cols, rows, mib(needed), mib(memory_cap))));
+                }
+                cells = Some(vec![vec![Cell::water(); cols as usize]; rows as usize]);
+                continue;
+            }
+            let cells = cells.as_mut().ok_or_else(|| invalid("the 'size' line has to come first".into()))?;
+            match key {
+                "grid" => topology = rest.trim().parse().map_err(invalid)?,
+                "wrap" => wrap = true,
+                "layers" => layers = number(rest.trim())?,
+                "line-counts" => line_counts = true,
+                "mines" | "opened" | "flags" | "marks" | "holes" => {
+                    for pair in rest.split(',') {
+                        let (col, row) = pair.trim().split_once(' ').ok_or_else(|| invalid(format!("expected <col> <row>, found '{}'", pair.trim())))?;
+                        let (col, row) = (number(col.trim())?, number(row.trim())?);
+                        if row == 0 || col == 0 || row as usize > cells.len() || col as usize > cells[0].len() {
+                            return Err(invalid(format!("{} {} is off the board", col, row)));
+                        }
+                        let cell = &mut cells[row as usize - 1][col as usize - 1];
+                        match key {
+                            "mines" if cell.mine_count() >= 9 => return Err(invalid(format!("more than 9 mines at {} {}", col, row))),
+                            "mines" => {
+                                cell.mines = cell.mine_count() + 1;
+                                cell.value = CellValue::Mine;
+                            },
+                            "opened" => cell.opened = true,
+                            "flags" => {
+                                cell.flags = cell.flags().checked_add(1).ok_or_else(|| invalid(format!("more than {} flags at {} {}", u8::MAX, col, row)))?;
+                                cell.mark = Mark::Flag;
+                            },
+                            "marks" => cell.mark = Mark::Question,
+                            _ => *cell = Cell::hole(),
+                        }
+                    }
+                },
+                _ => return Err(invalid(format!("unknown line '{}'", key))),
+            }
+        }
+        let cells = cells.ok_or(MinesError::EmptyField)?;
+        for (x, row) in cells.iter().enumerate() {
+            for (y, cell) in row.iter().enumerate() {
+                if cell.opened && !cell.hole && cell.value == CellValue::Mine {
+                    return Err(MinesError::MineOpenedInBoard(x as u16, y as u16));
+                }
+            }
+        }
+        let per_cell = cells.iter().flatten().map(|cell| cell.mine_count()).max().unwrap_or(1).max(1);
+        let mut field = Self { per_cell, ..Self::with_cells(cells) };
+        field.set_topology(topology);
+        field.set_wrap(wrap);
+        field.set_layers(layers)?;
+        field.set_line_counts(line_counts);
+        Ok(field)
+    }
+
+    /// Generates a random board with `width` rows, `height` columns and `mines` mines.
+    pub fn generate<R: Rng>(rng: &mut R, width: u16, height: u16, mines: u16) -> Result<Self, MinesError> {
+        let cells = generate_cells(rng, width, height, mines)?;
+        Ok(Self::with_grid(cells))
+    }
+
+    /// Reads the shape of a board: every line is a row with `#` for a cell
+    /// of the board and `.` or a space for a hole. Rows shorter than the
+    /// longest are filled up with holes and blank lines are left out.
+    pub fn parse_mask(text: &str) -> Result<Vec<Vec<bool>>, MinesError> {
+        let mut mask = vec![];
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            let row = line.chars()
+                .map(|c| match c {
+                    '#' => Ok(true),
+                    '.' | ' ' => Ok(false),
+                    _ => Err(MinesError::InvalidBoard(line_no + 1, format!("unknown mask cell '{}', expected '#' or '.'", c))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            mask.push(row);
+        }
+        let cols = mask.iter().map(|row| row.len()).max().unwrap_or(0);
+        for row in mask.iter_mut() {
+            row.resize(cols, false);
+        }
+        if !mask.iter().flatten().any(|&cell| cell) {
+            return Err(MinesError::EmptyField);
+        }
+        Ok(mask)
+    }
+
+    /// Generates a board shaped like `mask`, a row of cells for every row
+    /// of the board with `false` for holes, with `mines` mines on the cells.
+    pub fn generate_masked<R: Rng>(rng: &mut R, mask: &[Vec<bool>], mines: u16) -> Result<Self, MinesError> {
+        let playable = mask.iter().flatten().filter(|&&cell| cell).count();
+        check_size(playable, 1, mines, 1)?;
+        let (rows, cols) = (mask.len(), mask[0].len());
+        let mut cells: Vec<Vec<Cell>> = mask.iter()
+            .map(|row| row.iter().map(|&cell| if cell { Cell::water() } else { Cell::hole() }).collect())
+            .collect();
+        for _ in 0..mines {
+            loop {
+                let (x, y) = (rng.gen_range(0, rows), rng.gen_range(0, cols));
+                if mask[x][y] && !cells[x][y].is_mine() {
+                    cells[x][y] = Cell::mine();
+                    break;
+                }
+            }
+        }
+        Ok(Self::with_cells(cells))
+    }
+
+    /// Generates a multimine board with `width` rows and `height` columns,
+    /// where `mines` mines are spread so that a cell holds up to `per_cell`
+    /// of them.
+    pub fn generate_multimine<R: Rng>(rng: &mut R, width: u16, height: u16, mines: u16, per_cell: u8) -> Result<Self, MinesError> {
+        let per_cell = per_cell.clamp(1, 9);
+        check_size(width as usize, height as usize, mines, per_cell)?;
+        let mut counts = vec![vec![0u8; height as usize]; width as usize];
+        let mut mined = 0;
+        for _ in 0..mines {
+            loop {
+                let (x, y) = (rng.gen_range(0, width) as usize, rng.gen_range(0, height) as usize);
+                // keep the last free cell free
+                let last_free = counts[x][y] == 0 && mined + 1 == width as usize * height as usize;
+                if counts[x][y] < per_cell && !last_free {
+                    mined += (counts[x][y] == 0) as usize;
+                    counts[x][y] += 1;
+                    break;
+                }
+            }
+        }
+        let cells = counts.into_iter()
+            .map(|row| row.into_iter().map(Cell::with_mines).collect())
+            .collect();
+        Ok(Self { per_cell, ..Self::with_cells(cells) })
+    }
+
+    /// Most mines a cell holds, more than 1 on multimine boards.
+    pub fn mines_per_cell(&self) -> u8 {
+        self.per_cell
+    }
+
+    /// Bytes a `width` by `height` board takes up: every cell with its
+    /// number and stage, and the chunks holding them.
+    pub fn memory_estimate(width: u16, height: u16) -> usize {
+        let (rows, cols) = (width as usize, height as usize);
+        let cell = std::mem::size_of::<Cell>() + 2;
+        let chunk = 3 * std::mem::size_of::<Vec<u8>>();
+        rows * cols * cell + Grid::<u8>::chunks_for(rows, cols) * chunk + std::mem::size_of::<Self>()
+    }
+
+    /// The cell of the mine that was opened, if any.
+    pub fn opened_mine(&self) -> Option<(u16, u16)> {
+        self.opened_mines().first().copied()
+    }
+
+    /// The cells of all opened mines, more than one after a chord with
+    /// wrong flags.
+    pub fn opened_mines(&self) -> Vec<(u16, u16)> {
+        let mut mines = vec![];
+        for (x, col) in self.cells.iter().enumerate() {
+            for (y, cell) in col.iter().enumerate() {
+                if cell.opened && cell.value == CellValue::Mine {
+                    mines.push((x as u16, y as u16));
+                }
+            }
+        }
+        mines
+    }
+
+    /// Groups all mines into the frames in which they are revealed.
+    pub fn mine_reveal_order(&self, style: RevealStyle) -> Vec<Vec<(u16, u16)>> {
+        let mut mines = vec![];
+        for (x, col) in self.cells.iter().enumerate() {
+            for (y, cell) in col.iter().enumerate() {
+                if cell.value == CellValue::Mine {
+                    mines.push((x as u16, y as u16));
+                }
+            }
+        }
+        let hit = self.opened_mine();
+        let frame_of = |&(x, y): &(u16, u16)| -> u16 {
+            match style {
+                RevealStyle::Instant => 0,
+                RevealStyle::Sweep => y,
+                RevealStyle::Cascade => {
+                    let (hx, hy) = hit.unwrap_or((x, y));
+                    std::cmp::max(
+                        (x as i32 - hx as i32).abs(),
+                        (y as i32 - hy as i32).abs(),
+                    ) as u16
+                },
+            }
+        };
+        let mut frames: Vec<Vec<(u16, u16)>> = vec![];
+        mines.sort_by_key(frame_of);
+        for mine in mines {
+            match frames.last_mut() {
+                Some(frame) if frame_of(&frame[0]) == frame_of(&mine) => frame.push(mine),
+                _ => frames.push(vec![mine]),
+            }
+        }
+        frames
+    }
+
+    #[cfg(any(feature = "terminal", test))]
+    pub(crate) fn reveal(&mut self, x: u16, y: u16) {
+        let quadrant = self.quadrant(x, y);
+        if let Some(cell) = self.cells.get_mut(x as usize).and_then(|col| col.get_mut(y as usize)) {
+            self.flags[quadrant] -= cell.flags() as usize;
+            cell.mark = Mark::None;
+            cell.opened = true;
+        }
+    }
+
+    /// Toggles the flag on a closed cell. On multimine boards every flag
+    /// adds one more, up to the most mines a cell holds, and the next takes
+    /// them all away.
+    pub fn flag(&mut self, x: u16, y: u16) -> Result<(), MinesError> {
+        let max = self.per_cell;
+        if max > 1 {
+            self.change_mark(x, y, |cell| cell.add_flag(max))
+        } else {
+            self.change_mark(x, y, Cell::toggle_flag)
+        }
+    }
+
+    /// Moves a closed cell on from no mark to a flag, a question mark and
+    /// back to no mark.
+    pub fn cycle_mark(&mut self, x: u16, y: u16) -> Result<(), MinesError> {
+        self.change_mark(x, y, Cell::cycle_mark)
+    }
+
+    fn change_mark<F: FnOnce(&mut Cell)>(&mut self, x: u16, y: u16, change: F) -> Result<(), MinesError> {
+        let _ = get_2d(&self.cells, x, y)?;
+        let quadrant = self.quadrant(x, y);
+        let cell: &mut Cell = self.cells.get_mut(x as usize).unwrap().get_mut(y as usize).unwrap();
+        let flags = cell.flags() as usize;
+        change(cell);
+        self.flags[quadrant] = self.flags[quadrant] + cell.flags() as usize - flags;
+        Ok(())
+    }
+
+    /// Opens a cell, flood filling around cells without neighbouring mines.
+    /// A mine is opened and `MinesError::MineOpened` returned, the game is
+    /// lost.
+    pub fn open(&mut self, x: u16, y: u16) -> Result<(), MinesError> {
+        let result = self.try_open(x, y);
+        if result == Err(MinesError::MineOpened) {
+            self.cells[x as usize][y as usize].explode();
+        }
+        result
+    }
+
+    /// Opens a cell like `open`, but keeps a mine closed, returning
+    /// `MinesError::MineOpened` with the board untouched.
+    pub fn try_open(&mut self, x: u16, y: u16) -> Result<(), MinesError> {
+        let _ = get_2d(&self.cells, x, y)?;
+        // a stage cleared by this opening unlocks the next one with the next move
+        let unlocked = self.unlocked_zone();
+        if self.is_locked_with(x, y, unlocked) {
+            return Err(MinesError::ZoneLocked(x, y));
+        }
+        let cell: &mut Cell = self.cells.get_mut(x as usize).unwrap().get_mut(y as usize).unwrap();
+        // a flagged cell stays closed, so it mustn't spread an opening
+        if cell.opened || cell.is_flagged() {
+            return Ok(());
+        }
+        cell.open()?;
+        // the cells around a zero hide no mines, so the opening can only stop
+        // at locked ones; a cell is opened as it is queued, which keeps it
+        // from being queued twice, and the queue spares the stack on big boards
+        let mut pending = vec![(x, y)];
+        while let Some((x, y)) = pending.pop() {
+            if self.numbers[x as usize][y as usize] != 0 {
+                continue;
+            }
+            for (nx, ny) in self.neighbours(x, y) {
+                if self.is_locked_with(nx, ny, unlocked) {
+                    continue;
+                }
+                let cell = &mut self.cells[nx as usize][ny as usize];
+                if !cell.opened && !cell.is_flagged() && cell.open().is_ok() {
+                    pending.push((nx, ny));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens all unflagged neighbours of an opened cell once it has as many
+    /// flagged neighbours as its number. With a wrong flag among them that
+    /// opens a mine: all of them are opened anyway, mines included, and
+    /// `MinesError::MineOpened` is returned.
+    pub fn chord(&mut self, x: u16, y: u16) -> Result<(), MinesError> {
+        let cells = self.chord_cells(x, y)?;
+        let mines: Vec<(u16, u16)> = cells.iter().copied().filter(|&(nx, ny)| self.cells[nx as usize][ny as usize].is_mine()).collect();
+        for &(nx, ny) in cells.iter().filter(|cell| !mines.contains(cell)) {
+            let _ = self.try_open(nx, ny);
+        }
+        for &(nx, ny) in &mines {
+            self.cells[nx as usize][ny as usize].explode();
+        }
+        if mines.is_empty() { Ok(()) } else { Err(MinesError::MineOpened) }
+    }
+
+    /// Chords like `chord`, but opens nothing when a mine is among the
+    /// cells, returning `MinesError::MineOpened` with the board untouched.
+    pub fn try_chord(&mut self, x: u16, y: u16) -> Result<(), MinesError> {
+        let cells = self.chord_cells(x, y)?;
+        if cells.iter().any(|&(nx, ny)| self.cells[nx as usize][ny as usize].is_mine()) {
+            return Err(MinesError::MineOpened);
+        }
+        for (nx, ny) in cells {
+            let _ = self.try_open(nx, ny);
+        }
+        Ok(())
+    }
+
+    /// The closed, unflagged and unlocked neighbours a chord on `(x, y)`
+    /// opens, none unless it is an opened cell with as many flags around
+    /// as its number.
+    fn chord_cells(&self, x: u16, y: u16) -> Result<Vec<(u16, u16)>, MinesError> {
+        let cell = get_2d(&self.cells, x, y)?;
+        let number = *get_2d(&self.numbers, x, y)? as usize;
+        let neighbours = self.neighbours(x, y);
+        let flags: usize = neighbours.iter().map(|&(nx, ny)| self.cells[nx as usize][ny as usize].flags() as usize).sum();
+        if !cell.opened || number != flags {
+            return Ok(vec![]);
+        }
+        let unlocked = self.unlocked_zone();
+        Ok(neighbours.into_iter()
+            .filter(|&(nx, ny)| {
+                let cell = &self.cells[nx as usize][ny as usize];
+                !cell.opened && !cell.is_flagged() && !self.is_locked_with(nx, ny, unlocked)
+            })
+            .collect())
+    }
+
+    /// The cell at `(x, y)`.
+    pub fn cell(&self, x: u16, y: u16) -> Result<&Cell, MinesError> {
+        get_2d(&self.cells, x, y)
+    }
+
+    /// Number of mines around the cell at `(x, y)`.
+    pub fn number(&self, x: u16, y: u16) -> Result<u8, MinesError> {
+        get_2d(&self.numbers, x, y).copied()
+    }
+
+    /// Coordinates of all cells adjacent to `(x, y)`. Holes have none and
+    /// aren't adjacent to any cell.
+    pub fn neighbours(&self, x: u16, y: u16) -> Vec<(u16, u16)> {
+        neighbours(&self.cells, self.topology, self.wrap, self.layers, x, y)
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Changes how the cells touch each other and counts the neighbouring
+    /// mines again.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        self.numbers = compute_numbers(&self.cells, topology, self.wrap, self.layers);
+    }
+
+    /// Whether the board wraps around, with the first and last rows and the
+    /// first and last columns next to each other.
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    /// Makes the board wrap around or not and counts the neighbouring mines
+    /// again.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+        self.numbers = compute_numbers(&self.cells, self.topology, wrap, self.layers);
+    }
+
+    /// Number of layers of a three-dimensional board, 1 for a flat one.
+    pub fn layers(&self) -> u16 {
+        self.layers
+    }
+
+    /// Rows of one layer, all rows on a flat board.
+    pub fn layer_height(&self) -> u16 {
+        self.cells.len() as u16 / self.layers
+    }
+
+    /// Splits the rows into `layers` layers of a three-dimensional board and
+    /// counts the neighbouring mines again.
+    pub fn set_layers(&mut self, layers: u16) -> Result<(), MinesError> {
+        if layers == 0 || !(self.cells.len() as u16).is_multiple_of(layers) {
+            return Err(MinesError::UnevenLayers(self.cells.len() as u16, layers));
+        }
+        self.layers = layers;
+        self.numbers = compute_numbers(&self.cells, self.topology, self.wrap, layers);
+        Ok(())
+    }
+
+    /// The cell at `(x, y)` the way players type it: column and row from 1,
+    /// followed by the layer on a three-dimensional board.
+    pub fn coords(&self, x: u16, y: u16) -> String {
+        if self.layers > 1 {
+            let height = self.layer_height();
+            format!("{} {} {}", y + 1, x % height + 1, x / height + 1)
+        } else {
+            format!("{} {}", y + 1, x + 1)
+        }
+    }
+
+    /// Number of columns and rows as shown on screen.
+    pub fn size(&self) -> (u16, u16) {
+        (self.cells.first().map_or(0, |col| col.len()) as u16, self.cells.len() as u16)
+    }
+
+    /// Number of opened cells, not counting holes.
+    pub fn opened_count(&self) -> usize {
+        self.cells.iter()
+            .map(|col| col.iter().filter(|cell| cell.opened && !cell.hole).count())
+            .sum()
+    }
+
+    /// The board's 3BV, the fewest clicks that clear it without flags: one
+    /// for every opening, which clears its border too, and one for every
+    /// other cell without a mine. Tells boards of the same size apart by
+    /// how much work they are.
+    pub fn three_bv(&self) -> u32 {
+        let safe = |x: usize, y: usize| !self.cells[x][y].is_mine() && !self.cells[x][y].hole;
+        let mut cleared: Vec<Vec<bool>> = self.cells.iter().map(|row| vec![false; row.len()]).collect();
+        let mut clicks = 0;
+        for (x, row) in self.numbers.iter().enumerate() {
+            for (y, &number) in row.iter().enumerate() {
+                if number != 0 || !safe(x, y) || cleared[x][y] {
+                    continue;
+                }
+                clicks += 1;
+                cleared[x][y] = true;
+                let mut opening = vec![(x as u16, y as u16)];
+                while let Some((ox, oy)) = opening.pop() {
+                    for (nx, ny) in self.neighbours(ox, oy) {
+                        let (nx, ny) = (nx as usize, ny as usize);
+                        if !cleared[nx][ny] && safe(nx, ny) {
+                            cleared[nx][ny] = true;
+                            if self.numbers[nx][ny] == 0 {
+                                opening.push((nx as u16, ny as u16));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for (x, row) in cleared.iter().enumerate() {
+            clicks += row.iter().enumerate().filter(|&(y, &cleared)| !cleared && safe(x, y)).count() as u32;
+        }
+        clicks
+    }
+
+    /// Number of cells that are part of the board, all but the holes.
+    pub fn cell_count(&self) -> usize {
+        self.cells.iter().flatten().filter(|cell| !cell.hole).count()
+    }
+
+    /// Number of cells without a mine, the ones to open to win. Fewer than
+    /// the cells less the mines on multimine boards.
+    pub fn safe_count(&self) -> usize {
+        self.cells.iter().flatten().filter(|cell| !cell.hole && cell.value == CellValue::Water).count()
+    }
+
+    /// Number of mines on the board.
+    pub fn mine_count(&self) -> usize {
+        self.mines.iter().sum()
+    }
+
+    /// Number of flags placed.
+    pub fn flag_count(&self) -> usize {
+        self.flags.iter().sum()
+    }
+
+    /// Number of flagged cells without a mine, or with fewer mines than
+    /// flags.
+    pub fn wrong_flags(&self) -> usize {
+        self.cells.iter().flatten()
+            .filter(|cell| cell.flags() > cell.mine_count())
+            .count()
+    }
+
+    /// Mines minus flags, the classic counter of remaining mines. Goes below
+    /// zero when there are more flags than mines.
+    pub fn mines_left(&self) -> isize {
+        self.mine_count() as isize - self.flag_count() as isize
+    }
+
+    /// Shows the number of mines in every row and column, see
+    /// `row_mines` and `col_mines`.
+    pub fn set_line_counts(&mut self, line_counts: bool) {
+        self.line_counts = line_counts;
+    }
+
+    pub fn has_line_counts(&self) -> bool {
+        self.line_counts
+    }
+
+    /// Number of mines in every row.
+    pub fn row_mines(&self) -> Vec<usize> {
+        self.cells.iter()
+            .map(|col| col.iter().map(|cell| cell.mine_count() as usize).sum())
+            .collect()
+    }
+
+    /// Number of mines in every column.
+    pub fn col_mines(&self) -> Vec<usize> {
+        let (cols, _) = self.size();
+        (0..cols as usize)
+            .map(|y| self.cells.iter().map(|col| col[y].mine_count() as usize).sum())
+            .collect()
+    }
+
+    /// The quadrant of a cell: 0 top left, 1 top right, 2 bottom left and
+    /// 3 bottom right. The middle row and column of odd sizes belong to the
+    /// bottom and right quadrants.
+    pub fn quadrant(&self, x: u16, y: u16) -> usize {
+        let (cols, rows) = self.size();
+        quadrant(rows as usize, cols as usize, x as usize, y as usize)
+    }
+
+    /// `mines_left` for every quadrant.
+    pub fn quadrant_mines_left(&self) -> [isize; 4] {
+        let mut left = [0; 4];
+        for (i, left) in left.iter_mut().enumerate() {
+            *left = self.mines[i] as isize - self.flags[i] as isize;
+        }
+        left
+    }
+
+    /// Whether every cell without a mine is opened.
+    pub fn is_won(&self) -> bool {
+        for col in self.cells.iter() {
+            for cell in col.iter() {
+                if cell.value.eq(&CellValue::Water) && !cell.opened {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Fails for boards of `rows` rows and `cols` columns that `mines` mines,
+/// up to `per_cell` on a cell, can't be placed on.
+pub(crate) fn check_size(rows: usize, cols: usize, mines: u16, per_cell: u8) -> Result<(), MinesError> {
+    let cells = rows as u64 * cols as u64;
+    if cells == 0 {
+        return Err(MinesError::EmptyField);
+    }
+    // the first click needs at least one safe cell
+    if mines as u64 > (cells - 1) * per_cell as u64 {
+        return Err(MinesError::TooManyMines);
+    }
+    Ok(())
+}
+
+fn generate_cells<R: Rng>(rng: &mut R, width: u16, height: u16, mines: u16) -> Result<Grid<Cell>, MinesError> {
+    check_size(width as usize, height as usize, mines, 1)?;
+    let mut cells = Grid::new(width as usize, height as usize, Cell::water());
+    draw_mines(rng, width, height, mines, |x, y| !std::mem::replace(&mut cells[x][y], Cell::mine()).is_mine());
+    Ok(cells)
+}
+
+/// Draws the cells of `mines` mines on a board of `rows` rows and `cols`
+/// columns from `rng`, passing each to `place`, which tells whether it was
+/// free. A cell that wasn't is drawn again.
+pub(crate) fn draw_mines<R: Rng>(rng: &mut R, rows: u16, cols: u16, mines: u16, mut place: impl FnMut(usize, usize) -> bool) {
+    for _ in 0..mines {
+        loop {
+            let (x, y) = (
+                rng.gen_range(0, rows) as usize,
+                rng.gen_range(0, cols) as usize,
+            );
+            if place(x, y) {
+                break;
+            }
+        }
+    }
+}
+
+/// Neighbours of `(x, y)` that aren't holes, none for a hole.
+fn neighbours(cells: &Grid<Cell>, topology: Topology, wrap: bool, layers: u16, x: u16, y: u16) -> Vec<(u16, u16)> {
+    if get_2d(cells, x, y).is_ok_and(|cell| cell.hole) {
+        return vec![];
+    }
+    let (rows, cols) = (cells.len() as u16, cells.first().map_or(0, |col| col.len()) as u16);
+    let mut neighbours = topology.stacked_neighbours(x, y, rows, cols, wrap, layers);
+    neighbours.retain(|&(nx, ny)| !cells[nx as usize][ny as usize].hole);
+    neighbours
+}
+
+fn compute_numbers(cells: &Grid<Cell>, topology: Topology, wrap: bool, layers: u16) -> Grid<u8> {
+    let cols = cells.first().map_or(0, |col| col.len());
+    Grid::from_rows(cells.len(), cols, (0..cells.len())
+        .map(|x| (0..cols)
+            .map(|y| neighbours(cells, topology, wrap, layers, x as u16, y as u16).into_iter()
+                .map(|(nx, ny)| cells[nx as usize][ny as usize].mine_count() as u32)
+                .sum::<u32>()
+                .min(u8::MAX as u32) as u8)
+            .collect()))
+}
+
+fn quadrant(rows: usize, cols: usize, x: usize, y: usize) -> usize {
+    2 * (x >= rows / 2) as usize + (y >= cols / 2) as usize
+}
+
+fn get_2d<T>(grid: &Grid<T>, x: u16, y: u16) -> Result<&T, MinesError> {
+    if let Some(col) = grid.get(x as usize) {
+        if let Some(item) = col.get(y as usize) {
+            return Ok(item);
+        }
+    }
+    Err(MinesError::OutOfBounds(x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cell, CellValue::{self, *}, Field, MinesError, RevealStyle::*, Topology};
+    use crate::grid::Grid;
+
+    #[test]
+    fn with_cells() {
+        let cells = cells_from_types(vec![
+            vec![Water, Water, Mine],
+            vec![Mine, Water, Water],
+            vec![Mine, Water, Mine],
+        ]);
+        let field = Field::with_cells(cells);
+        let numbers = vec![
+            vec![1, 2, 0],
+            vec![1, 4, 2],
+            vec![1, 3, 0],
+        ];
+        assert_eq!(Grid::from(numbers), field.numbers);
+    }
+
+    #[test]
+    fn flag_cell() {
+        let cells = cells_from_types(vec![
+            vec![Water, Mine],
+            vec![Water, Water],
+        ]);
+        let mut field = Field::with_cells(cells);
+        field.flag(1, 0).unwrap();
+        assert!(field.cells[1][0].is_flagged());
+        field.flag(1, 0).unwrap();
+        assert!(!field.cells[1][0].is_flagged());
+    }
+
+    #[test]
+    fn open_around_flag() {
+        let mut field = Field::parse("f..\n...\n..*").unwrap();
+        field.open(1, 0).unwrap();
+        assert!(!field.cells[0][0].opened);
+        assert_eq!(7, field.opened_count());
+    }
+
+    #[test]
+    fn failed_moves_change_nothing() {
+        let untouched = |field: &Field, change: &dyn Fn(&mut Field) -> Result<(), MinesError>| {
+            let mut changed = field.clone();
+            assert!(change(&mut changed).is_err());
+            assert!(field.cells == changed.cells);
+            assert_eq!(field.numbers, changed.numbers);
+            assert_eq!((field.flags, field.layers), (changed.flags, changed.layers));
+        };
+        let mut field = Field::parse("o..\n.f*\n..*\n").unwrap();
+        untouched(&field, &|field| field.open(3, 0));
+        untouched(&field, &|field| field.flag(0, 3));
+        untouched(&field, &|field| field.cycle_mark(5, 5));
+        untouched(&field, &|field| field.set_layers(2));
+        untouched(&field, &|field| field.try_open(1, 2));
+        // the flag is wrong, so chording the opened cell would hit a mine
+        field.open(0, 1).unwrap();
+        untouched(&field, &|field| field.try_chord(0, 1));
+        untouched(&field, &|field| field.chord(9, 9));
+        untouched(&Field::parse("**\n*o\n").unwrap(), &|field| field.ensure_safe(0, 0, &mut rand::thread_rng()));
+        let mut staged = Field::parse("....\n").unwrap();
+        staged.split_stages(2);
+        untouched(&staged, &|field| field.open(0, 3));
+    }
+
+    #[test]
+    fn lost_moves_are_made_in_full() {
+        let mut field = Field::parse("o..\n.f*\n..*\n").unwrap();
+        assert_eq!(Err(MinesError::MineOpened), field.open(1, 2));
+        assert!(field.cells[1][2].opened);
+
+        // every cell of the chord is opened, whichever comes first
+        let mut field = Field::parse("o.*\n.f.\n...\n").unwrap();
+        field.open(0, 1).unwrap();
+        assert_eq!(Err(MinesError::MineOpened), field.chord(0, 1));
+        assert!(field.cells[0][2].opened);
+        assert!(field.cells[1][0].opened && field.cells[1][2].opened);
+        assert!(!field.cells[1][1].opened);
+        assert_eq!(vec![(0, 2)], field.opened_mines());
+    }
+
+    #[test]
+    fn closed() {
+        let mut field = Field::parse("oF.\n*q.").unwrap();
+        field.set_line_counts(true);
+        let closed = field.closed();
+        assert_eq!(".*.\n*..\n", closed.to_board_file());
+        assert_eq!((0, 2), (closed.flag_count(), closed.mine_count()));
+        assert!(closed.has_line_counts());
+    }
+
+    #[test]
+    fn wrap() {
+        let mut field = Field::parse("*...\n....\n....\n....").unwrap();
+        assert_eq!(0, field.number(3, 3).unwrap());
+        field.set_wrap(true);
+        assert_eq!(1, field.number(3, 3).unwrap());
+        assert_eq!(0, field.number(2, 2).unwrap());
+        field.open(2, 2).unwrap();
+        assert!(field.is_won());
+        assert!(field.closed().wraps());
+    }
+
+    #[test]
+    fn layers() {
+        let mut field = Field::parse("*..\n...\n...\n...\n...\n..*").unwrap();
+        assert_eq!(0, field.number(3, 1).unwrap());
+        field.set_layers(2).unwrap();
+        assert_eq!(1, field.number(3, 1).unwrap());
+        assert_eq!(2, field.number(4, 1).unwrap());
+        assert_eq!("2 1 2", field.coords(3, 1));
+        assert_eq!(Err(MinesError::UnevenLayers(6, 4)), field.set_layers(4));
+        field.open(2, 0).unwrap();
+        // the opening spreads into the layer below
+        assert!(field.cell(5, 1).unwrap().opened);
+        assert!(field.closed().layers() == 2);
+    }
+
+    #[test]
+    fn three_bv() {
+        // one opening on the left, two lone numbers on the right
+        let field = Field::parse("...*.\n.....\n....*").unwrap();
+        assert_eq!(3, field.three_bv());
+        assert_eq!(1, Field::parse("...\n...").unwrap().three_bv());
+        assert_eq!(2, Field::parse("*.*").unwrap().three_bv() + Field::parse(".*").unwrap().three_bv());
+        assert_eq!(2, Field::parse("-.*.-").unwrap().three_bv());
+    }
+
+    #[test]
+    fn mask() {
+        use rand::{SeedableRng, rngs::StdRng};
+        let mask = Field::parse_mask(".#\n###\n\n #").unwrap();
+        assert_eq!(vec![vec![false, true, false], vec![true; 3], vec![false, true, false]], mask);
+        assert!(Field::parse_mask("#x").is_err());
+        assert_eq!(Err(MinesError::EmptyField), Field::parse_mask("..").map(|_| ()));
+        let field = Field::generate_masked(&mut StdRng::seed_from_u64(1), &mask, 4).unwrap();
+        assert_eq!((4, 5, 1), (field.mine_count(), field.cell_count(), field.safe_count()));
+        assert!(field.cells.iter().flatten().all(|cell| !(cell.is_hole() && cell.is_mine())));
+        assert_eq!(Err(MinesError::TooManyMines), Field::generate_masked(&mut StdRng::seed_from_u64(1), &mask, 5).map(|_| ()));
+
+        let mut field = Field::parse("-*-\n...\n-.-").unwrap();
+        assert_eq!(Grid::from(vec![vec![0, 0, 0], vec![1, 1, 1], vec![0, 0, 0]]), field.numbers);
+        assert_eq!(vec![(1, 0), (1, 1), (1, 2)], field.neighbours(2, 1));
+        assert!(field.neighbours(2, 0).is_empty());
+        assert_eq!("mines 1\n- _ -\n_ _ _\n- _ -\n", field.position());
+        field.open(2, 1).unwrap();
+        assert!(field.is_won());
+        assert_eq!(4, field.opened_count());
+        assert_eq!("-*-\nooo\n-o-\n", field.to_board_file());
+        assert_eq!("-*-\n...\n-.-\n", field.closed().to_board_file());
+        let position = field.position();
+        assert_eq!("mines 1\n- _ -\n1 1 1\n- 0 -\n", position);
+        assert_eq!(position, Field::from_position(&position).unwrap().position());
+    }
+
+    #[test]
+    fn multimine() {
+        let mut field = Field::parse("3..\n.o.\n..*").unwrap();
+        assert_eq!((3, 4), (field.mines_per_cell(), field.mine_count()));
+        // two cells hold the four mines
+        assert_eq!(7, field.safe_count());
+        assert_eq!(4, field.number(1, 1).unwrap());
+        assert_eq!("3..\n.o.\n..*\n", field.to_board_file());
+        field.flag(0, 0).unwrap();
+        field.flag(0, 0).unwrap();
+        assert_eq!((2, 0), (field.mines_left(), field.wrong_flags()));
+        field.flag(0, 0).unwrap();
+        field.flag(2, 2).unwrap();
+        field.flag(2, 2).unwrap();
+        assert_eq!((-1, 1), (field.mines_left(), field.wrong_flags()));
+        field.flag(2, 2).unwrap();
+        field.flag(2, 2).unwrap();
+        field.flag(2, 2).unwrap();
+        assert_eq!((0, 0), (field.mines_left(), field.wrong_flags()));
+        field.chord(1, 1).unwrap();
+        assert!(field.is_won());
+        assert_eq!(3, field.closed().mines_per_cell());
+
+        use rand::{SeedableRng, rngs::StdRng};
+        let field = Field::generate_multimine(&mut StdRng::seed_from_u64(1), 3, 3, 16, 2).unwrap();
+        assert_eq!(16, field.mine_count());
+        assert_eq!(1, field.cells.iter().flatten().filter(|cell| !cell.is_mine()).count());
+        assert!(Field::generate_multimine(&mut StdRng::seed_from_u64(1), 3, 3, 17, 2).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let field = Field::parse("# comment\n.*o\nfF.\n").unwrap();
+        assert_eq!((3, 2), field.size());
+        assert_eq!(2, field.mine_count());
+        assert!(field.cells[0][2].opened);
+        assert!(field.cells[1][0].is_flagged());
+        assert!(field.cells[1][1].is_flagged());
+        assert!(field.cells[1][1].value == Mine);
+        assert_eq!(Grid::from(vec![vec![2, 1, 2], vec![2, 1, 2]]), field.numbers);
+    }
+
+    #[test]
+    fn mines_left() {
+        let mut field = Field::parse("o*.\nf..\n..*").unwrap();
+        assert_eq!((2, 1, 1), (field.mine_count(), field.flag_count(), field.mines_left()));
+        field.flag(0, 1).unwrap();
+        field.flag(2, 2).unwrap();
+        field.flag(2, 1).unwrap();
+        assert_eq!(-2, field.mines_left());
+        assert_eq!(2, field.wrong_flags());
+        field.flag(1, 0).unwrap();
+        field.flag(0, 0).unwrap();
+        assert_eq!(-1, field.mines_left());
+        field.reveal(2, 2);
+        assert_eq!(0, field.mines_left());
+    }
+
+    #[test]
+    fn quadrants() {
+        let mut field = Field::parse("*..*.\n.....\n*...*\n.*..*").unwrap();
+        assert_eq!((0, 3), (field.quadrant(1, 1), field.quadrant(2, 2)));
+        assert_eq!([1, 1, 2, 2], field.quadrant_mines_left());
+        field.flag(0, 3).unwrap();
+        field.flag(3, 1).unwrap();
+        field.flag(1, 1).unwrap();
+        assert_eq!([0, 0, 1, 2], field.quadrant_mines_left());
+        field.ensure_safe(0, 0, &mut rand::thread_rng()).unwrap();
+        assert_eq!(6, field.mine_count());
+        assert_eq!(field.mines_left(), field.quadrant_mines_left().iter().sum());
+    }
+
+    #[test]
+    fn line_mines() {
+        let field = Field::parse("*..*.\n.....\n*...*").unwrap();
+        assert_eq!(vec![2, 0, 2], field.row_mines());
+        assert_eq!(vec![2, 0, 0, 1, 1], field.col_mines());
+    }
+
+    #[test]
+    fn generate_crosssweeper() {
+        use rand::{SeedableRng, rngs::StdRng};
+        let mut rng = StdRng::seed_from_u64(3);
+        let (field, (x, y)) = Field::generate_crosssweeper_until(&mut rng, 9, 9, 16, 1000, &crate::CancelToken::new(), |_| true).unwrap();
+        assert!(field.has_line_counts());
+        let mut solved = field.clone();
+        solved.open(x, y).unwrap();
+        assert!(crate::solver::apply_deductions(&mut solved) && solved.is_won());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(Err(MinesError::MineOpenedInBoard(1, 2)), Field::parse("...\n..X").map(|_| ()));
+        assert_eq!(Err(MinesError::InvalidBoard(2, "expected 3 cells, found 2".into())), Field::parse("...\n..").map(|_| ()));
+        assert_eq!(Err(MinesError::InvalidBoard(1, "unknown cell 'z'".into())), Field::parse("z").map(|_| ()));
+        assert_eq!(Err(MinesError::EmptyField), Field::parse("# nothing").map(|_| ()));
+        let too_wide = ".".repeat(u16::MAX as usize + 1);
+        assert_eq!(Err(MinesError::InvalidBoard(1, "boards have at most 65535 rows and columns".into())), Field::parse(&too_wide).map(|_| ()));
+        assert_eq!(Err(MinesError::InvalidBoard(65536, "boards have at most 65535 rows and columns".into())), Field::parse(&".\n".repeat(65536)).map(|_| ()));
+    }
+
+    #[test]
+    fn to_board_file() {
+        let text = "oo.\nf*F\n*qQ\n";
+        assert_eq!(text, Field::parse(text).unwrap().to_board_file());
+    }
+
+    #[test]
+    fn export() {
+        let field = Field::parse("oo.\nf*F\n*q-\n").unwrap();
+        let text = field.export();
+        assert_eq!("size 3 3\nmines 2 2, 3 2, 1 3\nopened 1 1, 2 1\nflags 1 2, 3 2\nmarks 2 3\nholes 3 3\n", text);
+        let imported = Field::import(&text, 1 << 20).unwrap();
+        assert_eq!(field.to_board_file(), imported.to_board_file());
+        assert_eq!(field.numbers, imported.numbers);
+
+        let mut field = Field::parse("3.\n..\n..\n.2\n").unwrap();
+        field.set_topology(crate::Topology::Hex);
+        field.set_wrap(true);
+        field.set_layers(2).unwrap();
+        field.set_line_counts(true);
+        field.flag(0, 0).unwrap();
+        field.flag(0, 0).unwrap();
+        let imported = Field::import(&field.export(), 1 << 20).unwrap();
+        assert_eq!(
+            (crate::Topology::Hex, true, 2, true, 3, 2),
+            (imported.topology(), imported.wraps(), imported.layers(), imported.has_line_counts(), imported.mines_per_cell(), imported.cells[0][0].flags()),
+        );
+        assert_eq!(field.numbers, imported.numbers);
+    }
+
+    #[test]
+    fn import_invalid() {
+        let invalid = |line, reason: &str| Err(MinesError::InvalidBoard(line, reason.into()));
+        assert_eq!(invalid(1, "the 'size' line has to come first"), Field::import("mines 1 1", 1 << 20).map(|_| ()));
+        assert_eq!(invalid(3, "3 1 is off the board"), Field::import("size 2 2\n# a comment\nmines 3 1", 1 << 20).map(|_| ()));
+        assert_eq!(invalid(2, "expected <col> <row>, found '1'"), Field::import("size 2 2\nflags 1", 1 << 20).map(|_| ()));
+        assert_eq!(invalid(2, "unknown line 'bombs'"), Field::import("size 2 2\nbombs 1 1", 1 << 20).map(|_| ()));
+        assert_eq!(Err(MinesError::MineOpenedInBoard(0, 1)), Field::import("size 2 2\nopened 2 1\nmines 2 1", 1 << 20).map(|_| ()));
+        assert_eq!(Err(MinesError::UnevenLayers(3, 2)), Field::import("size 2 3\nlayers 2", 1 << 20).map(|_| ()));
+        assert_eq!(Err(MinesError::EmptyField), Field::import("size 0 2", 1 << 20).map(|_| ()));
+        match Field::import("size 65535 65535", 1 << 20) {
+            Err(MinesError::InvalidBoard(1, reason)) => assert!(reason.starts_with("a 65535x65535 board needs about "), "{}", reason),
+            other => panic!("expected a board too big, got {:?}", other.map(|_| ())),
+        }
+        let flags = vec!["1 1"; 256].join(", ");
+        assert_eq!(invalid(2, "more than 255 flags at 1 1"), Field::import(&format!("size 1 1\nflags {}", flags), 1 << 20).map(|_| ()));
+    }
+
+    #[test]
+    fn position() {
+        let field = Field::parse("oo.\nf*.\n*..").unwrap();
+        let text = field.position();
+        assert_eq!("mines 2\n1 1 _\nF _ _\n_ _ _\n", text);
+        let loaded = Field::from_position(&text).unwrap();
+        assert_eq!(2, loaded.mine_count());
+        assert_eq!(field.opened_count(), loaded.opened_count());
+        assert!(loaded.cells[1][0].is_flagged());
+        assert_eq!(field.numbers[0][1], loaded.numbers[0][1]);
+        assert_eq!(Err(MinesError::TooManyMines), Field::from_position("mines 3\n1_").map(|_| ()));
+        assert_eq!(Err(MinesError::InvalidBoard(1, "missing 'mines' line".into())), Field::from_position("1_").map(|_| ()));
+        assert_eq!(Err(MinesError::InvalidBoard(2, "unknown cell '*'".into())), Field::from_position("mines 1\n*_").map(|_| ()));
+        // the old rows without spaces
+        assert_eq!(text, Field::from_position("mines 2\n11_\nF__\n___\n").unwrap().position());
+    }
+
+    #[test]
+    fn position_shapes() {
+        // numbers of 10 and more and several flags on a cell
+        let field = Field::import("size 3 3\nmines 1 1, 1 1, 1 1, 1 1, 1 1, 2 1, 2 1, 2 1, 1 2, 1 2, 1 2\nflags 2 1, 2 1\nopened 2 2", 1 << 20).unwrap();
+        let text = field.position();
+        assert_eq!("mines 11\nper-cell 5\n_ F2 _\n_ 11 _\n_ _ _\n", text);
+        let loaded = Field::from_position(&text).unwrap();
+        assert_eq!((11, 5, 2), (loaded.mine_count(), loaded.mines_per_cell(), loaded.cells[0][1].flags()));
+        assert_eq!(text, loaded.position());
+
+        let mut field = Field::parse("o*..\n....\n..*.\n....").unwrap();
+        field.set_topology(Topology::Hex);
+        field.set_wrap(true);
+        field.set_layers(2).unwrap();
+        let text = field.position();
+        assert!(text.starts_with("mines 2\ngrid hex\nwrap\nlayers 2\n"));
+        let loaded = Field::from_position(&text).unwrap();
+        assert_eq!((Topology::Hex, true, 2), (loaded.topology(), loaded.wraps(), loaded.layers()));
+        assert_eq!(field.neighbours(0, 0), loaded.neighbours(0, 0));
+        assert_eq!(text, loaded.position());
+    }
+
+    #[test]
+    fn memory_estimate() {
+        let small = Field::memory_estimate(9, 9);
+        let large = Field::memory_estimate(1000, 1000);
+        assert!(small < 4096);
+        // a few bytes for every cell
+        assert!(large > 3_000_000 && large < 10_000_000);
+    }
+
+    #[test]
+    fn ensure_safe() {
+        let cells = cells_from_types(vec![
+            vec![Mine, Water],
+            vec![Water, Water],
+        ]);
+        let mut field = Field::with_cells(cells);
+        field.ensure_safe(0, 0, &mut rand::thread_rng()).unwrap();
+        assert!(!field.cells[0][0].is_mine());
+        assert_eq!(1, field.mine_count());
+        assert_eq!(1, field.numbers[0][0]);
+        field.open(0, 0).unwrap();
+    }
+
+    #[test]
+    fn ensure_safe_water() {
+        let cells = cells_from_types(vec![
+            vec![Mine, Water],
+        ]);
+        let mut field = Field::with_cells(cells);
+        field.ensure_safe(0, 1, &mut rand::thread_rng()).unwrap();
+        assert!(field.cells[0][0].is_mine());
+    }
+
+    #[test]
+    fn ensure_safe_flagged() {
+        let mut field = Field::parse("*..\n...").unwrap();
+        field.flag(1, 2).unwrap();
+        for _ in 0..20 {
+            let mut moved = field.clone();
+            moved.ensure_safe(0, 0, &mut rand::thread_rng()).unwrap();
+            assert!(moved.cells[1][2].is_flagged() && !moved.cells[1][2].is_mine());
+            assert_eq!((1, 1, 0), (moved.mine_count(), moved.flag_count(), moved.mines_left()));
+        }
+    }
+
+    #[test]
+    fn generate_seeded() {
+        use rand::{SeedableRng, rngs::StdRng};
+        let mines = |seed| {
+            let field = Field::generate(&mut StdRng::seed_from_u64(seed), 9, 9, 8).unwrap();
+            field.mine_reveal_order(Instant)
+        };
+        assert_eq!(mines(1), mines(1));
+        assert_ne!(mines(1), mines(2));
+    }
+
+    #[test]
+    fn generate_limits() {
+        let mut rng = rand::thread_rng();
+        let field = Field::generate(&mut rng, 3, 2, 5).unwrap();
+        assert_eq!(5, field.mine_count());
+        assert!(Field::generate(&mut rng, 3, 2, 6).is_err());
+        assert_eq!(Err(MinesError::EmptyField), Field::generate(&mut rng, 0, 9, 0).map(|_| ()));
+        // 255 x 257 cells are exactly u16::MAX, which a u16 product would wrap past
+        assert_eq!(Err(MinesError::TooManyMines), Field::generate(&mut rng, 255, 257, u16::MAX).map(|_| ()));
+        assert_eq!(Err(MinesError::TooManyMines), Field::generate_multimine(&mut rng, 1, 1, 1, 9).map(|_| ()));
+        let field = Field::generate(&mut rng, 300, 300, 9000).unwrap();
+        assert_eq!((300, 300, 9000), (field.size().0, field.size().1, field.mine_count()));
+    }
+
+    #[test]
+    fn open_huge() {
+        use rand::{SeedableRng, rngs::StdRng};
+        let mut field = Field::generate(&mut StdRng::seed_from_u64(1), 1000, 1000, 100).unwrap();
+        let (x, y) = (0..1000).flat_map(|x| (0..1000).map(move |y| (x, y)))
+            .find(|&(x, y)| field.numbers[x as usize][y as usize] == 0 && !field.cells[x as usize][y as usize].is_mine())
+            .unwrap();
+        field.open(x, y).unwrap();
+        assert!(field.opened_count() > 900_000);
+    }
+
+    #[test]
+    fn generate_no_guess() {
+        let mut rng = rand::thread_rng();
+        let (field, (x, y)) = Field::generate_no_guess(&mut rng, 9, 9, 8, 1000).unwrap();
+        assert_eq!(0, field.numbers[x as usize][y as usize]);
+        assert!(crate::solver::solvable_from(&field, x, y));
+    }
+
+    #[test]
+    fn generate_no_guess_accept_best() {
+        let mut attempts = 0;
+        let (field, (x, y)) = Field::generate_no_guess_until(&mut rand::thread_rng(), 30, 16, 170, 1000, &crate::CancelToken::new(), |progress| {
+            attempts = progress.attempts;
+            assert!(progress.best_cleared < 1.0);
+            progress.attempts < 5
+        }).unwrap();
+        assert!(attempts >= 5);
+        assert_eq!(0, field.numbers[x as usize][y as usize]);
+    }
+
+    #[test]
+    fn generate_no_guess_cancelled() {
+        let cancel = crate::CancelToken::new();
+        cancel.cancel();
+        let result = Field::generate_no_guess_until(&mut rand::thread_rng(), 9, 9, 8, 1000, &cancel, |_| true);
+        assert_eq!(Err(MinesError::Cancelled), result.map(|_| ()));
+    }
+
+    #[test]
+    fn stages() {
+        let cells = cells_from_types(vec![
+            vec![Water, Water, Water, Water],
+            vec![Mine, Water, Water, Mine],
+        ]);
+        let mut field = Field::with_cells(cells);
+        field.split_stages(2);
+        assert_eq!(Grid::from(vec![vec![0, 0, 1, 1], vec![0, 0, 1, 1]]), field.zones);
+        assert!(field.is_locked(0, 2));
+        assert!(field.is_locked_with(0, 2, field.unlocked_zone()) && !field.is_locked_with(0, 2, 1));
+        assert_eq!(Err(MinesError::ZoneLocked(0, 3)), field.open(0, 3));
+        assert!(!field.cells[0][3].opened);
+        field.open(0, 0).unwrap();
+        field.open(0, 1).unwrap();
+        assert!(field.is_locked(1, 2));
+        field.open(1, 1).unwrap();
+        assert!(!field.is_locked(1, 2));
+        field.open(0, 3).unwrap();
+        assert!(field.cells[0][3].opened);
+    }
+
+    #[test]
+    fn mine_reveal_order() {
+        let cells = cells_from_types(vec![
+            vec![Mine, Water, Water, Mine],
+            vec![Water, Water, Water, Water],
+            vec![Water, Mine, Water, Water],
+        ]);
+        let mut field = Field::with_cells(cells);
+        assert_eq!(vec![vec![(0, 0), (0, 3), (2, 1)]], field.mine_reveal_order(Instant));
+        assert_eq!(vec![vec![(0, 0)], vec![(2, 1)], vec![(0, 3)]], field.mine_reveal_order(Sweep));
+        field.cells[2][1].opened = true;
+        assert_eq!(vec![vec![(2, 1)], vec![(0, 0), (0, 3)]], field.mine_reveal_order(Cascade));
+    }
+
+    fn cells_from_types(types: Vec<Vec<CellValue>>) -> Vec<Vec<Cell>> {
+        types.into_iter().map(|c|
+            c.into_iter().map(|v|
+                Cell {
+                    value: v,
+                    ..Cell::water()
+                }
+            ).collect()
+        ).collect()
+    }
+}
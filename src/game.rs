@@ -0,0 +1,117 @@
+//! A game with a safe first move for frontends that keep the board on the
+//! other side of an API: the browser, `serve` and programs playing through
+//! the JSON protocol.
+
+use crate::{Difficulty, Field, MinesError};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+/// Where a game stands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Playing = 0,
+    Lost = 1,
+    Won = 2,
+}
+
+impl State {
+    pub fn name(self) -> &'static str {
+        match self {
+            State::Playing => "playing",
+            State::Lost => "lost",
+            State::Won => "won",
+        }
+    }
+}
+
+/// A game on a board of a difficulty, made from a seed so it can be played
+/// again.
+pub struct Game {
+    pub field: Field,
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    rng: StdRng,
+    started: bool,
+    pub state: State,
+}
+
+impl Game {
+    pub fn new(difficulty: Difficulty, seed: u64) -> Result<Self, MinesError> {
+        let (width, height, mines) = difficulty.dimensions();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let field = Field::generate(&mut rng, height, width, mines)?;
+        Ok(Self::with_field(field, difficulty, seed, rng, true))
+    }
+
+    /// A game on a board made elsewhere, with `rng` moving a mine away from
+    /// the first opened cell if `safe_start` is set.
+    pub fn with_field(field: Field, difficulty: Difficulty, seed: u64, rng: StdRng, safe_start: bool) -> Self {
+        Self { field, difficulty, seed, rng, started: !safe_start, state: State::Playing }
+    }
+
+    /// Plays `action`, one of `open`, `flag` or `chord`, on the cell at
+    /// `row` and `col`. Moves after the end of the game change nothing.
+    pub fn play(&mut self, action: &str, row: u16, col: u16) -> Result<State, MinesError> {
+        if self.state != State::Playing {
+            return Ok(self.state);
+        }
+        let result = match action {
+            "open" => {
+                if !self.started {
+                    self.field.ensure_safe(row, col, &mut self.rng)?;
+                    self.started = true;
+                }
+                self.field.open(row, col)
+            },
+            "flag" => self.field.flag(row, col),
+            _ => self.field.chord(row, col),
+        };
+        self.state = match result {
+            Err(MinesError::MineOpened) => State::Lost,
+            Err(e) => return Err(e),
+            Ok(()) if self.field.is_won() => State::Won,
+            Ok(()) => State::Playing,
+        };
+        Ok(self.state)
+    }
+
+    /// What the player sees, one string per row: `.` for a closed cell, `F`
+    /// for a flag, `-` for a hole, `*` for a mine once opened or the game is
+    /// lost, and the number of neighbouring mines of an opened cell, with
+    /// `a` for 10 and on for the bigger numbers of boards with layers.
+    pub fn board(&self) -> Vec<String> {
+        self.field.cells.iter().enumerate()
+            .map(|(x, row)| row.iter().enumerate()
+                .map(|(y, cell)| match cell {
+                    _ if cell.is_hole() => '-',
+                    _ if cell.is_mine() && (cell.opened || self.state == State::Lost) => '*',
+                    _ if cell.is_flagged() => 'F',
+                    _ if cell.opened => std::char::from_digit(self.field.numbers[x][y] as u32, 36).unwrap_or('+'),
+                    _ => '.',
+                })
+                .collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::game::{Game, State};
+
+    #[test]
+    fn moves_after_loss() {
+        let mut game = Game::new(Difficulty::Beginner, 4).unwrap();
+        game.play("open", 4, 4).unwrap();
+        let mine = (0..81).map(|i| (i / 9, i % 9)).find(|&(x, y)| game.field.cell(x, y).unwrap().is_mine()).unwrap();
+        assert_eq!(Ok(State::Lost), game.play("open", mine.0, mine.1));
+        let board = game.board();
+        for (x, y) in (0..81).map(|i| (i / 9, i % 9)) {
+            for action in ["open", "flag", "chord"] {
+                assert_eq!(Ok(State::Lost), game.play(action, x, y));
+            }
+        }
+        assert_eq!(board, game.board());
+        assert!(board.iter().any(|row| row.contains('*')));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::Difficulty;
+use crate::stats::{data_dir, local_day, GameRecord, Profile};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A daily practice goal, one per line of the goals file:
+///
+/// * `win <count> [difficulty] [nf] [no-guess]` counts won games, `nf` only
+///   those without flags and `no-guess` only boards generated without guessing
+/// * `play <count> [difficulty]` counts finished games, won or lost
+///
+/// `complete` is another word for `win`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Goal {
+    pub wins_only: bool,
+    pub count: u32,
+    pub difficulty: Option<Difficulty>,
+    pub no_flags: bool,
+    pub no_guess: bool,
+}
+
+impl Goal {
+    pub fn matches(&self, record: &GameRecord) -> bool {
+        (record.won || !self.wins_only)
+            && self.difficulty.is_none_or(|d| d == record.difficulty)
+            && (!record.flagged || !self.no_flags)
+            && (record.no_guess || !self.no_guess)
+    }
+
+    /// Number of games that count towards the goal on the local day `day`.
+    pub fn progress(&self, records: &[GameRecord], day: i64) -> u32 {
+        records.iter()
+            .filter(|record| local_day(record.finished) == day && self.matches(record))
+            .count() as u32
+    }
+}
+
+impl FromStr for Goal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let wins_only = match words.next() {
+            Some("win") | Some("complete") => true,
+            Some("play") => false,
+            _ => return Err(format!("Invalid goal '{}', expected win or play", s)),
+        };
+        let count = words.next()
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(|| format!("Invalid goal '{}', expected a number of games", s))?;
+        let mut goal = Goal {
+            wins_only,
+            count,
+            difficulty: None,
+            no_flags: false,
+            no_guess: false,
+        };
+        while let Some(word) = words.next() {
+            match word {
+                "nf" => goal.no_flags = true,
+                "no-guess" => goal.no_guess = true,
+                "custom" => goal.difficulty = Some(format!("custom {}", words.next().unwrap_or_default()).parse()?),
+                _ => goal.difficulty = Some(word.parse()?),
+            }
+        }
+        Ok(goal)
+    }
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", if self.wins_only { "win" } else { "play" }, self.count)?;
+        if let Some(difficulty) = self.difficulty {
+            write!(f, " {}", difficulty)?;
+        }
+        if self.no_flags {
+            write!(f, " nf")?;
+        }
+        if self.no_guess {
+            write!(f, " no-guess")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the goals file, skipping empty lines and `#` comments.
+pub fn parse_goals(text: &str) -> Result<Vec<Goal>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .map(|(line_no, line)| line.parse().map_err(|e| format!("Line {}: {}", line_no + 1, e)))
+        .collect()
+}
+
+/// Directory for the settings of `profile`,
+/// `$XDG_CONFIG_HOME/minesweep_rs` or the platform's usual place.
+pub fn config_dir(profile: &Profile) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+        return Some(profile.dir(PathBuf::from(dir).join("minesweep_rs")));
+    }
+    if std::env::var_os("APPDATA").is_some() {
+        return data_dir(profile);
+    }
+    std::env::var_os("HOME").map(|home| profile.dir(PathBuf::from(home).join(".config/minesweep_rs")))
+}
+
+/// The goals file in `config_dir`.
+pub fn goals_path(profile: &Profile) -> Option<PathBuf> {
+    config_dir(profile).map(|dir| dir.join("goals"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::goals::{parse_goals, Goal};
+    use crate::stats::{local_day, Clicks, GameRecord};
+    use std::time::Duration;
+
+    #[test]
+    fn parse() {
+        let goals = parse_goals("# daily\nwin 3 intermediate\n\ncomplete 1 nf\nplay 5 custom 20x10/30 no-guess\n").unwrap();
+        assert_eq!(3, goals.len());
+        assert_eq!(Some(Difficulty::Intermediate), goals[0].difficulty);
+        assert!(goals[1].wins_only && goals[1].no_flags);
+        assert_eq!("play 5 custom 20x10/30 no-guess", goals[2].to_string());
+        assert_eq!(Err("Line 2: Invalid goal 'lose 3', expected win or play".into()), parse_goals("win 1\nlose 3"));
+        assert!("win many".parse::<Goal>().is_err());
+    }
+
+    #[test]
+    fn progress() {
+        let today = 1_700_000_000;
+        let game = |won, flagged, finished| GameRecord {
+            finished,
+            difficulty: Difficulty::Beginner,
+            won,
+            time: Duration::from_secs(10),
+            no_guess: false,
+            flagged,
+            hints: 0,
+            clicks: Clicks::default(),
+            three_bv: 0,
+        };
+        let records = vec![
+            game(true, true, today),
+            game(true, false, today),
+            game(false, false, today),
+            game(true, false, today - 3 * 24 * 60 * 60),
+        ];
+        let day = local_day(today);
+        assert_eq!(2, "win 3 beginner".parse::<Goal>().unwrap().progress(&records, day));
+        assert_eq!(1, "win 1 nf".parse::<Goal>().unwrap().progress(&records, day));
+        assert_eq!(3, "play 3".parse::<Goal>().unwrap().progress(&records, day));
+        assert_eq!(0, "win 1 expert".parse::<Goal>().unwrap().progress(&records, day));
+    }
+}
@@ -0,0 +1,166 @@
+use std::iter::FromIterator;
+use std::ops::{Index, IndexMut};
+
+/// Most items a `Grid` keeps in one allocation. Bigger grids are split into
+/// chunks of whole rows so that a huge board doesn't need one block of
+/// memory for all its cells.
+pub(crate) const CHUNK_ITEMS: usize = 1 << 20;
+
+/// Rows of equal length packed row by row into one `Vec`, or into chunks of
+/// rows once there are more than `CHUNK_ITEMS` items. Indexing a row gives a
+/// slice, so `grid[x][y]` works as it does for nested `Vec`s.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Grid<T> {
+    rows: usize,
+    cols: usize,
+    rows_per_chunk: usize,
+    chunks: Vec<Vec<T>>,
+}
+
+impl<T> Grid<T> {
+    fn empty(rows: usize, cols: usize) -> Self {
+        Self { rows, cols, rows_per_chunk: rows_per_chunk(rows, cols), chunks: vec![] }
+    }
+
+    /// Number of chunks a grid of `rows` rows and `cols` columns is kept in.
+    pub(crate) fn chunks_for(rows: usize, cols: usize) -> usize {
+        rows.div_ceil(rows_per_chunk(rows, cols))
+    }
+
+    /// Builds a grid from `rows` rows of `cols` items each.
+    pub(crate) fn from_rows(rows: usize, cols: usize, items: impl IntoIterator<Item = Vec<T>>) -> Self {
+        let mut grid = Self::empty(rows, cols);
+        for row in items {
+            debug_assert_eq!(cols, row.len());
+            match grid.chunks.last_mut() {
+                Some(chunk) if chunk.len() < grid.rows_per_chunk * cols => chunk.extend(row),
+                _ => {
+                    let mut chunk = Vec::with_capacity(grid.rows_per_chunk.min(rows) * cols);
+                    chunk.extend(row);
+                    grid.chunks.push(chunk);
+                },
+            }
+        }
+        grid
+    }
+
+    /// Number of rows.
+    pub(crate) fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub(crate) fn first(&self) -> Option<&[T]> {
+        self.get(0)
+    }
+
+    pub(crate) fn get(&self, x: usize) -> Option<&[T]> {
+        if x < self.rows { Some(&self[x]) } else { None }
+    }
+
+    pub(crate) fn get_mut(&mut self, x: usize) -> Option<&mut [T]> {
+        if x < self.rows { Some(&mut self[x]) } else { None }
+    }
+
+    /// The rows from first to last.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.rows).map(move |x| &self[x])
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        let cols = self.cols.max(1);
+        self.chunks.iter_mut().flat_map(move |chunk| chunk.chunks_mut(cols))
+    }
+
+    /// A grid of the same size with `f` applied to every item.
+    pub(crate) fn map<U>(&self, mut f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            rows: self.rows,
+            cols: self.cols,
+            rows_per_chunk: self.rows_per_chunk,
+            chunks: self.chunks.iter().map(|chunk| chunk.iter().map(&mut f).collect()).collect(),
+        }
+    }
+}
+
+fn rows_per_chunk(rows: usize, cols: usize) -> usize {
+    if rows * cols <= CHUNK_ITEMS { rows.max(1) } else { (CHUNK_ITEMS / cols).max(1) }
+}
+
+impl<T: Clone> Grid<T> {
+    /// A grid of `rows` rows of `cols` copies of `item`.
+    pub(crate) fn new(rows: usize, cols: usize, item: T) -> Self {
+        Self::from_rows(rows, cols, (0..rows).map(|_| vec![item.clone(); cols]))
+    }
+}
+
+impl<T> From<Vec<Vec<T>>> for Grid<T> {
+    fn from(rows: Vec<Vec<T>>) -> Self {
+        let cols = rows.first().map_or(0, |row| row.len());
+        Self::from_rows(rows.len(), cols, rows)
+    }
+}
+
+impl<T> FromIterator<Vec<T>> for Grid<T> {
+    fn from_iter<I: IntoIterator<Item = Vec<T>>>(rows: I) -> Self {
+        Self::from(rows.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<T> Index<usize> for Grid<T> {
+    type Output = [T];
+
+    fn index(&self, x: usize) -> &[T] {
+        assert!(x < self.rows, "row {} of a grid with {} rows", x, self.rows);
+        match self.chunks.get(x / self.rows_per_chunk) {
+            Some(chunk) => {
+                let start = x % self.rows_per_chunk * self.cols;
+                &chunk[start..start + self.cols]
+            },
+            None => &[],
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Grid<T> {
+    fn index_mut(&mut self, x: usize) -> &mut [T] {
+        assert!(x < self.rows, "row {} of a grid with {} rows", x, self.rows);
+        let cols = self.cols;
+        match self.chunks.get_mut(x / self.rows_per_chunk) {
+            Some(chunk) => {
+                let start = x % self.rows_per_chunk * cols;
+                &mut chunk[start..start + cols]
+            },
+            None => &mut [],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::{Grid, CHUNK_ITEMS};
+
+    #[test]
+    fn rows() {
+        let mut grid = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        assert_eq!((2, 1), (grid.len(), grid.chunks.len()));
+        assert_eq!(&[4, 5, 6], &grid[1]);
+        grid[0][2] = 7;
+        assert_eq!(vec![vec![1, 2, 7], vec![4, 5, 6]], grid.iter().map(|row| row.to_vec()).collect::<Vec<_>>());
+        assert_eq!(None, grid.get(2));
+        assert_eq!(Grid::from(vec![vec![2, 4, 14], vec![8, 10, 12]]), grid.map(|item| item * 2));
+    }
+
+    #[test]
+    fn chunks() {
+        let cols = 1000;
+        let rows = 2 * CHUNK_ITEMS / cols + 5;
+        let mut grid = Grid::new(rows, cols, 0u8);
+        assert_eq!((3, 3), (grid.chunks.len(), Grid::<u8>::chunks_for(rows, cols)));
+        grid[rows - 1][cols - 1] = 1;
+        grid[CHUNK_ITEMS / cols][0] = 2;
+        assert_eq!(rows, grid.iter().count());
+        assert_eq!(rows, grid.iter_mut().count());
+        assert_eq!(3, grid.iter().flatten().map(|&item| item as usize).sum::<usize>());
+        assert_eq!(&2, &grid.iter().nth(CHUNK_ITEMS / cols).unwrap()[0]);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{CellValue, Difficulty, Field};
+use rand::Rng;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Per-player adjustments that even out a match between players of
+/// different strength. Parsed from a comma separated list like
+/// `reveals=3,time=30,density=2`, or `none`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Handicap {
+    /// Safe cells opened for the player before the game starts.
+    pub extra_reveals: u16,
+    /// Time subtracted from the player's final time, see `adjusted_time`.
+    /// In a relay, where players share one board and one clock, it makes
+    /// each of the player's timed turns this much longer instead.
+    pub time_bonus: Duration,
+    /// Percentage points taken off the mine density of the player's board.
+    pub density_reduction: u8,
+}
+
+impl Handicap {
+    /// Number of mines for a `width` x `height` board that would get `mines`
+    /// mines without handicap.
+    pub fn mines(&self, width: u16, height: u16, mines: u16) -> u16 {
+        let cells = width as u32 * height as u32;
+        let removed = cells * self.density_reduction as u32 / 100;
+        (mines as u32).saturating_sub(removed) as u16
+    }
+
+    /// The difficulty of the player's board, with `mines` for the mines.
+    pub fn difficulty(&self, difficulty: Difficulty) -> Difficulty {
+        let (width, height, mines) = difficulty.dimensions();
+        match self.mines(width, height, mines) {
+            fewer if fewer < mines => Difficulty::Custom(width, height, fewer.max(1)),
+            _ => difficulty,
+        }
+    }
+
+    /// Reads the handicap of one player, like `2:reveals=3`, counting the
+    /// players from 1. Returns the player counted from 0.
+    pub fn for_player(s: &str) -> Result<(usize, Handicap), String> {
+        let (player, handicap) = s.split_once(':').ok_or_else(|| format!("Expected <player>:<handicap>, got '{}'", s))?;
+        let player = player.trim().parse::<usize>().ok()
+            .and_then(|player| player.checked_sub(1))
+            .ok_or_else(|| format!("Invalid player '{}', counting from 1", player))?;
+        Ok((player, handicap.parse()?))
+    }
+
+    /// Opens `extra_reveals` random safe cells of `field`.
+    pub fn apply<R: Rng>(&self, field: &mut Field, rng: &mut R) {
+        for _ in 0..self.extra_reveals {
+            let mut candidates = vec![];
+            for (x, col) in field.cells.iter().enumerate() {
+                for (y, cell) in col.iter().enumerate() {
+                    if cell.value == CellValue::Water && !cell.opened && !cell.is_flagged() {
+                        candidates.push((x as u16, y as u16));
+                    }
+                }
+            }
+            if candidates.is_empty() {
+                return;
+            }
+            let (x, y) = candidates[rng.gen_range(0, candidates.len())];
+            let _ = field.open(x, y);
+        }
+    }
+
+    /// The time that counts for the player after playing for `elapsed`.
+    pub fn adjusted_time(&self, elapsed: Duration) -> Duration {
+        elapsed.checked_sub(self.time_bonus).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Handicap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        if self.extra_reveals > 0 {
+            parts.push(format!("reveals={}", self.extra_reveals));
+        }
+        if self.time_bonus > Duration::ZERO {
+            parts.push(format!("time={}", self.time_bonus.as_secs()));
+        }
+        if self.density_reduction > 0 {
+            parts.push(format!("density={}", self.density_reduction));
+        }
+        if parts.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", parts.join(","))
+        }
+    }
+}
+
+impl FromStr for Handicap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut handicap = Handicap::default();
+        if s.trim() == "none" {
+            return Ok(handicap);
+        }
+        for part in s.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let mut kv = part.splitn(2, '=');
+            let key = kv.next().unwrap_or_default();
+            let value = kv.next().ok_or_else(|| format!("Missing value for '{}'", key))?;
+            let invalid = |_| format!("Invalid value '{}' for '{}'", value, key);
+            match key {
+                "reveals" => handicap.extra_reveals = value.parse().map_err(invalid)?,
+                "time" => handicap.time_bonus = Duration::from_secs(value.parse().map_err(invalid)?),
+                "density" => handicap.density_reduction = value.parse().map_err(invalid)?,
+                _ => return Err(format!("Unknown handicap '{}'", key)),
+            }
+        }
+        Ok(handicap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Difficulty, Field};
+    use crate::handicap::Handicap;
+    use std::time::Duration;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Ok(Handicap {
+            extra_reveals: 3,
+            time_bonus: Duration::from_secs(30),
+            density_reduction: 2,
+        }), "reveals=3, time=30,density=2".parse());
+        assert_eq!(Ok(Handicap::default()), "".parse());
+        assert!("lives=3".parse::<Handicap>().is_err());
+        assert!("reveals".parse::<Handicap>().is_err());
+        assert!("time=soon".parse::<Handicap>().is_err());
+        let handicap: Handicap = "time=30,reveals=3".parse().unwrap();
+        assert_eq!("reveals=3,time=30", handicap.to_string());
+        assert_eq!(Ok(handicap), "reveals=3,time=30".parse());
+        assert_eq!(Ok(Handicap::default()), Handicap::default().to_string().parse());
+        assert_eq!(Ok((1, Handicap { density_reduction: 2, ..Handicap::default() })), Handicap::for_player("2:density=2"));
+        assert!(Handicap::for_player("0:density=2").is_err());
+        assert!(Handicap::for_player("density=2").is_err());
+    }
+
+    #[test]
+    fn mines() {
+        let handicap = Handicap { density_reduction: 5, ..Handicap::default() };
+        assert_eq!(35, handicap.mines(20, 10, 45));
+        assert_eq!(0, handicap.mines(20, 10, 5));
+        assert_eq!(Difficulty::Custom(30, 16, 75), handicap.difficulty(Difficulty::Expert));
+        assert_eq!(Difficulty::Custom(9, 9, 6), handicap.difficulty(Difficulty::Beginner));
+        assert_eq!(Difficulty::Expert, Handicap::default().difficulty(Difficulty::Expert));
+    }
+
+    #[test]
+    fn apply() {
+        let mut field = Field::parse("*....\n.....\n....*").unwrap();
+        let handicap = Handicap { extra_reveals: 1, ..Handicap::default() };
+        handicap.apply(&mut field, &mut rand::thread_rng());
+        assert!(field.opened_count() > 0);
+        assert!(field.opened_mine().is_none());
+    }
+
+    #[test]
+    fn adjusted_time() {
+        let handicap = Handicap { time_bonus: Duration::from_secs(10), ..Handicap::default() };
+        assert_eq!(Duration::from_secs(5), handicap.adjusted_time(Duration::from_secs(15)));
+        assert_eq!(Duration::from_secs(0), handicap.adjusted_time(Duration::from_secs(5)));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::Field;
+
+/// Earlier states of a board, so moves can be undone and redone.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Field>,
+    redo: Vec<Field>,
+    /// Most moves that can be undone, unlimited if `None`.
+    limit: Option<usize>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A history that forgets the oldest moves beyond the last `limit`, for
+    /// boards too big to keep a copy of every move.
+    pub fn with_limit(limit: usize) -> Self {
+        Self { limit: Some(limit), ..Self::default() }
+    }
+
+    /// Remembers `before`, the board as it was before a move that led to
+    /// `after`. Moves that didn't change anything aren't remembered, and a
+    /// new move can't be followed by a redo of an undone one.
+    pub fn record(&mut self, before: Field, after: &Field) {
+        if before.cells != after.cells {
+            self.undo.push(before);
+            self.redo.clear();
+            if let Some(limit) = self.limit {
+                let excess = self.undo.len().saturating_sub(limit);
+                self.undo.drain(..excess);
+            }
+        }
+    }
+
+    /// Puts `field` back into the state before the last move. Returns
+    /// `false` if there is no move left to undo.
+    pub fn undo(&mut self, field: &mut Field) -> bool {
+        match self.undo.pop() {
+            Some(before) => {
+                self.redo.push(std::mem::replace(field, before));
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Makes the last undone move again. Returns `false` if nothing was
+    /// undone since the last move.
+    pub fn redo(&mut self, field: &mut Field) -> bool {
+        match self.redo.pop() {
+            Some(after) => {
+                self.undo.push(std::mem::replace(field, after));
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Drops the undone moves, e.g. so a fatal move can't be redone.
+    pub fn forget_redo(&mut self) {
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Field;
+    use crate::history::History;
+
+    #[test]
+    fn undo_redo() {
+        let mut field = Field::parse("..*\n...").unwrap();
+        let mut history = History::new();
+        assert!(!history.undo(&mut field));
+        let before = field.clone();
+        field.flag(0, 2).unwrap();
+        history.record(before, &field);
+        let before = field.clone();
+        field.open(1, 0).unwrap();
+        history.record(before, &field);
+        // flagging an opened cell changes nothing
+        let before = field.clone();
+        field.flag(1, 0).unwrap();
+        history.record(before, &field);
+
+        assert!(history.undo(&mut field));
+        assert_eq!((0, 1), (field.opened_count(), field.flag_count()));
+        assert!(history.undo(&mut field));
+        assert_eq!(0, field.flag_count());
+        assert!(!history.undo(&mut field));
+        assert!(history.redo(&mut field));
+        assert!(history.redo(&mut field));
+        assert!(!history.redo(&mut field));
+        assert_eq!((4, 1), (field.opened_count(), field.flag_count()));
+
+        assert!(history.undo(&mut field));
+        let before = field.clone();
+        field.open(1, 2).unwrap();
+        history.record(before, &field);
+        assert!(!history.redo(&mut field));
+    }
+
+    #[test]
+    fn limit() {
+        let mut field = Field::parse("...\n..*").unwrap();
+        let mut history = History::with_limit(2);
+        for y in 0..3 {
+            let before = field.clone();
+            field.flag(0, y).unwrap();
+            history.record(before, &field);
+        }
+        assert!(history.undo(&mut field) && history.undo(&mut field));
+        assert!(!history.undo(&mut field));
+        assert_eq!(1, field.flag_count());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::Difficulty;
+use crate::stats::{data_dir, local_day, Profile};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A played board as written to the journal: when the game ended, how it
+/// went and what it takes to play the same board again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Seconds since the Unix epoch when the game ended.
+    pub finished: u64,
+    pub difficulty: Difficulty,
+    pub won: bool,
+    pub time: Duration,
+    /// `None` for boards read from a file.
+    pub seed: Option<u64>,
+    /// The arguments of `play` that make the board again, none if it can't
+    /// be made again.
+    pub args: Vec<String>,
+}
+
+impl Entry {
+    /// Writes the entry as a tab separated line: end time, result,
+    /// difficulty, time in milliseconds, the seed or `-` and the arguments.
+    pub fn to_line(&self) -> String {
+        let mut line = format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.finished,
+            if self.won { "won" } else { "lost" },
+            self.difficulty,
+            self.time.as_millis(),
+            self.seed.map_or("-".to_string(), |seed| seed.to_string()),
+        );
+        for arg in &self.args {
+            line.push('\t');
+            line.push_str(arg);
+        }
+        line
+    }
+
+    /// Reads a line written by `to_line`.
+    pub fn parse_line(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 {
+            return Err(format!("expected at least 5 fields, found {}", fields.len()));
+        }
+        let number = |field: &str| field.parse::<u64>().map_err(|_| format!("invalid number '{}'", field));
+        Ok(Self {
+            finished: number(fields[0])?,
+            won: match fields[1] {
+                "won" => true,
+                "lost" => false,
+                other => return Err(format!("unknown result '{}'", other)),
+            },
+            difficulty: fields[2].parse()?,
+            time: Duration::from_millis(number(fields[3])?),
+            seed: match fields[4] {
+                "-" => None,
+                seed => Some(number(seed)?),
+            },
+            args: fields[5..].iter().map(|arg| arg.to_string()).collect(),
+        })
+    }
+}
+
+/// Which entries to show, from words like `expert losses this week`:
+///
+/// * `won` or `wins`, `lost` or `losses` for the result
+/// * `today`, `week` and `month` for games of the last 1, 7 or 30 days
+/// * a difficulty like `expert` or `custom 20x10/30`
+/// * any other word for entries with an argument containing it, like `hex`
+///
+/// `all`, `this` and `last` are only there to read well and match anything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    pub won: Option<bool>,
+    /// Number of local days back to the earliest one shown, 0 for today.
+    pub days: Option<i64>,
+    pub difficulty: Option<Difficulty>,
+    pub words: Vec<String>,
+}
+
+impl Query {
+    pub fn parse(words: &[String]) -> Result<Self, String> {
+        let mut query = Query::default();
+        let mut words = words.iter().map(String::as_str);
+        while let Some(word) = words.next() {
+            match word {
+                "all" | "this" | "last" => {},
+                "won" | "wins" => query.won = Some(true),
+                "lost" | "losses" => query.won = Some(false),
+                "today" => query.days = Some(0),
+                "week" => query.days = Some(6),
+                "month" => query.days = Some(29),
+                "custom" => query.difficulty = Some(format!("custom {}", words.next().unwrap_or_default()).parse()?),
+                _ => match word.parse() {
+                    Ok(difficulty) => query.difficulty = Some(difficulty),
+                    Err(_) => query.words.push(word.to_string()),
+                },
+            }
+        }
+        Ok(query)
+    }
+
+    /// Whether `entry` is one to show on the local day `today`.
+    pub fn matches(&self, entry: &Entry, today: i64) -> bool {
+        self.won.is_none_or(|won| won == entry.won)
+            && self.days.is_none_or(|days| today - local_day(entry.finished) <= days)
+            && self.difficulty.is_none_or(|d| d == entry.difficulty)
+            && self.words.iter().all(|word| entry.args.iter().any(|arg| arg.contains(word.as_str())))
+    }
+}
+
+/// The journal of every played board in `data_dir`.
+pub fn journal_path(profile: &Profile) -> Option<PathBuf> {
+    data_dir(profile).map(|dir| dir.join("journal.tsv"))
+}
+
+/// The entries of the journal picked to keep, in `data_dir`.
+pub fn favorites_path(profile: &Profile) -> Option<PathBuf> {
+    data_dir(profile).map(|dir| dir.join("favorites.tsv"))
+}
+
+/// Reads all entries from the journal at `path`. A missing file holds no
+/// entries yet.
+pub fn load(path: &Path) -> Result<Vec<Entry>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("Can't read {}: {}", path.display(), e)),
+    };
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(line_no, line)| Entry::parse_line(line).map_err(|e| format!("{} line {}: {}", path.display(), line_no + 1, e)))
+        .collect()
+}
+
+/// Appends `entry` to the journal at `path`, creating it if needed.
+pub fn append(path: &Path, entry: &Entry) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Can't write {}: {}", path.display(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(error)?;
+    writeln!(file, "{}", entry.to_line()).map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::journal::{append, load, Entry, Query};
+    use crate::stats::local_day;
+    use std::time::Duration;
+
+    fn entry() -> Entry {
+        Entry {
+            finished: 1_700_000_000,
+            difficulty: Difficulty::Expert,
+            won: false,
+            time: Duration::from_millis(83_400),
+            seed: Some(1234),
+            args: vec!["--grid".into(), "hex".into(), "--seed".into(), "1234".into(), "expert".into()],
+        }
+    }
+
+    fn query(words: &str) -> Query {
+        let words: Vec<String> = words.split_whitespace().map(String::from).collect();
+        Query::parse(&words).unwrap()
+    }
+
+    #[test]
+    fn line() {
+        let line = entry().to_line();
+        assert_eq!("1700000000\tlost\texpert\t83400\t1234\t--grid\thex\t--seed\t1234\texpert", line);
+        assert_eq!(Ok(entry()), Entry::parse_line(&line));
+        let file = Entry { seed: None, args: vec![], ..entry() };
+        assert_eq!(Ok(file.clone()), Entry::parse_line(&file.to_line()));
+        assert!(Entry::parse_line("1700000000\tlost\texpert\t83400").is_err());
+        assert!(Entry::parse_line("1700000000\tquit\texpert\t83400\t-").is_err());
+    }
+
+    #[test]
+    fn queries() {
+        let today = local_day(entry().finished);
+        assert!(query("all expert losses this week").matches(&entry(), today));
+        assert!(query("").matches(&entry(), today + 30));
+        assert!(query("week hex").matches(&entry(), today + 6));
+        assert!(!query("week").matches(&entry(), today + 7));
+        assert!(!query("today").matches(&entry(), today + 1));
+        assert!(!query("wins").matches(&entry(), today));
+        assert!(!query("beginner").matches(&entry(), today));
+        assert!(!query("wrap").matches(&entry(), today));
+        assert_eq!(Some(Difficulty::Custom(20, 10, 30)), query("custom 20x10/30 won").difficulty);
+        assert!(Query::parse(&["custom".to_string(), "big".to_string()]).is_err());
+    }
+
+    #[test]
+    fn file() {
+        let path = std::env::temp_dir().join(format!("minesweep_journal_{}.tsv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Ok(vec![]), load(&path));
+        append(&path, &entry()).unwrap();
+        append(&path, &Entry { won: true, ..entry() }).unwrap();
+        let entries = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vec![entry(), Entry { won: true, ..entry() }], entries);
+    }
+}
@@ -0,0 +1,86 @@
+//! Minesweeper engine behind the `minesweep_rs` binary.
+//!
+//! A game is played on a [`Field`](struct.Field.html), either generated with
+//! [`Field::generate`](struct.Field.html#method.generate) or loaded with
+//! [`Field::parse`](struct.Field.html#method.parse), through
+//! [`open`](struct.Field.html#method.open), [`flag`](struct.Field.html#method.flag)
+//! and [`chord`](struct.Field.html#method.chord) until
+//! [`is_won`](struct.Field.html#method.is_won) returns `true` or a mine is
+//! opened. The `term` module renders boards for terminal frontends and `tui`
+//! reads keys for full-screen ones, both with the `terminal` feature. With
+//! the `wasm` feature instead, the `wasm` module exports a small API for
+//! frontends in the browser. Bots play many games at once in an `arena`.
+//! The rules around the moves of a game on one screen, undo, hints and
+//! relays among them, are kept by a `session::Session`, and the sessions of
+//! `race` and `coop` play against and with others over TCP, so frontends
+//! only read moves and show boards.
+
+#[cfg(feature = "terminal")]
+extern crate colored;
+#[cfg(unix)]
+extern crate libc;
+extern crate rand;
+
+pub mod analysis;
+pub mod arena;
+pub mod boardinfo;
+pub mod bot;
+pub mod bracket;
+mod cancel;
+pub mod challenge;
+pub mod check;
+mod cell;
+pub mod coop;
+mod difficulty;
+pub mod environment;
+mod error;
+#[cfg(feature = "network")]
+pub mod fetch;
+mod field;
+pub mod game;
+mod json;
+pub mod goals;
+mod grid;
+pub mod handicap;
+pub mod history;
+pub mod journal;
+pub mod lobby;
+pub mod macros;
+pub mod mbf;
+pub mod notify;
+pub mod numpad;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod pack;
+pub mod protocol;
+#[cfg(feature = "qr")]
+pub mod qr;
+pub mod race;
+pub mod relay;
+pub mod replay;
+pub mod save;
+pub mod scores;
+pub mod server;
+pub mod session;
+pub mod solver;
+pub mod speech;
+pub mod stats;
+pub mod sync;
+#[cfg(feature = "terminal")]
+pub mod term;
+pub mod timer;
+mod topology;
+pub mod tournament;
+pub mod training;
+#[cfg(feature = "terminal")]
+pub mod tui;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod websocket;
+
+pub use cancel::CancelToken;
+pub use cell::{Cell, CellValue, Mark};
+pub use difficulty::Difficulty;
+pub use error::MinesError;
+pub use field::{Field, GenerationProgress, RevealStyle};
+pub use topology::Topology;
@@ -0,0 +1,220 @@
+use crate::Difficulty;
+use rand::Rng;
+
+/// Letters of join codes, without ones easily mistaken for each other.
+const CODE_LETTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+const CODE_LENGTH: usize = 4;
+
+/// A player waiting in a game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Seat {
+    pub name: String,
+    pub ready: bool,
+}
+
+/// A game being set up, joined with its code.
+#[derive(Clone, Debug)]
+pub struct OpenGame {
+    pub code: String,
+    pub difficulty: Difficulty,
+    /// Most players the game takes.
+    pub capacity: usize,
+    /// Seats in the order the players joined, the host first.
+    pub seats: Vec<Seat>,
+    /// Seed of the board, set once the game started.
+    pub started: Option<u64>,
+}
+
+impl OpenGame {
+    /// Whether more players can still join.
+    pub fn is_open(&self) -> bool {
+        self.started.is_none() && self.seats.len() < self.capacity
+    }
+}
+
+/// What every player of a started game needs to build the same board.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Start {
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    pub players: Vec<String>,
+}
+
+/// Matchmaking for shared boards: games are created with their settings,
+/// listed while players can join, joined by code and started once every
+/// player is ready. Players are numbered by their seat, as in the moves of
+/// the sync module.
+#[derive(Default)]
+pub struct Lobby {
+    games: Vec<OpenGame>,
+}
+
+impl Lobby {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a game hosted by `host` and returns its code.
+    pub fn create<R: Rng>(&mut self, host: &str, difficulty: Difficulty, capacity: usize, rng: &mut R) -> Result<String, String> {
+        if capacity < 2 {
+            return Err("A game needs room for at least 2 players".into());
+        }
+        let code = loop {
+            let code: String = (0..CODE_LENGTH)
+                .map(|_| CODE_LETTERS[rng.gen_range(0, CODE_LETTERS.len())] as char)
+                .collect();
+            if self.game(&code).is_none() {
+                break code;
+            }
+        };
+        self.games.push(OpenGame {
+            code: code.clone(),
+            difficulty,
+            capacity,
+            seats: vec![Seat { name: host.into(), ready: false }],
+            started: None,
+        });
+        Ok(code)
+    }
+
+    /// Closes the game created first, to make room for new ones. Returns
+    /// its code.
+    pub fn close_oldest(&mut self) -> Option<String> {
+        (!self.games.is_empty()).then(|| self.games.remove(0).code)
+    }
+
+    /// Games created and not closed yet, started ones included.
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    /// Games players can still join.
+    pub fn open_games(&self) -> Vec<&OpenGame> {
+        self.games.iter().filter(|game| game.is_open()).collect()
+    }
+
+    /// The game with `code`, typed in any case.
+    pub fn game(&self, code: &str) -> Option<&OpenGame> {
+        self.games.iter().find(|game| game.code.eq_ignore_ascii_case(code))
+    }
+
+    fn game_mut(&mut self, code: &str) -> Result<&mut OpenGame, String> {
+        self.games.iter_mut()
+            .find(|game| game.code.eq_ignore_ascii_case(code))
+            .ok_or_else(|| format!("There is no game {}", code.to_uppercase()))
+    }
+
+    /// Seats `name` in the game with `code` and returns their seat.
+    pub fn join(&mut self, code: &str, name: &str) -> Result<usize, String> {
+        let game = self.game_mut(code)?;
+        if game.started.is_some() {
+            return Err(format!("Game {} already started", game.code));
+        }
+        if game.seats.len() >= game.capacity {
+            return Err(format!("Game {} is full", game.code));
+        }
+        if game.seats.iter().any(|seat| seat.name == name) {
+            return Err(format!("{} is already in game {}", name, game.code));
+        }
+        game.seats.push(Seat { name: name.into(), ready: false });
+        Ok(game.seats.len() - 1)
+    }
+
+    /// Gives up the seat of `player` before the game started. The game is
+    /// closed once the last player left.
+    pub fn leave(&mut self, code: &str, player: usize) -> Result<(), String> {
+        let game = self.game_mut(code)?;
+        if game.started.is_some() || player >= game.seats.len() {
+            return Err(format!("No seat {} to leave in game {}", player + 1, game.code));
+        }
+        game.seats.remove(player);
+        self.games.retain(|game| !game.seats.is_empty());
+        Ok(())
+    }
+
+    pub fn ready(&mut self, code: &str, player: usize, ready: bool) -> Result<(), String> {
+        let game = self.game_mut(code)?;
+        let code = game.code.clone();
+        let seat = game.seats.get_mut(player).ok_or_else(|| format!("No seat {} in game {}", player + 1, code))?;
+        seat.ready = ready;
+        Ok(())
+    }
+
+    /// Starts the game with `code` once at least two players sit in it and
+    /// all of them are ready.
+    pub fn start<R: Rng>(&mut self, code: &str, rng: &mut R) -> Result<Start, String> {
+        let game = self.game_mut(code)?;
+        if game.started.is_some() {
+            return Err(format!("Game {} already started", game.code));
+        }
+        if game.seats.len() < 2 {
+            return Err(format!("Game {} needs another player", game.code));
+        }
+        if let Some(seat) = game.seats.iter().find(|seat| !seat.ready) {
+            return Err(format!("{} isn't ready", seat.name));
+        }
+        let seed = rng.gen();
+        game.started = Some(seed);
+        Ok(Start {
+            difficulty: game.difficulty,
+            seed,
+            players: game.seats.iter().map(|seat| seat.name.clone()).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::lobby::Lobby;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn matchmaking() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut lobby = Lobby::new();
+        let code = lobby.create("ada", Difficulty::Beginner, 2, &mut rng).unwrap();
+        assert_eq!(4, code.len());
+        assert_eq!(1, lobby.open_games().len());
+        assert_eq!(Err(format!("Game {} needs another player", code)), lobby.start(&code, &mut rng).map(|_| ()));
+
+        assert_eq!(Ok(1), lobby.join(&code.to_lowercase(), "bo"));
+        assert!(lobby.open_games().is_empty());
+        assert_eq!(Err(format!("Game {} is full", code)), lobby.join(&code, "cy"));
+        lobby.ready(&code, 0, true).unwrap();
+        assert_eq!(Err("bo isn't ready".into()), lobby.start(&code, &mut rng).map(|_| ()));
+        lobby.ready(&code, 1, true).unwrap();
+        let start = lobby.start(&code, &mut rng).unwrap();
+        assert_eq!(vec!["ada".to_string(), "bo".into()], start.players);
+        assert_eq!(Some(start.seed), lobby.game(&code).unwrap().started);
+        assert!(lobby.join("ZZZZ", "cy").is_err());
+    }
+
+    #[test]
+    fn leave() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut lobby = Lobby::new();
+        let code = lobby.create("ada", Difficulty::Beginner, 3, &mut rng).unwrap();
+        lobby.join(&code, "bo").unwrap();
+        lobby.leave(&code, 0).unwrap();
+        assert_eq!("bo", lobby.game(&code).unwrap().seats[0].name);
+        lobby.leave(&code, 0).unwrap();
+        assert!(lobby.game(&code).is_none());
+        assert!(lobby.is_empty());
+    }
+
+    #[test]
+    fn close_oldest() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut lobby = Lobby::new();
+        let first = lobby.create("ada", Difficulty::Beginner, 2, &mut rng).unwrap();
+        lobby.create("bo", Difficulty::Expert, 2, &mut rng).unwrap();
+        assert_eq!(Some(first), lobby.close_oldest());
+        assert_eq!(1, lobby.len());
+    }
+}
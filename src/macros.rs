@@ -0,0 +1,106 @@
+use crate::save::check_name;
+use crate::stats::{data_dir, Profile};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+/// Input lines recorded under a name and played back in later games, e.g.
+/// to practice an opening on fresh boards. Commands that control macros
+/// are never recorded.
+#[derive(Default)]
+pub struct Macros {
+    recording: Option<(String, Vec<String>)>,
+    queued: VecDeque<String>,
+}
+
+impl Macros {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The next line of a macro being played, if any.
+    pub fn next_line(&mut self) -> Option<String> {
+        self.queued.pop_front()
+    }
+
+    /// Whether a macro being played has lines left.
+    pub fn is_playing(&self) -> bool {
+        !self.queued.is_empty()
+    }
+
+    /// Adds a line of input to the macro being recorded.
+    pub fn push(&mut self, line: &str) {
+        if let Some((_, lines)) = self.recording.as_mut() {
+            if !line.trim().is_empty() {
+                lines.push(line.trim().into());
+            }
+        }
+    }
+
+    /// Starts recording the following lines as `name`, dropping a recording
+    /// that wasn't stopped.
+    pub fn record(&mut self, name: &str) -> Result<(), String> {
+        check_name(name)?;
+        self.recording = Some((name.into(), vec![]));
+        Ok(())
+    }
+
+    /// Stops recording and writes the macro to the macros of `profile`.
+    /// Returns its name and number of lines.
+    pub fn stop(&mut self, profile: &Profile) -> Result<(String, usize), String> {
+        let (name, lines) = self.recording.take().ok_or("No macro is being recorded")?;
+        let path = macro_path(profile, &name)?;
+        let error = |e: std::io::Error| format!("Can't write {}: {}", path.display(), e);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(error)?;
+        }
+        let mut text = lines.join("\n");
+        text.push('\n');
+        fs::write(&path, text).map_err(error)?;
+        Ok((name, lines.len()))
+    }
+
+    /// Queues the lines of the macro `name` of `profile` and returns how
+    /// many there are.
+    pub fn play(&mut self, profile: &Profile, name: &str) -> Result<usize, String> {
+        let path = macro_path(profile, name)?;
+        let text = fs::read_to_string(&path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+        let lines = parse(&text);
+        let count = lines.len();
+        self.queued.extend(lines);
+        Ok(count)
+    }
+}
+
+/// The lines of a macro file, skipping empty ones and `#` comments.
+pub fn parse(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.into())
+        .collect()
+}
+
+/// Where the macro `name` of `profile` goes, in `macros` in its data
+/// directory.
+pub fn macro_path(profile: &Profile, name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    let dir = data_dir(profile).ok_or("No data directory to keep macros in")?;
+    Ok(dir.join("macros").join(format!("{}.txt", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::macros::{parse, Macros};
+    use crate::stats::Profile;
+
+    #[test]
+    fn recording() {
+        let mut macros = Macros::new();
+        macros.push("5 5");
+        assert!(macros.stop(&Profile::default()).is_err());
+        assert!(macros.record("../opening").is_err());
+        assert_eq!(None, macros.next_line());
+        assert_eq!(vec!["1 1", "f 2 2"], parse("# corner\n1 1\n\n  f 2 2 \n"));
+    }
+}
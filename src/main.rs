@@ -1,448 +1,3762 @@
 extern crate colored;
+extern crate minesweep_rs;
 extern crate rand;
 
 use colored::*;
-use rand::{Rng, rngs::ThreadRng};
-use std::io;
+use minesweep_rs::{CancelToken, Difficulty, Field, GenerationProgress, MinesError, RevealStyle, Topology};
+use minesweep_rs::analysis::{Analysis, Analyst};
+use minesweep_rs::goals::{goals_path, parse_goals, Goal};
+use minesweep_rs::journal::{self, Entry, Query};
+use minesweep_rs::macros::Macros;
+use minesweep_rs::mbf;
+use minesweep_rs::notify;
+use minesweep_rs::numpad::{self, numpad_move};
+use minesweep_rs::boardinfo::BoardInfo;
+use minesweep_rs::bot::{self, strategy, wilson_interval, Bot, Opening, SeedWatch, Step};
+use minesweep_rs::bracket::{self, Bracket, Entrant, Outcome};
+use minesweep_rs::challenge::Challenge;
+use minesweep_rs::check;
+use minesweep_rs::coop;
+use minesweep_rs::handicap::Handicap;
+use minesweep_rs::sync::{self, Applied, GRACE};
+use minesweep_rs::game;
+use minesweep_rs::pack;
+use minesweep_rs::protocol;
+use minesweep_rs::race;
+use minesweep_rs::relay::Relay;
+use minesweep_rs::replay::{Action, Event, Frame, Recorder, Replay};
+use minesweep_rs::save::{self, SavedGame};
+use minesweep_rs::scores::{self, Score};
+use minesweep_rs::server;
+use minesweep_rs::session::{Rules, Session, Turn};
+use minesweep_rs::solver::{self, Deduction};
+use minesweep_rs::speech;
+use minesweep_rs::stats::{self, Clicks, GameRecord, Profile, Summary};
+use minesweep_rs::timer::{format_time, Timer};
+use minesweep_rs::tournament;
+use minesweep_rs::training;
+use minesweep_rs::websocket::{self, Message};
+use minesweep_rs::tui::{input_pending, Button, Cursor, Interrupts, Key, RawTerminal};
+use minesweep_rs::term::{fireworks, needs_compact, probability_map, reveal_mines, side_by_side, stdin_is_tty, stdout_is_tty, terminal_height, terminal_width, timeline, Border, Buckets, ColorDepth, Glyphs, Orientation, Palette, SummaryCard, Theme, View, PLAYER_COLORS};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(PartialEq)]
-enum CellValue {
-    Mine,
-    Water,
-}
+const USAGE: &str = "\
+Usage: minesweep [play] [options] [difficulty]
+       minesweep generate [options] [difficulty]
+       minesweep solve <board-or-position-file>
+       minesweep solve --seed <n> [options] [difficulty]
+       minesweep compare [options] [difficulty]
+       minesweep simulate [options] [difficulty]
+       minesweep position export|import <file>
+       minesweep export <board-file> [--grid <shape>] [--wrap] [--layers <n>] [--mbf <file>]
+       minesweep import <file>
+       minesweep boardinfo [--board <file>] [board options] [difficulty]
+       minesweep replay <file> [--move <n>]
+       minesweep check-board <file> [--json]
+       minesweep goals
+       minesweep stats
+       minesweep journal [list] [--favorites] [query...]
+       minesweep journal play <n> [--favorites]
+       minesweep journal favorite <n>
+       minesweep scores
+       minesweep load <name> [options]
+       minesweep profiles
+       minesweep bracket --players <n> [options] [difficulty]
+       minesweep bracket result <file> <round> <player> <time|lost>...
+       minesweep bracket show <file>
+       minesweep bracket serve <file> [--port <n>]
+       minesweep serve [--port <n>] [--bind <address>]
+       minesweep race host [--port <n>] [--name <name>] [--seed <n>] [--handicap <player>:<handicap>]... [difficulty]
+       minesweep race join <address>[:<port>] [--name <name>]
+       minesweep coop host [--port <n>] [--name <name>] [--seed <n>] [difficulty]
+       minesweep coop join <address>[:<port>] [--name <name>] [--seat <n> --token <t>]
+       minesweep pack install <file>
+       minesweep pack install <url> --sha256 <checksum>
+       minesweep pack update [<name>]
+       minesweep pack list
+       minesweep pack info <name>
+       minesweep pack play <name> [<puzzle>] [play options]
+
+Difficulty: beginner (default), intermediate, expert,
+            custom <width> <height> [mines] or <width> <height>
+
+Board options (play, generate, solve):
+  --mines <n>               number of mines
+  --density <percent>       share of cells that are mines
+  --seed <n>                seed of the board
+  --no-guess                only boards that can be solved from the start cell
+  --crosssweeper            show the mines of every row and column, with boards
+                            solvable from the start cell using them
+  --notify                  desktop notification once a no-guess board is found
+  --max-memory <MiB>        refuse boards that need more memory (1024)
+  --grid <shape>            square (default), hex or triangle cells, hex rows
+                            are offset by half a cell and every cell has six
+                            neighbours, triangles point up and down in turn
+                            and have twelve, or knight for square cells
+                            counting the eight cells a knight jumps to
+  --multimine <n>           up to n mines per cell (2 to 9), flag a cell again
+                            for every mine it holds
+  --layers <n>              stack n boards of the size into a 3D board where
+                            every cell touches up to 26 cells, the mines of
+                            presets and --density are for every layer, those
+                            of custom boards and --mines for the whole stack
+  --wrap                    the edges of the board touch the opposite edges,
+                            hex boards need an even number of rows and
+                            triangle boards even rows and columns
+  --mask <file>             shape the board like the mask in the file, # for a
+                            cell and . or a space for a hole, instead of the
+                            difficulty; --mines or --density (10) set the mines
+
+Play options:
+  --board <file>            play a board file instead of a generated board
+  --puzzle <file>           play a board file as a puzzle, see below
+  --unsafe-start            the first click may hit a mine
+  --stages <n>              split the board into stages cleared one by one,
+                            with the time of every stage on the result
+  --reveal <style>          instant, sweep or cascade reveal of the mines
+  --fireworks               celebrate a win
+  --tui                     full-screen mode with a cursor and mouse support
+  --debug-fps               show the frame rate and redrawn lines in full-screen mode
+  --scroll                  print the board again after every move instead of
+                            drawing it over the previous one
+  --load <name>             resume a game saved with save <name> while playing
+  --hint-penalty <secs>     time added per hint on preset boards (20)
+  --mono                    no colors, cell states told apart by their glyphs
+  --palette <p>             colors of numbers, mines and flags: classic,
+                            deuteranopia, protanopia or tritanopia (classic)
+  --accessible              tell the board and every move in sentences for
+                            screen readers instead of drawing it, see below
+  --border <b>              frame the board and number its rows and columns as
+                            moves take them: none, ascii or unicode (none)
+  --informed                show the mines left in every quadrant of the board
+  --orientation <o>         wide draws the board as it is, tall turned with
+                            its rows as columns and auto turned when it only
+                            fits the terminal that way (wide)
+  --buckets <a,b,c>         mine chance thresholds of the probability overlay
+                            in percent (5,20,50)
+  --marks                   flagging a flag turns it into a question mark
+  --kids                    simple mode: a tiny board, big pictures, no timer
+                            and only opening and flagging
+  --no-undo                 honest play, without undo and redo
+  --relay <players>         take turns with other players
+  --relay-moves <n>         moves per relay turn
+  --relay-seconds <n>       seconds per relay turn
+  --hotseat                 two players take turns and score points, see below
+  --handicap <n>:<list>     head start for player n of a hotseat game, like
+                            2:reveals=3,time=10, see below
+  --spectate <a>,<b>        watch two bot strategies race
+  --record <file>           write the game as JSON lines
+  --annotate                add the solver's view of every move to the record
+  --challenge <code>        play the board of a challenge code from the result
+                            of another game, like E-1234
+  --protocol json           let a program play: the board is written as a JSON
+                            line before every move and a JSON action is read,
+                            see below
+
+Finished games are recorded in games.tsv in the data directory, see stats.
+The ten best times of every preset are kept in scores.tsv next to it.
+
+Every played board goes into journal.tsv there as well, with its seed, the
+arguments that make it again, the result and the time. journal lists the
+entries matching a query like \"expert losses this week\": won or lost,
+today, week or month, a difficulty and any other word an argument contains,
+like hex. journal play <n> plays entry n again and journal favorite <n>
+keeps it in favorites.tsv, which --favorites lists and plays instead.
+
+--profile <name> before or after any command keeps stats, high scores,
+saves, macros and goals apart for everyone sharing the machine, as does
+MINESWEEP_PROFILE. The profile default is the shared one. While playing,
+profile <name> switches whose game it is, and w picks the profile from a
+menu in full-screen mode.
+
+--no-color before or after any command, NO_COLOR set to anything or output
+that doesn't go to a terminal leaves out colors, and boards tell their cells
+apart by glyph like with --mono: # closed, . empty, a digit for a number, F
+flagged, * a mine, @ the mine that was hit and x a wrong flag. CLICOLOR_FORCE
+keeps the colors on anyway.
+
+While playing, pause stops the clock until enter is pressed, or z until any
+key in full-screen mode. Paused time doesn't count, in the result or in the
+times of moves written by --record.
+
+On a terminal the board is drawn over the previous one at the top of the
+screen after every move, unless it doesn't fit or --scroll is given. Piped
+output always gets a new board after every move.
+
+In full-screen mode the cell under the mouse is named below the board, with
+its mine chance while the odds are shown with p. The solver works them out
+in the background after every move, and x stops it until the next one.
+
+While --no-guess looks for a board, enter takes the best one so far and q
+or Ctrl-C stops looking.
+
+n <col> <row> anchors the numpad on an opened cell, drawn like the cursor.
+A digit alone then opens the cell around it where the key lies on a numpad,
+7 8 9 above and 1 2 3 below, 5 chords the anchor, f7 flags, c7 chords and n
+alone lets go. In full-screen mode n turns the numpad on around the cursor
+and off again, and f before a digit flags.
+
+The result of a game on a generated board shows its challenge code: the
+difficulty, the seed and NG for boards without guessing. Anyone playing it
+with --challenge gets the same mines if they start on the same cell, or
+anywhere on boards without guessing. Built with the qr feature, the code
+is shown as a QR code too.
+
+Once a game is won or lost, n starts a new one with the same options on a
+new board and r plays the same board again. v watches the game again if it
+was recorded with --record, e exports the board to a file to play with
+--board and s shows how others can play it with its challenge code.
+
+With --accessible the board isn't drawn. Every move is told in a sentence
+like \"Row 3, column 5: 2 adjacent mines\", followed by the mines left to
+flag and the cells still closed. read <col> <row> tells what is in a cell,
+read row <n> and read column <n> what is in a whole line, with runs of cells
+alike read at once, and read alone how the game stands. Moves are typed as
+usual, the column first.
+
+On a board turned by --orientation, moves take the column and row as they
+are on screen.
+
+On boards with --layers, moves take the layer after the column and row,
+like 3 2 1 for the third column of the second row of the top layer.
+
+With --protocol json the game writes lines like {\"turn\": 0, \"rows\": 9,
+\"cols\": 9, \"mines\": 10, \"mines_left\": 10, \"state\": \"playing\", \"board\":
+[\".........\", ...]} and reads lines like {\"action\": \"open\", \"row\": 4,
+\"col\": 4} with open, flag, chord or quit, counting from 0. The board is as
+for serve below. Invalid actions come back with an \"error\". The game ends
+when it is won or lost and isn't kept in the stats.
+
+In a relay, flags show in the color of the player who placed them and
+say <message> leaves a note for the next player.
+
+--hotseat is a relay of two players with a move each, played for points:
+one for every opened cell and 3 for every flag left on a mine, with 3 off for
+every flag left on water and 20 off for opening a mine, which ends the game.
+--handicap evens out a game between players of different strength: reveals
+opens safe cells for the player before the game, counted as theirs, and
+time adds seconds to each of their turns with --relay-seconds.
+
+Moves typed while playing can be kept with macro record <name> and macro
+stop, and typed again in any game with macro play <name>.
+
+Goals are read from goals in the config directory, one per line:
+  win <n> [difficulty] [nf] [no-guess]   win n games today, nf without flags
+  play <n> [difficulty]                  finish n games today
+
+Compare options:
+  --games <n>               games per strategy (100)
+  --seed <n>                seed of the first game (0)
+  --strategies <list>       comma separated strategies, first, random, basic,
+                            greedy, info or onnx:<file> for a model scoring the cells
+                            of the board encoded as by --export-training,
+                            which needs a build with the onnx feature
+  --opening <opening>       corner, center, random or table:<file>
+  --notify                  desktop notification when done
+  --export-training <dir>   write every move of the bots to <dir>/<strategy>.jsonl
+                            as training data, one JSON object per move with the
+                            visible board (row by row, numbers of opened cells,
+                            -1 closed, -2 flagged), the move, whether it was
+                            right and the mines of every cell
+
+Simulate options:
+  --games <n>               games to play (1000)
+  --seed <n>                seed of the first game (0)
+  --strategy <name>         strategy guessing for the solver, as for compare (greedy)
+  --preset <difficulty>     the difficulty, like the argument
+  --notify                  desktop notification when done
+
+--notify needs a build with the notify feature.
+
+replay shows a game written by --record move by move. Left and right step
+through the moves on the timeline below the board, up and down jump to the
+first guess, big cascades and opened mines. Without a terminal it prints the
+board after move n, the last one by default. Only boards made by the seed
+alone replay correctly, not those with --no-guess or a --grid.
+
+boardinfo shows how the mines of a board file or a generated board are
+spread: the mine density in up to 3 x 3 regions of the board, its openings
+and the cells the largest of them opens, and its 3BV. After a game, i shows
+the same for the board just played.
+
+export writes a board file as keyed lines for bug reports and test fixtures:
+size <cols> <rows>, its grid, wrap, layers and line-counts if any, then the
+mines, opened cells, flags, question marks and holes as lists of 1-based
+columns and rows like \"mines 3 1, 5 2\". import turns them back into a board
+file, and --board and --puzzle play them as they are.
+
+Boards of other clients in the Minesweeper Board File format are read from
+files ending in .mbf wherever a board file is taken, and export --mbf writes
+one. MBF only knows where the mines of flat square boards up to 255x255 are.
+
+--puzzle plays a board file as a logic puzzle: the mines are where the file
+puts them, some cells may already be open, and there are no hints or undo.
+It is solved once every cell without a mine is open and failed by the first
+mine opened.
+
+check-board reads a board file like --board and lists every problem with it:
+rows of different lengths, unknown or opened mine cells, cells with several
+mines, flags without a mine, areas holes cut off from the rest, and whether
+the solver clears it without guessing, from its opened cells or from the best
+first click. --json prints the same as one JSON object with 0-based rows and
+columns. It exits with 1 if the board can't be played.
 
-struct Cell {
-    value: CellValue,
-    opened: bool,
-    flagged: bool,
+Bracket options:
+  --players <n>             number of players, named Player 1 and on
+  --names <list>            comma separated player names, the favourite first
+  --rounds <n>              check the rounds the players need
+  --games <n>               boards per round (1)
+  --seed <n>                seed of the seeds of the boards
+  --json                    write the sheet as JSON to enter results in
+
+bracket serve shares a bracket file over HTTP on --port (8080). Anybody can
+GET /bracket and /standings. Players POST /results with the token drawn for
+them in <file>.tokens as Authorization: Bearer <token> and a body like
+{\"round\": 1, \"games\": [83250, null], \"replays\": [...]} with their times in
+milliseconds, null for a lost board, and optionally the --record files. The
+results of a round are entered once, a second POST gets a 409.
+
+Puzzle packs bundle hand-made boards in one JSON file with a title, an
+author, a description and puzzles of a title, an optional author, a rating
+from 1 to 5, an optional order and a board in rows of the --board format:
+{\"title\": \"First steps\", \"puzzles\": [{\"title\": \"One-two\", \"rating\": 1,
+\"board\": [\"..*\", \"...\"]}]}. pack install checks a pack and keeps it under
+the name of its file. Builds with the network feature also install packs
+from http:// URLs with the SHA-256 checksum the author published given with
+--sha256, which the download has to match. pack update fetches them again
+and installs a changed pack only with the checksum of the new one. pack play
+plays its puzzles in order from the given one or the first unsolved, keeping
+the best time of every solved puzzle.
+
+race host waits on --port (7878) for one opponent to race on the same board,
+picked by the host, with the first cell already opened for both. They join
+with race join and the address of the host. Both see how far the other got
+after every move. The first to clear the board wins, or else the one who
+stays alive longest. The host can give a player, 1 for the host and 2 for
+the opponent, a --handicap as for --hotseat, where density takes percent
+points off the mines of their board and time comes off their time once
+they clear it.
+
+coop host plays a board together with up to 15 others joining with coop
+join and the address of the host, on --port (7879). Moves show up on every
+board as they are played, and flags in the color of whoever placed them,
+with a line of what every player did so far below the board. say <message>
+talks to everybody in the game. The host plays moves in the order they
+arrive, so a move on a cell somebody else just opened or flagged is turned
+down. Only whoever placed a flag takes it away. One mine opened by anybody
+ends the game for all. A player who loses the host is taken back within a
+minute, on their own or with --seat and --token as printed when they
+joined.
+
+serve plays games over HTTP on --port (8080) for web and mobile clients,
+on this computer only unless --bind gives another address, like 0.0.0.0.
+POST /games with {\"difficulty\": \"expert\"} or {\"width\": 20, \"height\": 10,
+\"mines\": 30} and an optional \"seed\" starts one, GET /games/<id> shows it,
+POST /games/<id>/open, /flag or /chord with {\"row\": 3, \"col\": 4} plays a
+move, counting from 0, and DELETE /games/<id> ends it. Every answer is the
+game as JSON with its state and a board of . closed, F flagged, - hole,
+* mine and the numbers of opened cells. Players meet in its lobby: POST
+/lobby with {\"name\": \"ada\", \"players\": 2} and a board creates a game
+with a join code, GET /lobby lists those that can be joined and POST
+/lobby/<code>/join with {\"name\": \"bo\"} takes a seat. Both answer with a
+token for POST /lobby/<code>/ready, /leave and, for the host, /start. A
+started game shows a seed, to start the same board for all at POST /games.
+Clients that want to be told of joins, ready players and the start open a
+WebSocket at /lobby/socket instead and send the same bodies with an
+\"action\" of list, create, join, ready, leave or start. The lobby pushes
+the open games to clients without a seat and their game to players.
+";
+
+/// Parses the value that follows `flag`.
+fn value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> where T::Err: Display {
+    let value = value.ok_or_else(|| format!("{} expects a value", flag))?;
+    value.parse().map_err(|e| format!("Invalid value '{}' for {}: {}", value, flag, e))
 }
 
-#[derive(Debug, PartialEq)]
-enum MinesError {
-    MineOpened,
-    OutOfBounds(u16, u16),
-    EmptyField,
-    FieldTooSmall(u16, u16),
-    TooManyMines,
+/// Memory a board may take up without `--max-memory`, in MiB.
+const DEFAULT_MAX_MEMORY: usize = 1024;
+
+/// Options that pick the board, shared by `play`, `generate` and `solve`.
+#[derive(Default)]
+struct BoardOptions {
+    positional: Vec<String>,
+    mines: Option<u16>,
+    density: Option<u8>,
+    seed: Option<u64>,
+    no_guess: bool,
+    crosssweeper: bool,
+    notify: bool,
+    /// In MiB.
+    max_memory: Option<usize>,
+    grid: Topology,
+    wrap: bool,
+    layers: Option<u16>,
+    /// Most mines per cell.
+    multimine: Option<u8>,
+    /// Shape of the board, `false` for holes.
+    mask: Option<Vec<Vec<bool>>>,
 }
 
-impl Cell {
-    fn mine() -> Self {
-        Self {
-            value: CellValue::Mine,
-            opened: false,
-            flagged: false,
+impl BoardOptions {
+    /// Takes `arg` and its value if it is a board option or a positional
+    /// argument. Returns `false` for options it doesn't know.
+    fn take(&mut self, arg: &str, args: &mut impl Iterator<Item = String>) -> Result<bool, String> {
+        match arg {
+            "--mines" => self.mines = Some(value(arg, args.next())?),
+            "--density" => {
+                let percent = value(arg, args.next())?;
+                if percent > 100 {
+                    return Err(format!("{} has to be a percentage", arg));
+                }
+                self.density = Some(percent);
+            },
+            "--seed" => self.seed = Some(value(arg, args.next())?),
+            "--no-guess" => self.no_guess = true,
+            // the counts are only a puzzle of their own if they are needed
+            "--crosssweeper" => {
+                self.crosssweeper = true;
+                self.no_guess = true;
+            },
+            "--notify" => self.notify = notify_option()?,
+            "--max-memory" => self.max_memory = Some(value(arg, args.next())?),
+            "--grid" => self.grid = value(arg, args.next())?,
+            "--wrap" => self.wrap = true,
+            "--mask" => {
+                let path: String = value(arg, args.next())?;
+                let mask = Field::parse_mask(&read(&path)?).map_err(|e| format!("{}: {}", path, e))?;
+                if mask.len() > u16::MAX as usize || mask[0].len() > u16::MAX as usize {
+                    return Err(format!("{}: the mask is too big", path));
+                }
+                self.mask = Some(mask);
+            },
+            "--multimine" => {
+                let per_cell = value(arg, args.next())?;
+                if !(2..=9).contains(&per_cell) {
+                    return Err(format!("{} takes 2 to 9 mines per cell", arg));
+                }
+                self.multimine = Some(per_cell);
+            },
+            "--layers" => {
+                let layers = value(arg, args.next())?;
+                if layers == 0 {
+                    return Err(format!("{} needs at least one layer", arg));
+                }
+                self.layers = Some(layers);
+            },
+            _ if arg.starts_with('-') => return Ok(false),
+            _ => self.positional.push(arg.to_string()),
         }
+        Ok(true)
     }
 
-    fn water() -> Self {
-        Self {
-            value: CellValue::Water,
-            opened: false,
-            flagged: false,
+    fn difficulty(&self) -> Result<Difficulty, String> {
+        if self.no_guess && self.grid != Topology::Square {
+            return Err(format!("Boards without guessing are only made with square cells, not {}", self.grid));
+        }
+        if self.no_guess && self.wrap {
+            return Err("Boards without guessing don't wrap around".into());
+        }
+        if self.no_guess && self.layers.is_some() {
+            return Err("Boards without guessing are flat, they don't have layers".into());
+        }
+        if self.no_guess && self.multimine.is_some() {
+            return Err("Boards without guessing have one mine per cell".into());
+        }
+        if self.mask.is_some() && (self.no_guess || self.layers.is_some() || self.multimine.is_some()) {
+            return Err("Boards shaped by a mask are flat, take their chances and have one mine per cell".into());
+        }
+        let mut difficulty = match self.mask.as_ref() {
+            Some(_) if !self.positional.is_empty() => return Err("A mask sets the size of the board, leave out the difficulty".into()),
+            // the density is of the cells, not the holes
+            Some(mask) => {
+                let cells = mask.iter().flatten().filter(|&&cell| cell).count();
+                let mines = cells * self.density.unwrap_or(10) as usize / 100;
+                Difficulty::Custom(mask[0].len() as u16, mask.len() as u16, mines.min(u16::MAX as usize) as u16)
+            },
+            None => Difficulty::from_args(&self.positional)?,
+        };
+        if let (Some(percent), None) = (self.density, self.mask.as_ref()) {
+            difficulty = difficulty.with_density(percent);
         }
+        let layers = self.layers.unwrap_or(1);
+        let (width, height, mines) = difficulty.dimensions();
+        // the mines of a preset or a density fill one layer
+        let per_layer = self.density.is_some() || !matches!(difficulty, Difficulty::Custom(..));
+        if layers > 1 && per_layer && self.mines.is_none() {
+            difficulty = difficulty.with_mines(mines.saturating_mul(layers));
+        }
+        if let Some(mines) = self.mines {
+            difficulty = difficulty.with_mines(mines);
+        }
+        if self.wrap && !self.grid.can_wrap(height, width) {
+            return Err(format!("A {}x{} {} board can't wrap around", width, height, self.grid));
+        }
+        if height.checked_mul(layers).is_none() {
+            return Err(format!("{} layers of {} rows are too many", layers, height));
+        }
+        let mut needed = Field::memory_estimate(height * layers, width);
+        if self.no_guess {
+            // the best board so far and the one being solved come on top
+            needed *= 3;
+        }
+        let mib = |bytes: usize| bytes.div_ceil(1 << 20);
+        if needed > self.memory_cap() {
+            return Err(format!(
+                "A {} board needs about {} MiB, more than the {} MiB allowed by --max-memory",
+                difficulty, mib(needed), mib(self.memory_cap()),
+            ));
+        }
+        if needed > self.memory_cap() / 4 {
+            eprintln!("{}", format!("A {} board needs about {} MiB", difficulty, mib(needed)).yellow());
+        }
+        Ok(difficulty)
     }
 
-    fn open(&mut self) -> Result<(), MinesError> {
-        if !self.flagged {
-            self.opened = true;
-            match self.value {
-                CellValue::Mine => Err(MinesError::MineOpened),
-                CellValue::Water => Ok(()),
-            }
-        } else {
-            Ok(())
-        }
+    /// The code to share a generated board by, for the boards it describes:
+    /// flat ones with square cells, without a mask or stacked mines.
+    fn challenge(&self, difficulty: Difficulty, seed: u64) -> Option<Challenge> {
+        let plain = self.grid == Topology::Square && !self.wrap && self.layers.is_none()
+            && self.multimine.is_none() && self.mask.is_none() && !self.crosssweeper;
+        plain.then_some(Challenge { difficulty, seed, no_guess: self.no_guess })
     }
 
-    fn toggle_flag(&mut self) {
-        if !self.opened {
-            self.flagged = !self.flagged;
+    /// The arguments giving the shape of `field`, its grid, wrap and layers.
+    fn shape_args(&self, field: &Field) -> Vec<String> {
+        let mut args = vec![];
+        if field.topology() != Topology::Square {
+            args.extend(["--grid".to_string(), field.topology().to_string()]);
+        }
+        if field.wraps() {
+            args.push("--wrap".to_string());
         }
+        if field.layers() > 1 {
+            args.extend(["--layers".to_string(), field.layers().to_string()]);
+        }
+        args
     }
-}
-
-struct Field {
-    cells: Vec<Vec<Cell>>,
-    numbers: Vec<Vec<u8>>,
-}
 
-impl Field {
-    fn with_cells(cells: Vec<Vec<Cell>>) -> Self {
-        let mut numbers = vec![];
-        for (x, c_col) in cells.iter().enumerate() {
-            let mut col = vec![];
-            for (y, _cell) in c_col.iter().enumerate() {
-                col.push(count_neighbours(&cells, x as u16, y as u16).unwrap());
-            }
-            numbers.push(col);
+    /// The arguments of `play` that generate `field` again as `difficulty`
+    /// from `seed`, none for boards cut out by a mask.
+    fn remake_args(&self, field: &Field, difficulty: Difficulty, seed: u64) -> Vec<String> {
+        if self.mask.is_some() {
+            return vec![];
+        }
+        let mut args = self.shape_args(field);
+        if field.mines_per_cell() > 1 {
+            args.extend(["--multimine".to_string(), field.mines_per_cell().to_string()]);
         }
-        Self {
-            cells,
-            numbers,
+        if self.crosssweeper {
+            args.push("--crosssweeper".to_string());
+        } else if self.no_guess {
+            args.push("--no-guess".to_string());
         }
+        args.extend(["--seed".to_string(), seed.to_string()]);
+        match difficulty {
+            Difficulty::Custom(width, height, mines) => args.extend(["custom".to_string(), width.to_string(), height.to_string(), mines.to_string()]),
+            preset => args.push(preset.to_string()),
+        }
+        args
     }
 
-    fn generate(rng: &mut ThreadRng, width: u16, height: u16, mines: u16) -> Result<Self, MinesError> {
-        let cells = generate_cells(rng, width.clone(), height.clone(), mines)?;
-        Ok(Self::with_cells(cells))
+    /// Bytes a board may take up.
+    fn memory_cap(&self) -> usize {
+        self.max_memory.unwrap_or(DEFAULT_MAX_MEMORY).saturating_mul(1 << 20)
     }
 
-    fn print(&self) {
-        for (x, col) in self.cells.iter().enumerate() {
-            for (y, cell) in col.iter().enumerate() {
-                if cell.flagged {
-                    print!("F ");
-                } else if !cell.opened {
-                    print!("_ ");
-                } else {
-                    match cell.value {
-                        CellValue::Mine => print!("{} ", "X".red()),
-                        CellValue::Water => print!("{} ", color_number(self.numbers.get(x).unwrap().get(y).unwrap())),
-                    }
-                }
-            }
-            println!();
+    /// Reads a board file, shaped by these options, a board written by
+    /// `export`, which knows its shape, or an MBF file.
+    fn read_board(&self, path: &str) -> Result<Field, String> {
+        if is_mbf(path) {
+            return read_mbf(path);
         }
+        let text = read(path)?;
+        if is_export(&text) {
+            return Field::import(&text, self.memory_cap()).map_err(|e| format!("{}: {}", path, e));
+        }
+        let mut field = Field::parse(&text).map_err(|e| e.to_string())?;
+        field.set_topology(self.grid);
+        field.set_layers(self.layers.unwrap_or(1)).map_err(|e| e.to_string())?;
+        let (cols, rows) = (field.size().0, field.layer_height());
+        if self.wrap && !self.grid.can_wrap(rows, cols) {
+            return Err(format!("A {}x{} {} board can't wrap around", cols, rows, self.grid));
+        }
+        field.set_wrap(self.wrap);
+        Ok(field)
     }
 
-    fn flag(&mut self, x: u16, y: u16) -> Result<(), MinesError> {
-        let _ = get_2d(&mut self.cells, x, y)?;
-        let cell: &mut Cell = self.cells.get_mut(x as usize).unwrap().get_mut(y as usize).unwrap();
-        cell.toggle_flag();
-        Ok(())
-    }
-
-    fn open(&mut self, x: u16, y: u16) -> Result<(), MinesError> {
-        let _ = get_2d(&self.cells, x, y)?;
-        let cell: &mut Cell = self.cells.get_mut(x as usize).unwrap().get_mut(y as usize).unwrap();
-        if cell.opened {
-            return Ok(());
-        }
-        cell.open()?;
-        if self.numbers.get(x as usize).unwrap().get(y as usize).unwrap().eq(&0) {
-            for nx in min_coord(x)..x + 2 {
-                for ny in min_coord(y)..y + 2 {
-                    let _ = self.open(nx, ny);
+    /// Generates the board, together with the cell to start at on no-guess
+    /// boards. Taking a board that needs guessing clears `no_guess`.
+    fn generate(&mut self, difficulty: Difficulty, rng: &mut StdRng) -> Result<(Field, Option<(u16, u16)>), MinesError> {
+        let (width, height, mines) = difficulty.dimensions();
+        if self.no_guess {
+            // show the search on a terminal and let the player settle for the best board so far,
+            // or stop looking with q or Ctrl-C
+            let interactive = stdout_is_tty() && stdin_is_tty();
+            let cancel = CancelToken::new();
+            let interrupts = Interrupts::catch();
+            let mut last_report = None;
+            let mut frame = 0;
+            let mut accepted = None;
+            let report = |progress: &GenerationProgress| {
+                if interrupts.caught() {
+                    cancel.cancel();
+                }
+                if !interactive {
+                    return true;
+                }
+                if last_report.is_none_or(|at: Instant| at.elapsed() >= Duration::from_millis(100)) {
+                    last_report = Some(Instant::now());
+                    frame += 1;
+                    print!(
+                        "\r{} Looking for a board without guessing: {}/{} attempts, best clears {:.0}%, press enter to take it or q to stop\x1b[K",
+                        ['|', '/', '-', '\\'][frame % 4], progress.attempts, progress.max_attempts, 100.0 * progress.best_cleared,
+                    );
+                    let _ = io::stdout().flush();
+                }
+                if input_pending() {
+                    let mut line = String::new();
+                    let _ = io::stdin().read_line(&mut line);
+                    if line.trim() == "q" {
+                        cancel.cancel();
+                    } else if progress.best_cleared > 0.0 {
+                        // there is a best board once one had an opening
+                        accepted = Some(progress.best_cleared);
+                        return false;
+                    }
                 }
+                true
+            };
+            let result = if self.crosssweeper {
+                Field::generate_crosssweeper_until(rng, height, width, mines, 10_000, &cancel, report)
+            } else {
+                Field::generate_no_guess_until(rng, height, width, mines, 10_000, &cancel, report)
+            };
+            drop(interrupts);
+            if last_report.is_some() {
+                print!("\r\x1b[K");
+                let _ = io::stdout().flush();
             }
+            if result.as_ref().err() == Some(&MinesError::Cancelled) {
+                println!("Stopped looking for a board");
+                std::process::exit(130);
+            }
+            if let Some(cleared) = accepted {
+                println!("Taking the best board so far, guessing starts after {:.0}% of it", 100.0 * cleared);
+                self.no_guess = false;
+            } else if self.notify {
+                send_notification("Board ready", &format!("The {} board without guessing is ready", difficulty));
+            }
+            result.map(|(field, cell)| (field, Some(cell)))
+        } else {
+            let layers = self.layers.unwrap_or(1);
+            let generated = match (self.mask.as_ref(), self.multimine) {
+                (Some(mask), _) => Field::generate_masked(rng, mask, mines),
+                (None, Some(per_cell)) => Field::generate_multimine(rng, height * layers, width, mines, per_cell),
+                (None, None) => Field::generate(rng, height * layers, width, mines),
+            };
+            generated.map(|mut field| {
+                field.set_topology(self.grid);
+                field.set_wrap(self.wrap);
+                field.set_layers(layers).expect("the rows are whole layers");
+                (field, None)
+            })
         }
-        Ok(())
     }
+}
 
-    fn chord(&mut self, x: u16, y: u16) -> Result<(), MinesError>{
-        {
-            let cell  = get_2d(&self.cells, x, y)?;
-            if !cell.opened {
-                return Ok(());
-            }
+/// Races two bots on copies of the same board, drawing both boards side by
+/// side. With `safe_start` the first cell each bot opens is made safe on its
+/// own copy.
+fn spectate(field: Field, view: &View, bots: [Bot; 2], safe_start: bool, rng: &mut StdRng) {
+    let water = field.safe_count();
+    let mut fields = [field.clone(), field];
+    let mut openings = [None, None];
+    if safe_start {
+        for i in 0..2 {
+            openings[i] = bots[i].safe_opening(&mut fields[i], rng);
         }
-        let number = get_2d(&self.numbers, x, y)?;
-        let mut counter = 0;
-        do_with_neighbours(&self.cells, x, y, |_, _, c| {
-            if c.flagged {
-                counter += 1;
+    }
+    // tick at which each bot finished and whether it cleared its board
+    let mut finished: [Option<(u32, bool)>; 2] = [None, None];
+    let animate = stdout_is_tty();
+    let half = terminal_width() / 2;
+    let mut printed = 0;
+    let mut tick = 0;
+    while finished.iter().any(|f| f.is_none()) {
+        tick += 1;
+        for i in 0..2 {
+            if finished[i].is_some() {
+                continue;
             }
-            Ok(())
-        })?;
-        if number.ne(&counter) {
-            return Ok(())
-        }
-        self.open(x, y)?;
-        for x in min_coord(x)..x+2 {
-            for y in min_coord(y)..y+2 {
-                {
-                    let cell = get_2d(&self.cells, x, y)?;
-                    if cell.opened || cell.flagged {
-                        continue;
-                    }
-
-                }
-                self.open(x, y)?;
+            match bots[i].step_guessing(&mut fields[i], rng, openings[i].take()) {
+                Step::Won => finished[i] = Some((tick, true)),
+                Step::Lost(_, _) => finished[i] = Some((tick, false)),
+                _ => {},
             }
         }
-        Ok(())
-    }
-
-    fn is_won(&self) -> bool {
-        for col in self.cells.iter() {
-            for cell in col.iter() {
-                if cell.value.eq(&CellValue::Water) && !cell.opened {
-                    return false;
-                }
+        let boards: Vec<Vec<String>> = (0..2).map(|i| {
+            let mut lines = vec![format!(
+                "Bot {} ({}): {}/{}",
+                i + 1, bots[i].name(), fields[i].opened_count(), water,
+            )];
+            lines.extend(view.render(&fields[i], half));
+            lines
+        }).collect();
+        let lines = side_by_side(&boards[0], &boards[1], half);
+        if animate || finished.iter().all(|f| f.is_some()) {
+            if animate && printed > 0 {
+                print!("\x1b[{}A", printed);
+            }
+            for line in lines.iter() {
+                println!("{}", line);
+            }
+            printed = lines.len();
+            let _ = io::stdout().flush();
+            if animate {
+                thread::sleep(Duration::from_millis(100));
             }
         }
-        true
+    }
+    let score = |i: usize| {
+        let (tick, won) = finished[i].unwrap();
+        (won, if won { u32::MAX - tick } else { fields[i].opened_count() as u32 })
+    };
+    println!();
+    match score(0).cmp(&score(1)) {
+        std::cmp::Ordering::Greater => println!("{}", "Bot 1 wins!".green().bold()),
+        std::cmp::Ordering::Less => println!("{}", "Bot 2 wins!".green().bold()),
+        std::cmp::Ordering::Equal => println!("{}", "Draw!".yellow().bold()),
     }
 }
 
-fn generate_cells(rng: &mut ThreadRng, width: u16, height: u16, mines: u16) -> Result<Vec<Vec<Cell>>, MinesError> {
-    if width * height < mines * 10 {
-        return Err(MinesError::TooManyMines);
+/// Lets one strategy play many boards and prints how it did: its win rate,
+/// the time it took and how many guesses it needed.
+fn simulate(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut notify = false;
+    let mut games = 1000;
+    let mut first_seed = 0;
+    let mut name = "greedy".to_string();
+    let mut positional = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => games = value(&arg, args.next())?,
+            "--seed" => first_seed = value(&arg, args.next())?,
+            "--strategy" => name = value(&arg, args.next())?,
+            "--preset" => positional.push(value::<String>(&arg, args.next())?),
+            "--notify" => notify = notify_option()?,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+            _ => positional.push(arg),
+        }
     }
-    if width < 8 && height < 8 {
-        return Err(MinesError::FieldTooSmall(width, height));
+    let difficulty = Difficulty::from_args(&positional)?;
+    let strategy = strategy(&name)?;
+    let (width, height, _) = difficulty.dimensions();
+    strategy.check(height, width)?;
+    let bot = Bot::new(strategy);
+    let simulation = bot::simulate(&bot, difficulty, games, first_seed).map_err(|e| e.to_string())?;
+    let wins = simulation.wins();
+    let (low, high) = wilson_interval(wins, games);
+    let percent = |part: u32, whole: u32| if whole == 0 { 0.0 } else { 100.0 * part as f64 / whole as f64 };
+    println!("{} {} games with {}, seeds {}..{}", games, difficulty, bot.name(), first_seed, first_seed + games as u64);
+    println!("Wins:     {} ({:.1}%, 95% interval {:.1}% - {:.1}%)", wins, percent(wins, games), 100.0 * low, 100.0 * high);
+    println!(
+        "Time:     {:.2} ms per game, {} in total",
+        simulation.time.as_secs_f64() * 1000.0 / games.max(1) as f64, format_time(simulation.time),
+    );
+    println!(
+        "Guesses:  {:.2} per game, {:.2} per won game, at most {}",
+        simulation.mean_guesses(false), simulation.mean_guesses(true), simulation.max_guesses(),
+    );
+    println!();
+    println!("{:>8} {:>7} {:>7} {:>7}", "guesses", "games", "share", "won");
+    let most = simulation.max_guesses().min(10);
+    for (guesses, (played, won)) in simulation.by_guesses(most).into_iter().enumerate() {
+        let label = if guesses as u32 == most && simulation.max_guesses() > most { format!("{}+", guesses) } else { guesses.to_string() };
+        println!("{:>8} {:>7} {:>6.1}% {:>6.1}%", label, played, percent(played, games), percent(won, played));
     }
-    let mut bombs = vec![];
-    for _ in 0..mines {
-        loop {
-            let coords = (
-                rng.gen_range(0, width),
-                rng.gen_range(0, height)
-            );
-            if !bombs.contains(&coords) {
-                bombs.push(coords);
-                break;
-            }
-        }
+    if notify {
+        send_notification("Simulation finished", &format!("{} won {} of {} {} games", bot.name(), wins, games, difficulty));
     }
-    let cells = (0..width).map(|x| {
-        (0..height).map(|y| {
-            if bombs.contains(&(x, y)) {
-                Cell::mine()
-            } else {
-                Cell::water()
-            }
-        }).collect()
-    }).collect();
-    Ok(cells)
+    Ok(())
 }
 
-fn min_coord(c: u16) -> u16 {
-    if c > 0 {
-        c - 1
+/// Checks that `--notify` can be honoured by this build.
+fn notify_option() -> Result<bool, String> {
+    if notify::supported() {
+        Ok(true)
     } else {
-        c
+        Err("--notify needs a build with the notify feature, cargo build --features notify".into())
     }
 }
 
-fn count_neighbours(cells: &Vec<Vec<Cell>>, x: u16, y: u16) -> Result<u8, MinesError> {
-    let mut counter = 0;
-    if cells.is_empty() {
-        return Err(MinesError::EmptyField);
+/// Sends a desktop notification. Failing to is only worth a warning, the
+/// result is in the terminal anyway.
+fn send_notification(summary: &str, body: &str) {
+    if let Err(e) = notify::notify(summary, body) {
+        eprintln!("{}", e);
     }
-    if x as usize >= cells.len() {
-        return Err(MinesError::OutOfBounds(x, y));
-    }
-    do_with_neighbours(cells, x, y, |_, _, c| {
-        if c.value.eq(&CellValue::Mine) {
-            counter += 1;
-        }
-        Ok(())
-    })?;
-    Ok(counter)
 }
 
-fn do_with_neighbours<F>(cells: &Vec<Vec<Cell>>, x: u16, y: u16, mut cb: F) -> Result<(), MinesError>
-    where F: FnMut(u16, u16, &Cell) -> Result<(), MinesError> {
-    for curr_x in min_coord(x)..x+2 {
-        for curr_y in min_coord(y)..y+2 {
-            if curr_x == x && curr_y == y {
-                continue;
-            }
-            if let Ok(cell) = get_2d(cells, curr_x, curr_y) {
-                cb(curr_x, curr_y, cell)?;
+/// Plays every strategy on the same seeded boards and prints their win rates.
+fn compare(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut notify = false;
+    let mut games = 100;
+    let mut first_seed = 0;
+    let mut names = "first,random,basic,greedy,info".to_string();
+    let mut opening = None;
+    let mut export: Option<PathBuf> = None;
+    let mut positional = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => games = value(&arg, args.next())?,
+            "--export-training" => export = Some(value(&arg, args.next())?),
+            "--seed" => first_seed = value(&arg, args.next())?,
+            "--strategies" => names = value(&arg, args.next())?,
+            "--opening" => opening = Some(value::<Opening>(&arg, args.next())?),
+            "--notify" => notify = notify_option()?,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+            _ => positional.push(arg),
+        }
+    }
+    let difficulty = Difficulty::from_args(&positional)?;
+    let strategies = names.split(',').map(strategy).collect::<Result<Vec<_>, _>>()?;
+    let (width, height, _) = difficulty.dimensions();
+    for strategy in &strategies {
+        strategy.check(height, width)?;
+    }
+    if let Some(dir) = export.as_ref() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+    }
+    println!("{} {} games, seeds {}..{}", games, difficulty, first_seed, first_seed + games as u64);
+    println!("{:<10} {:<8} {:>6} {:>8} {:>17} {:>8}", "strategy", "opening", "wins", "rate", "95% interval", "guesses");
+    for strategy in strategies {
+        let mut bot = Bot::new(strategy);
+        bot.opening = opening.clone();
+        let mut examples = match export.as_ref() {
+            Some(dir) => {
+                let path = dir.join(format!("{}.jsonl", bot.name()));
+                let file = std::fs::File::create(&path).map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+                Some((path, io::BufWriter::new(file)))
+            },
+            None => None,
+        };
+        let mut written = Ok(());
+        let mut write = |_: u64, before: &Field, step: Step| {
+            if let (Some((_, out)), Some(line), Ok(())) = (examples.as_mut(), training::example(before, step), &written) {
+                written = writeln!(out, "{}", line);
             }
+        };
+        let watch: Option<SeedWatch> = if export.is_some() { Some(&mut write) } else { None };
+        let simulation = bot::simulate_watched(&bot, difficulty, games, first_seed, watch).map_err(|e| e.to_string())?;
+        if let (Err(e), Some((path, _))) = (written, examples.as_ref()) {
+            return Err(format!("Can't write {}: {}", path.display(), e));
         }
+        let wins = simulation.wins();
+        let guesses = simulation.results.iter().map(|result| result.guesses).sum::<u32>();
+        let (low, high) = wilson_interval(wins, games);
+        println!(
+            "{:<10} {:<8} {:>6} {:>7.1}% {:>7.1}% - {:>5.1}% {:>8.2}",
+            bot.name(), bot.opening.as_ref().map_or("auto", |o| o.name()), wins, 100.0 * wins as f64 / games as f64, 100.0 * low, 100.0 * high,
+            guesses as f64 / games as f64,
+        );
+        if let Some((path, mut out)) = examples {
+            out.flush().map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+        }
+    }
+    if notify {
+        send_notification("Comparison finished", &format!("{} {} games per strategy", games, difficulty));
     }
     Ok(())
 }
 
-fn get_2d<T>(vec: &Vec<Vec<T>>, x: u16, y: u16) -> Result<&T, MinesError> {
-    if let Some(col) = vec.get(x as usize) {
-        if let Some(item) = col.get(y as usize) {
-            return Ok(item);
+fn print_deductions(deductions: &[Deduction]) {
+    for deduction in deductions {
+        match deduction {
+            Deduction::Safe(x, y) => println!("Safe at {} {}", y + 1, x + 1),
+            Deduction::Mine(x, y) => println!("Mine at {} {}", y + 1, x + 1),
         }
     }
-    Err(MinesError::OutOfBounds(x, y))
 }
 
-fn color_number(num: &u8) -> ColoredString {
-    let s = format!("{}", num);
-    match num {
-        0 => s.blue(),
-        1 => s.bright_green(),
-        2 => s.green(),
-        3 => s.yellow(),
-        4 => s.bright_red(),
-        5 => s.red(),
-        _ => s.magenta(),
+/// Converts a board file to a shareable position, or loads a shared
+/// position and prints what follows from it.
+fn position(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = || "Usage: position export|import <file>".to_string();
+    let command = args.next().ok_or_else(usage)?;
+    let path = args.next().ok_or_else(usage)?;
+    let text = read(&path)?;
+    match command.as_str() {
+        "export" => print!("{}", Field::parse(&text).map_err(|e| e.to_string())?.position()),
+        "import" => {
+            let field = Field::from_position(&text).map_err(|e| e.to_string())?;
+            View::new(Theme::for_output(ColorDepth::detect(), Palette::Classic)).print(&field);
+            println!("Mines: {}", field.mine_count());
+            print_deductions(&solver::deductions(&field));
+        },
+        _ => return Err(usage()),
     }
+    Ok(())
 }
 
-fn main() {
-    let mut args = std::env::args();
-    args.next();
-    let width: u16 = args.next().unwrap().parse().unwrap();
-    let height: u16 = args.next().unwrap().parse().unwrap();
-    let mines = width * height / 10;
-
-    let mut rng = rand::thread_rng();
-    let field = Field::generate(&mut rng, height.clone(), width.clone(), mines);
-    let mut field = match field {
-        Ok(field) => field,
-        Err(MinesError::TooManyMines) => panic!("Too many mines"),
-        _ => panic!("Error?!"),
-    };
-    let mut turns = 0;
-    let mut in_buffer = String::new();
-    let stdin = io::stdin();
-    field.print();
-    loop {
-        let mut flag = false;
-        let mut chord = false;
-        let selection;
-        loop {
-            turns += 1;
-            stdin.read_line(&mut in_buffer).unwrap();
-            let mut input: Vec<String> = in_buffer.trim().split(" ").filter(|s| s.len() > 0).map(|s| s.into()).collect();
-            if let Some(first) = input.get(0) {
-                if first.trim().eq("f") {
-                    flag = true;
-                    input.remove(0);
-                } else if first.trim().eq("c") {
-                    chord = true;
-                    input.remove(0);
-                }
-            }
-            if input.len() == 2 {
-                let input: Vec<Result<u16, _>> = input.iter().map(|s| s.trim()).map(|s| s.parse()).filter(|v| v.is_ok()).collect();
-                let input: Vec<u16> = input.into_iter().map(|r| r.unwrap()).collect();
-                if input.len() == 2 {
-                    let x = input.get(0).unwrap().clone();
-                    let y = input.get(1).unwrap().clone();
-                    selection = (
-                        if x > 0 { x - 1 } else { x },
-                        if y > 0 { y - 1 } else { y },
-                    );
-                    break;
-                } else {
-                    println!("Wrong coords count ({})", input.len());
-                    in_buffer.clear();
-                }
-            } else {
-                println!("Wrong input count ({})", input.len());
-                in_buffer.clear();
-            }
+/// Prints a generated board in the board file format.
+fn generate(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut options = BoardOptions::default();
+    while let Some(arg) = args.next() {
+        if !options.take(&arg, &mut args)? {
+            return Err(format!("Unknown option '{}'", arg));
         }
-        in_buffer.clear();
-        if flag {
-            let _ = field.flag(selection.1, selection.0);
-        } else if chord {
-            match field.chord(selection.1, selection.0) {
-                Err(MinesError::MineOpened) => {
-                    field.print();
-                    panic!("You lost!");
-                },
-                _ => {},
-            }
-        } else {
-            match field.open(selection.1, selection.0) {
-                Err(MinesError::MineOpened) => {
-                    field.print();
-                    panic!("You lost!");
-                }
-                _ => {},
-            }
+    }
+    let difficulty = options.difficulty()?;
+    let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let (field, start) = options.generate(difficulty, &mut StdRng::seed_from_u64(seed)).map_err(|e| e.to_string())?;
+    println!("# {}, seed {}, 3BV {}", difficulty, seed, field.three_bv());
+    if let Some((x, y)) = start {
+        println!("# start at {} {}", y + 1, x + 1);
+    }
+    print_board_file(&field);
+    Ok(())
+}
+
+/// Prints `field` in the board file format, with comments on the options
+/// needed to play it as it is.
+fn print_board_file(field: &Field) {
+    if field.has_line_counts() {
+        println!("# solvable with the mines of every row and column");
+    }
+    if field.topology() != Topology::Square {
+        println!("# {} grid, play with --grid {}", field.topology(), field.topology());
+    }
+    if field.wraps() {
+        println!("# edges wrap around, play with --wrap");
+    }
+    if field.layers() > 1 {
+        println!("# {} layers, play with --layers {}", field.layers(), field.layers());
+    }
+    if field.mines_per_cell() > 1 {
+        println!("# digits are cells with that many mines");
+    }
+    print!("{}", field.to_board_file());
+}
+
+/// Whether `text` is a board written by `Field::export` rather than a
+/// board file: its first line that isn't a comment gives the size.
+fn is_export(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .is_some_and(|line| line.starts_with("size "))
+}
+
+/// Whether `path` names an MBF file, see `mbf`.
+fn is_mbf(path: &str) -> bool {
+    path.to_lowercase().ends_with(".mbf")
+}
+
+fn read_mbf(path: &str) -> Result<Field, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    mbf::read(&bytes).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Prints a board file, shaped by the board options given, as keyed lines
+/// that keep its shape, see `Field::export`, or writes its mines to an MBF
+/// file.
+fn export(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let path = args.next().ok_or("Usage: export <board-file> [--grid <shape>] [--wrap] [--layers <n>] [--mbf <file>]")?;
+    let mut options = BoardOptions::default();
+    let mut mbf_file = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--grid" | "--wrap" | "--layers" => {
+                options.take(&arg, &mut args)?;
+            },
+            "--mbf" => mbf_file = Some(value::<String>(&arg, args.next())?),
+            _ => return Err(format!("Unknown option '{}'", arg)),
         }
-        println!();
-        field.print();
-        println!();
-        if field.is_won() {
-            println!("{}", "You won!".green().bold());
-            println!("With {} turns.", turns);
-            break;
+    }
+    let field = options.read_board(&path)?;
+    match mbf_file {
+        Some(out) => std::fs::write(&out, mbf::write(&field)?).map_err(|e| format!("Can't write {}: {}", out, e))?,
+        None => print!("{}", field.export()),
+    }
+    Ok(())
+}
+
+/// Prints how the mines of a board file or a generated board are spread,
+/// see `BoardInfo`.
+fn boardinfo(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut options = BoardOptions::default();
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--board" => path = Some(value::<String>(&arg, args.next())?),
+            _ => if !options.take(&arg, &mut args)? {
+                return Err(format!("Unknown option '{}'", arg));
+            },
         }
     }
+    match path {
+        Some(path) => print_board_info(&options.read_board(&path)?, None),
+        None => {
+            let difficulty = options.difficulty()?;
+            let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+            let (field, _) = options.generate(difficulty, &mut StdRng::seed_from_u64(seed)).map_err(|e| e.to_string())?;
+            print_board_info(&field, Some(seed));
+        },
+    }
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{CellValue, Cell};
+/// Prints the card of `BoardInfo` for `field`, with the seed it was
+/// generated from if known.
+fn print_board_info(field: &Field, seed: Option<u64>) {
+    let info = BoardInfo::of(field);
+    let (cols, rows) = field.size();
+    let mut card = SummaryCard {
+        title: "Board info".into(),
+        rows: vec![
+            ("Board".into(), format!("{} x {}", cols, rows)),
+            ("Mines".into(), format!("{} ({:.1}% of the cells)", info.mines, 100.0 * info.mines as f64 / info.cells.max(1) as f64)),
+            ("3BV".into(), info.three_bv.to_string()),
+            ("Openings".into(), match info.largest_opening() {
+                0 => "none".into(),
+                largest => format!("{}, the largest opens {} cells", info.openings.len(), largest),
+            }),
+        ],
+    };
+    if let Some(seed) = seed {
+        card.rows.insert(0, ("Seed".into(), seed.to_string()));
+    }
+    let names: &[&str] = match info.density.len() {
+        1 => &["Density"],
+        2 => &["Density top", "Density bottom"],
+        _ => &["Density top", "Density middle", "Density bottom"],
+    };
+    for (name, regions) in names.iter().zip(info.density.iter()) {
+        let regions: Vec<String> = regions.iter().map(|density| match density {
+            Some(density) => format!("{:>3.0}%", 100.0 * density),
+            None => "   -".into(),
+        }).collect();
+        card.rows.push((name.to_string(), regions.join(" ")));
+    }
+    for line in card.render() {
+        println!("{}", line);
+    }
+}
 
-    mod cell {
-        use crate::{Cell, MinesError};
+/// Prints a board written by `export` or an MBF file in the board file
+/// format.
+fn import(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let path = args.next().ok_or("Usage: import <file>")?;
+    if let Some(extra) = args.next() {
+        return Err(format!("Unexpected argument '{}'", extra));
+    }
+    let field = match is_mbf(&path) {
+        true => read_mbf(&path)?,
+        false => Field::import(&read(&path)?, DEFAULT_MAX_MEMORY << 20).map_err(|e| format!("{}: {}", path, e))?,
+    };
+    print_board_file(&field);
+    Ok(())
+}
 
-        #[test]
-        fn open_water() {
-            assert!(Cell::water().open().is_ok());
+/// Analyses a shared position, printing its certain moves and mine
+/// probabilities, or plays every certain move on a board file and tells
+/// whether that clears it. With board options it generates the board and
+/// has the solver play it to the end instead.
+fn solve(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let args: Vec<String> = args.collect();
+    if args.iter().any(|arg| arg.starts_with("--")) {
+        return solve_generated(args.into_iter());
+    }
+    let mut args = args.into_iter();
+    let path = args.next().ok_or("Usage: solve <board-or-position-file>")?;
+    if let Some(extra) = args.next() {
+        return Err(format!("Unexpected argument '{}'", extra));
+    }
+    let text = read(&path)?;
+    let view = View::new(Theme::for_output(ColorDepth::detect(), Palette::Classic));
+    if text.lines().any(|line| line.trim().starts_with("mines ")) {
+        let field = Field::from_position(&text).map_err(|e| e.to_string())?;
+        view.print(&field);
+        println!();
+        let deductions = solver::deductions(&field);
+        if deductions.is_empty() {
+            println!("Nothing is certain, a guess is needed");
         }
-
-        #[test]
-        fn open_mine() {
-            assert_eq!(Err(MinesError::MineOpened), Cell::mine().open());
+        print_deductions(&deductions);
+        println!();
+        println!("Mine probabilities:");
+        for line in probability_map(&field, &solver::probabilities(&field)) {
+            println!("{}", line);
         }
+        return Ok(());
+    }
+    let mut field = Field::parse(&text).map_err(|e| e.to_string())?;
+    let opened_before = field.opened_count();
+    let consistent = solver::apply_deductions(&mut field);
+    view.print(&field);
+    if !consistent {
+        println!("A deduction opened a mine, some flags are wrong");
+    } else if field.is_won() {
+        println!("Solved without guessing");
+    } else {
+        println!("Opened {} cells, then a guess is needed", field.opened_count() - opened_before);
+    }
+    Ok(())
+}
 
-        #[test]
-        fn toggle_flag() {
-            let mut cell = Cell::mine();
-            assert!(!cell.flagged);
-            cell.toggle_flag();
-            assert!(cell.flagged);
-            cell.toggle_flag();
-            assert!(!cell.flagged);
+/// Generates a board like `generate` and lets the solver clear it, counting
+/// the guesses it needs. The first cell is safe, like for players.
+fn solve_generated(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut options = BoardOptions::default();
+    while let Some(arg) = args.next() {
+        if !options.take(&arg, &mut args)? {
+            return Err(format!("Unknown option '{}'", arg));
         }
+    }
+    let difficulty = options.difficulty()?;
+    let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (mut field, start) = options.generate(difficulty, &mut rng).map_err(|e| e.to_string())?;
+    // start in the middle, or next to it when that is a hole of a shaped board
+    let (x, y) = start.unwrap_or_else(|| {
+        let (cols, rows) = field.size();
+        let distance = |&(x, y): &(u16, u16)| (x as i32 - rows as i32 / 2).abs() + (y as i32 - cols as i32 / 2).abs();
+        (0..rows)
+            .flat_map(|x| (0..cols).map(move |y| (x, y)))
+            .filter(|&(x, y)| field.cell(x, y).is_ok_and(|cell| !cell.is_hole()))
+            .min_by_key(distance)
+            .unwrap_or((rows / 2, cols / 2))
+    });
+    field.ensure_safe(x, y, &mut rng).map_err(|e| e.to_string())?;
+    let solution = solver::solve(&mut field, x, y).map_err(|e| e.to_string())?;
+    println!("# {}, seed {}, start at {} {}", difficulty, seed, y + 1, x + 1);
+    View {
+        compact: needs_compact(field.size().0 as usize, terminal_width()),
+        ..View::new(Theme::for_output(ColorDepth::detect(), Palette::Classic))
+    }.print(&field);
+    if solution.without_guessing() {
+        println!("Solved without guessing in {} moves", solution.moves);
+        return Ok(());
+    }
+    if solution.cleared {
+        println!("Solved in {} moves, {} of them guesses", solution.moves, solution.guesses);
+    } else if solution.guesses > 0 {
+        println!("Hit a mine after {} moves, on guess {}", solution.moves, solution.guesses);
+    } else {
+        println!("Stuck after {} moves", solution.moves);
+    }
+    println!("Not solvable without guessing, the first guess came after move {}", solution.certain_moves);
+    Ok(())
+}
 
-        #[test]
-        fn toggle_opened() {
-            let mut cell = Cell::water();
-            let _ = cell.open();
-            cell.toggle_flag();
-            assert!(!cell.flagged);
+/// Shows a game recorded with `--record`, scrubbing through its moves on a
+/// terminal or printing one of them otherwise.
+fn replay(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut path = None;
+    let mut at = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--move" => at = Some(value::<usize>(&arg, args.next())?),
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return Err(format!("Unknown option '{}'", arg)),
+        }
+    }
+    let path = path.ok_or("replay expects a file written by --record")?;
+    let replay = Replay::parse(&read(&path)?).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    let frames = replay.frames().map_err(|e| e.to_string())?;
+    let last = frames.len() - 1;
+    let mut current = at.map_or(last, |at| at.min(last));
+    let field = &frames[0].field;
+    let mut view = View {
+        compact: needs_compact(field.size().0 as usize, terminal_width()),
+        ..View::new(Theme::for_output(ColorDepth::detect(), Palette::Classic))
+    };
+    if !stdin_is_tty() || !stdout_is_tty() {
+        for line in replay_screen(&frames, current, &mut view, terminal_width()) {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+    let events: Vec<usize> = frames.iter().enumerate()
+        .filter(|(_, frame)| frame.event.is_some())
+        .map(|(i, _)| i)
+        .collect();
+    let mut terminal = RawTerminal::enter().map_err(|e| format!("Can't start full-screen mode: {}", e))?;
+    loop {
+        let mut lines = replay_screen(&frames, current, &mut view, terminal_width());
+        lines.push("left/right: move, up/down: key event, q: quit".dimmed().to_string());
+        terminal.draw(&lines).map_err(|e| e.to_string())?;
+        for key in terminal.read_keys().map_err(|e| e.to_string())? {
+            current = match key {
+                Key::Left => current.saturating_sub(1),
+                Key::Right => (current + 1).min(last),
+                Key::Up => events.iter().rev().find(|&&i| i < current).copied().unwrap_or(0),
+                Key::Down => events.iter().find(|&&i| i > current).copied().unwrap_or(last),
+                Key::Char('q') | Key::Escape | Key::Interrupt => return Ok(()),
+                _ => current,
+            };
         }
+    }
+}
 
-        #[test]
-        fn open_flagged() {
-            let mut cell = Cell::water();
-            cell.flagged = true;
-            cell.open().unwrap();
-            assert!(!cell.opened);
+/// Checks a board file for puzzle authors, see `check::check`.
+fn check_board(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut path = None;
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return Err(format!("Unknown option '{}'", arg)),
+        }
+    }
+    let path = path.ok_or("check-board expects a board file")?;
+    let report = check::check(&read(&path)?);
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        println!("{}: {} rows of {} cells, {} mines", path, report.rows, report.cols, report.mines);
+        for issue in report.issues.iter() {
+            println!("  {}", issue);
+        }
+        match (report.solvable, report.start) {
+            (Some(true), Some((x, y))) => println!("Solvable without guessing from {} {}", y + 1, x + 1),
+            (Some(true), None) => println!("Solvable without guessing"),
+            (Some(false), _) => println!("Not solvable without guessing"),
+            (None, _) => {},
         }
     }
+    if !report.is_valid() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// The board of a replay after move `current`, with the timeline and what
+/// happened in the move below it.
+fn replay_screen(frames: &[Frame], current: usize, view: &mut View, width: usize) -> Vec<String> {
+    let frame = &frames[current];
+    view.exploded = frame.field.opened_mine();
+    let mut lines = view.render(&frame.field, width);
+    lines.push(String::new());
+    let events: Vec<Option<Event>> = frames.iter().map(|frame| frame.event).collect();
+    lines.push(timeline(&events, current, width));
+    let mut status = format!("Move {} of {}", current, frames.len() - 1);
+    if let Some((action, x, y)) = frame.step {
+        status.push_str(&format!(": {} {}", action, frame.field.coords(x, y)));
+    }
+    if let Some(time) = frame.time {
+        status.push_str(&format!(" at {}", format_time(time)));
+    }
+    if let Some(event) = frame.event {
+        status.push_str(&format!(", {}", event));
+    }
+    lines.push(status);
+    lines
+}
+
+fn read(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))
+}
 
-    mod field {
-        use crate::{CellValue::*, Field};
-        use crate::tests::cells_from_types;
+/// Takes `--no-color` out of `args`, wherever it is, and turns colors off.
+fn take_no_color(args: &mut Vec<String>) {
+    let before = args.len();
+    args.retain(|arg| arg != "--no-color");
+    if args.len() < before {
+        colored::control::set_override(false);
+    }
+}
 
-        #[test]
-        fn with_cells() {
-            let cells = cells_from_types(vec![
-                vec![Water, Water, Mine],
-                vec![Mine, Water, Water],
-                vec![Mine, Water, Mine],
-            ]);
-            let field = Field::with_cells(cells);
-            let numbers = vec![
-                vec![1, 2, 0],
-                vec![1, 4, 2],
-                vec![1, 3, 0],
-            ];
-            assert_eq!(numbers, field.numbers);
+/// Takes `--profile <name>` out of `args`, wherever it is, and returns that
+/// profile. `MINESWEEP_PROFILE` picks one without the option.
+fn take_profile(args: &mut Vec<String>) -> Result<Profile, String> {
+    let mut name = std::env::var("MINESWEEP_PROFILE").ok().filter(|name| !name.is_empty());
+    while let Some(i) = args.iter().position(|arg| arg == "--profile") {
+        args.remove(i);
+        if i >= args.len() {
+            return Err("--profile expects a value".into());
         }
+        name = Some(args.remove(i));
+    }
+    match name {
+        Some(name) => Profile::named(&name),
+        None => Ok(Profile::default()),
+    }
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+    take_no_color(&mut args);
+    let mut profile = match take_profile(&mut args) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Run with --help for usage");
+            std::process::exit(2);
+        },
+    };
+    let command = args.first().cloned().unwrap_or_default();
+    let rest = args.iter().skip(1).cloned();
+    let result = match command.as_str() {
+        "play" => play(&mut profile, rest),
+        "generate" => generate(rest),
+        "solve" => solve(rest),
+        "compare" => compare(rest),
+        "simulate" => simulate(rest),
+        "position" => position(rest),
+        "export" => export(rest),
+        "import" => import(rest),
+        "boardinfo" => boardinfo(rest),
+        "replay" => replay(rest),
+        "check-board" => check_board(rest),
+        "goals" => goals(&profile, rest),
+        "stats" => show_stats(&profile, rest),
+        "journal" => journal(&mut profile, rest),
+        "scores" => show_scores(&profile, rest),
+        "profiles" => show_profiles(&profile, rest),
+        "bracket" => bracket(rest),
+        "serve" => serve(rest),
+        "race" => race(&mut profile, rest),
+        "coop" => coop(&mut profile, rest),
+        "pack" => pack(&mut profile, rest),
+        "load" => play(&mut profile, std::iter::once("--load".to_string()).chain(rest)),
+        // playing is the default, so `minesweep expert` keeps working
+        _ => play(&mut profile, args.into_iter()),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        eprintln!("Run with --help for usage");
+        std::process::exit(2);
+    }
+}
+
+/// Today's goals that aren't reached yet, to remind the player at the start.
+fn pending_goals(profile: &Profile) -> Vec<String> {
+    let goals = match load_goals(profile) {
+        Ok(goals) => goals,
+        Err(e) => return vec![e],
+    };
+    let records = stats::stats_path(profile).map(|path| stats::load(&path).unwrap_or_default()).unwrap_or_default();
+    let today = stats::local_day(stats::now());
+    goals.iter()
+        .map(|goal| (goal, goal.progress(&records, today)))
+        .filter(|(goal, done)| *done < goal.count)
+        .map(|(goal, done)| format!("Goal: {} ({}/{})", goal, done, goal.count).dimmed().to_string())
+        .collect()
+}
+
+/// The goals from the goals file of `profile`, none if there isn't one.
+fn load_goals(profile: &Profile) -> Result<Vec<Goal>, String> {
+    let path = match goals_path(profile) {
+        Some(path) if path.exists() => path,
+        _ => return Ok(vec![]),
+    };
+    parse_goals(&read(&path.to_string_lossy())?).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Appends a finished game to the stats file of `profile` and its board,
+/// made from `seed` and `args`, to the journal, and returns a message for
+/// every goal it completed today. Failing to write the files is only a
+/// warning, the game is over anyway.
+fn record_game(profile: &Profile, record: GameRecord, seed: Option<u64>, args: &[String]) -> Vec<String> {
+    let path = match stats::stats_path(profile) {
+        Some(path) => path,
+        None => return vec![],
+    };
+    let entry = Entry {
+        finished: record.finished,
+        difficulty: record.difficulty,
+        won: record.won,
+        time: record.time,
+        seed,
+        args: args.to_vec(),
+    };
+    if let Some(Err(e)) = journal::journal_path(profile).map(|path| journal::append(&path, &entry)) {
+        return vec![e];
+    }
+    let mut records = stats::load(&path).unwrap_or_default();
+    if let Err(e) = stats::append(&path, &record) {
+        return vec![e];
+    }
+    let today = stats::local_day(record.finished);
+    let goals = load_goals(profile).unwrap_or_default();
+    let before: Vec<u32> = goals.iter().map(|goal| goal.progress(&records, today)).collect();
+    records.push(record);
+    goals.iter().zip(before)
+        .filter(|(goal, before)| *before < goal.count && goal.progress(&records, today) >= goal.count)
+        .map(|(goal, _)| format!("Goal reached: {}", goal))
+        .collect()
+}
 
-        #[test]
-        fn flag_cell() {
-            let cells = cells_from_types(vec![
-                vec![Water, Mine],
-                vec![Water, Water],
-            ]);
-            let mut field = Field::with_cells(cells);
-            field.flag(1, 0).unwrap();
-            assert!(field.cells.get(1).unwrap().get(0).unwrap().flagged);
-            field.flag(1, 0).unwrap();
-            assert!(!field.cells.get(1).unwrap().get(0).unwrap().flagged);
+/// Shows today's progress on every goal.
+fn goals(profile: &Profile, mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    if let Some(extra) = args.next() {
+        return Err(format!("Unexpected argument '{}'", extra));
+    }
+    let goals = load_goals(profile)?;
+    if goals.is_empty() {
+        let path = goals_path(profile).map_or("the goals file".into(), |path| path.display().to_string());
+        println!("No goals set. Add one per line to {}, for example:", path);
+        println!("  win 3 intermediate");
+        println!("  win 1 nf");
+        println!("  play 5 expert no-guess");
+        return Ok(());
+    }
+    let records = match stats::stats_path(profile) {
+        Some(path) => stats::load(&path)?,
+        None => vec![],
+    };
+    let today = stats::local_day(stats::now());
+    for goal in goals {
+        let done = goal.progress(&records, today);
+        let line = format!("[{}] {} ({}/{})", if done >= goal.count { "x" } else { " " }, goal, done.min(goal.count), goal.count);
+        if done >= goal.count {
+            println!("{}", line.green());
+        } else {
+            println!("{}", line);
         }
     }
+    Ok(())
+}
 
-    fn cells_from_types(types: Vec<Vec<CellValue>>) -> Vec<Vec<Cell>> {
-        types.into_iter().map(|c|
-            c.into_iter().map(|v|
-                Cell {
-                    value: v,
-                    opened: false,
-                    flagged: false,
-                }
-            ).collect()
-        ).collect()
+/// Summarizes all recorded games.
+fn show_stats(profile: &Profile, mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    if let Some(extra) = args.next() {
+        return Err(format!("Unexpected argument '{}'", extra));
+    }
+    let records = match stats::stats_path(profile) {
+        Some(path) => stats::load(&path)?,
+        None => vec![],
+    };
+    if records.is_empty() {
+        println!("No games played yet.");
+        return Ok(());
+    }
+    let summary = Summary::of(&records);
+    let mut rows = vec![
+        ("Played".to_string(), summary.played.to_string()),
+        ("Won".to_string(), format!("{} ({:.0}%)", summary.won, 100.0 * summary.win_rate())),
+    ];
+    if let Some(efficiency) = summary.efficiency {
+        rows.push(("Efficiency".to_string(), format!("{:.0}%", 100.0 * efficiency)));
+    }
+    for (difficulty, time) in summary.best {
+        rows.push((format!("Best {}", difficulty), format_time(time)));
+    }
+    let card = SummaryCard {
+        title: match profile.is_shared() {
+            true => "Statistics".into(),
+            false => format!("Statistics of {}", profile),
+        },
+        rows,
+    };
+    for line in card.render() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Lists the entries of the journal matching a query, plays one of them
+/// again or makes it a favorite.
+fn journal(profile: &mut Profile, args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut args: Vec<String> = args.collect();
+    let favorites = match args.iter().position(|arg| arg == "--favorites") {
+        Some(i) => {
+            args.remove(i);
+            true
+        },
+        None => false,
+    };
+    let path = match favorites {
+        true => journal::favorites_path(profile),
+        false => journal::journal_path(profile),
+    };
+    let entries = match path.as_ref() {
+        Some(path) => journal::load(path)?,
+        None => vec![],
+    };
+    let entry = |number: Option<&String>| -> Result<&Entry, String> {
+        let number = value::<usize>(&args[0], number.cloned())?;
+        number.checked_sub(1)
+            .and_then(|i| entries.get(i))
+            .ok_or_else(|| format!("There is no entry {}, the {} has {}", number, if favorites { "favorites list" } else { "journal" }, entries.len()))
+    };
+    match args.first().map(String::as_str) {
+        Some("play") => {
+            if let Some(extra) = args.get(2) {
+                return Err(format!("Unexpected argument '{}'", extra));
+            }
+            let entry = entry(args.get(1))?;
+            if entry.args.is_empty() {
+                return Err("That board can't be made again".into());
+            }
+            play(profile, entry.args.clone().into_iter())
+        },
+        Some("favorite") => {
+            if favorites || args.len() > 2 {
+                return Err("Usage: journal favorite <n>".into());
+            }
+            let entry = entry(args.get(1))?;
+            let path = journal::favorites_path(profile).ok_or("No data directory for the favorites")?;
+            let kept = journal::load(&path)?;
+            if kept.contains(entry) {
+                return Err("That entry is a favorite already".into());
+            }
+            journal::append(&path, entry)?;
+            println!("Kept as favorite {}", kept.len() + 1);
+            Ok(())
+        },
+        first => {
+            let words = if first == Some("list") { &args[1..] } else { &args[..] };
+            let query = Query::parse(words)?;
+            let today = stats::local_day(stats::now());
+            let mut shown = 0;
+            for (i, entry) in entries.iter().enumerate().filter(|(_, entry)| query.matches(entry, today)) {
+                let line = format!(
+                    "{:>4}  {:<10}  {:<4}  {:<20}  {:>7}  {}",
+                    i + 1,
+                    days_ago(today - stats::local_day(entry.finished)),
+                    if entry.won { "won" } else { "lost" },
+                    entry.difficulty.to_string(),
+                    format_time(entry.time),
+                    if entry.args.is_empty() { "can't be made again".to_string() } else { entry.args.join(" ") },
+                );
+                if entry.won {
+                    println!("{}", line.green());
+                } else {
+                    println!("{}", line);
+                }
+                shown += 1;
+            }
+            if shown == 0 {
+                println!("No {} match.", if favorites { "favorites" } else { "journal entries" });
+            }
+            Ok(())
+        },
+    }
+}
+
+/// A day `days` before today, in words.
+fn days_ago(days: i64) -> String {
+    match days {
+        0 => "today".into(),
+        1 => "yesterday".into(),
+        _ => format!("{} days ago", days),
+    }
+}
+
+/// Lists the profiles with the games played in each, `current` marked.
+fn show_profiles(current: &Profile, mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    if let Some(extra) = args.next() {
+        return Err(format!("Unexpected argument '{}'", extra));
+    }
+    let mut rows = vec![];
+    for profile in stats::profiles() {
+        let played = match stats::stats_path(&profile) {
+            Some(path) => stats::load(&path)?.len(),
+            None => 0,
+        };
+        let marker = if profile == *current { "* " } else { "  " };
+        rows.push((format!("{}{}", marker, profile), format!("{} {}", played, if played == 1 { "game" } else { "games" })));
+    }
+    for line in (SummaryCard { title: "Profiles".into(), rows }).render() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Shows the high score table of every preset.
+fn show_scores(profile: &Profile, mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    if let Some(extra) = args.next() {
+        return Err(format!("Unexpected argument '{}'", extra));
+    }
+    let all = match scores::scores_path(profile) {
+        Some(path) => scores::load(&path)?,
+        None => vec![],
+    };
+    for difficulty in scores::RANKED.iter() {
+        let table = scores::table(&all, *difficulty);
+        let mut card = SummaryCard {
+            title: format!("High scores {}", difficulty),
+            rows: table.iter()
+                .enumerate()
+                .map(|(place, score)| (format!("{:>2}. {}", place + 1, score.name), format_time(score.time)))
+                .collect(),
+        };
+        if card.rows.is_empty() {
+            card.rows.push(("No wins yet".into(), String::new()));
+        }
+        for line in card.render() {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Draws a single elimination bracket and prints its sheet, or enters
+/// results into a bracket written with `--json` and shows where it stands.
+fn bracket(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut args = args.peekable();
+    match args.peek().map(|arg| arg.as_str()) {
+        Some("result") => return bracket_result(args.skip(1)),
+        Some("serve") => return bracket_serve(args.skip(1)),
+        Some("show") => {
+            args.next();
+            let path = args.next().ok_or("Usage: bracket show <file>")?;
+            if let Some(extra) = args.next() {
+                return Err(format!("Unexpected argument '{}'", extra));
+            }
+            let bracket = Bracket::from_json(&read(&path)?).map_err(|e| format!("{}: {}", path, e))?;
+            print_bracket(&bracket);
+            println!();
+            print_standings(&bracket);
+            return Ok(());
+        },
+        _ => {},
+    }
+    let mut players: Option<usize> = None;
+    let mut names: Option<Vec<String>> = None;
+    let mut rounds: Option<usize> = None;
+    let mut games = 1;
+    let mut seed = None;
+    let mut json = false;
+    let mut positional = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--players" => players = Some(value(&arg, args.next())?),
+            "--names" => names = Some(value::<String>(&arg, args.next())?.split(',').map(|name| name.trim().to_string()).collect()),
+            "--rounds" => rounds = Some(value(&arg, args.next())?),
+            "--games" => games = value(&arg, args.next())?,
+            "--seed" => seed = Some(value(&arg, args.next())?),
+            "--json" => json = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+            _ => positional.push(arg),
+        }
+    }
+    let names = match (names, players) {
+        (Some(names), Some(players)) if names.len() != players => {
+            return Err(format!("--names has {} players, --players {}", names.len(), players));
+        },
+        (Some(names), _) => names,
+        (None, Some(players)) => (1..=players).map(|n| format!("Player {}", n)).collect(),
+        (None, None) => return Err("A bracket needs --players or --names".into()),
+    };
+    if let Some(rounds) = rounds {
+        let needed = bracket::rounds_for(names.len());
+        if rounds != needed {
+            return Err(format!("{} players play {} rounds, not {}", names.len(), needed, rounds));
+        }
+    }
+    let difficulty = Difficulty::from_args(&positional)?;
+    let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let bracket = Bracket::new(names, games, difficulty, &mut StdRng::seed_from_u64(seed))?;
+    if json {
+        println!("{}", bracket.to_json());
+    } else {
+        print_bracket(&bracket);
+    }
+    Ok(())
+}
+
+/// Enters a player's results of a round into a bracket file and tells how
+/// their match turned out.
+fn bracket_result(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = || "Usage: bracket result <file> <round> <player> <time|lost>...".to_string();
+    let path = args.next().ok_or_else(usage)?;
+    let round: usize = args.next().ok_or_else(usage)?.parse().map_err(|_| usage())?;
+    let name = args.next().ok_or_else(usage)?;
+    let outcomes = args.map(|arg| arg.parse()).collect::<Result<Vec<Outcome>, String>>()?;
+    let mut bracket = Bracket::from_json(&read(&path)?).map_err(|e| format!("{}: {}", path, e))?;
+    let player = bracket.player(&name).ok_or_else(|| format!("No player '{}' in the bracket", name))?;
+    if round == 0 {
+        return Err("Rounds count from 1".into());
+    }
+    bracket.record(round - 1, player, outcomes)?;
+    std::fs::write(&path, bracket.to_json() + "\n").map_err(|e| format!("Can't write {}: {}", path, e))?;
+    let (a, b) = bracket.matches(round - 1).into_iter()
+        .find(|&(a, b)| a == Entrant::Player(player) || b == Entrant::Player(player))
+        .expect("recorded players play in the round");
+    match bracket.winner(round - 1, a, b) {
+        Some(winner) => println!("{} wins the match", bracket.players[winner]),
+        None => println!("Result entered, waiting for the opponent"),
+    }
+    if let Some(champion) = bracket.champion() {
+        println!("{} wins the bracket", bracket.players[champion].green().bold());
+    }
+    Ok(())
+}
+
+/// Serves a bracket file over HTTP. Tokens for the players are drawn once
+/// and kept next to the bracket, entered results are written back to it and
+/// replays saved in a directory next to it.
+fn bracket_serve(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let path = args.next().ok_or("Usage: bracket serve <file> [--port <n>]")?;
+    let mut port: u16 = 8080;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = value(&arg, args.next())?,
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    let bracket = Bracket::from_json(&read(&path)?).map_err(|e| format!("{}: {}", path, e))?;
+    let tokens_path = format!("{}.tokens", path);
+    let tokens = match std::fs::read_to_string(&tokens_path) {
+        Ok(text) => tournament::parse_tokens(&bracket, &text).map_err(|e| format!("{}: {}", tokens_path, e))?,
+        Err(_) => {
+            let tokens = tournament::new_tokens(bracket.players.len(), &mut rand::thread_rng());
+            std::fs::write(&tokens_path, tournament::format_tokens(&bracket, &tokens))
+                .map_err(|e| format!("Can't write {}: {}", tokens_path, e))?;
+            println!("Tokens for the players written to {}, hand them out", tokens_path);
+            tokens
+        },
+    };
+    let replays = PathBuf::from(format!("{}.replays", path));
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| format!("Can't listen on port {}: {}", port, e))?;
+    println!("Serving {} on port {}, standings at /standings", path, port);
+    let mut server = tournament::Server::new(bracket, tokens);
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+        let request = match stream.try_clone().and_then(|read| tournament::Request::read(&mut io::BufReader::new(read))) {
+            Ok(request) => request,
+            Err(_) => continue,
+        };
+        let (response, submission) = server.handle(&request);
+        if let Some(submission) = submission {
+            let name = &server.bracket.players[submission.player];
+            std::fs::write(&path, server.bracket.to_json() + "\n").map_err(|e| format!("Can't write {}: {}", path, e))?;
+            if !submission.replays.is_empty() {
+                std::fs::create_dir_all(&replays).map_err(|e| format!("Can't create {}: {}", replays.display(), e))?;
+            }
+            for (game, replay) in submission.replays.iter().enumerate() {
+                let file = replays.join(format!("round{}-player{}-game{}.jsonl", submission.round + 1, submission.player + 1, game + 1));
+                std::fs::write(&file, replay).map_err(|e| format!("Can't write {}: {}", file.display(), e))?;
+            }
+            println!("{} entered round {}", name, submission.round + 1);
+        }
+        let _ = response.write(&mut stream);
+    }
+    Ok(())
+}
+
+/// Reads what is left of the arguments of `pack install` and `pack update`,
+/// an optional `--sha256 <checksum>`.
+fn sha256_option(args: &mut impl Iterator<Item = String>) -> Result<Option<String>, String> {
+    let mut checksum = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sha256" => checksum = Some(value::<String>(&arg, args.next())?),
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    Ok(checksum)
+}
+
+/// Installs, lists, shows and plays puzzle packs.
+fn pack(profile: &mut Profile, mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = "Usage: pack install <file> | install <url> --sha256 <checksum> | update [<name> [--sha256 <checksum>]] | list | info <name> | play <name> [<puzzle>] [play options]";
+    match args.next().as_deref() {
+        Some("install") => {
+            let source = args.next().ok_or(usage)?;
+            let checksum = sha256_option(&mut args)?;
+            let name = match checksum {
+                Some(_) if !source.contains("://") => return Err("--sha256 checks downloads, not local files".into()),
+                #[cfg(feature = "network")]
+                Some(checksum) => pack::install_url(profile, &source, &checksum)?,
+                #[cfg(not(feature = "network"))]
+                Some(_) => return Err("Installing from a URL needs a build with the network feature, cargo build --features network".into()),
+                None if source.contains("://") => return Err(format!("Pass the SHA-256 checksum the author published for {} with --sha256", source)),
+                None => pack::install(profile, std::path::Path::new(&source))?,
+            };
+            println!("Installed {}, play it with pack play {}", source, name);
+            Ok(())
+        },
+        #[cfg(feature = "network")]
+        Some("update") => {
+            let names = match args.next() {
+                Some(name) => vec![name],
+                None => pack::installed(profile),
+            };
+            let checksum = sha256_option(&mut args)?;
+            if checksum.is_some() && names.len() != 1 {
+                return Err("--sha256 checks the update of one pack, name it".into());
+            }
+            for name in names {
+                if pack::source(profile, &name)?.is_none() {
+                    println!("{:<16} installed from a file", name);
+                    continue;
+                }
+                match pack::update(profile, &name, checksum.as_deref()) {
+                    Ok(pack::Update::Installed) => println!("{:<16} {}", name, "updated".green()),
+                    Ok(pack::Update::Changed) => println!("{:<16} changed, install it with pack update {} --sha256 <checksum>", name, name),
+                    Ok(pack::Update::UpToDate) => println!("{:<16} up to date", name),
+                    Err(e) => println!("{:<16} {}", name, e.red()),
+                }
+            }
+            Ok(())
+        },
+        #[cfg(not(feature = "network"))]
+        Some("update") => Err("Updating packs needs a build with the network feature, cargo build --features network".into()),
+        Some("list") => {
+            if let Some(extra) = args.next() {
+                return Err(format!("Unexpected argument '{}'", extra));
+            }
+            let names = pack::installed(profile);
+            if names.is_empty() {
+                println!("No packs installed yet, add one with pack install <file>");
+            }
+            for name in names {
+                match (pack::load(profile, &name), pack::load_progress(profile, &name)) {
+                    (Ok(pack), Ok(progress)) => println!(
+                        "{:<16} {} ({} of {} solved)",
+                        name, pack.title, progress.solved.len(), pack.puzzles.len(),
+                    ),
+                    (Err(e), _) | (_, Err(e)) => println!("{:<16} {}", name, e.red()),
+                }
+            }
+            Ok(())
+        },
+        Some("info") => {
+            let name = args.next().ok_or(usage)?;
+            if let Some(extra) = args.next() {
+                return Err(format!("Unexpected argument '{}'", extra));
+            }
+            let pack = pack::load(profile, &name)?;
+            let progress = pack::load_progress(profile, &name)?;
+            println!("{}", pack.title.bold());
+            if let Some(author) = pack.author.as_ref() {
+                println!("by {}", author);
+            }
+            if let Some(description) = pack.description.as_ref() {
+                println!("{}", description);
+            }
+            println!();
+            for (i, puzzle) in pack.puzzles.iter().enumerate() {
+                let field = puzzle.field().map_err(|e| format!("{}: {}", puzzle.title, e))?;
+                let (cols, rows) = field.size();
+                let mines = field.mine_count();
+                let mut line = format!("{:>3}. {} ({}x{}, {} {}", i + 1, puzzle.title, cols, rows, mines, if mines == 1 { "mine" } else { "mines" });
+                if let Some(rating) = puzzle.rating {
+                    line += &format!(", rated {}/5", rating);
+                }
+                if let Some(author) = puzzle.author.as_ref() {
+                    line += &format!(", by {}", author);
+                }
+                line += ")";
+                match progress.solved.get(&i) {
+                    Some(&time) => println!("{} {}", line, format!("solved in {}", format_time(time)).green()),
+                    None => println!("{}", line),
+                }
+            }
+            println!();
+            println!("{} of {} solved", progress.solved.len(), pack.puzzles.len());
+            Ok(())
+        },
+        Some("play") => pack_play(profile, args),
+        _ => Err(usage.into()),
+    }
+}
+
+/// Plays the puzzles of a pack from `<puzzle>` or the first unsolved one,
+/// keeping the best time of every solved puzzle.
+fn pack_play(profile: &mut Profile, mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let name = args.next().ok_or("pack play expects the name of an installed pack")?;
+    let mut rest: Vec<String> = args.collect();
+    let pack = pack::load(profile, &name)?;
+    let mut progress = pack::load_progress(profile, &name)?;
+    let mut current = match rest.first().and_then(|arg| arg.parse::<usize>().ok()) {
+        Some(puzzle) => {
+            rest.remove(0);
+            if puzzle == 0 || puzzle > pack.puzzles.len() {
+                return Err(format!("{} has puzzles 1 to {}", pack.title, pack.puzzles.len()));
+            }
+            puzzle - 1
+        },
+        None => progress.next(pack.puzzles.len()).unwrap_or(0),
+    };
+    loop {
+        let puzzle = &pack.puzzles[current];
+        let field = puzzle.field().map_err(|e| format!("{}: {}", puzzle.title, e))?;
+        let (cols, rows) = field.size();
+        println!("{}", format!("{}, puzzle {} of {}: {}", pack.title, current + 1, pack.puzzles.len(), puzzle.title).bold());
+        if let Some(author) = pack.author_of(puzzle) {
+            println!("by {}", author);
+        }
+        // puzzles are kept in the stats by their size, like board files
+        let mut args: Vec<String> = vec!["custom".into(), cols.to_string(), rows.to_string(), field.mine_count().to_string()];
+        args.extend(rest.iter().cloned());
+        let ended = match play_game(profile, args.into_iter(), Some((field, None)))? {
+            Some(ended) => ended,
+            None => return Ok(()),
+        };
+        if let Some(time) = ended.time {
+            let best = progress.solved.get(&current).copied();
+            if progress.record(current, time) {
+                pack::save_progress(profile, &name, &progress)?;
+                if best.is_some() {
+                    println!("{}", "New best time for this puzzle".green());
+                }
+            }
+            println!("{} of {} puzzles of {} solved", progress.solved.len(), pack.puzzles.len(), pack.title);
+        }
+        match another_game(&ended)? {
+            // on to the next unsolved puzzle, this one last
+            Some(Next::New) => match (current + 1..pack.puzzles.len()).chain(0..=current).find(|p| !progress.solved.contains_key(p)) {
+                Some(puzzle) => current = puzzle,
+                None => {
+                    println!("{}", "Every puzzle of the pack is solved!".green().bold());
+                    return Ok(());
+                },
+            },
+            Some(Next::Retry) => {},
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Port races are hosted on without `--port`.
+const RACE_PORT: u16 = 7878;
+
+/// Races another player on the same board over TCP, see `race`. The host
+/// picks the board and waits for one opponent to join.
+fn race(profile: &mut Profile, mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = "Usage: race host [--port <n>] [--name <name>] [--seed <n>] [--handicap <player>:<handicap>]... [difficulty] or race join <address>[:<port>] [--name <name>]";
+    let role = args.next().ok_or(usage)?;
+    let mut port = RACE_PORT;
+    let mut name = std::env::var("USER").ok().filter(|name| !name.trim().is_empty());
+    let mut seed = None;
+    let mut handicaps = [Handicap::default(), Handicap::default()];
+    let mut positional = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = value(&arg, args.next())?,
+            "--name" => name = Some(value(&arg, args.next())?),
+            "--seed" => seed = Some(value(&arg, args.next())?),
+            "--handicap" if role == "host" => {
+                let (player, handicap) = Handicap::for_player(&value::<String>(&arg, args.next())?)?;
+                *handicaps.get_mut(player).ok_or("A race has players 1, the host, and 2")? = handicap;
+            },
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+            _ => positional.push(arg),
+        }
+    }
+    let name = name.unwrap_or_else(|| if role == "host" { "Host".into() } else { "Guest".into() });
+    let session = match role.as_str() {
+        "host" => {
+            let difficulty = Difficulty::from_args(&positional)?;
+            let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+            let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| format!("Can't listen on port {}: {}", port, e))?;
+            println!("Waiting for an opponent on port {}, they join with: minesweep race join <this machine>:{}", port, port);
+            race::Session::host(&listener, &name, difficulty, seed, handicaps)?
+        },
+        "join" => {
+            if positional.len() != 1 || seed.is_some() {
+                return Err("race join takes the address of the host, the board is theirs".into());
+            }
+            let address = if positional[0].contains(':') { positional[0].clone() } else { format!("{}:{}", positional[0], port) };
+            race::Session::join(&address, &name)?
+        },
+        _ => return Err(usage.into()),
+    };
+    race_game(profile, session)
+}
+
+/// Plays the race once both sides agreed on the board and the handicaps.
+fn race_game(profile: &mut Profile, mut session: race::Session) -> Result<(), String> {
+    let mut view = View {
+        compact: needs_compact(session.field.size().0 as usize, terminal_width()),
+        ..View::new(Theme::for_output(ColorDepth::detect(), Palette::Classic))
+    };
+    let (water, their_water) = session.water();
+    let opponent = session.opponent.clone();
+    let progress = |session: &race::Session| {
+        let theirs = match session.state.opponent {
+            race::Status::Playing(opened) => format!("{}/{}", opened, their_water),
+            race::Status::Won(_) => "cleared".into(),
+            race::Status::Lost(_) => "hit a mine".into(),
+            race::Status::Gone => "left".into(),
+        };
+        format!("You: {}/{}, {}: {}", session.field.opened_count(), water, opponent, theirs)
+    };
+    println!("Racing {} on {}, seed {}. The first to clear the board wins.", opponent, session.difficulty, session.seed);
+    for (who, handicap) in [("You play".to_string(), &session.own), (format!("{} plays", opponent), &session.theirs)] {
+        if *handicap != Handicap::default() {
+            println!("{} with the handicap {}", who, handicap);
+        }
+    }
+    println!();
+    view.print(&session.field);
+    println!("{}", progress(&session));
+    let stdin = io::stdin();
+    let mut in_buffer = String::new();
+    let mut macros = Macros::new();
+    let mut analyst = Analyst::spawn();
+    let mut timer = Timer::new();
+    timer.start(Instant::now());
+    let save_as = |_: &Profile, _: &str, _: Duration| Err("Races can't be saved".to_string());
+    'race: while session.state.verdict().is_none() {
+        // follow the opponent while waiting for the next move
+        loop {
+            session.poll();
+            if session.state.verdict().is_some() {
+                break 'race;
+            }
+            if !cfg!(unix) || input_pending() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        let (action, (col, row)) = match line_move(&stdin, &mut in_buffer, profile, &mut macros, &session.field, &mut view, &mut analyst, &mut timer, &save_as, false, None)? {
+            Typed::Move(selected) => selected,
+            Typed::Said => continue,
+            Typed::End => {
+                session.leave();
+                return Ok(());
+            },
+        };
+        if !matches!(action, Action::Open | Action::Flag | Action::Chord) {
+            println!("No hints or undo in a race");
+            continue;
+        }
+        if let Err(e) = session.play(action, row, col, timer.elapsed(Instant::now())) {
+            println!("{}", e);
+        }
+        if session.state.verdict().is_none() {
+            println!();
+            view.print(&session.field);
+            println!("{}", progress(&session));
+        }
+    }
+    timer.stop(Instant::now());
+    // the race may have ended on the other side, with this one left standing
+    session.leave();
+    view.exploded = session.field.opened_mine();
+    println!();
+    view.print(&session.field);
+    println!("{}", progress(&session));
+    let how = match (session.state.own, session.state.opponent) {
+        (race::Status::Won(time), _) => format!("You cleared the board in {}", format_time(time)),
+        (_, race::Status::Won(time)) => format!("{} cleared the board in {}", opponent, format_time(time)),
+        (race::Status::Lost(own), race::Status::Lost(theirs)) => format!(
+            "You hit a mine after {}, {} after {}", format_time(own), opponent, format_time(theirs),
+        ),
+        (race::Status::Lost(time), _) => format!("You hit a mine after {}", format_time(time)),
+        (_, race::Status::Lost(time)) => format!("{} hit a mine after {}", opponent, format_time(time)),
+        _ => format!("{} left the race", opponent),
+    };
+    println!("{}", how);
+    match session.state.verdict() {
+        Some(race::Verdict::Won) => println!("{}", "You win the race!".green().bold()),
+        Some(race::Verdict::Lost) => println!("{}", format!("{} wins the race", opponent).red().bold()),
+        _ => println!("{}", "It's a draw!".yellow().bold()),
+    }
+    Ok(())
+}
+
+/// Port co-op games are hosted on without `--port`.
+const COOP_PORT: u16 = 7879;
+
+/// Clears a board together with other players over TCP, see `coop`. The
+/// host picks the board and the others join it at any time.
+fn coop(profile: &mut Profile, mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let usage = "Usage: coop host [--port <n>] [--name <name>] [--seed <n>] [difficulty] or coop join <address>[:<port>] [--name <name>] [--seat <n> --token <t>]";
+    let role = args.next().ok_or(usage)?;
+    let mut port = COOP_PORT;
+    let mut name = std::env::var("USER").ok().filter(|name| !name.trim().is_empty());
+    let mut seed = None;
+    let mut seat = None;
+    let mut token = None;
+    let mut positional = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = value(&arg, args.next())?,
+            "--name" => name = Some(value(&arg, args.next())?),
+            "--seed" => seed = Some(value(&arg, args.next())?),
+            "--seat" => seat = Some(value::<usize>(&arg, args.next())?.checked_sub(1).ok_or("--seat counts from 1")?),
+            "--token" => token = Some(value::<String>(&arg, args.next())?),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+            _ => positional.push(arg),
+        }
+    }
+    let name = name.map(|name| name.trim().to_string()).unwrap_or_else(|| if role == "host" { "Host".into() } else { "Guest".into() });
+    let session = match role.as_str() {
+        "host" if seat.is_none() && token.is_none() => {
+            let difficulty = Difficulty::from_args(&positional)?;
+            let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+            let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| format!("Can't listen on port {}: {}", port, e))?;
+            println!("Others join with: minesweep coop join <this machine>:{}", port);
+            coop::Session::host(listener, name, difficulty, seed)?
+        },
+        "join" => {
+            if positional.len() != 1 || seed.is_some() {
+                return Err("coop join takes the address of the host, the board is theirs".into());
+            }
+            let address = if positional[0].contains(':') { positional[0].clone() } else { format!("{}:{}", positional[0], port) };
+            let seat = match (seat, token) {
+                (Some(seat), Some(token)) => Some((seat, token)),
+                (None, None) => None,
+                _ => return Err("--seat and --token come together, as printed when joining".into()),
+            };
+            coop::Session::join(&address, name, seat)?
+        },
+        _ => return Err(usage.into()),
+    };
+    play_coop(profile, session)
+}
+
+fn coop_name(session: &coop::Session, player: usize) -> String {
+    let name = session.names.get(player).filter(|name| !name.is_empty()).cloned().unwrap_or_else(|| format!("Player {}", player + 1));
+    name.color(PLAYER_COLORS[player % PLAYER_COLORS.len()]).bold().to_string()
+}
+
+/// Tells what happened in a co-op game.
+fn coop_news(session: &coop::Session, news: &coop::News) -> String {
+    let name = |player| coop_name(session, player);
+    match *news {
+        coop::News::Joined(player) => format!("{} joined", name(player)),
+        coop::News::Left(player) => format!("{} left, and has {} seconds to come back", name(player), GRACE.as_secs()),
+        coop::News::Back(player) => format!("{} is back", name(player)),
+        coop::News::Forfeited(player) => format!("{} didn't come back", name(player)),
+        coop::News::Played(Applied { mv, outcome: sync::Outcome::Applied, .. }) => {
+            let what = match mv.action {
+                Action::Flag if session.coop.flag_owners.get(&(mv.x, mv.y)) == Some(&mv.player) => "flagged",
+                Action::Flag => "took the flag off",
+                Action::Chord => "chorded",
+                _ => "opened",
+            };
+            format!("{} {} {}", name(mv.player), what, session.field().coords(mv.x, mv.y))
+        },
+        coop::News::Played(Applied { mv, .. }) => format!("Not played: somebody changed {} first", session.field().coords(mv.x, mv.y)),
+        coop::News::NotPlayed(ref reason) => format!("Not played: {}", reason),
+        coop::News::Chat(player, ref text) => format!("{}: {}", name(player), text),
+        coop::News::Reconnecting => "Lost the host, trying to get back".to_string(),
+        coop::News::Reconnected => "Back with the host".to_string(),
+    }
+}
+
+/// Plays a co-op game until the board is cleared, a mine is opened or this
+/// player leaves.
+fn play_coop(profile: &mut Profile, mut session: coop::Session) -> Result<(), String> {
+    let mut view = View {
+        compact: needs_compact(session.field().size().0 as usize, terminal_width()),
+        ..View::new(Theme::for_output(ColorDepth::detect(), Palette::Classic))
+    };
+    let show = |session: &coop::Session, view: &mut View, news: Vec<coop::News>| {
+        view.flag_owners = session.coop.flag_owners.clone();
+        println!();
+        view.print(session.field());
+        println!("{}", coop_roster(session));
+        for news in news {
+            println!("{}", coop_news(session, &news));
+        }
+    };
+    println!("Playing {} together as {}, seed {}", session.difficulty, coop_name(&session, session.player), session.seed);
+    if session.player > 0 {
+        println!("Come back on this seat with --seat {} --token {}", session.player + 1, session.token);
+    }
+    let news = session.poll()?;
+    show(&session, &mut view, news);
+    let stdin = io::stdin();
+    let mut in_buffer = String::new();
+    let mut macros = Macros::new();
+    let mut analyst = Analyst::spawn();
+    let mut timer = Timer::new();
+    timer.start(Instant::now());
+    let save_as = |_: &Profile, _: &str, _: Duration| Err("Co-op games can't be saved".to_string());
+    'game: while !session.is_over() {
+        // follow the others while waiting for the next move
+        loop {
+            let news = session.poll()?;
+            if !news.is_empty() {
+                show(&session, &mut view, news);
+            }
+            if session.is_over() {
+                break 'game;
+            }
+            if !cfg!(unix) || input_pending() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        // the board is looked at while the session takes chat lines
+        let field = session.field().clone();
+        let (action, (col, row)) = match line_move(&stdin, &mut in_buffer, profile, &mut macros, &field, &mut view, &mut analyst, &mut timer, &save_as, false, Some(&mut session))? {
+            Typed::Move(selected) => selected,
+            Typed::Said => continue,
+            Typed::End => return Ok(()),
+        };
+        let news = session.submit(action, row, col);
+        if !session.is_over() {
+            show(&session, &mut view, news);
+        }
+    }
+    timer.stop(Instant::now());
+    view.exploded = session.field().opened_mine();
+    show(&session, &mut view, vec![]);
+    let time = format_time(timer.elapsed(Instant::now()));
+    match session.coop.hit_by {
+        Some(player) => println!("{}", format!("{} opened a mine after {}", coop_name(&session, player), time).red().bold()),
+        None => println!("{}", format!("Cleared together in {}!", time).green().bold()),
+    }
+    for player in 0..session.names.len() {
+        let (opened, flags) = coop_counts(&session, player);
+        println!("{}: {} cells, {} flags", coop_name(&session, player), opened, flags);
+    }
+    Ok(())
+}
+
+/// Cells opened and flags standing of `player` in a co-op game.
+fn coop_counts(session: &coop::Session, player: usize) -> (usize, usize) {
+    let flags = session.coop.flag_owners.values().filter(|&&owner| owner == player).count();
+    (session.coop.opened.get(&player).copied().unwrap_or_default(), flags)
+}
+
+/// The players of a co-op game in the colors of their flags with what they
+/// did so far, like `roster` for a relay.
+fn coop_roster(session: &coop::Session) -> String {
+    (0..session.names.len())
+        .map(|player| {
+            let (opened, flags) = coop_counts(session, player);
+            format!("{} {} cells, {} flags", coop_name(session, player), opened, flags)
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Serves games over HTTP for web and mobile clients, see `server::Server`
+/// for the endpoints.
+fn serve(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut port: u16 = 8080;
+    let mut address = "127.0.0.1".to_string();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = value(&arg, args.next())?,
+            "--bind" => address = value(&arg, args.next())?,
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    let listener = TcpListener::bind((address.as_str(), port)).map_err(|e| format!("Can't listen on {} port {}: {}", address, port, e))?;
+    println!("Serving games on {} port {}, start one with POST /games", address, port);
+    let server = Arc::new(Mutex::new(server::Server::new()));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let server = Arc::clone(&server);
+        // a slow client only holds up its own connection
+        thread::spawn(move || {
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+            let mut reader = match stream.try_clone() {
+                Ok(read) => io::BufReader::new(read),
+                Err(_) => return,
+            };
+            let request = match tournament::Request::read(&mut reader) {
+                Ok(request) => request,
+                Err(_) => return,
+            };
+            if request.path == server::SOCKET_PATH && websocket::is_upgrade(&request) {
+                return lobby_socket(stream, reader, &request, &server);
+            }
+            let response = server.lock().unwrap_or_else(|e| e.into_inner()).handle(&request);
+            let _ = response.write(&mut stream);
+        });
+    }
+    Ok(())
+}
+
+/// How long a socket of the lobby may go without a message from its
+/// client, pings included.
+const LOBBY_IDLE: Duration = Duration::from_secs(600);
+
+/// Plays the messages of a client of the lobby on a WebSocket, and pushes
+/// the events of the lobby to it from a thread of its own.
+fn lobby_socket(mut stream: TcpStream, reader: io::BufReader<TcpStream>, request: &tournament::Request, server: &Mutex<server::Server>) {
+    let lock = || server.lock().unwrap_or_else(|e| e.into_inner());
+    match websocket::handshake(request) {
+        Ok(head) => {
+            if stream.write_all(head.as_bytes()).is_err() {
+                return;
+            }
+        },
+        Err(response) => {
+            let _ = response.write(&mut stream);
+            return;
+        },
+    }
+    let _ = stream.set_read_timeout(Some(LOBBY_IDLE));
+    let out = Arc::new(Mutex::new(stream));
+    let (id, events) = lock().connect();
+    let writer = {
+        let out = Arc::clone(&out);
+        // ends once the server dropped the socket
+        thread::spawn(move || {
+            for event in events {
+                if websocket::write_text(&mut *out.lock().unwrap_or_else(|e| e.into_inner()), &event.to_string()).is_err() {
+                    break;
+                }
+            }
+        })
+    };
+    let mut messages = websocket::MessageReader::new(reader);
+    loop {
+        match messages.read() {
+            Ok(Message::Text(text)) => lock().message(id, &text),
+            Ok(Message::Ping(payload)) => {
+                if websocket::write_pong(&mut *out.lock().unwrap_or_else(|e| e.into_inner()), &payload).is_err() {
+                    break;
+                }
+            },
+            Ok(Message::Close) | Err(_) => break,
+        }
+    }
+    lock().disconnect(id);
+    let _ = writer.join();
+    let _ = websocket::write_close(&mut *out.lock().unwrap_or_else(|e| e.into_inner()));
+}
+
+/// Prints the boards and matches of every round, with the results entered
+/// so far.
+fn print_bracket(bracket: &Bracket) {
+    println!(
+        "Bracket of {} players, {}, {} {} per round",
+        bracket.players.len(), bracket.difficulty, bracket.games(), if bracket.games() == 1 { "board" } else { "boards" },
+    );
+    let board = match bracket.difficulty {
+        Difficulty::Custom(width, height, mines) => format!("custom {} {} {}", width, height, mines),
+        difficulty => difficulty.to_string(),
+    };
+    for round in 0..bracket.rounds() {
+        println!();
+        println!("{}", format!("Round {}", round + 1).bold());
+        for seed in bracket.seeds[round].iter() {
+            println!("  minesweep play --seed {} {}", seed, board);
+        }
+        for (i, (a, b)) in bracket.matches(round).into_iter().enumerate() {
+            let side = |entrant: Entrant| match entrant {
+                Entrant::Player(p) => {
+                    let outcomes = bracket.outcomes(round, p)
+                        .map(|outcomes| outcomes.iter().map(|o| o.to_string()).collect::<Vec<_>>().join(" "));
+                    match outcomes {
+                        Some(outcomes) => format!("{} ({})", bracket.players[p], outcomes),
+                        None => bracket.players[p].clone(),
+                    }
+                },
+                Entrant::Bye => "bye".into(),
+                Entrant::WinnerOf(m) => format!("winner of match {}", m + 1),
+            };
+            let mut line = format!("  Match {}: {} vs {}", i + 1, side(a), side(b));
+            if let (Some(winner), Entrant::Player(_), Entrant::Player(_)) = (bracket.winner(round, a, b), a, b) {
+                line.push_str(&format!(", {} goes through", bracket.players[winner]));
+            }
+            println!("{}", line);
+        }
+    }
+}
+
+fn print_standings(bracket: &Bracket) {
+    let champion = bracket.champion();
+    let card = SummaryCard {
+        title: "Standings".into(),
+        rows: bracket.standings().iter()
+            .enumerate()
+            .map(|(place, standing)| {
+                let status = if champion == Some(standing.player) {
+                    "champion".to_string()
+                } else if standing.out {
+                    format!("out in round {}", standing.reached)
+                } else {
+                    format!("in round {}", standing.reached)
+                };
+                (
+                    format!("{:>2}. {}", place + 1, bracket.players[standing.player]),
+                    format!("{}, {} games won, {}", status, standing.games_won, format_time(standing.time)),
+                )
+            })
+            .collect(),
+    };
+    for line in card.render() {
+        println!("{}", line);
+    }
+}
+
+/// Asks for a name and adds the win to the high score table if `time` is
+/// fast enough for it. Like `record_game`, a file that can't be written
+/// only costs the entry.
+fn high_score(profile: &Profile, difficulty: Difficulty, time: Duration) -> Result<(), String> {
+    let path = match scores::scores_path(profile) {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let place = match scores::rank(&scores::load(&path)?, difficulty, time) {
+        Some(place) => place,
+        None => return Ok(()),
+    };
+    let default = Some(profile.to_string()).filter(|_| !profile.is_shared())
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "anonymous".into());
+    println!("{}", format!("New high score: #{} on {}!", place + 1, difficulty).green().bold());
+    let mut name = String::new();
+    if stdin_is_tty() {
+        print!("Name [{}]: ", default);
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        io::stdin().read_line(&mut name).map_err(|e| e.to_string())?;
+    }
+    scores::append(&path, &Score {
+        difficulty,
+        time,
+        finished: stats::now(),
+        name: scores::clean_name(&name, &default),
+    })
+}
+
+/// The challenge code of a game as a QR code, for a friend to scan and play
+/// the board with `--challenge`.
+#[cfg(feature = "qr")]
+fn print_challenge_qr(challenge: Option<Challenge>) {
+    let code = match challenge.map(|challenge| minesweep_rs::qr::QrCode::encode(&challenge.to_string())) {
+        Some(Ok(code)) => code,
+        _ => return,
+    };
+    println!();
+    for line in code.render() {
+        println!("{}", line.black().on_white());
+    }
+}
+
+/// Plays games in the terminal, another one with the same options on a new
+/// board for as long as the player asks for it.
+fn play(profile: &mut Profile, args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut args: Vec<String> = args.collect();
+    let mut retry = None;
+    while let Some(ended) = play_game(profile, args.clone().into_iter(), retry.take())? {
+        match another_game(&ended)? {
+            Some(Next::New) => args = without_options(&args, &["--seed", "--load", "--challenge"]),
+            Some(Next::Retry) => {
+                args = without_options(&args, &["--load"]);
+                retry = Some(ended.board);
+            },
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+/// What to play once a game is over.
+enum Next {
+    /// A new board with the same options.
+    New,
+    /// The board just played, closed again.
+    Retry,
+}
+
+/// A board to play again with the cell to start at on no-guess boards.
+type Board = (Field, Option<(u16, u16)>);
+
+/// How a game ended that was won or lost.
+struct Ended {
+    /// The board closed again.
+    board: Board,
+    /// The time of a win.
+    time: Option<Duration>,
+    /// The code of a generated board, for others to play it.
+    challenge: Option<Challenge>,
+    /// The file the game was recorded in with `--record`.
+    replay: Option<String>,
+}
+
+/// `args` without the options in `dropped` and their values.
+fn without_options(args: &[String], dropped: &[&str]) -> Vec<String> {
+    let mut kept = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if dropped.contains(&arg.as_str()) {
+            args.next();
+        } else {
+            kept.push(arg.clone());
+        }
+    }
+    kept
+}
+
+/// Asks whether to play on once a game is won or lost, offering to watch
+/// its replay, export the board or share its challenge code meanwhile.
+fn another_game(ended: &Ended) -> Result<Option<Next>, String> {
+    let mut choices = vec!["n for a new game", "r to retry this board"];
+    if ended.replay.is_some() {
+        choices.push("v to watch the replay");
+    }
+    choices.push("e to export the board");
+    if ended.challenge.is_some() {
+        choices.push("s to share it");
+    }
+    choices.push("i for board info");
+    loop {
+        println!();
+        print!("Type {}, or just enter to quit: ", choices.join(", "));
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        let line = read_line()?;
+        return Ok(match line.trim() {
+            "n" => Some(Next::New),
+            "r" | "retry" => Some(Next::Retry),
+            "v" | "replay" => {
+                match ended.replay.as_ref() {
+                    Some(path) => replay(std::iter::once(path.clone()))?,
+                    None => println!("Record a game with --record <file> to watch it again"),
+                }
+                continue;
+            },
+            "e" | "export" => {
+                print!("File to export the board to: ");
+                io::stdout().flush().map_err(|e| e.to_string())?;
+                let path = read_line()?;
+                match path.trim() {
+                    "" => {},
+                    path => match std::fs::write(path, ended.board.0.export()) {
+                        Ok(()) => println!("Exported to {}, play it again with --board {}", path, path),
+                        Err(e) => println!("Can't write {}: {}", path, e),
+                    },
+                }
+                continue;
+            },
+            "s" | "share" => {
+                match ended.challenge {
+                    Some(challenge) => {
+                        println!("Anyone can play this board with: minesweep --challenge {}", challenge);
+                        #[cfg(feature = "qr")]
+                        print_challenge_qr(Some(challenge));
+                    },
+                    None => println!("Only generated boards have a challenge code"),
+                }
+                continue;
+            },
+            "i" | "info" => {
+                print_board_info(&ended.board.0, None);
+                continue;
+            },
+            _ => None,
+        });
+    }
+}
+
+fn read_line() -> Result<String, String> {
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).map_err(|e| e.to_string())?;
+    Ok(line)
+}
+
+/// Plays one game in the terminal, on `retry` if given. Returns how it
+/// ended if it was won or lost, and `None` if the player quit.
+fn play_game(profile: &mut Profile, mut args: impl Iterator<Item = String>, retry: Option<Board>) -> Result<Option<Ended>, String> {
+    let mut reveal_style = RevealStyle::Instant;
+    let mut show_fireworks = false;
+    let mut full_screen = false;
+    let mut board_file = None;
+    let mut puzzle = false;
+    let mut safe_start = true;
+    let mut stages = 1;
+    let mut relay_players = 0;
+    let mut relay_moves = None;
+    let mut relay_seconds = None;
+    let mut hotseat = false;
+    let mut handicaps = vec![];
+    let mut spectate_bots = None;
+    let mut options = BoardOptions::default();
+    let mut record_file = None;
+    let mut annotate_moves = false;
+    let mut monochrome = false;
+    let mut palette = None;
+    let mut hint_penalty = Duration::from_secs(20);
+    let mut load_name = None;
+    let mut informed = false;
+    let mut buckets = Buckets::default();
+    let mut undo_enabled = true;
+    let mut debug_fps = false;
+    let mut marks = false;
+    let mut simple = false;
+    let mut bot_protocol = false;
+    let mut challenge = None;
+    let mut orientation = Orientation::Wide;
+    let mut scroll = false;
+    let mut accessible = false;
+    let mut border = Border::None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--protocol" => match value::<String>(&arg, args.next())?.as_str() {
+                "json" => bot_protocol = true,
+                other => return Err(format!("Unknown protocol '{}', expected json", other)),
+            },
+            "--reveal" => reveal_style = value(&arg, args.next())?,
+            "--fireworks" => show_fireworks = true,
+            "--tui" => full_screen = true,
+            "--debug-fps" => debug_fps = true,
+            "--scroll" => scroll = true,
+            "--marks" => marks = true,
+            "--kids" => simple = true,
+            "--mono" => monochrome = true,
+            "--palette" => palette = Some(value::<Palette>(&arg, args.next())?),
+            "--accessible" => accessible = true,
+            "--border" => border = value(&arg, args.next())?,
+            "--informed" => informed = true,
+            "--orientation" => orientation = value(&arg, args.next())?,
+            "--buckets" => buckets = value(&arg, args.next())?,
+            "--no-undo" => undo_enabled = false,
+            "--load" => load_name = Some(value::<String>(&arg, args.next())?),
+            "--hint-penalty" => hint_penalty = Duration::from_secs(value(&arg, args.next())?),
+            "--board" => board_file = Some(value::<String>(&arg, args.next())?),
+            "--puzzle" => {
+                board_file = Some(value::<String>(&arg, args.next())?);
+                puzzle = true;
+            },
+            "--unsafe-start" => safe_start = false,
+            "--record" => record_file = Some(value::<String>(&arg, args.next())?),
+            "--annotate" => annotate_moves = true,
+            "--stages" => stages = value(&arg, args.next())?,
+            "--relay" => relay_players = value(&arg, args.next())?,
+            "--relay-moves" => relay_moves = Some(value(&arg, args.next())?),
+            "--relay-seconds" => relay_seconds = Some(Duration::from_secs(value(&arg, args.next())?)),
+            "--hotseat" => hotseat = true,
+            "--handicap" => handicaps.push(Handicap::for_player(&value::<String>(&arg, args.next())?)?),
+            "--challenge" => challenge = Some(value::<Challenge>(&arg, args.next())?),
+            "--spectate" => {
+                let names: String = value(&arg, args.next())?;
+                let mut bots = names.split(',')
+                    .map(|name| strategy(name).map(Bot::new))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if bots.len() != 2 {
+                    return Err("--spectate expects two strategies, e.g. first,random".into());
+                }
+                let second = bots.pop().unwrap();
+                spectate_bots = Some([bots.pop().unwrap(), second]);
+            },
+            _ => if !options.take(&arg, &mut args)? {
+                return Err(format!("Unknown option '{}'", arg));
+            },
+        }
+    }
+    if let Some(challenge) = challenge {
+        if !options.positional.is_empty() || options.seed.is_some() || options.mines.is_some() || options.density.is_some() || board_file.is_some() || load_name.is_some() {
+            return Err("A challenge brings its own board, leave out the difficulty, --seed, --mines, --density, --board and --load".into());
+        }
+        options.positional = match challenge.difficulty {
+            Difficulty::Custom(width, height, mines) => vec!["custom".into(), width.to_string(), height.to_string(), mines.to_string()],
+            preset => vec![preset.to_string()],
+        };
+        options.seed = Some(challenge.seed);
+        options.no_guess = challenge.no_guess;
+    }
+    if puzzle {
+        if load_name.is_some() || challenge.is_some() || relay_players > 0 || hotseat || simple {
+            return Err("A puzzle is played alone from its file, leave out --load, --challenge, --relay, --hotseat and --kids".into());
+        }
+        // solved or not is up to the first mine opened
+        undo_enabled = false;
+    }
+    if monochrome && palette.is_some() {
+        return Err("--mono plays without colors, leave out --palette".into());
+    }
+    if accessible && full_screen {
+        return Err("--accessible plays without --tui".into());
+    }
+    if simple {
+        if full_screen {
+            return Err("--kids plays without --tui".into());
+        }
+        // a tiny board with few mines unless one is asked for
+        if options.positional.is_empty() && options.mines.is_none() && options.density.is_none() {
+            options.positional = ["custom", "6", "6", "3"].iter().map(|arg| arg.to_string()).collect();
+        }
+        undo_enabled = false;
+    }
+
+    // a saved game brings its own board and progress
+    let saved = match load_name.as_ref() {
+        Some(name) => Some(save::load(profile, name)?),
+        None => None,
+    };
+    let seed = match saved.as_ref() {
+        Some(game) => game.seed,
+        None => options.seed.unwrap_or_else(|| rand::thread_rng().gen()),
+    };
+    let mut rng = StdRng::seed_from_u64(seed);
+    // hand-authored boards are played as they are and no-guess boards come
+    // with a safe start that moving mines would spoil
+    safe_start &= board_file.is_none() && !options.no_guess;
+    let mut difficulty = options.difficulty()?;
+    let mut resumed = None;
+    // the mines of a retried board are where the last game left them
+    safe_start &= retry.is_none();
+    let retried = retry.is_some();
+    let no_guess = options.no_guess;
+    let (mut field, start) = match (saved, board_file.as_ref()) {
+        _ if retried => retry.unwrap(),
+        (Some(game), _) => {
+            difficulty = game.difficulty;
+            safe_start = game.safe_start;
+            options.no_guess = game.no_guess;
+            let field = game.field.clone();
+            resumed = Some(game);
+            (field, None)
+        },
+        (None, Some(path)) => (options.read_board(path)?, None),
+        (None, None) => options.generate(difficulty, &mut rng).map_err(|e| e.to_string())?,
+    };
+    // board files are stored by their size, like custom boards
+    let played = match board_file {
+        Some(_) if load_name.is_none() => {
+            let (cols, rows) = field.size();
+            let mines = u16::try_from(field.mine_count()).map_err(|_| format!("A board file can hold at most {} mines", u16::MAX))?;
+            Difficulty::Custom(cols, rows, mines)
+        },
+        _ => difficulty,
+    };
+    // a board taken before the search for one without guessing ended can't be made again
+    let challenge = match (board_file.as_ref(), stages) {
+        (None, 1) if options.no_guess == no_guess => options.challenge(played, seed),
+        _ => None,
+    };
+    let (journal_seed, mut journal_args) = match (board_file.as_ref(), load_name.as_ref()) {
+        (Some(path), _) => (None, options.shape_args(&field).into_iter().chain([if puzzle { "--puzzle" } else { "--board" }.to_string(), path.clone()]).collect()),
+        // a saved game doesn't know all the options it was started with
+        (None, Some(_)) => (Some(seed), vec![]),
+        (None, None) if options.no_guess == no_guess => (Some(seed), options.remake_args(&field, played, seed)),
+        (None, None) => (Some(seed), vec![]),
+    };
+    if stages > 1 && !journal_args.is_empty() {
+        journal_args.extend(["--stages".to_string(), stages.to_string()]);
+    }
+    // stages of a saved game are part of the save
+    if load_name.is_none() {
+        field.split_stages(stages);
+    }
+    let mut theme = if monochrome {
+        colored::control::set_override(false);
+        Theme::monochrome()
+    } else {
+        Theme::for_output(ColorDepth::detect(), palette.unwrap_or(Palette::Classic))
+    };
+    if simple {
+        theme.glyphs = Glyphs::EMOJI;
+    }
+    let (cols, rows) = field.size();
+    // rows and columns are read out as the board lies
+    let transposed = !accessible && orientation.transposed(cols as usize, rows as usize, terminal_width(), terminal_height());
+    let mut view = View {
+        informed,
+        buckets,
+        transposed,
+        reprint: !full_screen && !scroll && !accessible && stdout_is_tty(),
+        spoken: accessible,
+        border,
+        ..View::new(theme)
+    };
+    let screen_cols = if view.is_transposed(&field) { rows } else { cols };
+    view.compact = needs_compact(screen_cols as usize, terminal_width().saturating_sub(view.frame_width(&field)));
+    if let Some(bots) = spectate_bots {
+        spectate(field, &view, bots, safe_start, &mut rng);
+        return Ok(None);
+    }
+    if bot_protocol {
+        if full_screen || relay_players > 1 || hotseat || record_file.is_some() || load_name.is_some() {
+            return Err("--protocol json plays without --tui, --relay, --record and --load".into());
+        }
+        let mut game = game::Game::with_field(field, played, seed, rng, safe_start);
+        protocol::run(&mut game, start, io::stdin().lock(), io::stdout().lock()).map_err(|e| e.to_string())?;
+        return Ok(None);
+    }
+    if hotseat {
+        if relay_players > 2 || relay_moves.is_some() {
+            return Err("--hotseat is for two players with a move each".into());
+        }
+        relay_players = 2;
+    } else if !handicaps.is_empty() {
+        return Err("--handicap evens out --hotseat games".into());
+    }
+    let rules = Rules { undo: undo_enabled, marks, hint_penalty, puzzle, stages, annotate: annotate_moves, memory_cap: options.memory_cap() };
+    let mut session = match resumed {
+        Some(game) => Session::resume(game, rng, rules),
+        None => Session::new(field, played, seed, rng, safe_start, rules),
+    };
+    session.no_guess = options.no_guess;
+    if relay_players > 1 {
+        if relay_moves.is_none() && relay_seconds.is_none() {
+            relay_moves = Some(1);
+        }
+        session.relay = Some(Relay::new(relay_players, relay_moves, relay_seconds, Instant::now()));
+    }
+    // a retried board keeps the cells opened the first time
+    if !retried {
+        for (player, handicap) in &handicaps {
+            session.handicap(*player, handicap)?;
+        }
+    }
+    // the first game was recorded already, and its first move may have moved a mine
+    let replay_file = if retried { None } else { record_file.clone() };
+    // a replay can only rebuild generated boards
+    session.recorder = match record_file {
+        Some(path) if board_file.is_some() => return Err(format!("Can't record {}: games on board files aren't replayable", path)),
+        Some(path) if options.mask.is_some() => return Err(format!("Can't record {}: games on masked boards aren't replayable", path)),
+        Some(path) if load_name.is_some() => return Err(format!("Can't record {}: loaded games aren't replayable", path)),
+        Some(path) if !handicaps.is_empty() => return Err(format!("Can't record {}: games with handicaps aren't replayable", path)),
+        Some(_) if retried => None,
+        Some(path) => {
+            let file: Box<dyn Write> = Box::new(std::fs::File::create(&path).map_err(|e| format!("Can't write {}: {}", path, e))?);
+            Some(Recorder::new(file, seed, difficulty, safe_start).map_err(|e| format!("Can't write {}: {}", path, e))?)
+        },
+        None => None,
+    };
+    let mut in_buffer = String::new();
+    let stdin = io::stdin();
+    let mut macros = Macros::new();
+    let mut tui = if full_screen {
+        let terminal = RawTerminal::enter().map_err(|e| format!("Can't start full-screen mode: {}", e))?;
+        Some(if debug_fps { terminal.with_fps_overlay() } else { terminal })
+    } else {
+        None
+    };
+    let mut cursor = Cursor::default();
+    let mut analyst = Analyst::spawn();
+    let hint_penalty = session.rules().hint_penalty;
+    // messages for the player, printed below the board
+    let mut status = if simple {
+        vec![SIMPLE_HELP.to_string()]
+    } else {
+        // a board from a file wasn't made from the seed
+        let mut status = vec![match journal_seed {
+            Some(seed) => format!("Seed: {}, 3BV: {}", seed, session.field.three_bv()),
+            None => format!("3BV: {}", session.field.three_bv()),
+        }];
+        if puzzle {
+            status[0] = format!("Puzzle, 3BV: {}. Clear it without opening a mine, there are no hints or undo", session.field.three_bv());
+        }
+        status.extend(pending_goals(profile));
+        if accessible {
+            status.push(ACCESSIBLE_HELP.to_string());
+        }
+        status
+    };
+    if let Some((x, y)) = start {
+        status.push(format!("Start at {}", view.coords(&session.field, x, y)));
+        cursor = Cursor { x, y, ..cursor };
+    }
+    if let Some(relay) = session.relay.as_ref() {
+        status.push(format!("Player {}'s turn", relay.current + 1));
+        view.turn = Some(relay.current);
+    }
+    if tui.is_none() {
+        view.print(&session.field);
+        for line in status.drain(..) {
+            println!("{}", line);
+        }
+    }
+    loop {
+        let (action, (col, row)) = match tui.as_mut() {
+            Some(terminal) => match tui_move(terminal, profile, &session.field, &mut view, &mut cursor, &status, &mut analyst, &mut session.timer)? {
+                Some(selected) => selected,
+                None => return Ok(None),
+            },
+            None => {
+                let safe_start = session.safe_start();
+                let Session { field, seed, difficulty, no_guess, timer, turns, clicks, used_flags, hints, relay, .. } = &mut session;
+                let save_as = |profile: &Profile, name: &str, elapsed: Duration| save::save(profile, name, &SavedGame {
+                    field: field.clone(),
+                    seed: *seed,
+                    difficulty: *difficulty,
+                    elapsed,
+                    safe_start,
+                    no_guess: *no_guess,
+                    flagged: *used_flags,
+                    hints: *hints,
+                    turns: *turns,
+                    clicks: *clicks,
+                });
+                match line_move(&stdin, &mut in_buffer, profile, &mut macros, field, &mut view, &mut analyst, timer, &save_as, simple, relay.as_mut().map(|relay| relay as &mut dyn Chat))? {
+                    Typed::Move(selected) => selected,
+                    // only live games come back without a move
+                    Typed::Said => continue,
+                    Typed::End => return Ok(None),
+                }
+            },
+        };
+        status.clear();
+        if action == Action::Hint {
+            match session.hint() {
+                Ok(deduction) => {
+                    let ((x, y), what) = match deduction {
+                        Deduction::Safe(x, y) => ((x, y), "safe"),
+                        Deduction::Mine(x, y) => ((x, y), "a mine"),
+                    };
+                    cursor = Cursor { x, y, ..cursor };
+                    view.hint = Some(deduction);
+                    let cost = if hint_penalty > Duration::default() {
+                        format!(" (+{}s)", hint_penalty.as_secs())
+                    } else {
+                        String::new()
+                    };
+                    status.push(format!("Hint: {} is {}{}", view.coords(&session.field, x, y), what, cost));
+                },
+                Err(e) => status.push(e),
+            }
+            if tui.is_none() {
+                if view.hint.is_some() {
+                    view.print_again(&session.field);
+                }
+                for line in status.drain(..) {
+                    println!("{}", line);
+                }
+            }
+            continue;
+        }
+        if action == Action::Undo || action == Action::Redo {
+            match session.undo(action == Action::Redo) {
+                Ok(()) => {
+                    view.hint = None;
+                    status.push(if action == Action::Undo { "Move undone" } else { "Move redone" }.to_string());
+                },
+                Err(e) => status.push(e),
+            }
+            if tui.is_none() {
+                view.print_again(&session.field);
+                for line in status.drain(..) {
+                    println!("{}", line);
+                }
+            }
+            continue;
+        }
+        // the hint was shown, the next move is up to the player
+        view.hint = None;
+        let before = if view.spoken { Some(session.field.clone()) } else { None };
+        let played = match session.play(action, row, col)? {
+            Turn::Over => break,
+            Turn::TimeUp => {
+                let current = session.relay.as_ref().map_or(0, |relay| relay.current);
+                status.push(format!("Time's up! Player {}'s turn", current + 1));
+                view.turn = Some(current);
+                if tui.is_none() {
+                    for line in status.drain(..) {
+                        println!("{}", line);
+                    }
+                }
+                continue;
+            },
+            Turn::Played(played) => played,
+        };
+        if let Some(before) = before {
+            println!("{}", speech::outcome(&before, &session.field, action, row, col));
+        }
+        match played.result {
+            Err(MinesError::MineOpened) if session.mine_hit() => {
+                status.push("You hit a mine! Undo to take it back, any other move ends the game".into());
+            },
+            Err(MinesError::MineOpened) => break,
+            Err(MinesError::ZoneLocked(_, _)) => status.push("Clear the previous stage first".into()),
+            Err(ref e @ MinesError::OutOfBounds(_, _)) => status.push(e.to_string()),
+            Ok(()) if simple && action == Action::Flag => status.push("Good thinking!".into()),
+            Ok(()) if simple && played.opened > 0 => {
+                status.push(CHEERS[rand::thread_rng().gen_range(0, CHEERS.len())].into());
+            },
+            _ => {},
+        }
+        view.flag_owners.clone_from(&session.flag_owners);
+        if let Some(stage) = played.stage {
+            status.push(format!("Stage {} cleared at {}", stage, format_time(session.timer.elapsed(Instant::now()))));
+        }
+        if session.is_won() {
+            let field = &session.field;
+            let time = session.timer.elapsed(Instant::now());
+            view.cursor = None;
+            if tui.take().is_some() {
+                view.print(field);
+            } else {
+                view.print_again(field);
+                println!();
+            }
+            if (show_fireworks || simple) && stdout_is_tty() {
+                fireworks(&mut rand::thread_rng(), 40, 8);
+            }
+            if simple {
+                println!("{}", "You did it! Every square without a bomb is open. Amazing!".green().bold());
+                record_game(profile, session.record(true), journal_seed, &journal_args);
+                return Ok(Some(Ended { board: (field.closed(), start), time: Some(time), challenge, replay: replay_file }));
+            }
+            let (width, height) = field.size();
+            let mut card = SummaryCard {
+                title: if puzzle { "Puzzle solved!" } else { "You won!" }.into(),
+                rows: vec![
+                    ("Difficulty".into(), format!("{}", session.difficulty)),
+                    ("Board".into(), format!("{} x {}", width, height)),
+                    ("Mines".into(), format!("{}", field.mine_count())),
+                    ("3BV".into(), format!("{}", field.three_bv())),
+                    ("Turns".into(), format!("{}", session.turns)),
+                    ("Time".into(), format_time(time)),
+                    ("3BV/s".into(), three_bv_per_second(field.three_bv(), time)),
+                    ("Seed".into(), format!("{}", seed)),
+                ],
+            };
+            if let Some(challenge) = challenge {
+                card.rows.push(("Challenge".into(), challenge.to_string()));
+            }
+            if puzzle {
+                card.rows.retain(|(name, _)| name != "Difficulty" && name != "Seed");
+            } else if journal_seed.is_none() {
+                card.rows.retain(|(name, _)| name != "Seed");
+            }
+            card.rows.push(("Clicks".into(), describe_clicks(session.clicks)));
+            if let Some(efficiency) = session.clicks.efficiency(field.three_bv()) {
+                card.rows.push(("Efficiency".into(), format!("{:.0}%", 100.0 * efficiency)));
+            }
+            for (stage, split) in session.timer.splits().iter().enumerate() {
+                card.rows.push((format!("Stage {}", stage + 1), format_time(*split)));
+            }
+            if session.hints > 0 {
+                card.rows.push(("Hints".into(), format!("{} (+{}s)", session.hints, (hint_penalty * session.hints).as_secs())));
+            }
+            if let Some(relay) = session.relay.as_ref() {
+                for (player, stats) in relay.stats.iter().enumerate() {
+                    card.rows.push((
+                        format!("Player {}", player + 1),
+                        format!("{} moves, {} cells, {} flags", stats.moves, stats.cells_opened, stats.flags),
+                    ));
+                }
+                if hotseat {
+                    card.rows.extend(hotseat_rows(relay, field, &session.flag_owners, None));
+                }
+            }
+            for line in card.render() {
+                println!("{}", line.green().bold());
+            }
+            #[cfg(feature = "qr")]
+            print_challenge_qr(challenge);
+            for line in record_game(profile, session.record(true), journal_seed, &journal_args) {
+                println!("{}", line.green());
+            }
+            if let Err(e) = high_score(profile, session.difficulty, time) {
+                println!("{}", e);
+            }
+            return Ok(Some(Ended { board: (field.closed(), start), time: Some(time), challenge, replay: replay_file }));
+        }
+        if played.handed_over {
+            let relay = session.relay.as_mut().unwrap();
+            status.push(roster(relay));
+            status.extend(relay.messages.drain(..).map(|(player, text)| chat_line(player, &text)));
+            status.push(format!("Player {}'s turn", relay.current + 1));
+            view.turn = Some(relay.current);
+        }
+        if tui.is_none() {
+            view.print_again(&session.field);
+            println!();
+            if !simple {
+                println!("Time: {}", format_time(session.timer.elapsed(Instant::now())));
+            }
+            for line in status.drain(..) {
+                println!("{}", line);
+            }
+        }
+    }
+    // a mine was opened
+    session.timer.stop(Instant::now());
+    view.turn = None;
+    // leave full-screen mode so the end of the game stays on screen
+    drop(tui.take());
+    view.cursor = None;
+    view.exploded = session.field.opened_mine();
+    // the hit mines are the only opened ones until the rest are revealed
+    let cleared = session.field.opened_count() - session.field.opened_mines().len();
+    let wrong_flags = session.field.wrong_flags();
+    let points = match session.relay.as_ref() {
+        Some(relay) if hotseat => hotseat_rows(relay, &session.field, &session.flag_owners, Some(relay.current)),
+        _ => vec![],
+    };
+    let record = session.record(false);
+    let field = &mut session.field;
+    reveal_mines(field, &view, reveal_style);
+    if simple {
+        println!("{}", "Boom! That one had a bomb. Good try, you'll get it next time!".yellow().bold());
+        record_game(profile, record, journal_seed, &journal_args);
+        return Ok(Some(Ended { board: (field.closed(), start), time: None, challenge, replay: replay_file }));
+    }
+    let (width, height) = field.size();
+    let mut card = SummaryCard {
+        title: if puzzle { "Puzzle failed" } else { "Game over" }.into(),
+        rows: vec![
+            ("Difficulty".into(), format!("{}", session.difficulty)),
+            ("Board".into(), format!("{} x {}", width, height)),
+            ("Cleared".into(), format!("{} of {}", cleared, field.cell_count() - field.mine_count())),
+            ("3BV".into(), format!("{}", field.three_bv())),
+            ("Turns".into(), format!("{}", session.turns)),
+            ("Time".into(), format_time(record.time)),
+            ("Seed".into(), format!("{}", seed)),
+        ],
+    };
+    if let Some(challenge) = challenge {
+        card.rows.push(("Challenge".into(), challenge.to_string()));
+    }
+    if puzzle {
+        card.rows.retain(|(name, _)| name != "Difficulty" && name != "Seed");
+    } else if journal_seed.is_none() {
+        card.rows.retain(|(name, _)| name != "Seed");
+    }
+    if let Some((x, y)) = view.exploded {
+        card.rows.insert(2, ("Hit mine at".into(), view.coords(field, x, y)));
+    }
+    card.rows.push(("Clicks".into(), describe_clicks(session.clicks)));
+    if wrong_flags > 0 {
+        card.rows.push(("Wrong flags".into(), format!("{}", wrong_flags)));
+    }
+    if session.hints > 0 {
+        card.rows.push(("Hints".into(), format!("{} (+{}s)", session.hints, (hint_penalty * session.hints).as_secs())));
+    }
+    if let Some(relay) = session.relay.as_ref() {
+        card.rows.push(("Hit by".into(), format!("Player {}", relay.current + 1)));
+    }
+    card.rows.extend(points);
+    for line in card.render() {
+        println!("{}", line.red().bold());
+    }
+    #[cfg(feature = "qr")]
+    print_challenge_qr(challenge);
+    for line in record_game(profile, record, journal_seed, &journal_args) {
+        println!("{}", line);
+    }
+    Ok(Some(Ended { board: (session.field.closed(), start), time: None, challenge, replay: replay_file }))
+}
+
+/// How fast a board was cleared, for the end screen. A game won on the
+/// first click may not have taken a tenth of a second, which has no speed.
+fn three_bv_per_second(three_bv: u32, time: Duration) -> String {
+    if time < Duration::from_millis(100) {
+        return "-".into();
+    }
+    format!("{:.2}", three_bv as f64 / time.as_secs_f64())
+}
+
+/// The clicks of a game for the end screen, with their kinds.
+fn describe_clicks(clicks: Clicks) -> String {
+    format!("{} ({} opens, {} flags, {} chords)", clicks.total(), clicks.opens, clicks.flags, clicks.chords)
+}
+
+/// How to play in the simple mode, which knows nothing else.
+const SIMPLE_HELP: &str = "Type two numbers like 3 2 to open a square, or f 3 2 to put a flag on it";
+
+/// The moves around the anchor once the numpad is anchored in line mode.
+const NUMPAD_HELP: &str = "Numpad: 1-9 open the cells around the anchor as the keys lie, 5 chords it, f1-f9 flag and c1-c9 chord, n lets go";
+
+/// How to find your way around the board with `--accessible`.
+const ACCESSIBLE_HELP: &str = "Type a column and a row like 3 2 to open a cell, f 3 2 to flag it or c 3 2 to chord it. read 3 2 tells what is in a cell, read row 2 and read column 3 a line, read alone how the game stands";
+
+/// Things to say after a good move in the simple mode.
+const CHEERS: [&str; 5] = ["Nice!", "Great job!", "Well done!", "You're doing great!", "Keep going!"];
+
+/// Whether `input` opens or flags a cell, the only moves of the simple mode.
+fn is_simple_move(input: &[String]) -> bool {
+    let coords = match input.first().map(|s| s.as_str()) {
+        Some("f") => &input[1..],
+        _ => input,
+    };
+    coords.len() == 2 && coords.iter().all(|c| c.parse::<u16>().is_ok())
+}
+
+/// The players of a relay in their colors with what they did so far.
+fn roster(relay: &Relay) -> String {
+    relay.stats.iter()
+        .enumerate()
+        .map(|(player, stats)| format!(
+            "{} {} cells, {} flags",
+            player_name(player), stats.cells_opened, stats.flags,
+        ))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// The points of a hotseat game and who won, from the flags still on
+/// `field` before its mines are revealed.
+fn hotseat_rows(relay: &Relay, field: &Field, flag_owners: &HashMap<(u16, u16), usize>, hit_by: Option<usize>) -> Vec<(String, String)> {
+    let flags: Vec<(usize, bool)> = flag_owners.iter()
+        .filter_map(|(&(x, y), &owner)| field.cell(x, y).ok().filter(|cell| cell.is_flagged()).map(|cell| (owner, cell.is_mine())))
+        .collect();
+    let scores = relay.scores(&flags, hit_by);
+    let mut rows: Vec<(String, String)> = scores.iter()
+        .enumerate()
+        .map(|(player, score)| (format!("Player {} points", player + 1), score.to_string()))
+        .collect();
+    let best = scores.iter().max().copied().unwrap_or_default();
+    let leaders: Vec<String> = (0..scores.len()).filter(|&player| scores[player] == best).map(|player| format!("Player {}", player + 1)).collect();
+    rows.push(if leaders.len() == 1 {
+        ("Winner".into(), leaders[0].clone())
+    } else {
+        ("Tie".into(), leaders.join(" and "))
+    });
+    rows
+}
+
+fn player_name(player: usize) -> String {
+    format!("Player {}", player + 1).color(PLAYER_COLORS[player % PLAYER_COLORS.len()]).bold().to_string()
+}
+
+fn chat_line(player: usize, text: &str) -> String {
+    format!("{}: {}", player_name(player), text)
+}
+
+/// Where the `say` command of a game for several players goes.
+trait Chat {
+    /// Passes `text` on to the others, returning the line to show for it.
+    fn say(&mut self, text: &str) -> String;
+
+    /// Whether the others play and talk on while this player types.
+    fn is_live(&self) -> bool {
+        false
+    }
+}
+
+impl Chat for Relay {
+    fn say(&mut self, text: &str) -> String {
+        Relay::say(self, text);
+        chat_line(self.current, text)
+    }
+}
+
+impl Chat for coop::Session {
+    fn say(&mut self, text: &str) -> String {
+        coop::Session::say(self, text);
+        format!("{}: {}", coop_name(self, self.player), text)
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+}
+
+/// An action with the selected column and row.
+type Move = (Action, (u16, u16));
+
+/// What `line_move` read.
+enum Typed {
+    Move(Move),
+    /// Only a chat line in a game where the others go on meanwhile, to be
+    /// followed before reading on.
+    Said,
+    /// The end of input.
+    End,
+}
+
+/// Reads lines until one holds a move, handling the view, export, save,
+/// chat and macro commands on the way. Lines of a macro being played come before
+/// stdin. Returns `None` at the end of input.
+#[allow(clippy::too_many_arguments)]
+fn line_move(stdin: &io::Stdin, in_buffer: &mut String, profile: &mut Profile, macros: &mut Macros, field: &Field, view: &mut View, analyst: &mut Analyst, timer: &mut Timer, save_as: &dyn Fn(&Profile, &str, Duration) -> Result<PathBuf, String>, simple: bool, mut chat: Option<&mut dyn Chat>) -> Result<Typed, String> {
+    loop {
+        in_buffer.clear();
+        if let Some(line) = macros.next_line() {
+            println!("> {}", line);
+            in_buffer.push_str(&line);
+        } else if stdin.read_line(in_buffer).map_err(|e| e.to_string())? == 0 {
+            // end of input quits instead of waiting forever
+            return Ok(Typed::End);
+        }
+        let mut action = Action::Open;
+        let mut input: Vec<String> = in_buffer.trim().split(' ').filter(|s| !s.is_empty()).map(|s| s.into()).collect();
+        // only opening and flagging in the simple mode, and reading the board out
+        if simple && !is_simple_move(&input) && !(view.spoken && input.first().is_some_and(|first| first == "read")) {
+            println!("{}", SIMPLE_HELP);
+            continue;
+        }
+        if input.first().map(|s| s.as_str()) == Some("macro") {
+            match (input.get(1).map(|s| s.as_str()), input.get(2)) {
+                (Some("record"), Some(name)) if input.len() == 3 => match macros.record(name) {
+                    Ok(()) => println!("Recording macro {}, stop with: macro stop", name),
+                    Err(e) => println!("{}", e),
+                },
+                (Some("stop"), None) => match macros.stop(profile) {
+                    Ok((name, lines)) => println!("Macro {} saved with {} lines, play it with: macro play {}", name, lines, name),
+                    Err(e) => println!("{}", e),
+                },
+                (Some("play"), Some(name)) if input.len() == 3 => {
+                    if let Err(e) = macros.play(profile, name) {
+                        println!("{}", e);
+                    }
+                },
+                _ => println!("Usage: macro record <name>, macro stop or macro play <name>"),
+            }
+            continue;
+        }
+        macros.push(in_buffer);
+        if let Some(first) = input.first() {
+            if first.trim().eq("f") {
+                action = Action::Flag;
+                input.remove(0);
+            } else if first.trim().eq("c") {
+                action = Action::Chord;
+                input.remove(0);
+            } else if first.trim().eq("h") || first.trim().eq("hint") {
+                return Ok(Typed::Move((Action::Hint, (0, 0))));
+            } else if first.trim().eq("u") || first.trim().eq("undo") {
+                return Ok(Typed::Move((Action::Undo, (0, 0))));
+            } else if first.trim().eq("redo") {
+                return Ok(Typed::Move((Action::Redo, (0, 0))));
+            } else if first.trim().eq("x") {
+                match input.get(1) {
+                    Some(path) if input.len() == 2 => match std::fs::write(path, field.position()) {
+                        Ok(()) => println!("Position written to {}", path),
+                        Err(e) => println!("Can't write {}: {}", path, e),
+                    },
+                    _ => println!("Usage: x <file>"),
+                }
+                continue;
+            } else if first.trim().eq("pause") {
+                if !timer.is_running() {
+                    println!("The clock isn't running");
+                    continue;
+                }
+                timer.pause(Instant::now());
+                println!("Paused at {}, press enter to go on", format_time(timer.elapsed(Instant::now())));
+                in_buffer.clear();
+                let read = stdin.read_line(in_buffer);
+                timer.resume(Instant::now());
+                if matches!(read, Ok(0) | Err(_)) {
+                    return Ok(Typed::End);
+                }
+                view.print_again(field);
+                continue;
+            } else if first.trim().eq("save") {
+                match input.get(1) {
+                    Some(name) if input.len() == 2 => match save_as(profile, name, timer.elapsed(Instant::now())) {
+                        Ok(path) => println!("Saved to {}, resume with: minesweep load {}", path.display(), name),
+                        Err(e) => println!("{}", e),
+                    },
+                    _ => println!("Usage: save <name>"),
+                }
+                continue;
+            } else if first.trim().eq("say") {
+                let text = in_buffer.trim()["say".len()..].trim();
+                match chat.as_mut() {
+                    Some(chat) if !text.is_empty() => {
+                        println!("{}", chat.say(text));
+                        if chat.is_live() && !macros.is_playing() && !input_pending() {
+                            return Ok(Typed::Said);
+                        }
+                    },
+                    Some(_) => println!("Usage: say <message>"),
+                    None => println!("Chat is for relay and co-op games, there's nobody to talk to"),
+                }
+                continue;
+            } else if first.trim().eq("profile") {
+                match input.get(1) {
+                    Some(name) if input.len() == 2 => match Profile::named(name) {
+                        Ok(named) => {
+                            *profile = named;
+                            println!("Playing as {}, this game counts for that profile", profile);
+                        },
+                        Err(e) => println!("{}", e),
+                    },
+                    None => println!("Playing as {}", profile),
+                    _ => println!("Usage: profile [name]"),
+                }
+                continue;
+            } else if first.trim().eq("prob") {
+                if field.mines_per_cell() > 1 {
+                    println!("No mine chances with several mines per cell");
+                    continue;
+                }
+                analyst.analyse(field);
+                let probabilities = match wait_for_analysis(analyst) {
+                    Some(analysis) => analysis.probabilities,
+                    None => {
+                        println!("Stopped working out the odds");
+                        continue;
+                    },
+                };
+                println!();
+                for line in view.render_probabilities(field, &probabilities, terminal_width()) {
+                    println!("{}", line);
+                }
+                println!("{}", view.probability_legend().dimmed());
+                continue;
+            } else if first.trim().eq("n") {
+                // the anchor is drawn like the cursor of full-screen mode
+                if input.len() == 1 {
+                    view.cursor = None;
+                    println!("Numpad let go");
+                    continue;
+                }
+                match typed_cell(field, view, &input[1..]) {
+                    Ok((y, x)) if field.cell(x, y).is_ok_and(|cell| cell.is_opened()) => {
+                        view.cursor = Some((x, y));
+                        view.print_again(field);
+                        println!("{}", NUMPAD_HELP);
+                    },
+                    Ok(_) => println!("The numpad works around an opened cell"),
+                    Err(e) => println!("{}", e),
+                }
+                continue;
+            } else if let (Some((action, digit)), 1) = (numpad::parse(first.trim()), input.len()) {
+                match view.cursor {
+                    Some(anchor) => match numpad_move(field, anchor, action, digit, view.is_transposed(field)) {
+                        Ok((action, (x, y))) => return Ok(Typed::Move((action, (y, x)))),
+                        Err(e) => println!("{}", e),
+                    },
+                    None => println!("Anchor the numpad on an opened cell with n <col> <row> first"),
+                }
+                continue;
+            } else if first.trim().eq("read") {
+                if !view.spoken {
+                    println!("read tells the board in sentences with --accessible");
+                    continue;
+                }
+                match read_aloud(field, view, &input[1..]) {
+                    Ok(sentence) => println!("{}", sentence),
+                    Err(e) => println!("{}", e),
+                }
+                continue;
+            } else if first.trim().eq("v") {
+                match input.get(1).map(|s| s.trim().parse::<u16>()) {
+                    Some(Ok(col)) if input.len() == 2 => {
+                        view.first_col = if col > 0 { col - 1 } else { col };
+                        view.print_again(field);
+                    },
+                    _ => println!("Usage: v <col>"),
+                }
+                continue;
+            }
+        }
+        match typed_cell(field, view, &input) {
+            Ok(selection) => return Ok(Typed::Move((action, selection))),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// What `read` followed by `words` tells: a cell from its coordinates, a
+/// row or column from its number, followed by the layer on a
+/// three-dimensional board, and how the game stands without any words.
+fn read_aloud(field: &Field, view: &View, words: &[String]) -> Result<String, String> {
+    let (cols, _) = field.size();
+    let height = field.layer_height();
+    // the number of a row or column and the top row of its layer
+    let line = |max: u16| -> Result<(u16, u16), String> {
+        let numbers: Vec<u16> = words[1..].iter().map(|word| word.parse()).collect::<Result<_, _>>()
+            .map_err(|_| format!("Usage: read {} <n>{}", words[0], if field.layers() > 1 { " <layer>" } else { "" }))?;
+        let (number, layer) = match numbers[..] {
+            [number] => (number, 1),
+            [number, layer] if field.layers() > 1 => (number, layer),
+            _ => return Err(format!("Usage: read {} <n>{}", words[0], if field.layers() > 1 { " <layer>" } else { "" })),
+        };
+        if number == 0 || number > max {
+            return Err(format!("There's no {} {}, they go from 1 to {}", words[0], number, max));
+        }
+        if layer == 0 || layer > field.layers() {
+            return Err(format!("There's no layer {}, they go from 1 to {}", layer, field.layers()));
+        }
+        Ok((number - 1, (layer - 1) * height))
+    };
+    match words.first().map(String::as_str) {
+        None => Ok(speech::board(field)),
+        Some("row") => line(height).map(|(row, top)| speech::row(field, top + row)),
+        Some("column") | Some("col") => line(cols).map(|(col, top)| speech::column(field, col, top)),
+        _ => {
+            let (y, x) = typed_cell(field, view, words)?;
+            Ok(speech::cell(field, x, y))
+        },
+    }
+}
+
+/// The cell at the coordinates typed in `input`, as seen on screen: the
+/// column, the row and on a three-dimensional board the layer. Returned
+/// column first like a `Move`.
+fn typed_cell(field: &Field, view: &View, input: &[String]) -> Result<(u16, u16), String> {
+    // the layer comes third on a three-dimensional board
+    let count = if field.layers() > 1 { 3 } else { 2 };
+    if input.len() != count {
+        return Err(format!("Wrong input count ({})", input.len()));
+    }
+    let input: Vec<Result<u16, _>> = input.iter().map(|s| s.trim()).map(|s| s.parse()).filter(|v| v.is_ok()).collect();
+    let input: Vec<u16> = input.into_iter().map(|r| r.unwrap()).collect();
+    if input.len() != count {
+        return Err(format!("Wrong coords count ({})", input.len()));
+    }
+    let x = input[0];
+    let y = input[1];
+    let y = if y > 0 { y - 1 } else { y };
+    let y = match input.get(2) {
+        Some(&z) => (if z > 0 { z - 1 } else { z }).saturating_mul(field.layer_height()).saturating_add(y),
+        None => y,
+    };
+    // typed as seen on a turned board
+    let (x, y) = view.screen_cell(field, y, if x > 0 { x - 1 } else { x });
+    Ok((y, x))
+}
+
+/// Moves the cursor in full-screen mode until a cell is opened, flagged or
+/// chorded. Returns `None` when the player quits. `w` switches `profile`.
+#[allow(clippy::too_many_arguments)]
+fn tui_move(terminal: &mut RawTerminal, profile: &mut Profile, field: &Field, view: &mut View, cursor: &mut Cursor, status: &[String], analyst: &mut Analyst, timer: &mut Timer) -> Result<Option<Move>, String> {
+    let (cols, rows) = field.size();
+    analyst.analyse(field);
+    let mut analysis: Option<Analysis> = None;
+    // the odds come from the background solver and show once it's done
+    let mut odds = false;
+    let mut hovered = None;
+    // a numpad move flags once f was pressed before the digit
+    let mut flag_next = false;
+    let mut note = None;
+    loop {
+        let width = terminal_width();
+        view.cursor = Some((cursor.x, cursor.y));
+        let (_, screen_col) = view.screen_cell(field, cursor.x, cursor.y);
+        let screen_cols = if view.is_transposed(field) { rows } else { cols };
+        view.scroll_to(screen_col, screen_cols as usize, width.saturating_sub(view.frame_width(field)));
+        let time = format_time(timer.elapsed(Instant::now()));
+        // a burst of keys, like a held arrow key, is drawn once it's read
+        if !terminal.input_pending() {
+            let overlay = analysis.as_ref().filter(|_| odds).map(|analysis| &analysis.probabilities);
+            let mut lines = match overlay {
+                Some(probabilities) => view.render_probabilities(field, probabilities, width),
+                None => view.render(field, width),
+            };
+            lines.push(String::new());
+            lines.push(format!("Time: {}", time));
+            lines.extend(status.iter().cloned());
+            lines.extend(note.iter().cloned());
+            if let Some((x, y)) = hovered {
+                let mut line = format!("Mouse on {}", view.coords(field, x, y));
+                let chance = overlay.into_iter().flatten().find(|&&(cell, _)| cell == (x, y));
+                if let Some((_, p)) = chance {
+                    line.push_str(&format!(", mine chance {:.0}%", 100.0 * p));
+                }
+                lines.push(line.dimmed().to_string());
+            }
+            lines.push(match analysis.as_ref() {
+                Some(analysis) => solver_summary(analysis),
+                None if analyst.is_cancelled() => "Solver: stopped until the next move".into(),
+                None => "Solver: thinking…".into(),
+            }.dimmed().to_string());
+            if overlay.is_some() {
+                lines.push(view.probability_legend().dimmed().to_string());
+            }
+            lines.push(match (cursor.numpad, flag_next) {
+                (true, true) => "numpad: 1-9 flag the cell that way",
+                (true, false) => "numpad: 1-9 open the cells around the cursor as the keys lie, 5 chord, f then 1-9 flag, n leaves",
+                _ => "arrows move, space/enter/left click open, f/right click flag, c/middle click chord, n numpad, h hint, p odds, x stop solver, u undo, r redo, z pause, w profile, q quit",
+            }.dimmed().to_string());
+            terminal.draw(&lines).map_err(|e| e.to_string())?;
+        }
+        // wait for a key, redrawing once the solver is done or the time changes
+        let keys = loop {
+            if let Some(done) = analyst.poll() {
+                analysis = Some(done);
+                break vec![];
+            }
+            if format_time(timer.elapsed(Instant::now())) != time {
+                break vec![];
+            }
+            let keys = terminal.poll_keys(Duration::from_millis(50)).map_err(|e| e.to_string())?;
+            if !keys.is_empty() {
+                break keys;
+            }
+        };
+        for key in keys {
+            note = None;
+            // arrows move on screen, across the rows of a turned board
+            let key = match key {
+                Key::Up if view.is_transposed(field) => Key::Left,
+                Key::Down if view.is_transposed(field) => Key::Right,
+                Key::Left if view.is_transposed(field) => Key::Up,
+                Key::Right if view.is_transposed(field) => Key::Down,
+                key => key,
+            };
+            if cursor.apply(key, rows, cols) {
+                continue;
+            }
+            if let Key::Hover(column, row) = key {
+                hovered = view.cell_at(field, width, column, row);
+                continue;
+            }
+            if let Key::Click(button, column, row) = key {
+                let (x, y) = match view.cell_at(field, width, column, row) {
+                    Some(cell) => cell,
+                    None => continue,
+                };
+                *cursor = Cursor { x, y, ..*cursor };
+                let action = match button {
+                    Button::Left => Action::Open,
+                    Button::Right => Action::Flag,
+                    Button::Middle => Action::Chord,
+                };
+                return Ok(Some((action, (y, x))));
+            }
+            let action = match key {
+                Key::Char('n') => {
+                    cursor.numpad = !cursor.numpad;
+                    flag_next = false;
+                    continue;
+                },
+                Key::Char('f') if cursor.numpad => {
+                    flag_next = !flag_next;
+                    continue;
+                },
+                Key::Char(digit) if cursor.numpad && numpad::offset(digit).is_some() => {
+                    let action = if std::mem::take(&mut flag_next) { Action::Flag } else { Action::Open };
+                    match numpad_move(field, (cursor.x, cursor.y), action, digit, view.is_transposed(field)) {
+                        Ok((action, (x, y))) => return Ok(Some((action, (y, x)))),
+                        Err(e) => {
+                            note = Some(e);
+                            continue;
+                        },
+                    }
+                },
+                Key::Enter | Key::Char(' ') => Action::Open,
+                Key::Char('f') => Action::Flag,
+                Key::Char('c') => Action::Chord,
+                Key::Char('h') => Action::Hint,
+                Key::Char('u') => Action::Undo,
+                Key::Char('r') => Action::Redo,
+                Key::Char('x') => {
+                    analyst.cancel();
+                    continue;
+                },
+                Key::Char('p') => {
+                    odds = !odds;
+                    if odds && analysis.is_none() && analyst.is_cancelled() {
+                        analyst.analyse(field);
+                    }
+                    continue;
+                },
+                // the board stays hidden while the clock stands still
+                Key::Char('z') if timer.is_running() => {
+                    timer.pause(Instant::now());
+                    let paused = format!("Paused at {}, press any key to go on", format_time(timer.elapsed(Instant::now())));
+                    terminal.draw(&[paused]).map_err(|e| e.to_string())?;
+                    // moving the mouse isn't pressing a key
+                    let read = loop {
+                        match terminal.read_keys() {
+                            Ok(keys) if keys.iter().all(|key| matches!(key, Key::Hover(_, _))) => {},
+                            read => break read,
+                        }
+                    };
+                    timer.resume(Instant::now());
+                    read.map_err(|e| e.to_string())?;
+                    continue;
+                },
+                // like a pause, the clock stands still while the menu hides the board
+                Key::Char('w') => {
+                    let running = timer.is_running();
+                    if running {
+                        timer.pause(Instant::now());
+                    }
+                    let picked = pick_profile(terminal, profile);
+                    if running {
+                        timer.resume(Instant::now());
+                    }
+                    if let Some(picked) = picked? {
+                        note = Some(format!("Playing as {}, this game counts for that profile", picked));
+                        *profile = picked;
+                    }
+                    continue;
+                },
+                Key::Char('q') | Key::Escape | Key::Interrupt => return Ok(None),
+                _ => continue,
+            };
+            return Ok(Some((action, (cursor.y, cursor.x))));
+        }
+    }
+}
+
+/// The profile menu of full-screen mode: the profiles that have files and a
+/// line to type a new one in. Returns `None` when the player backs out.
+fn pick_profile(terminal: &mut RawTerminal, current: &Profile) -> Result<Option<Profile>, String> {
+    let profiles = stats::profiles();
+    let mut selected = profiles.iter().position(|profile| profile == current).unwrap_or(0);
+    let mut typed = String::new();
+    let mut error = None;
+    loop {
+        let mut lines = vec!["Who is playing?".bold().to_string(), String::new()];
+        for (i, profile) in profiles.iter().enumerate() {
+            let marker = if i == selected { "> " } else { "  " };
+            lines.push(format!("{}{}{}", marker, profile, if profile == current { " (playing)" } else { "" }));
+        }
+        lines.push(format!("{}new: {}", if selected == profiles.len() { "> " } else { "  " }, typed));
+        lines.push(String::new());
+        lines.extend(error.iter().cloned());
+        lines.push("up/down pick, type a new name, enter switches, escape goes back".dimmed().to_string());
+        terminal.draw(&lines).map_err(|e| e.to_string())?;
+        for key in terminal.read_keys().map_err(|e| e.to_string())? {
+            match key {
+                Key::Up => selected = selected.saturating_sub(1),
+                Key::Down => selected = (selected + 1).min(profiles.len()),
+                // typing goes to the new name, wherever the selection is
+                Key::Char(c) => {
+                    selected = profiles.len();
+                    typed.push(c);
+                },
+                Key::Backspace => {
+                    typed.pop();
+                },
+                Key::Enter => {
+                    let picked = match profiles.get(selected) {
+                        Some(profile) => Ok(profile.clone()),
+                        None => Profile::named(typed.trim()),
+                    };
+                    match picked {
+                        Ok(profile) => return Ok(Some(profile)),
+                        Err(e) => error = Some(e.red().to_string()),
+                    }
+                },
+                Key::Escape | Key::Interrupt => return Ok(None),
+                _ => {},
+            }
+        }
+    }
+}
+
+/// Waits for the background solver in the line mode, telling how to stop
+/// it if it takes a while. Returns `None` once enter stopped it.
+fn wait_for_analysis(analyst: &mut Analyst) -> Option<Analysis> {
+    let started = Instant::now();
+    let mut told = false;
+    loop {
+        if let Some(analysis) = analyst.poll() {
+            return Some(analysis);
+        }
+        if input_pending() {
+            let _ = io::stdin().read_line(&mut String::new());
+            analyst.cancel();
+            return None;
+        }
+        if !told && started.elapsed() >= Duration::from_millis(500) {
+            println!("Working out the odds, press enter to stop");
+            told = true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn solver_summary(analysis: &Analysis) -> String {
+    let safe = analysis.deductions.iter().filter(|d| matches!(d, Deduction::Safe(_, _))).count();
+    let mines = analysis.deductions.len() - safe;
+    if !analysis.deductions.is_empty() {
+        format!("Solver: {} safe, {} mines certain", safe, mines)
+    } else if let Some(((x, y), p)) = analysis.safest() {
+        format!("Solver: nothing certain, safest guess {:.0}% at {} {}", 100.0 * p, y + 1, x + 1)
+    } else {
+        "Solver: nothing left to open".into()
     }
 }
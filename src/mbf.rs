@@ -0,0 +1,94 @@
+//! The Minesweeper Board File format other clients share boards in: a byte
+//! for the width and one for the height, two bytes for the number of mines
+//! with the high byte first, then the column and row of every mine, a byte
+//! each and counted from 0. It only knows where the mines are.
+
+use crate::{Cell, Field, Topology};
+
+/// Reads a board from the bytes of an MBF file.
+pub fn read(bytes: &[u8]) -> Result<Field, String> {
+    let (width, height, count, mines) = match bytes {
+        [width, height, high, low, mines @ ..] => (*width as usize, *height as usize, u16::from_be_bytes([*high, *low]) as usize, mines),
+        _ => return Err("An MBF file starts with 4 bytes of size and mine count".into()),
+    };
+    if width == 0 || height == 0 {
+        return Err("An MBF board needs at least one row and column".into());
+    }
+    if mines.len() != 2 * count {
+        return Err(format!("An MBF file with {} mines has {} bytes, not {}", count, 4 + 2 * count, 4 + mines.len()));
+    }
+    if count >= width * height {
+        return Err(format!("{} mines don't fit a {}x{} board with a cell left free", count, width, height));
+    }
+    let mut cells = vec![vec![Cell::water(); width]; height];
+    for mine in mines.chunks(2) {
+        let (col, row) = (mine[0] as usize, mine[1] as usize);
+        let cell = cells.get_mut(row).and_then(|row| row.get_mut(col))
+            .ok_or_else(|| format!("The mine at {} {} is off the {}x{} board", col + 1, row + 1, width, height))?;
+        if cell.is_mine() {
+            return Err(format!("The mine at {} {} is listed twice", col + 1, row + 1));
+        }
+        *cell = Cell::mine();
+    }
+    Ok(Field::with_cells(cells))
+}
+
+/// Writes the mines of `field` as an MBF file. Opened cells and flags are
+/// left out, and only flat boards with square cells, one mine per cell and
+/// no holes fit the format.
+pub fn write(field: &Field) -> Result<Vec<u8>, String> {
+    let (cols, rows) = field.size();
+    if field.topology() != Topology::Square || field.wraps() || field.layers() > 1 || field.mines_per_cell() > 1 {
+        return Err("MBF only holds flat boards with square cells and one mine per cell".into());
+    }
+    if field.cells.iter().flatten().any(|cell| cell.is_hole()) {
+        return Err("MBF boards don't have holes".into());
+    }
+    if cols > 255 || rows > 255 {
+        return Err(format!("MBF boards are at most 255x255, not {}x{}", cols, rows));
+    }
+    let mut bytes = vec![cols as u8, rows as u8];
+    bytes.extend_from_slice(&(field.mine_count() as u16).to_be_bytes());
+    for (x, row) in field.cells.iter().enumerate() {
+        for (y, cell) in row.iter().enumerate() {
+            if cell.is_mine() {
+                bytes.extend_from_slice(&[y as u8, x as u8]);
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Field, Topology};
+    use crate::mbf::{read, write};
+
+    #[test]
+    fn round_trip() {
+        let field = Field::parse("..*\n*..\n").unwrap();
+        let bytes = write(&field).unwrap();
+        assert_eq!(vec![3, 2, 0, 2, 2, 0, 0, 1], bytes);
+        assert_eq!(field.to_board_file(), read(&bytes).unwrap().to_board_file());
+
+        // opened cells and flags aren't kept
+        let played = Field::parse("oF*\n*..\n").unwrap();
+        assert_eq!(".**\n*..\n", read(&write(&played).unwrap()).unwrap().to_board_file());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(read(&[3, 2, 0]).is_err());
+        assert!(read(&[0, 2, 0, 0]).is_err());
+        assert!(read(&[3, 2, 0, 2, 2, 0]).is_err());
+        assert!(read(&[3, 2, 0, 1, 3, 0]).is_err());
+        assert!(read(&[3, 2, 0, 2, 1, 1, 1, 1]).is_err());
+        assert!(read(&[1, 1, 0, 1, 0, 0]).is_err());
+
+        let mut hex = Field::parse("..*\n*..\n").unwrap();
+        hex.set_topology(Topology::Hex);
+        assert!(write(&hex).is_err());
+        assert!(write(&Field::parse("-.*\n").unwrap()).is_err());
+        assert!(write(&Field::parse(&".".repeat(256)).unwrap()).is_err());
+    }
+}
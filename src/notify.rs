@@ -0,0 +1,31 @@
+use std::process::Command;
+
+/// Whether this build can send desktop notifications, see the `notify`
+/// feature.
+pub fn supported() -> bool {
+    cfg!(feature = "notify")
+}
+
+/// Shows a desktop notification so a long run can finish in a terminal in
+/// the background. Uses `notify-send` on Linux and `osascript` on macOS.
+pub fn notify(summary: &str, body: &str) -> Result<(), String> {
+    if !supported() {
+        return Err("Desktop notifications need a build with the notify feature".into());
+    }
+    let mut command = if cfg!(target_os = "macos") {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!("display notification {} with title {}", quote(body), quote(summary)));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg("--app-name=minesweep").arg(summary).arg(body);
+        command
+    };
+    let status = command.status().map_err(|e| format!("Can't send a notification: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Can't send a notification: {}", status))
+    }
+}
@@ -0,0 +1,117 @@
+//! Moves typed on a numpad around an anchor cell: the digits stand for the
+//! cells around it as the keys are laid out, 7 8 9 above, 4 and 6 beside
+//! and 1 2 3 below, and 5 for the anchor itself. Working around a number
+//! then takes a key per cell instead of its coordinates.
+
+use crate::{Field, Topology};
+use crate::replay::Action;
+
+/// Rows and columns from the anchor to the cell of `digit`, as on screen.
+pub fn offset(digit: char) -> Option<(i32, i32)> {
+    let digit = digit.to_digit(10).filter(|digit| (1..=9).contains(digit))? as i32;
+    Some((1 - (digit - 1) / 3, (digit - 1) % 3 - 1))
+}
+
+/// Reads a numpad move like `7`, `f7` or `c7`: opening, flagging or
+/// chording the cell of the digit.
+pub fn parse(token: &str) -> Option<(Action, char)> {
+    let (action, digit) = match token.strip_prefix('f') {
+        Some(digit) => (Action::Flag, digit),
+        None => match token.strip_prefix('c') {
+            Some(digit) => (Action::Chord, digit),
+            None => (Action::Open, token),
+        },
+    };
+    let mut chars = digit.chars();
+    match (chars.next(), chars.next()) {
+        (Some(digit), None) if offset(digit).is_some() => Some((action, digit)),
+        _ => None,
+    }
+}
+
+/// The cell of `digit` around `anchor`, a row and column of `field`, with
+/// the offsets turned on a `transposed` view. Rows stay in the layer of the
+/// anchor and go around on boards that wrap.
+pub fn target(field: &Field, anchor: (u16, u16), digit: char, transposed: bool) -> Option<(u16, u16)> {
+    let (rows, cols) = offset(digit)?;
+    let (dx, dy) = if transposed { (cols, rows) } else { (rows, cols) };
+    let height = field.layer_height() as i32;
+    let width = field.size().0 as i32;
+    let layer = anchor.0 as i32 / height;
+    let (mut x, mut y) = (anchor.0 as i32 % height + dx, anchor.1 as i32 + dy);
+    if field.wraps() {
+        x = x.rem_euclid(height);
+        y = y.rem_euclid(width);
+    }
+    if x < 0 || x >= height || y < 0 || y >= width {
+        return None;
+    }
+    Some(((layer * height + x) as u16, y as u16))
+}
+
+/// The move of `action` on the cell of `digit` around the opened cell
+/// `anchor`. Opening the anchor, which is open already, chords it instead.
+pub fn numpad_move(field: &Field, anchor: (u16, u16), action: Action, digit: char, transposed: bool) -> Result<(Action, (u16, u16)), String> {
+    if field.topology() != Topology::Square {
+        return Err("The numpad only knows its way around square cells".into());
+    }
+    if !field.cell(anchor.0, anchor.1).is_ok_and(|cell| cell.is_opened()) {
+        return Err("The numpad works around an opened cell".into());
+    }
+    let cell = target(field, anchor, digit, transposed).ok_or("There's no cell that way")?;
+    match action {
+        Action::Open if cell == anchor => Ok((Action::Chord, cell)),
+        action => Ok((action, cell)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Field, Topology};
+    use crate::numpad::{numpad_move, offset, parse, target};
+    use crate::replay::Action;
+
+    #[test]
+    fn layout() {
+        assert_eq!(Some((-1, -1)), offset('7'));
+        assert_eq!(Some((-1, 0)), offset('8'));
+        assert_eq!(Some((0, 1)), offset('6'));
+        assert_eq!(Some((0, 0)), offset('5'));
+        assert_eq!(Some((1, 1)), offset('3'));
+        assert_eq!(None, offset('0'));
+        assert_eq!(Some((Action::Open, '7')), parse("7"));
+        assert_eq!(Some((Action::Flag, '1')), parse("f1"));
+        assert_eq!(Some((Action::Chord, '9')), parse("c9"));
+        for invalid in ["", "0", "f", "77", "x7", "f 7"] {
+            assert_eq!(None, parse(invalid), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn targets() {
+        let mut field = Field::parse("...\n...\n...\n...\n").unwrap();
+        assert_eq!(Some((0, 0)), target(&field, (1, 1), '7', false));
+        assert_eq!(Some((1, 2)), target(&field, (1, 1), '6', false));
+        assert_eq!(Some((2, 1)), target(&field, (1, 1), '2', false));
+        // a turned board has the rows across
+        assert_eq!(Some((2, 1)), target(&field, (1, 1), '6', true));
+        assert_eq!(None, target(&field, (0, 0), '8', false));
+        field.set_wrap(true);
+        assert_eq!(Some((3, 2)), target(&field, (0, 0), '7', false));
+        field.set_wrap(false);
+        field.set_layers(2).unwrap();
+        assert_eq!(None, target(&field, (2, 1), '8', false));
+        assert_eq!(Some((3, 1)), target(&field, (2, 1), '2', false));
+    }
+
+    #[test]
+    fn moves() {
+        let mut field = Field::parse("o.*\n...\n").unwrap();
+        assert_eq!(Ok((Action::Chord, (0, 0))), numpad_move(&field, (0, 0), Action::Open, '5', false));
+        assert_eq!(Ok((Action::Flag, (1, 1))), numpad_move(&field, (0, 0), Action::Flag, '3', false));
+        assert!(numpad_move(&field, (0, 0), Action::Open, '7', false).is_err());
+        assert!(numpad_move(&field, (0, 1), Action::Open, '6', false).is_err());
+        field.set_topology(Topology::Hex);
+        assert!(numpad_move(&field, (0, 0), Action::Open, '6', false).is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use crate::Field;
+use crate::bot::Strategy;
+use crate::solver::mine_estimates;
+use crate::training::visible_state;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// A field of a protobuf message as it comes over the wire.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Wire<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+/// Reads a variable length number off the front of `bytes`.
+fn varint(bytes: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or("truncated model")?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("invalid number in model".into())
+}
+
+/// Splits a protobuf message into its fields, numbered as in the schema.
+fn fields(mut bytes: &[u8]) -> Result<Vec<(u32, Wire<'_>)>, String> {
+    fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], String> {
+        if bytes.len() < n {
+            return Err("truncated model".into());
+        }
+        let (taken, rest) = bytes.split_at(n);
+        *bytes = rest;
+        Ok(taken)
+    }
+    let mut fields = vec![];
+    while !bytes.is_empty() {
+        let key = varint(&mut bytes)?;
+        let wire = match key & 7 {
+            0 => Wire::Varint(varint(&mut bytes)?),
+            1 => Wire::Fixed64(u64::from_le_bytes(take(&mut bytes, 8)?.try_into().unwrap())),
+            2 => {
+                let len = varint(&mut bytes)? as usize;
+                Wire::Bytes(take(&mut bytes, len)?)
+            },
+            5 => Wire::Fixed32(u32::from_le_bytes(take(&mut bytes, 4)?.try_into().unwrap())),
+            kind => return Err(format!("unsupported field kind {} in model", kind)),
+        };
+        fields.push(((key >> 3) as u32, wire));
+    }
+    Ok(fields)
+}
+
+fn text(wire: Wire) -> Result<String, String> {
+    match wire {
+        Wire::Bytes(bytes) => String::from_utf8(bytes.to_vec()).map_err(|_| "name isn't UTF-8".to_string()),
+        _ => Err("expected a name".into()),
+    }
+}
+
+/// The fields numbered `number` of a message, as nested messages.
+fn messages<'a>(fields: &[(u32, Wire<'a>)], number: u32) -> Vec<&'a [u8]> {
+    fields.iter()
+        .filter_map(|&(n, wire)| match wire {
+            Wire::Bytes(bytes) if n == number => Some(bytes),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Integers of a repeated field, packed or not.
+fn integers(fields: &[(u32, Wire)], number: u32) -> Result<Vec<i64>, String> {
+    let mut values = vec![];
+    for &(n, wire) in fields.iter().filter(|(n, _)| *n == number) {
+        match wire {
+            Wire::Varint(value) => values.push(value as i64),
+            Wire::Bytes(mut packed) => while !packed.is_empty() {
+                values.push(varint(&mut packed)? as i64);
+            },
+            _ => return Err(format!("field {} isn't a number", n)),
+        }
+    }
+    Ok(values)
+}
+
+/// A tensor of 32 bit floats, row major.
+#[derive(Clone, Debug, PartialEq)]
+struct Tensor {
+    dims: Vec<usize>,
+    data: Vec<f32>,
+}
+
+impl Tensor {
+    fn parse(bytes: &[u8]) -> Result<(String, Self), String> {
+        const FLOAT: i64 = 1;
+        let fields = fields(bytes)?;
+        let mut name = String::new();
+        let mut data = vec![];
+        let mut data_type = FLOAT;
+        for &(number, wire) in &fields {
+            match (number, wire) {
+                (2, Wire::Varint(kind)) => data_type = kind as i64,
+                (4, Wire::Fixed32(bits)) => data.push(f32::from_bits(bits)),
+                (4, Wire::Bytes(bytes)) | (9, Wire::Bytes(bytes)) => data.extend(bytes.chunks_exact(4)
+                    .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))),
+                (8, wire) => name = text(wire)?,
+                _ => {},
+            }
+        }
+        if data_type != FLOAT {
+            return Err(format!("tensor {} doesn't hold floats", name));
+        }
+        let dims: Vec<usize> = integers(&fields, 1)?.into_iter().map(|d| d.max(0) as usize).collect();
+        if dims.iter().product::<usize>() != data.len() {
+            return Err(format!("tensor {} doesn't match its size", name));
+        }
+        Ok((name, Self { dims, data }))
+    }
+
+    /// Rows and columns, with everything before the last dimension as rows.
+    fn matrix(&self) -> (usize, usize) {
+        let cols = self.dims.last().copied().unwrap_or(1).max(1);
+        (self.data.len() / cols, cols)
+    }
+
+    fn map(mut self, f: impl Fn(f32) -> f32) -> Self {
+        self.data.iter_mut().for_each(|v| *v = f(*v));
+        self
+    }
+
+    /// Applies `f` to both tensors, repeating the smaller one if it is a
+    /// scalar or a row of the larger one.
+    fn zip(&self, other: &Self, f: impl Fn(f32, f32) -> f32) -> Result<Self, String> {
+        let (big, small, swapped) = if self.data.len() >= other.data.len() { (self, other, false) } else { (other, self, true) };
+        if small.data.is_empty() || !big.data.len().is_multiple_of(small.data.len()) {
+            return Err("tensors of different shapes".into());
+        }
+        let data = big.data.iter().enumerate()
+            .map(|(i, &a)| {
+                let b = small.data[i % small.data.len()];
+                if swapped { f(b, a) } else { f(a, b) }
+            })
+            .collect();
+        Ok(Self { dims: big.dims.clone(), data })
+    }
+
+    fn transposed(&self) -> Self {
+        let (rows, cols) = self.matrix();
+        let data = (0..cols).flat_map(|c| (0..rows).map(move |r| (r, c))).map(|(r, c)| self.data[r * cols + c]).collect();
+        Self { dims: vec![cols, rows], data }
+    }
+
+    fn matmul(&self, other: &Self) -> Result<Self, String> {
+        let ((rows, inner), (other_rows, cols)) = (self.matrix(), other.matrix());
+        if inner != other_rows {
+            return Err(format!("can't multiply {}x{} by {}x{}", rows, inner, other_rows, cols));
+        }
+        let mut data = vec![0.0; rows * cols];
+        for r in 0..rows {
+            for k in 0..inner {
+                let a = self.data[r * inner + k];
+                for c in 0..cols {
+                    data[r * cols + c] += a * other.data[k * cols + c];
+                }
+            }
+        }
+        Ok(Self { dims: vec![rows, cols], data })
+    }
+}
+
+/// One operation of the graph.
+struct Node {
+    op: String,
+    inputs: Vec<String>,
+    output: String,
+    /// Float and integer attributes, like `alpha` and `transB` of `Gemm`.
+    attributes: HashMap<String, f32>,
+}
+
+impl Node {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let message = fields(bytes)?;
+        let mut node = Node { op: String::new(), inputs: vec![], output: String::new(), attributes: HashMap::new() };
+        for &(number, wire) in &message {
+            match number {
+                1 => node.inputs.push(text(wire)?),
+                2 if node.output.is_empty() => node.output = text(wire)?,
+                4 => node.op = text(wire)?,
+                _ => {},
+            }
+        }
+        for attribute in messages(&message, 5) {
+            let mut name = String::new();
+            let mut value = None;
+            for (number, wire) in fields(attribute)? {
+                match (number, wire) {
+                    (1, wire) => name = text(wire)?,
+                    (2, Wire::Fixed32(bits)) => value = Some(f32::from_bits(bits)),
+                    (3, Wire::Varint(i)) => value = Some(i as i64 as f32),
+                    _ => {},
+                }
+            }
+            if let Some(value) = value {
+                node.attributes.insert(name, value);
+            }
+        }
+        Ok(node)
+    }
+
+    fn run(&self, values: &HashMap<String, Tensor>) -> Result<Tensor, String> {
+        let input = |i: usize| -> Result<&Tensor, String> {
+            let name = self.inputs.get(i).ok_or_else(|| format!("{} is missing an input", self.op))?;
+            values.get(name).ok_or_else(|| format!("{} reads {} before it is computed", self.op, name))
+        };
+        let attribute = |name: &str, default: f32| self.attributes.get(name).copied().unwrap_or(default);
+        match self.op.as_str() {
+            "Gemm" => {
+                let a = if attribute("transA", 0.0) != 0.0 { input(0)?.transposed() } else { input(0)?.clone() };
+                let b = if attribute("transB", 0.0) != 0.0 { input(1)?.transposed() } else { input(1)?.clone() };
+                let product = a.matmul(&b)?.map(|v| v * attribute("alpha", 1.0));
+                match self.inputs.get(2).filter(|name| !name.is_empty()) {
+                    Some(_) => product.zip(input(2)?, |p, c| p + attribute("beta", 1.0) * c),
+                    None => Ok(product),
+                }
+            },
+            "MatMul" => input(0)?.matmul(input(1)?),
+            "Add" => input(0)?.zip(input(1)?, |a, b| a + b),
+            "Sub" => input(0)?.zip(input(1)?, |a, b| a - b),
+            "Mul" => input(0)?.zip(input(1)?, |a, b| a * b),
+            "Relu" => Ok(input(0)?.clone().map(|v| v.max(0.0))),
+            "Sigmoid" => Ok(input(0)?.clone().map(|v| 1.0 / (1.0 + (-v).exp()))),
+            "Tanh" => Ok(input(0)?.clone().map(f32::tanh)),
+            "Identity" | "Flatten" | "Softmax" => {
+                // softmax keeps the order of the scores, which is all that counts
+                let tensor = input(0)?;
+                Ok(Tensor { dims: vec![1, tensor.data.len()], data: tensor.data.clone() })
+            },
+            op => Err(format!("unsupported operation {}", op)),
+        }
+    }
+}
+
+/// A feed-forward network read from an ONNX model. It takes the board as
+/// `training::visible_state` encodes it, one row of floats, and gives a
+/// score for every cell in the same order.
+///
+/// Only what small policy networks are made of is supported: `Gemm`,
+/// `MatMul`, `Add`, `Sub`, `Mul`, `Relu`, `Sigmoid`, `Tanh`, `Softmax`,
+/// `Flatten` and `Identity` on float tensors.
+pub struct Model {
+    nodes: Vec<Node>,
+    weights: HashMap<String, Tensor>,
+    input: String,
+    /// Floats the input takes, the cells of the boards the model plays.
+    pub inputs: usize,
+    output: String,
+}
+
+impl Model {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let model = fields(bytes)?;
+        let graph = *messages(&model, 7).first().ok_or("no graph in model")?;
+        let graph = fields(graph)?;
+        let nodes = messages(&graph, 1).into_iter().map(Node::parse).collect::<Result<Vec<_>, _>>()?;
+        let weights = messages(&graph, 5).into_iter().map(Tensor::parse).collect::<Result<HashMap<_, _>, _>>()?;
+        // inputs may list the weights too
+        let (input, inputs) = messages(&graph, 11).into_iter()
+            .map(value_info)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .find(|(name, _)| !weights.contains_key(name))
+            .ok_or("the model has no input")?;
+        let output = messages(&graph, 12).first().map(|&info| value_info(info)).transpose()?
+            .map(|(name, _)| name)
+            .ok_or("the model has no output")?;
+        Ok(Self { nodes, weights, input, inputs, output })
+    }
+
+    /// Scores every cell of a board encoded as `training::visible_state`.
+    pub fn run(&self, state: &[f32]) -> Result<Vec<f32>, String> {
+        if state.len() != self.inputs {
+            return Err(format!("the model takes {} cells, not {}", self.inputs, state.len()));
+        }
+        let mut values = self.weights.clone();
+        values.insert(self.input.clone(), Tensor { dims: vec![1, state.len()], data: state.to_vec() });
+        for node in &self.nodes {
+            let result = node.run(&values)?;
+            values.insert(node.output.clone(), result);
+        }
+        let scores = values.remove(&self.output).ok_or("the model doesn't compute its output")?.data;
+        if scores.len() != state.len() {
+            return Err(format!("the model scores {} cells of {}", scores.len(), state.len()));
+        }
+        Ok(scores)
+    }
+}
+
+/// Name and number of floats of a graph input or output, from the shape of
+/// its tensor type.
+fn value_info(bytes: &[u8]) -> Result<(String, usize), String> {
+    let info = fields(bytes)?;
+    let name = info.iter().find(|(n, _)| *n == 1).map(|&(_, wire)| text(wire)).transpose()?.unwrap_or_default();
+    let dims = messages(&info, 2).into_iter()
+        .flat_map(|kind| fields(kind).map(|kind| messages(&kind, 1)).unwrap_or_default())
+        .flat_map(|tensor| fields(tensor).map(|tensor| messages(&tensor, 2)).unwrap_or_default())
+        .flat_map(|shape| fields(shape).map(|shape| messages(&shape, 1)).unwrap_or_default())
+        .map(|dim| integers(&fields(dim)?, 1).map(|value| value.first().copied().unwrap_or(1).max(1) as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((name, dims.iter().product()))
+}
+
+/// Opens the closed cell a learned model scores highest, to see how a
+/// trained policy does against the hand-written strategies.
+pub struct LearnedPolicy {
+    name: String,
+    model: Model,
+}
+
+impl LearnedPolicy {
+    pub fn new(name: String, model: Model) -> Self {
+        Self { name, model }
+    }
+}
+
+impl Strategy for LearnedPolicy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self, rows: u16, cols: u16) -> Result<(), String> {
+        let cells = rows as usize * cols as usize;
+        if cells == self.model.inputs {
+            Ok(())
+        } else {
+            Err(format!("{} plays boards of {} cells, not {}x{}", self.name, self.model.inputs, cols, rows))
+        }
+    }
+
+    fn guess(&self, field: &Field, _rng: &mut dyn RngCore) -> Option<(u16, u16)> {
+        let state: Vec<f32> = visible_state(field).into_iter().map(|v| v as f32).collect();
+        let scores = self.model.run(&state).ok()?;
+        let (cols, _) = field.size();
+        mine_estimates(field).into_iter()
+            .map(|((x, y), _)| ((x, y), scores[x as usize * cols as usize + y as usize]))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(cell, _)| cell)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Field;
+    use crate::bot::Strategy;
+    use crate::onnx::{LearnedPolicy, Model};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn message(number: u32, bytes: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        varint((number as u64) << 3 | 2, &mut out);
+        varint(bytes.len() as u64, &mut out);
+        out.extend_from_slice(bytes);
+        out
+    }
+
+    fn tensor(name: &str, dims: &[u64], data: &[f32]) -> Vec<u8> {
+        let mut packed = vec![];
+        for &dim in dims {
+            varint(dim, &mut packed);
+        }
+        let raw: Vec<u8> = data.iter().flat_map(|v| v.to_le_bytes()).collect();
+        [message(1, &packed), vec![2 << 3, 1], message(8, name.as_bytes()), message(9, &raw)].concat()
+    }
+
+    fn node(op: &str, inputs: &[&str], output: &str) -> Vec<u8> {
+        let mut bytes: Vec<u8> = inputs.iter().flat_map(|input| message(1, input.as_bytes())).collect();
+        bytes.extend(message(2, output.as_bytes()));
+        bytes.extend(message(4, op.as_bytes()));
+        bytes
+    }
+
+    fn value_info(name: &str, cells: u64) -> Vec<u8> {
+        let dims: Vec<u8> = [1, cells].iter().flat_map(|&d| {
+            let mut dim = vec![1 << 3];
+            varint(d, &mut dim);
+            message(1, &dim)
+        }).collect();
+        [message(1, name.as_bytes()), message(2, &message(1, &message(2, &dims)))].concat()
+    }
+
+    #[test]
+    fn learned_policy() {
+        // scores closed cells 1 and opened ones minus their number, plus a bias for the last cell
+        let mut weights = vec![0.0; 16];
+        for i in 0..4 {
+            weights[i * 5] = -1.0;
+        }
+        let graph = [
+            message(1, &node("MatMul", &["state", "w"], "h")),
+            message(1, &node("Add", &["h", "b"], "scores")),
+            message(5, &tensor("w", &[4, 4], &weights)),
+            message(5, &tensor("b", &[4], &[0.0, 0.0, 0.0, 0.5])),
+            message(11, &value_info("state", 4)),
+            message(12, &value_info("scores", 4)),
+        ].concat();
+        let model = Model::parse(&message(7, &graph)).unwrap();
+        assert_eq!(4, model.inputs);
+        assert_eq!(vec![-1.0, 1.0, 1.0, 1.5], model.run(&[1.0, -1.0, -1.0, -1.0]).unwrap());
+        assert!(model.run(&[0.0; 9]).is_err());
+
+        let policy = LearnedPolicy::new("onnx:test".into(), model);
+        let field = Field::parse("o.\n.*").unwrap();
+        assert_eq!(Some((1, 1)), policy.guess(&field, &mut StdRng::seed_from_u64(0)));
+        assert!(policy.check(2, 2).is_ok());
+        assert!(policy.check(3, 3).is_err());
+        assert!(Model::parse(&[0x3a, 0x05, 1]).is_err());
+    }
+}
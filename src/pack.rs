@@ -0,0 +1,359 @@
+//! Puzzle packs: hand-made boards bundled in one JSON file, like
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "title": "First steps",
+//!   "author": "Ada",
+//!   "description": "Openings and simple patterns",
+//!   "puzzles": [
+//!     {"title": "One-two", "rating": 1, "board": ["..*", "...", "*.."]},
+//!     {"title": "Corners", "author": "Bo", "rating": 3, "order": 5, "board": ["..."]}
+//!   ]
+//! }
+//! ```
+//!
+//! Boards are rows in the board file format of `Field::parse`. Puzzles are
+//! played in the order of their `order`, those without one where they stand
+//! in the file. `rating` goes from 1 for easy to 5, the author of a puzzle
+//! defaults to the author of the pack.
+
+use crate::{Field, MinesError};
+#[cfg(feature = "network")]
+use crate::fetch;
+use crate::json::Json;
+use crate::save::check_name;
+use crate::stats::{data_dir, Profile};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    pub title: String,
+    pub author: Option<String>,
+    /// From 1, easy, to 5.
+    pub rating: Option<u8>,
+    /// The board file.
+    pub board: String,
+}
+
+impl Puzzle {
+    /// The board to play. Those of a pack read with `Pack::from_json` are
+    /// checked already, one built by hand may not parse.
+    pub fn field(&self) -> Result<Field, MinesError> {
+        Field::parse(&self.board)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pack {
+    pub title: String,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    /// In the order they are meant to be played.
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl Pack {
+    /// Reads a pack, checking that every board can be played.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let json = Json::parse(text)?;
+        let string = |json: &Json, key: &str| json.get(key).and_then(|value| value.as_str()).map(String::from);
+        if let Some(version) = json.get("version").and_then(|v| v.as_u64()).filter(|&v| v != 1) {
+            return Err(format!("unknown version {}", version));
+        }
+        let title = string(&json, "title").ok_or("missing 'title'")?;
+        let entries = json.get("puzzles").and_then(|p| p.as_array()).ok_or("missing 'puzzles'")?;
+        if entries.is_empty() {
+            return Err("the pack has no puzzles".into());
+        }
+        let mut puzzles = vec![];
+        for (i, entry) in entries.iter().enumerate() {
+            let name = string(entry, "title").unwrap_or_else(|| format!("puzzle {}", i + 1));
+            let rows = entry.get("board").and_then(|b| b.as_array())
+                .and_then(|rows| rows.iter().map(|row| row.as_str()).collect::<Option<Vec<_>>>())
+                .ok_or_else(|| format!("{}: 'board' isn't an array of rows", name))?;
+            let board = rows.join("\n") + "\n";
+            Field::parse(&board).map_err(|e| format!("{}: {}", name, e))?;
+            let rating = match entry.get("rating") {
+                Some(rating) => Some(rating.as_u64().filter(|r| (1..=5).contains(r))
+                    .ok_or_else(|| format!("{}: 'rating' goes from 1 to 5", name))? as u8),
+                None => None,
+            };
+            let order = match entry.get("order") {
+                Some(order) => order.as_u64().ok_or_else(|| format!("{}: 'order' isn't a number", name))?,
+                None => i as u64,
+            };
+            let puzzle = Puzzle { title: name, author: string(entry, "author"), rating, board };
+            puzzles.push((order, puzzle));
+        }
+        puzzles.sort_by_key(|(order, _)| *order);
+        Ok(Self {
+            title,
+            author: string(&json, "author"),
+            description: string(&json, "description"),
+            puzzles: puzzles.into_iter().map(|(_, puzzle)| puzzle).collect(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let text = |text: &Option<String>| text.as_ref().map(|text| Json::String(text.clone()));
+        let mut pairs = vec![
+            ("version".into(), Json::Number(1.0)),
+            ("title".into(), Json::String(self.title.clone())),
+        ];
+        pairs.extend(text(&self.author).map(|author| ("author".into(), author)));
+        pairs.extend(text(&self.description).map(|description| ("description".into(), description)));
+        let puzzles = self.puzzles.iter().map(|puzzle| {
+            let mut pairs = vec![("title".into(), Json::String(puzzle.title.clone()))];
+            pairs.extend(text(&puzzle.author).map(|author| ("author".into(), author)));
+            pairs.extend(puzzle.rating.map(|rating| ("rating".into(), Json::Number(rating as f64))));
+            pairs.push(("board".into(), Json::Array(puzzle.board.lines().map(|row| Json::String(row.into())).collect())));
+            Json::Object(pairs)
+        }).collect();
+        pairs.push(("puzzles".into(), Json::Array(puzzles)));
+        Json::Object(pairs).to_string()
+    }
+
+    /// Who made the puzzle: its own author or that of the pack.
+    pub fn author_of<'a>(&'a self, puzzle: &'a Puzzle) -> Option<&'a str> {
+        puzzle.author.as_deref().or(self.author.as_deref())
+    }
+}
+
+/// The puzzles of a pack that were solved, with the best time of each.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Progress {
+    /// By the 0-based index of the puzzle.
+    pub solved: BTreeMap<usize, Duration>,
+}
+
+impl Progress {
+    /// Reads lines of the 1-based puzzle and the best time in milliseconds,
+    /// separated by a tab.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut solved = BTreeMap::new();
+        for (line_no, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let parsed = line.split_once('\t').and_then(|(puzzle, ms)| {
+                Some((puzzle.parse::<usize>().ok().filter(|&p| p >= 1)?, ms.trim().parse::<u64>().ok()?))
+            });
+            let (puzzle, ms) = parsed.ok_or_else(|| format!("line {} isn't a puzzle and a time", line_no + 1))?;
+            solved.insert(puzzle - 1, Duration::from_millis(ms));
+        }
+        Ok(Self { solved })
+    }
+
+    pub fn to_text(&self) -> String {
+        self.solved.iter().map(|(puzzle, time)| format!("{}\t{}\n", puzzle + 1, time.as_millis())).collect()
+    }
+
+    /// Keeps `time` for the puzzle if it is the first or a better one.
+    /// Returns whether it was.
+    pub fn record(&mut self, puzzle: usize, time: Duration) -> bool {
+        match self.solved.get(&puzzle) {
+            Some(&best) if best <= time => false,
+            _ => {
+                self.solved.insert(puzzle, time);
+                true
+            },
+        }
+    }
+
+    /// The first puzzle in the order of the pack that isn't solved yet.
+    pub fn next(&self, puzzles: usize) -> Option<usize> {
+        (0..puzzles).find(|puzzle| !self.solved.contains_key(puzzle))
+    }
+}
+
+/// The directory the packs `profile` installed are kept in, with their
+/// progress.
+pub fn packs_dir(profile: &Profile) -> Result<PathBuf, String> {
+    Ok(data_dir(profile).ok_or("No data directory to keep packs in")?.join("packs"))
+}
+
+/// Where the installed pack `name` is.
+pub fn pack_path(profile: &Profile, name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    Ok(packs_dir(profile)?.join(format!("{}.json", name)))
+}
+
+/// Where the progress through the installed pack `name` is kept.
+pub fn progress_path(profile: &Profile, name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    Ok(packs_dir(profile)?.join(format!("{}.progress", name)))
+}
+
+/// The names of the installed packs.
+pub fn installed(profile: &Profile) -> Vec<String> {
+    let entries = match packs_dir(profile).ok().and_then(|dir| fs::read_dir(dir).ok()) {
+        Some(entries) => entries,
+        None => return vec![],
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            path.file_stem()?.to_str().map(String::from)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// Checks the pack in the file at `path` and copies it to the installed
+/// packs, named after the file. Returns the name.
+pub fn install(profile: &Profile, path: &Path) -> Result<String, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default().to_string();
+    check_name(&name)?;
+    install_text(profile, &name, &text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    // a local copy isn't updated from where an earlier one came from
+    let _ = fs::remove_file(source_path(profile, &name)?);
+    Ok(name)
+}
+
+/// Downloads the pack at `url`, checks it against the SHA-256 `checksum`
+/// its author published and installs it named after the file. The URL is
+/// kept for `update`.
+#[cfg(feature = "network")]
+pub fn install_url(profile: &Profile, url: &str, checksum: &str) -> Result<String, String> {
+    let file = url.rsplit('/').next().unwrap_or_default();
+    let name = file.strip_suffix(".json").unwrap_or(file);
+    check_name(name).map_err(|e| format!("{}: {}", url, e))?;
+    let text = download(url)?;
+    fetch::verify(text.as_bytes(), checksum).map_err(|e| format!("{}: {}", url, e))?;
+    install_text(profile, name, &text).map_err(|e| format!("{}: {}", url, e))?;
+    let path = source_path(profile, name)?;
+    fs::write(&path, format!("{}\n", url)).map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+    Ok(name.to_string())
+}
+
+/// How the pack at the URL an installed pack came from compares to it.
+#[cfg(feature = "network")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Update {
+    UpToDate,
+    /// The pack changed, but no checksum for the new one was given.
+    Changed,
+    Installed,
+}
+
+/// Downloads the pack `name` again from the URL it was installed from and,
+/// if it changed, installs it when it matches `checksum`. Nothing downloaded
+/// is installed without a checksum, the one of the old pack can't vouch for
+/// a new one. Progress is kept by the position of the puzzles, so new
+/// puzzles should go at the end.
+#[cfg(feature = "network")]
+pub fn update(profile: &Profile, name: &str, checksum: Option<&str>) -> Result<Update, String> {
+    let url = source(profile, name)?.ok_or_else(|| format!("{} wasn't installed from a URL", name))?;
+    let text = download(&url)?;
+    if fs::read_to_string(pack_path(profile, name)?).ok().as_deref() == Some(text.as_str()) {
+        return Ok(Update::UpToDate);
+    }
+    let checksum = match checksum {
+        Some(checksum) => checksum,
+        None => return Ok(Update::Changed),
+    };
+    fetch::verify(text.as_bytes(), checksum).map_err(|e| format!("{}: {}", url, e))?;
+    install_text(profile, name, &text).map_err(|e| format!("{}: {}", url, e))?;
+    Ok(Update::Installed)
+}
+
+/// The URL the installed pack `name` came from, if any.
+pub fn source(profile: &Profile, name: &str) -> Result<Option<String>, String> {
+    let path = source_path(profile, name)?;
+    match fs::read_to_string(&path) {
+        Ok(text) => Ok(Some(text.trim().to_string()).filter(|url| !url.is_empty())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Can't read {}: {}", path.display(), e)),
+    }
+}
+
+/// Where the URL the installed pack `name` came from is kept.
+pub fn source_path(profile: &Profile, name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    Ok(packs_dir(profile)?.join(format!("{}.source", name)))
+}
+
+#[cfg(feature = "network")]
+fn download(url: &str) -> Result<String, String> {
+    let data = fetch::get(url)?;
+    String::from_utf8(data).map_err(|_| format!("{}: not a text file", url))
+}
+
+fn install_text(profile: &Profile, name: &str, text: &str) -> Result<(), String> {
+    Pack::from_json(text)?;
+    let target = pack_path(profile, name)?;
+    let error = |e: std::io::Error| format!("Can't write {}: {}", target.display(), e);
+    fs::create_dir_all(packs_dir(profile)?).map_err(error)?;
+    fs::write(&target, text).map_err(error)
+}
+
+pub fn load(profile: &Profile, name: &str) -> Result<Pack, String> {
+    let path = pack_path(profile, name)?;
+    let text = fs::read_to_string(&path).map_err(|e| format!("No pack {}: {}", name, e))?;
+    Pack::from_json(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The progress through the pack `name`, none if nothing was solved yet.
+pub fn load_progress(profile: &Profile, name: &str) -> Result<Progress, String> {
+    let path = progress_path(profile, name)?;
+    match fs::read_to_string(&path) {
+        Ok(text) => Progress::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Progress::default()),
+        Err(e) => Err(format!("Can't read {}: {}", path.display(), e)),
+    }
+}
+
+pub fn save_progress(profile: &Profile, name: &str, progress: &Progress) -> Result<(), String> {
+    let path = progress_path(profile, name)?;
+    fs::write(&path, progress.to_text()).map_err(|e| format!("Can't write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pack::{Pack, Progress};
+    use std::time::Duration;
+
+    const PACK: &str = "{\"title\":\"First steps\",\"author\":\"Ada\",\"puzzles\":[\
+        {\"title\":\"Late\",\"order\":9,\"board\":[\"*.\",\"..\"]},\
+        {\"title\":\"One-two\",\"author\":\"Bo\",\"rating\":1,\"board\":[\"..*\",\"...\"]}]}";
+
+    #[test]
+    fn packs() {
+        let pack = Pack::from_json(PACK).unwrap();
+        let titles: Vec<&str> = pack.puzzles.iter().map(|puzzle| puzzle.title.as_str()).collect();
+        assert_eq!(vec!["One-two", "Late"], titles);
+        assert_eq!((Some("Bo"), Some("Ada")), (pack.author_of(&pack.puzzles[0]), pack.author_of(&pack.puzzles[1])));
+        assert_eq!(1, pack.puzzles[1].field().unwrap().mine_count());
+        assert_eq!(Ok(pack.clone()), Pack::from_json(&pack.to_json()));
+        let mut edited = pack.puzzles[0].clone();
+        edited.board = "X.\n".into();
+        assert!(edited.field().is_err());
+
+        assert!(Pack::from_json("{\"title\":\"Empty\",\"puzzles\":[]}").is_err());
+        let broken = Pack::from_json("{\"title\":\"T\",\"puzzles\":[{\"title\":\"Bad\",\"board\":[\"..\",\"X.\"]}]}");
+        assert!(broken.unwrap_err().starts_with("Bad: "));
+        assert!(Pack::from_json("{\"title\":\"T\",\"puzzles\":[{\"rating\":6,\"board\":[\"..\"]}]}").is_err());
+    }
+
+    #[test]
+    fn progress() {
+        let mut progress = Progress::default();
+        assert_eq!(Some(0), progress.next(2));
+        assert!(progress.record(0, Duration::from_millis(5_000)));
+        assert!(!progress.record(0, Duration::from_millis(6_000)));
+        assert!(progress.record(0, Duration::from_millis(4_500)));
+        assert_eq!(Some(1), progress.next(2));
+        assert!(progress.record(1, Duration::from_secs(9)));
+        assert_eq!(None, progress.next(2));
+        assert_eq!("1\t4500\n2\t9000\n", progress.to_text());
+        assert_eq!(Ok(progress), Progress::parse("1\t4500\n\n2\t9000\n"));
+        assert!(Progress::parse("0\t100\n").is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//! A game driven by a program instead of a player, one JSON object per line
+//! each way. Before every move the game writes its state:
+//!
+//! ```text
+//! {"turn":0,"rows":9,"cols":9,"mines":10,"mines_left":10,"state":"playing","board":[".........",...]}
+//! ```
+//!
+//! with the board as in `game::Game::board`, and reads an action:
+//!
+//! ```text
+//! {"action":"open","row":4,"col":4}
+//! ```
+//!
+//! Actions are `open`, `flag`, `chord` and `quit`, rows and columns count
+//! from 0. An action that can't be played is answered by the same state
+//! with an `error`. Once the game is won or lost its last state is written
+//! and the game ends, as it does at the end of the input.
+
+use crate::json::Json;
+use crate::game::{Game, State};
+use std::io::{self, BufRead, Write};
+
+/// What a line of input asks for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// `open`, `flag` or `chord` on the cell at row and column.
+    Move(String, u16, u16),
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let json = Json::parse(line)?;
+        let action = json.get("action").and_then(|a| a.as_str()).ok_or("expected an 'action'")?;
+        if action == "quit" {
+            return Ok(Command::Quit);
+        }
+        if !["open", "flag", "chord"].contains(&action) {
+            return Err(format!("unknown action '{}', expected open, flag, chord or quit", action));
+        }
+        let number = |key: &str| json.get(key).and_then(|n| n.as_u64()).filter(|&n| n <= u16::MAX as u64).map(|n| n as u16);
+        let (row, col) = number("row").zip(number("col")).ok_or("expected a 'row' and a 'col'")?;
+        Ok(Command::Move(action.to_string(), row, col))
+    }
+}
+
+/// The state written before every move, with the start cell of boards
+/// without guessing and the reason the last action wasn't played.
+pub fn state(game: &Game, turn: u32, start: Option<(u16, u16)>, error: Option<&str>) -> String {
+    let (cols, rows) = game.field.size();
+    let mut pairs = vec![
+        ("turn".into(), Json::Number(turn as f64)),
+        ("rows".into(), Json::Number(rows as f64)),
+        ("cols".into(), Json::Number(cols as f64)),
+        ("mines".into(), Json::Number(game.field.mine_count() as f64)),
+        ("mines_left".into(), Json::Number(game.field.mines_left() as f64)),
+        ("state".into(), Json::String(game.state.name().into())),
+        ("board".into(), Json::Array(game.board().into_iter().map(Json::String).collect())),
+    ];
+    if let Some((x, y)) = start {
+        pairs.push(("start".into(), Json::Object(vec![
+            ("row".into(), Json::Number(x as f64)),
+            ("col".into(), Json::Number(y as f64)),
+        ])));
+    }
+    if let Some(error) = error {
+        pairs.push(("error".into(), Json::String(error.into())));
+    }
+    Json::Object(pairs).to_string()
+}
+
+/// Plays `game` with the actions read from `input` until it is over, the
+/// program quits or the input ends. Returns where the game stands then.
+pub fn run<R: BufRead, W: Write>(game: &mut Game, start: Option<(u16, u16)>, input: R, mut out: W) -> io::Result<State> {
+    let mut turn = 0;
+    let mut error = None;
+    let mut lines = input.lines();
+    loop {
+        writeln!(out, "{}", state(game, turn, start, error.as_deref()))?;
+        out.flush()?;
+        if game.state != State::Playing {
+            return Ok(game.state);
+        }
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(game.state),
+        };
+        if line.trim().is_empty() {
+            error = None;
+            continue;
+        }
+        error = match Command::parse(&line) {
+            Ok(Command::Quit) => return Ok(game.state),
+            Ok(Command::Move(action, row, col)) => match game.play(&action, row, col) {
+                Ok(_) => {
+                    turn += 1;
+                    None
+                },
+                Err(e) => Some(e.to_string()),
+            },
+            Err(e) => Some(e),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Difficulty, Field};
+    use crate::json::Json;
+    use crate::protocol::{run, Command};
+    use crate::game::{Game, State};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn commands() {
+        assert_eq!(Ok(Command::Move("flag".into(), 2, 3)), Command::parse("{\"action\":\"flag\",\"row\":2,\"col\":3}"));
+        assert_eq!(Ok(Command::Quit), Command::parse("{\"action\":\"quit\"}"));
+        assert!(Command::parse("{\"action\":\"dig\",\"row\":2,\"col\":3}").is_err());
+        assert!(Command::parse("{\"action\":\"open\",\"row\":-1,\"col\":3}").is_err());
+        assert!(Command::parse("open 1 2").is_err());
+    }
+
+    #[test]
+    fn game() {
+        let field = Field::parse("..*\n...\n").unwrap();
+        let mut game = Game::with_field(field, Difficulty::Custom(3, 2, 1), 0, StdRng::seed_from_u64(0), false);
+        let input = "{\"action\":\"open\",\"row\":9,\"col\":0}\n\n{\"action\":\"flag\",\"row\":0,\"col\":2}\n{\"action\":\"open\",\"row\":1,\"col\":0}\n{\"action\":\"open\",\"row\":1,\"col\":2}\n";
+        let mut out = vec![];
+        assert_eq!(State::Won, run(&mut game, None, input.as_bytes(), &mut out).unwrap());
+        let states: Vec<Json> = String::from_utf8(out).unwrap().lines().map(|line| Json::parse(line).unwrap()).collect();
+        assert_eq!(6, states.len());
+        assert!(states[1].get("error").is_some() && states[2].get("error").is_none());
+        let last = &states[5];
+        assert_eq!((Some(3), Some("won")), (last.get("turn").and_then(|t| t.as_u64()), last.get("state").and_then(|s| s.as_str())));
+        assert_eq!(Some("01F"), last.get("board").and_then(|b| b.as_array()).and_then(|b| b[0].as_str()));
+    }
+}
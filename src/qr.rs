@@ -0,0 +1,379 @@
+//! QR codes for challenge codes, so they can be scanned off the terminal
+//! with a phone. Only what short texts need: versions 1 to 6 at error
+//! correction level M, in alphanumeric mode when the text allows it and
+//! byte mode otherwise.
+
+/// Characters of alphanumeric mode, by their value.
+const ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+/// Total codewords, error correction codewords per block and blocks of the
+/// versions 1 to 6 at level M.
+const VERSIONS: [(usize, usize, usize); 6] = [(26, 10, 1), (44, 16, 1), (70, 26, 1), (100, 18, 2), (134, 24, 2), (172, 16, 4)];
+/// Light modules around the code that scanners need to find it.
+const QUIET_ZONE: usize = 4;
+
+/// The modules of a QR code, `true` for the dark ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QrCode {
+    modules: Vec<Vec<bool>>,
+}
+
+impl QrCode {
+    /// The smallest code holding `text`.
+    pub fn encode(text: &str) -> Result<Self, String> {
+        let (version, data) = codewords(text)?;
+        let (total, ec, blocks) = VERSIONS[version - 1];
+        let per_block = (total - ec * blocks) / blocks;
+        let divisor = rs_divisor(ec);
+        let data_blocks: Vec<&[u8]> = data.chunks(per_block).collect();
+        let ec_blocks: Vec<Vec<u8>> = data_blocks.iter().map(|block| rs_remainder(block, &divisor)).collect();
+        let mut interleaved = Vec::with_capacity(total);
+        for i in 0..per_block {
+            interleaved.extend(data_blocks.iter().map(|block| block[i]));
+        }
+        for i in 0..ec {
+            interleaved.extend(ec_blocks.iter().map(|block| block[i]));
+        }
+
+        let mut matrix = Matrix::new(version);
+        matrix.draw_codewords(&interleaved);
+        let best = (0..8)
+            .map(|mask| {
+                let mut masked = matrix.clone();
+                masked.apply_mask(mask);
+                masked.draw_format(mask);
+                masked
+            })
+            .min_by_key(|masked| masked.penalty())
+            .unwrap();
+        Ok(Self { modules: best.modules })
+    }
+
+    /// Modules per side.
+    pub fn size(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y][x]
+    }
+
+    /// Lines for a terminal with two rows of modules in every character and
+    /// a quiet zone around them. Dark modules are drawn with block
+    /// characters, so the lines belong in black on white.
+    pub fn render(&self) -> Vec<String> {
+        let size = self.size() + 2 * QUIET_ZONE;
+        let dark = |x: usize, y: usize| {
+            x >= QUIET_ZONE && y >= QUIET_ZONE && x < size - QUIET_ZONE && y < size - QUIET_ZONE
+                && self.modules[y - QUIET_ZONE][x - QUIET_ZONE]
+        };
+        (0..size).step_by(2)
+            .map(|y| (0..size)
+                .map(|x| match (dark(x, y), y + 1 < size && dark(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect())
+            .collect()
+    }
+}
+
+/// The smallest version holding `text` with its data codewords, padded to
+/// fill the version.
+fn codewords(text: &str) -> Result<(usize, Vec<u8>), String> {
+    let alphanumeric = text.chars().all(|c| ALPHANUMERIC.contains(c));
+    let mut bits = vec![];
+    let push = |bits: &mut Vec<bool>, value: usize, len: usize| {
+        bits.extend((0..len).rev().map(|i| value >> i & 1 == 1));
+    };
+    if alphanumeric {
+        push(&mut bits, 0b0010, 4);
+        push(&mut bits, text.len(), 9);
+        let values: Vec<usize> = text.chars().map(|c| ALPHANUMERIC.find(c).unwrap()).collect();
+        for pair in values.chunks(2) {
+            match pair {
+                [a, b] => push(&mut bits, a * 45 + b, 11),
+                [a] => push(&mut bits, *a, 6),
+                _ => unreachable!(),
+            }
+        }
+    } else {
+        push(&mut bits, 0b0100, 4);
+        push(&mut bits, text.len(), 8);
+        for &byte in text.as_bytes() {
+            push(&mut bits, byte as usize, 8);
+        }
+    }
+    // the length has to fit its count, which has as many bits up to version 9
+    let (version, capacity) = VERSIONS.iter()
+        .map(|(total, ec, blocks)| (total - ec * blocks) * 8)
+        .enumerate()
+        .find(|&(_, capacity)| bits.len() <= capacity && text.len() < 256)
+        .map(|(version, capacity)| (version + 1, capacity))
+        .ok_or_else(|| format!("'{}' is too long for a QR code", text))?;
+    let terminator = (capacity - bits.len()).min(4);
+    bits.extend(std::iter::repeat_n(false, terminator));
+    while bits.len() % 8 != 0 {
+        bits.push(false);
+    }
+    let mut data: Vec<u8> = bits.chunks(8).map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8)).collect();
+    for pad in [0xec, 0x11].iter().cycle() {
+        if data.len() * 8 >= capacity {
+            break;
+        }
+        data.push(*pad);
+    }
+    Ok((version, data))
+}
+
+/// Product of `a` and `b` in GF(256) modulo x^8 + x^4 + x^3 + x^2 + 1.
+fn gf_mul(a: u8, b: u8) -> u8 {
+    let (mut a, mut b, mut product) = (a as u16, b, 0u16);
+    while b > 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        a <<= 1;
+        if a & 0x100 != 0 {
+            a ^= 0x11d;
+        }
+        b >>= 1;
+    }
+    product as u8
+}
+
+/// The Reed-Solomon generator polynomial of `degree`, without its leading
+/// coefficient.
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_mul(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_mul(root, 2);
+    }
+    divisor
+}
+
+/// The error correction codewords of `data`.
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (r, &d) in remainder.iter_mut().zip(divisor) {
+            *r ^= gf_mul(d, factor);
+        }
+    }
+    remainder
+}
+
+/// A code being drawn, with the modules of the finder, timing, alignment
+/// and format patterns set apart from the data.
+#[derive(Clone)]
+struct Matrix {
+    modules: Vec<Vec<bool>>,
+    function: Vec<Vec<bool>>,
+}
+
+impl Matrix {
+    fn new(version: usize) -> Self {
+        let size = 17 + 4 * version;
+        let mut matrix = Self { modules: vec![vec![false; size]; size], function: vec![vec![false; size]; size] };
+        for i in 0..size {
+            matrix.set(6, i, i % 2 == 0);
+            matrix.set(i, 6, i % 2 == 0);
+        }
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4isize..=4 {
+                for dx in -4isize..=4 {
+                    let (x, y) = (cx as isize + dx, cy as isize + dy);
+                    if (0..size as isize).contains(&x) && (0..size as isize).contains(&y) {
+                        let distance = dx.abs().max(dy.abs());
+                        matrix.set(x as usize, y as usize, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+        // versions up to 6 have one alignment pattern, clear of the finders
+        if version > 1 {
+            let at = size - 7;
+            for dy in -2isize..=2 {
+                for dx in -2isize..=2 {
+                    matrix.set((at as isize + dx) as usize, (at as isize + dy) as usize, dx.abs().max(dy.abs()) != 1);
+                }
+            }
+        }
+        // reserve the format modules until the mask is known
+        matrix.draw_format(0);
+        matrix
+    }
+
+    fn size(&self) -> usize {
+        self.modules.len()
+    }
+
+    /// Sets a function module at column `x` and row `y`.
+    fn set(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y][x] = dark;
+        self.function[y][x] = true;
+    }
+
+    /// The error correction level and mask with their BCH code, twice.
+    fn draw_format(&mut self, mask: u16) {
+        // level M is 00
+        let data = mask;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let bit = |i: usize| bits >> i & 1 == 1;
+        let size = self.size();
+        for i in 0..6 {
+            self.set(8, i, bit(i));
+        }
+        self.set(8, 7, bit(6));
+        self.set(8, 8, bit(7));
+        self.set(7, 8, bit(8));
+        for i in 9..15 {
+            self.set(14 - i, 8, bit(i));
+        }
+        for i in 0..8 {
+            self.set(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set(8, size - 15 + i, bit(i));
+        }
+        self.set(8, size - 8, true);
+    }
+
+    /// Fills the data modules in the zigzag of two columns, up and down
+    /// from the bottom right, leaving the remainder bits light.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size();
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            // the vertical timing pattern takes a whole column
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vertical in 0..size {
+                let y = if upward { size - 1 - vertical } else { vertical };
+                for x in [right, right - 1] {
+                    if !self.function[y][x] && i < codewords.len() * 8 {
+                        self.modules[y][x] = codewords[i / 8] >> (7 - i % 8) & 1 == 1;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 3 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    fn apply_mask(&mut self, mask: u16) {
+        let size = self.size();
+        for y in 0..size {
+            for x in 0..size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if invert && !self.function[y][x] {
+                    self.modules[y][x] = !self.modules[y][x];
+                }
+            }
+        }
+    }
+
+    /// How hard the code is to scan, after the rules of the standard for
+    /// picking a mask.
+    fn penalty(&self) -> usize {
+        let size = self.size();
+        let rows = (0..size).map(|y| (0..size).map(|x| self.modules[y][x]).collect::<Vec<_>>());
+        let cols = (0..size).map(|x| (0..size).map(|y| self.modules[y][x]).collect::<Vec<_>>());
+        let finder = [true, false, true, true, true, false, true, false, false, false, false];
+        let mut penalty = 0;
+        for line in rows.chain(cols) {
+            let mut run = 1;
+            for i in 1..=size {
+                if i < size && line[i] == line[i - 1] {
+                    run += 1;
+                    continue;
+                }
+                if run >= 5 {
+                    penalty += run - 2;
+                }
+                run = 1;
+            }
+            for window in line.windows(finder.len()) {
+                if window == finder || window.iter().rev().eq(finder.iter()) {
+                    penalty += 40;
+                }
+            }
+        }
+        for y in 1..size {
+            for x in 1..size {
+                let color = self.modules[y][x];
+                if self.modules[y - 1][x] == color && self.modules[y][x - 1] == color && self.modules[y - 1][x - 1] == color {
+                    penalty += 3;
+                }
+            }
+        }
+        let dark = self.modules.iter().flatten().filter(|&&dark| dark).count();
+        let percent = dark * 100 / (size * size);
+        penalty + percent.abs_diff(50) / 5 * 10
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qr::{codewords, rs_divisor, rs_remainder, Matrix, QrCode};
+
+    #[test]
+    fn hello_world() {
+        let (version, data) = codewords("HELLO WORLD").unwrap();
+        assert_eq!(1, version);
+        assert_eq!(vec![32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17], data);
+        assert_eq!(vec![196, 35, 39, 119, 235, 215, 231, 226, 93, 23], rs_remainder(&data, &rs_divisor(10)));
+    }
+
+    #[test]
+    fn format() {
+        let mut matrix = Matrix::new(1);
+        matrix.draw_format(0);
+        // 101010000010010 from the top left down the column next to the finder
+        let column: Vec<bool> = [0, 1, 2, 3, 4, 5, 7, 8].iter().map(|&y| matrix.modules[y][8]).collect();
+        assert_eq!(vec![false, true, false, false, true, false, false, false], column);
+        assert!(matrix.modules[21 - 8][8]);
+    }
+
+    #[test]
+    fn codes() {
+        let code = QrCode::encode("E-18446744073709551615-NG").unwrap();
+        assert_eq!(25, code.size());
+        // the finders and the dark module
+        assert!(code.is_dark(0, 0) && code.is_dark(24, 0) && code.is_dark(0, 24) && code.is_dark(8, 17));
+        assert!(!code.is_dark(7, 7) && !code.is_dark(1, 1));
+        assert_eq!(17, code.render().len());
+        assert_eq!(21, QrCode::encode("b-1").unwrap().size());
+        assert!(QrCode::encode(&"x".repeat(200)).is_err());
+    }
+}
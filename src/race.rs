@@ -0,0 +1,456 @@
+//! Two players racing on the same board over a TCP connection. Both sides
+//! send lines of text:
+//!
+//! * `hello <name>` once connected,
+//! * `handicap <player> <handicap>` from the host for every player given one,
+//!   the host being player 0,
+//! * `start <seed> <difficulty>` from the host, after which both build their
+//!   board with `handicapped_board`,
+//! * `progress <opened cells>` once the boards are built and after every
+//!   move,
+//! * `won <ms>` or `lost <ms>` with the time into the race,
+//! * `quit` when leaving early.
+//!
+//! Lines are read like in co-op games, see `coop::read_line`, and a guest
+//! turns down boards of more than `server::MAX_CELLS` cells. A `Session`
+//! plays one side of a race.
+
+use crate::{CellValue, Difficulty, Field, MinesError};
+use crate::coop::read_line;
+use crate::handicap::Handicap;
+use crate::replay::Action;
+use crate::server::MAX_CELLS;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::fmt;
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Hello(String),
+    Handicap(usize, Handicap),
+    Start(u64, Difficulty),
+    Progress(usize),
+    Won(Duration),
+    Lost(Duration),
+    Quit,
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Hello(name) => write!(f, "hello {}", name),
+            Message::Handicap(player, handicap) => write!(f, "handicap {} {}", player, handicap),
+            Message::Start(seed, difficulty) => write!(f, "start {} {}", seed, difficulty),
+            Message::Progress(opened) => write!(f, "progress {}", opened),
+            Message::Won(time) => write!(f, "won {}", time.as_millis()),
+            Message::Lost(time) => write!(f, "lost {}", time.as_millis()),
+            Message::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (word, rest) = s.split_once(' ').unwrap_or((s, ""));
+        let invalid = || format!("invalid message '{}'", s);
+        let millis = |rest: &str| rest.parse().map(Duration::from_millis).map_err(|_| invalid());
+        match word {
+            "hello" if !rest.is_empty() => Ok(Message::Hello(rest.into())),
+            "handicap" => {
+                let (player, handicap) = rest.split_once(' ').ok_or_else(invalid)?;
+                Ok(Message::Handicap(player.parse().map_err(|_| invalid())?, handicap.parse()?))
+            },
+            "start" => {
+                let (seed, difficulty) = rest.split_once(' ').ok_or_else(invalid)?;
+                Ok(Message::Start(seed.parse().map_err(|_| invalid())?, difficulty.parse()?))
+            },
+            "progress" => Ok(Message::Progress(rest.parse().map_err(|_| invalid())?)),
+            "won" => Ok(Message::Won(millis(rest)?)),
+            "lost" => Ok(Message::Lost(millis(rest)?)),
+            "quit" => Ok(Message::Quit),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The board of a race, the same on both sides: generated from the seed
+/// with a cell already opened, an opening if there is one, so neither
+/// player has to guess first and no mine has to move.
+pub fn board(difficulty: Difficulty, seed: u64) -> Result<Field, MinesError> {
+    let (width, height, mines) = difficulty.dimensions();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut field = Field::generate(&mut rng, height, width, mines)?;
+    let safe: Vec<(u16, u16)> = field.cells.iter().enumerate()
+        .flat_map(|(x, row)| row.iter().enumerate()
+            .filter(|(_, cell)| cell.value == CellValue::Water)
+            .map(move |(y, _)| (x as u16, y as u16)))
+        .collect();
+    let zeros: Vec<(u16, u16)> = safe.iter().copied().filter(|&(x, y)| field.numbers[x as usize][y as usize] == 0).collect();
+    let pool = if zeros.is_empty() { &safe } else { &zeros };
+    let (x, y) = pool[rng.gen_range(0, pool.len())];
+    field.open(x, y)?;
+    Ok(field)
+}
+
+/// Whether a board of `difficulty` has more cells than a guest plays.
+pub(crate) fn too_big(difficulty: Difficulty) -> bool {
+    let (width, height, _) = difficulty.dimensions();
+    width as usize * height as usize > MAX_CELLS
+}
+
+/// The board of a player with `handicap` in a race on `difficulty` and
+/// `seed`: with fewer mines for a lower density and the extra cells opened
+/// the same way on both sides.
+pub fn handicapped_board(difficulty: Difficulty, seed: u64, handicap: &Handicap) -> Result<Field, MinesError> {
+    let mut field = board(handicap.difficulty(difficulty), seed)?;
+    handicap.apply(&mut field, &mut StdRng::seed_from_u64(seed));
+    Ok(field)
+}
+
+/// How far a player got.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    /// With the cells opened so far.
+    Playing(usize),
+    Won(Duration),
+    Lost(Duration),
+    /// Left the race or lost the connection.
+    Gone,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Won,
+    Lost,
+    Draw,
+}
+
+/// Both sides of a race, as one player sees it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Race {
+    pub own: Status,
+    pub opponent: Status,
+}
+
+impl Race {
+    pub fn new(opened: usize) -> Self {
+        Self { own: Status::Playing(opened), opponent: Status::Playing(opened) }
+    }
+
+    /// Takes a message from the opponent. Once they finished nothing they
+    /// send changes the race anymore.
+    pub fn receive(&mut self, message: &Message) {
+        if !matches!(self.opponent, Status::Playing(_)) {
+            return;
+        }
+        self.opponent = match *message {
+            Message::Progress(opened) => Status::Playing(opened),
+            Message::Won(time) => Status::Won(time),
+            Message::Lost(time) => Status::Lost(time),
+            Message::Quit => Status::Gone,
+            _ => self.opponent,
+        };
+    }
+
+    /// Who won, once it is decided: the first to clear the board, or else
+    /// the one who stayed alive longest. Leaving hands the race over.
+    pub fn verdict(&self) -> Option<Verdict> {
+        use Status::*;
+        let compare = |own: Duration, opponent: Duration, sooner: Verdict, later: Verdict| match own.cmp(&opponent) {
+            std::cmp::Ordering::Less => sooner,
+            std::cmp::Ordering::Greater => later,
+            std::cmp::Ordering::Equal => Verdict::Draw,
+        };
+        match (self.own, self.opponent) {
+            (Won(own), Won(opponent)) => Some(compare(own, opponent, Verdict::Won, Verdict::Lost)),
+            (Lost(own), Lost(opponent)) => Some(compare(own, opponent, Verdict::Lost, Verdict::Won)),
+            (Won(_), _) | (_, Lost(_)) | (_, Gone) => Some(Verdict::Won),
+            (Lost(_), _) | (_, Won(_)) | (Gone, _) => Some(Verdict::Lost),
+            (Playing(_), Playing(_)) => None,
+        }
+    }
+}
+
+/// How long the opponent has to tell where their board starts.
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A race as one player sees it, with the connection to the opponent.
+pub struct Session {
+    pub opponent: String,
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    /// Handicaps of this player and of the opponent.
+    pub own: Handicap,
+    pub theirs: Handicap,
+    pub field: Field,
+    pub state: Race,
+    messages: Receiver<Message>,
+    writer: TcpStream,
+}
+
+impl Session {
+    /// Waits for an opponent on `listener` and races them on the board of
+    /// `difficulty` and `seed`, with the handicaps of the host and the
+    /// opponent.
+    pub fn host(listener: &TcpListener, name: &str, difficulty: Difficulty, seed: u64, handicaps: [Handicap; 2]) -> Result<Self, String> {
+        let (stream, _) = listener.accept().map_err(|e| e.to_string())?;
+        Self::start(stream, name, Some((seed, difficulty, handicaps)))
+    }
+
+    /// Races the host at `address` on their board.
+    pub fn join(address: &str, name: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address).map_err(|e| format!("Can't connect to {}: {}", address, e))?;
+        Self::start(stream, name, None)
+    }
+
+    /// Greets the opponent and agrees on the board, sent by the host.
+    fn start(stream: TcpStream, name: &str, board: Option<(u64, Difficulty, [Handicap; 2])>) -> Result<Self, String> {
+        let mut writer = stream.try_clone().map_err(|e| e.to_string())?;
+        let mut reader = io::BufReader::new(stream);
+        let mut receive = || -> Result<Message, String> {
+            let mut line = String::new();
+            match read_line(&mut reader, &mut line) {
+                Ok(0) | Err(_) => Err("The opponent left".into()),
+                Ok(_) => line.parse(),
+            }
+        };
+        let mut send = |message: &Message| writeln!(writer, "{}", message).map_err(|e| format!("Can't reach the opponent: {}", e));
+        send(&Message::Hello(name.trim().to_string()))?;
+        let opponent = match receive()? {
+            Message::Hello(name) => name,
+            other => return Err(format!("Expected a greeting, got '{}'", other)),
+        };
+        let (seed, difficulty, [host, guest], own) = match board {
+            Some((seed, difficulty, handicaps)) => {
+                for (player, handicap) in handicaps.iter().enumerate().filter(|(_, handicap)| **handicap != Handicap::default()) {
+                    send(&Message::Handicap(player, handicap.clone()))?;
+                }
+                send(&Message::Start(seed, difficulty))?;
+                (seed, difficulty, handicaps, 0)
+            },
+            None => {
+                let mut handicaps = [Handicap::default(), Handicap::default()];
+                loop {
+                    match receive()? {
+                        Message::Handicap(player, handicap) if player < handicaps.len() => handicaps[player] = handicap,
+                        Message::Start(_, difficulty) if too_big(difficulty) => {
+                            return Err(format!("The host picked a board of more than {} cells", MAX_CELLS));
+                        },
+                        Message::Start(seed, difficulty) => break (seed, difficulty, handicaps, 1),
+                        other => return Err(format!("Expected the board, got '{}'", other)),
+                    }
+                }
+            },
+        };
+        let (own, theirs) = if own == 0 { (host, guest) } else { (guest, host) };
+        let field = handicapped_board(difficulty, seed, &own).map_err(|e| e.to_string())?;
+        let (sender, messages) = channel();
+        thread::spawn(move || {
+            let mut line = String::new();
+            while let Ok(1..) = read_line(&mut reader, &mut line) {
+                if let Ok(message) = line.parse::<Message>() {
+                    if sender.send(message).is_err() {
+                        return;
+                    }
+                }
+                line.clear();
+            }
+            let _ = sender.send(Message::Quit);
+        });
+        let mut session = Self { opponent, difficulty, seed, own, theirs, state: Race::new(field.opened_count()), field, messages, writer };
+        // the boards of players with handicaps start further along
+        session.send(Message::Progress(session.field.opened_count()));
+        if let Ok(message) = session.messages.recv_timeout(START_TIMEOUT) {
+            session.state.receive(&message);
+        }
+        Ok(session)
+    }
+
+    fn send(&mut self, message: Message) {
+        let _ = writeln!(self.writer, "{}", message);
+    }
+
+    /// Safe cells of the own board and of the opponent's.
+    pub fn water(&self) -> (usize, usize) {
+        let (width, height, _) = self.difficulty.dimensions();
+        let (_, _, mines) = self.theirs.difficulty(self.difficulty).dimensions();
+        let own = self.field.cell_count() - self.field.mine_count();
+        (own, width as usize * height as usize - mines as usize)
+    }
+
+    /// Takes what the opponent sent in the meantime, without waiting.
+    pub fn poll(&mut self) {
+        while let Ok(message) = self.messages.try_recv() {
+            self.state.receive(&message);
+        }
+    }
+
+    /// Plays `action`, one of open, flag or chord, `elapsed` into the race
+    /// and tells the opponent how far it got.
+    pub fn play(&mut self, action: Action, x: u16, y: u16, elapsed: Duration) -> Result<(), MinesError> {
+        let result = match action {
+            Action::Open => self.field.open(x, y),
+            Action::Flag => self.field.flag(x, y),
+            Action::Chord => self.field.chord(x, y),
+            _ => return Ok(()),
+        };
+        match result {
+            Err(MinesError::MineOpened) => {
+                self.state.own = Status::Lost(elapsed);
+                self.send(Message::Lost(elapsed));
+            },
+            Err(e) => return Err(e),
+            Ok(()) if self.field.is_won() => {
+                // the time bonus counts for clearing the board, not for staying alive
+                let time = self.own.adjusted_time(elapsed);
+                self.state.own = Status::Won(time);
+                self.send(Message::Won(time));
+            },
+            Ok(()) => self.send(Message::Progress(self.field.opened_count())),
+        }
+        Ok(())
+    }
+
+    /// Leaves the race, letting the opponent know if this side was still
+    /// playing.
+    pub fn leave(&mut self) {
+        if matches!(self.state.own, Status::Playing(_)) {
+            self.send(Message::Quit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::coop::MAX_LINE;
+    use crate::handicap::Handicap;
+    use crate::race::{board, handicapped_board, Message, Race, Session, Status, Verdict};
+    use crate::replay::Action;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn messages() {
+        let messages = vec![
+            Message::Hello("Ada Lovelace".into()),
+            Message::Handicap(1, "reveals=3,time=20".parse().unwrap()),
+            Message::Handicap(0, Handicap::default()),
+            Message::Start(u64::MAX, Difficulty::Custom(20, 10, 30)),
+            Message::Progress(42),
+            Message::Won(Duration::from_millis(61_500)),
+            Message::Lost(Duration::from_millis(900)),
+            Message::Quit,
+        ];
+        for message in messages {
+            assert_eq!(Ok(message.clone()), message.to_string().parse());
+        }
+        assert!("start 1 hard".parse::<Message>().is_err());
+        assert!("progress".parse::<Message>().is_err());
+        assert!("hello".parse::<Message>().is_err());
+    }
+
+    #[test]
+    fn same_board() {
+        let a = board(Difficulty::Expert, 7).unwrap();
+        assert_eq!(a.to_board_file(), board(Difficulty::Expert, 7).unwrap().to_board_file());
+        assert!(a.opened_count() > 0 && a.opened_mine().is_none());
+    }
+
+    #[test]
+    fn handicapped() {
+        let plain = board(Difficulty::Expert, 7).unwrap();
+        assert_eq!(plain.to_board_file(), handicapped_board(Difficulty::Expert, 7, &Handicap::default()).unwrap().to_board_file());
+        let handicap = Handicap { extra_reveals: 2, density_reduction: 5, ..Handicap::default() };
+        let easier = handicapped_board(Difficulty::Expert, 7, &handicap).unwrap();
+        assert_eq!(75, easier.mine_count());
+        assert!(easier.opened_count() > board(Difficulty::Custom(30, 16, 75), 7).unwrap().opened_count());
+        assert!(easier.opened_mine().is_none());
+        assert_eq!(easier.to_board_file(), handicapped_board(Difficulty::Expert, 7, &handicap).unwrap().to_board_file());
+    }
+
+    #[test]
+    fn verdicts() {
+        let secs = Duration::from_secs;
+        let mut race = Race::new(5);
+        race.receive(&Message::Progress(20));
+        assert_eq!((Status::Playing(20), None), (race.opponent, race.verdict()));
+        race.receive(&Message::Lost(secs(30)));
+        assert_eq!(Some(Verdict::Won), race.verdict());
+        race.own = Status::Lost(secs(20));
+        assert_eq!(Some(Verdict::Lost), race.verdict());
+        race.own = Status::Won(secs(40));
+        assert_eq!(Some(Verdict::Won), race.verdict());
+
+        let mut race = Race::new(5);
+        race.receive(&Message::Won(secs(50)));
+        assert_eq!(Some(Verdict::Lost), race.verdict());
+        race.own = Status::Won(secs(50));
+        assert_eq!(Some(Verdict::Draw), race.verdict());
+        race.receive(&Message::Quit);
+        assert_eq!(Some(Verdict::Draw), race.verdict());
+
+        let mut race = Race::new(5);
+        race.receive(&Message::Quit);
+        assert_eq!((Status::Gone, Some(Verdict::Won)), (race.opponent, race.verdict()));
+    }
+
+    #[test]
+    fn race_over_tcp() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handicap = Handicap { extra_reveals: 1, density_reduction: 5, ..Handicap::default() };
+        let guest = thread::spawn(move || Session::join(&address, "bo").unwrap());
+        let handicaps = [Handicap::default(), handicap.clone()];
+        let mut host = Session::host(&listener, "ada", Difficulty::Beginner, 3, handicaps).unwrap();
+        let mut guest = guest.join().unwrap();
+        assert_eq!(("bo", "ada"), (host.opponent.as_str(), guest.opponent.as_str()));
+        assert_eq!((Handicap::default(), handicap.clone()), (host.own.clone(), host.theirs.clone()));
+        assert_eq!((handicap, Handicap::default()), (guest.own.clone(), guest.theirs.clone()));
+        assert_eq!(guest.field.to_board_file(), handicapped_board(Difficulty::Beginner, 3, &guest.own).unwrap().to_board_file());
+        // both know where the other starts
+        assert_eq!(Status::Playing(guest.field.opened_count()), host.state.opponent);
+        assert_eq!(host.water(), (guest.water().1, guest.water().0));
+
+        let mine = (0..81).map(|i| (i / 9, i % 9)).find(|&(x, y)| host.field.cell(x, y).unwrap().is_mine()).unwrap();
+        host.play(Action::Open, mine.0, mine.1, Duration::from_secs(3)).unwrap();
+        assert_eq!(Status::Lost(Duration::from_secs(3)), host.state.own);
+        let start = Instant::now();
+        while guest.state.verdict().is_none() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            guest.poll();
+        }
+        assert_eq!(Some(Verdict::Won), guest.state.verdict());
+        guest.leave();
+        assert_eq!(Status::Playing(guest.field.opened_count()), guest.state.own);
+    }
+
+    #[test]
+    fn hostile_host() {
+        let huge = Message::Start(1, Difficulty::Custom(u16::MAX, u16::MAX, 10));
+        let endless = format!("hello {}", "a".repeat(MAX_LINE));
+        for (lines, refusal) in [(format!("hello eve\n{}\n", huge), "more than"), (endless, "left")] {
+            let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let host = thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let _ = stream.write_all(lines.as_bytes());
+                stream
+            });
+            let error = Session::join(&address, "bo").err().unwrap();
+            assert!(error.contains(refusal), "{}", error);
+            drop(host.join().unwrap());
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use crate::Field;
+use crate::handicap::Handicap;
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+/// Moves and opened cells attributed to one relay player.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerStats {
+    pub moves: u32,
+    pub cells_opened: usize,
+    pub flags: u32,
+}
+
+/// Points for every flag left on a mine at the end of a hotseat game, taken
+/// away for every flag left on water.
+pub const FLAG_POINTS: i64 = 3;
+/// Points lost by the player who opens a mine in a hotseat game.
+pub const MINE_PENALTY: i64 = 20;
+
+/// Hot-seat relay where players take turns on one board, handing over after
+/// a number of moves or when their time is up.
+pub struct Relay {
+    moves_per_turn: Option<u32>,
+    turn_length: Option<Duration>,
+    /// Index of the player whose turn it is.
+    pub current: usize,
+    moves_in_turn: u32,
+    turn_started: Instant,
+    /// Time added to every turn of a player, from their handicap.
+    bonus: Vec<Duration>,
+    pub stats: Vec<PlayerStats>,
+    /// Chat lines by player, said since the last hand-over.
+    pub messages: Vec<(usize, String)>,
+}
+
+impl Relay {
+    /// Starts a relay for `players` players with the first player's turn
+    /// beginning at `now`.
+    pub fn new(players: usize, moves_per_turn: Option<u32>, turn_length: Option<Duration>, now: Instant) -> Self {
+        Self {
+            moves_per_turn,
+            turn_length,
+            current: 0,
+            moves_in_turn: 0,
+            turn_started: now,
+            bonus: vec![Duration::ZERO; players.max(1)],
+            stats: vec![PlayerStats::default(); players.max(1)],
+            messages: vec![],
+        }
+    }
+
+    fn hand_over(&mut self, now: Instant) {
+        self.current = (self.current + 1) % self.stats.len();
+        self.moves_in_turn = 0;
+        self.turn_started = now;
+    }
+
+    /// Hands over to the next player if the current turn ran out of time.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        match self.turn_length {
+            Some(length) if now.duration_since(self.turn_started) > length + self.bonus[self.current] => {
+                self.hand_over(now);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Gives `player` a head start on the board everybody shares: the safe
+    /// cells `handicap` opens on `field` count as theirs, and their timed
+    /// turns last its time bonus longer. A lower density would need a board
+    /// of their own.
+    pub fn handicap<R: Rng>(&mut self, player: usize, handicap: &Handicap, field: &mut Field, rng: &mut R) -> Result<(), String> {
+        if handicap.density_reduction > 0 {
+            return Err("Players of a relay share the board, so its density is the same for all".into());
+        }
+        let stats = self.stats.get_mut(player).ok_or_else(|| format!("There is no player {}", player + 1))?;
+        let opened = field.opened_count();
+        handicap.apply(field, rng);
+        stats.cells_opened += field.opened_count() - opened;
+        self.bonus[player] = handicap.time_bonus;
+        Ok(())
+    }
+
+    /// Counts a flag placed by the current player.
+    pub fn record_flag(&mut self) {
+        self.stats[self.current].flags += 1;
+    }
+
+    /// Leaves a chat line from the current player for the others.
+    pub fn say(&mut self, text: &str) {
+        self.messages.push((self.current, text.trim().to_string()));
+    }
+
+    /// Attributes a move to the current player and hands over once the
+    /// player used up their moves.
+    pub fn record_move(&mut self, cells_opened: usize, now: Instant) -> bool {
+        let stats = &mut self.stats[self.current];
+        stats.moves += 1;
+        stats.cells_opened += cells_opened;
+        self.moves_in_turn += 1;
+        if self.moves_per_turn.is_some_and(|moves| self.moves_in_turn >= moves) {
+            self.hand_over(now);
+            return true;
+        }
+        false
+    }
+
+    /// Points of every player of a hotseat game: one for each cell they
+    /// opened and `FLAG_POINTS` for each flag they left, added for a flag on
+    /// a mine and taken away for one on water. `flags` holds the owner of
+    /// every flag and whether it is on a mine, `hit_by` who opened a mine.
+    pub fn scores(&self, flags: &[(usize, bool)], hit_by: Option<usize>) -> Vec<i64> {
+        let mut scores: Vec<i64> = self.stats.iter().map(|stats| stats.cells_opened as i64).collect();
+        for &(owner, on_mine) in flags {
+            if let Some(score) = scores.get_mut(owner) {
+                *score += if on_mine { FLAG_POINTS } else { -FLAG_POINTS };
+            }
+        }
+        if let Some(score) = hit_by.and_then(|player| scores.get_mut(player)) {
+            *score -= MINE_PENALTY;
+        }
+        scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Field;
+    use crate::handicap::Handicap;
+    use crate::relay::{PlayerStats, Relay, FLAG_POINTS, MINE_PENALTY};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn hand_over_after_moves() {
+        let now = Instant::now();
+        let mut relay = Relay::new(2, Some(2), None, now);
+        assert!(!relay.record_move(3, now));
+        assert!(relay.record_move(1, now));
+        assert_eq!(1, relay.current);
+        assert!(!relay.record_move(5, now));
+        assert!(relay.record_move(0, now));
+        assert_eq!(0, relay.current);
+        assert_eq!(vec![
+            PlayerStats { moves: 2, cells_opened: 4, flags: 0 },
+            PlayerStats { moves: 2, cells_opened: 5, flags: 0 },
+        ], relay.stats);
+    }
+
+    #[test]
+    fn hand_over_after_time() {
+        let now = Instant::now();
+        let mut relay = Relay::new(3, None, Some(Duration::from_secs(10)), now);
+        assert!(!relay.record_move(1, now));
+        assert!(!relay.expire(now + Duration::from_secs(5)));
+        assert!(relay.expire(now + Duration::from_secs(11)));
+        assert_eq!(1, relay.current);
+        assert!(!relay.expire(now + Duration::from_secs(12)));
+    }
+
+    #[test]
+    fn handicap() {
+        let now = Instant::now();
+        let mut relay = Relay::new(2, None, Some(Duration::from_secs(10)), now);
+        let mut field = Field::parse("*....\n.....\n....*").unwrap();
+        let handicap = Handicap { extra_reveals: 1, time_bonus: Duration::from_secs(5), ..Handicap::default() };
+        relay.handicap(1, &handicap, &mut field, &mut rand::thread_rng()).unwrap();
+        assert_eq!(field.opened_count(), relay.stats[1].cells_opened);
+        assert!(field.opened_count() > 0 && field.opened_mine().is_none());
+        assert!(relay.expire(now + Duration::from_secs(11)));
+        assert!(!relay.expire(now + Duration::from_secs(22)));
+        assert!(relay.expire(now + Duration::from_secs(27)));
+        let denser = Handicap { density_reduction: 2, ..Handicap::default() };
+        assert!(relay.handicap(0, &denser, &mut field, &mut rand::thread_rng()).is_err());
+        assert!(relay.handicap(2, &handicap, &mut field, &mut rand::thread_rng()).is_err());
+    }
+
+    #[test]
+    fn flags_and_chat() {
+        let now = Instant::now();
+        let mut relay = Relay::new(2, Some(1), None, now);
+        relay.say(" watch the corner ");
+        relay.record_flag();
+        relay.record_move(0, now);
+        relay.record_flag();
+        relay.record_flag();
+        assert_eq!(vec![1, 2], relay.stats.iter().map(|stats| stats.flags).collect::<Vec<_>>());
+        assert_eq!(vec![(0, "watch the corner".to_string())], relay.messages);
+    }
+
+    #[test]
+    fn hotseat_scores() {
+        let now = Instant::now();
+        let mut relay = Relay::new(2, Some(1), None, now);
+        relay.record_move(12, now);
+        relay.record_move(4, now);
+        assert_eq!(vec![12, 4], relay.scores(&[], None));
+        let flags = [(0, true), (0, false), (1, true), (1, true), (5, true)];
+        assert_eq!(vec![12, 4 + 2 * FLAG_POINTS - MINE_PENALTY], relay.scores(&flags, Some(1)));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{Difficulty, Field, MinesError};
+use crate::history::History;
+use crate::json::Json;
+use crate::solver::{constraint_deductions, Annotation, Deduction};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A move a player can make on a cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Open,
+    Flag,
+    Chord,
+    /// A cell pointed out by the solver, not a move on the board.
+    Hint,
+    /// Takes back the last move, recorded without a cell.
+    Undo,
+    Redo,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Open => write!(f, "open"),
+            Action::Flag => write!(f, "flag"),
+            Action::Chord => write!(f, "chord"),
+            Action::Hint => write!(f, "hint"),
+            Action::Undo => write!(f, "undo"),
+            Action::Redo => write!(f, "redo"),
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Action::Open),
+            "flag" => Ok(Action::Flag),
+            "chord" => Ok(Action::Chord),
+            "hint" => Ok(Action::Hint),
+            "undo" => Ok(Action::Undo),
+            "redo" => Ok(Action::Redo),
+            other => Err(format!("unknown action '{}'", other)),
+        }
+    }
+}
+
+/// Writes a game as JSON lines: a header with everything needed to rebuild
+/// the board, then one line per move with 0-based `row` and `col` and the
+/// game time of the move in `ms`, optionally annotated by the solver.
+pub struct Recorder<W: Write> {
+    out: W,
+    moves: u32,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W, seed: u64, difficulty: Difficulty, safe_start: bool) -> io::Result<Self> {
+        let (width, height, mines) = difficulty.dimensions();
+        writeln!(
+            out,
+            "{{\"seed\":{},\"difficulty\":\"{}\",\"width\":{},\"height\":{},\"mines\":{},\"safe_start\":{}}}",
+            seed, difficulty, width, height, mines, safe_start,
+        )?;
+        out.flush()?;
+        Ok(Self { out, moves: 0 })
+    }
+
+    /// Writes a move made `time` into the game, as `Timer::elapsed` has it.
+    pub fn record(&mut self, action: Action, x: u16, y: u16, time: Duration, annotation: Option<&Annotation>) -> io::Result<()> {
+        self.moves += 1;
+        write!(
+            self.out,
+            "{{\"move\":{},\"action\":\"{}\",\"row\":{},\"col\":{},\"ms\":{}",
+            self.moves, action, x, y, time.as_millis(),
+        )?;
+        if let Some(annotation) = annotation {
+            let alternatives: Vec<String> = annotation.alternatives.iter()
+                .map(|((ax, ay), p)| format!("{{\"row\":{},\"col\":{},\"mine_probability\":{:.4}}}", ax, ay, p))
+                .collect();
+            write!(
+                self.out,
+                ",\"annotation\":{{\"forced\":{},\"mine_probability\":{:.4},\"alternatives\":[{}]}}",
+                annotation.forced, annotation.mine_probability, alternatives.join(","),
+            )?;
+        }
+        writeln!(self.out, "}}")?;
+        self.out.flush()
+    }
+}
+
+/// Cells a single move has to open to count as a big cascade.
+pub const BIG_CASCADE: usize = 10;
+
+/// A game read back from the lines a `Recorder` wrote.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub safe_start: bool,
+    /// Every recorded move with its 0-based row and column.
+    pub moves: Vec<(Action, u16, u16)>,
+    /// The game time of every move, `None` in records from before it was
+    /// written.
+    pub times: Vec<Option<Duration>>,
+}
+
+/// A move of a replay worth jumping to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// The first cell opened that didn't follow from the board.
+    FirstGuess,
+    /// A move that opened at least `BIG_CASCADE` cells, with their number.
+    Cascade(usize),
+    /// A mine was opened, which ends the game unless it's undone.
+    MineOpened,
+    Won,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::FirstGuess => write!(f, "first guess"),
+            Event::Cascade(cells) => write!(f, "cascade of {} cells", cells),
+            Event::MineOpened => write!(f, "mine opened"),
+            Event::Won => write!(f, "won"),
+        }
+    }
+}
+
+/// The board after a move of a replay.
+#[derive(Clone)]
+pub struct Frame {
+    pub field: Field,
+    /// The move that led here, `None` for the board before the first move.
+    pub step: Option<(Action, u16, u16)>,
+    /// The game time of the move.
+    pub time: Option<Duration>,
+    pub event: Option<Event>,
+}
+
+impl Replay {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next().ok_or("empty replay")?;
+        let json = Json::parse(header)?;
+        let field = |key: &str| json.get(key).ok_or_else(|| format!("missing '{}'", key));
+        // seeds don't fit the f64 of a JSON number, so they are read as written
+        let seed = header.split("\"seed\":").nth(1)
+            .map(|rest| rest.trim_start().chars().take_while(|c| c.is_ascii_digit()).collect::<String>())
+            .and_then(|digits| digits.parse().ok())
+            .ok_or("'seed' isn't a number")?;
+        let difficulty = field("difficulty")?.as_str().ok_or("'difficulty' isn't a string")?.parse()?;
+        let safe_start = field("safe_start")?.as_bool().ok_or("'safe_start' isn't true or false")?;
+        let moves = lines.enumerate().map(|(i, line)| {
+            let json = Json::parse(line).map_err(|e| format!("move {}: {}", i + 1, e))?;
+            let number = |key: &str| json.get(key).and_then(Json::as_u64).filter(|&n| n <= u16::MAX as u64)
+                .map(|n| n as u16)
+                .ok_or_else(|| format!("move {}: missing '{}'", i + 1, key));
+            let action = json.get("action").and_then(Json::as_str)
+                .ok_or_else(|| format!("move {}: missing 'action'", i + 1))?
+                .parse()?;
+            let time = json.get("ms").and_then(Json::as_u64).map(Duration::from_millis);
+            Ok(((action, number("row")?, number("col")?), time))
+        }).collect::<Result<Vec<_>, String>>()?;
+        let (moves, times) = moves.into_iter().unzip();
+        Ok(Self { seed, difficulty, safe_start, moves, times })
+    }
+
+    /// Plays the moves on the board made from the seed, keeping the board
+    /// after every move that changed it or was undone or redone. Hints
+    /// don't change the board and are left out. Boards that weren't made
+    /// by the seed alone, like no-guess or hex boards, come out different.
+    pub fn frames(&self) -> Result<Vec<Frame>, MinesError> {
+        let (width, height, mines) = self.difficulty.dimensions();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut field = Field::generate(&mut rng, height, width, mines)?;
+        let mut safe_start = self.safe_start;
+        let mut guessed = false;
+        let mut history = History::new();
+        let mut frames = vec![Frame { field: field.clone(), step: None, time: None, event: None }];
+        for (i, &(action, x, y)) in self.moves.iter().enumerate() {
+            let before = field.clone();
+            let mut event = None;
+            match action {
+                Action::Hint => continue,
+                Action::Undo => {
+                    history.undo(&mut field);
+                },
+                Action::Redo => {
+                    history.redo(&mut field);
+                },
+                Action::Open | Action::Flag | Action::Chord => {
+                    if action == Action::Open && safe_start && field.cell(x, y).is_ok_and(|cell| !cell.is_hole()) {
+                        let _ = field.ensure_safe(x, y, &mut rng);
+                        safe_start = false;
+                    }
+                    let closed = field.cell(x, y).is_ok_and(|cell| !cell.opened && !cell.is_flagged());
+                    if action == Action::Open && !guessed && closed && field.opened_count() > 0
+                        && !constraint_deductions(&field).contains(&Deduction::Safe(x, y)) {
+                        guessed = true;
+                        event = Some(Event::FirstGuess);
+                    }
+                    let result = match action {
+                        Action::Open => field.open(x, y),
+                        Action::Flag => field.flag(x, y),
+                        _ => field.chord(x, y),
+                    };
+                    let before_opened = before.opened_count();
+                    event = match result {
+                        Err(MinesError::MineOpened) => Some(Event::MineOpened),
+                        _ if field.is_won() => Some(Event::Won),
+                        _ if field.opened_count() >= before_opened + BIG_CASCADE => Some(Event::Cascade(field.opened_count() - before_opened)),
+                        _ => event,
+                    };
+                    history.record(before, &field);
+                },
+            }
+            let time = self.times.get(i).cloned().flatten();
+            frames.push(Frame { field: field.clone(), step: Some((action, x, y)), time, event });
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::replay::{Action, Event, Recorder, Replay};
+    use crate::solver::Annotation;
+    use std::time::Duration;
+
+    #[test]
+    fn record() {
+        let mut out = vec![];
+        {
+            let mut recorder = Recorder::new(&mut out, 7, Difficulty::Beginner, true).unwrap();
+            recorder.record(Action::Open, 1, 2, Duration::from_millis(0), None).unwrap();
+            let annotation = Annotation {
+                forced: false,
+                mine_probability: 0.25,
+                alternatives: vec![((0, 0), 0.125)],
+            };
+            recorder.record(Action::Flag, 3, 4, Duration::from_millis(1_250), Some(&annotation)).unwrap();
+        }
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(vec![
+            "{\"seed\":7,\"difficulty\":\"beginner\",\"width\":9,\"height\":9,\"mines\":10,\"safe_start\":true}",
+            "{\"move\":1,\"action\":\"open\",\"row\":1,\"col\":2,\"ms\":0}",
+            "{\"move\":2,\"action\":\"flag\",\"row\":3,\"col\":4,\"ms\":1250,\"annotation\":{\"forced\":false,\"mine_probability\":0.2500,\"alternatives\":[{\"row\":0,\"col\":0,\"mine_probability\":0.1250}]}}",
+        ], lines);
+    }
+
+    #[test]
+    fn replay() {
+        let mut out = vec![];
+        {
+            let mut recorder = Recorder::new(&mut out, u64::MAX - 1, Difficulty::Beginner, true).unwrap();
+            recorder.record(Action::Open, 4, 4, Duration::from_millis(0), None).unwrap();
+            recorder.record(Action::Hint, 0, 0, Duration::from_millis(800), None).unwrap();
+            recorder.record(Action::Flag, 0, 0, Duration::from_millis(1_500), None).unwrap();
+            recorder.record(Action::Undo, 0, 0, Duration::from_millis(2_000), None).unwrap();
+        }
+        let replay = Replay::parse(&String::from_utf8(out).unwrap()).unwrap();
+        assert_eq!((u64::MAX - 1, Difficulty::Beginner, true), (replay.seed, replay.difficulty, replay.safe_start));
+        assert_eq!(4, replay.moves.len());
+        let frames = replay.frames().unwrap();
+        assert_eq!(Some(Duration::from_millis(1_500)), frames[2].time);
+        // the hint is left out
+        assert_eq!(4, frames.len());
+        assert_eq!(0, frames[0].field.opened_count());
+        assert!(frames[1].field.opened_count() > 0);
+        assert_eq!(frames[1].field.to_board_file(), frames[3].field.to_board_file());
+        assert!(frames[2].field.cell(0, 0).unwrap().is_flagged() || frames[2].field.cell(0, 0).unwrap().opened);
+        assert!(Replay::parse("").is_err());
+        assert!(Replay::parse("{\"seed\":1,\"difficulty\":\"beginner\",\"safe_start\":true}\n{\"action\":\"jump\",\"row\":0,\"col\":0}").is_err());
+    }
+
+    #[test]
+    fn events() {
+        let replay = Replay {
+            seed: 3,
+            difficulty: Difficulty::Custom(5, 5, 24),
+            safe_start: false,
+            moves: vec![(Action::Open, 0, 0)],
+            times: vec![None],
+        };
+        let frames = replay.frames().unwrap();
+        let mine = frames[0].field.cell(0, 0).unwrap().is_mine();
+        assert_eq!(Some(if mine { Event::MineOpened } else { Event::Won }), frames[1].event);
+        let replay = Replay { difficulty: Difficulty::Custom(20, 20, 1), safe_start: true, ..replay };
+        let frames = replay.frames().unwrap();
+        assert!(matches!(frames[1].event, Some(Event::Won)));
+    }
+}
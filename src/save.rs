@@ -0,0 +1,235 @@
+use crate::{Cell, CellValue, Difficulty, Field};
+use crate::grid::Grid;
+use crate::json::Json;
+use crate::stats::{data_dir, Clicks, Profile};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A game in progress, written as JSON so it can be resumed later.
+pub struct SavedGame {
+    pub field: Field,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub elapsed: Duration,
+    /// Whether the first opened cell is still to be made safe.
+    pub safe_start: bool,
+    pub no_guess: bool,
+    pub flagged: bool,
+    pub hints: u32,
+    pub turns: u32,
+    pub clicks: Clicks,
+}
+
+impl SavedGame {
+    /// Writes the game as one JSON object. The cells are rows in the board
+    /// file format of `Field::parse`, the numbers and stages are arrays of
+    /// rows.
+    pub fn to_json(&self) -> String {
+        let number = |n: u64| Json::Number(n as f64);
+        let grid = |rows: &Grid<u8>| Json::Array(rows.iter()
+            .map(|row| Json::Array(row.iter().map(|&n| number(n as u64)).collect()))
+            .collect());
+        let mut pairs = vec![
+            ("version".into(), number(1)),
+            ("seed".into(), Json::String(self.seed.to_string())),
+            ("difficulty".into(), Json::String(self.difficulty.to_string())),
+            ("elapsed_ms".into(), number(self.elapsed.as_millis() as u64)),
+            ("safe_start".into(), Json::Bool(self.safe_start)),
+            ("no_guess".into(), Json::Bool(self.no_guess)),
+            ("flagged".into(), Json::Bool(self.flagged)),
+            ("hints".into(), number(self.hints as u64)),
+            ("turns".into(), number(self.turns as u64)),
+            ("clicks".into(), Json::String(self.clicks.to_string())),
+            ("cells".into(), Json::Array(self.field.to_board_file().lines().map(|row| Json::String(row.into())).collect())),
+            ("numbers".into(), grid(&self.field.numbers)),
+            ("zones".into(), grid(&self.field.zones)),
+            ("line_counts".into(), Json::Bool(self.field.has_line_counts())),
+            ("topology".into(), Json::String(self.field.topology().to_string())),
+            ("wrap".into(), Json::Bool(self.field.wraps())),
+            ("layers".into(), number(self.field.layers() as u64)),
+        ];
+        // the board file can't tell how many mines a marked cell holds
+        if self.field.mines_per_cell() > 1 {
+            let counts = |count: fn(&Cell) -> u8| self.field.cells.map(count);
+            pairs.push(("mines_per_cell".into(), number(self.field.mines_per_cell() as u64)));
+            pairs.push(("mine_counts".into(), grid(&counts(Cell::mine_count))));
+            pairs.push(("flag_counts".into(), grid(&counts(Cell::flags))));
+        }
+        Json::Object(pairs).to_string()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let json = Json::parse(text)?;
+        let field = |key: &str| json.get(key).ok_or_else(|| format!("missing '{}'", key));
+        let number = |key: &str| field(key)?.as_u64().ok_or_else(|| format!("'{}' isn't a number", key));
+        let flag = |key: &str| field(key)?.as_bool().ok_or_else(|| format!("'{}' isn't true or false", key));
+        let grid = |key: &str| -> Result<Vec<Vec<u8>>, String> {
+            let invalid = || format!("'{}' isn't an array of rows", key);
+            field(key)?.as_array().ok_or_else(invalid)?.iter()
+                .map(|row| row.as_array().ok_or_else(invalid)?.iter()
+                    .map(|n| n.as_u64().filter(|&n| n <= u8::MAX as u64).map(|n| n as u8).ok_or_else(invalid))
+                    .collect())
+                .collect()
+        };
+        if number("version")? != 1 {
+            return Err("unknown version, the game was saved by a newer version".into());
+        }
+        let rows: Vec<&str> = field("cells")?.as_array()
+            .and_then(|rows| rows.iter().map(|row| row.as_str()).collect())
+            .ok_or("'cells' isn't an array of rows")?;
+        let mut board = Field::parse(&rows.join("\n")).map_err(|e| e.to_string())?;
+        if json.get("mines_per_cell").is_some() {
+            let (mines, flags) = (grid("mine_counts")?, grid("flag_counts")?);
+            let same_size = |counts: &[Vec<u8>]| counts.len() == board.cells.len() && counts.iter().zip(board.cells.iter()).all(|(a, b)| a.len() == b.len());
+            if !same_size(&mines) || !same_size(&flags) {
+                return Err("'mine_counts' or 'flag_counts' don't match the cells".into());
+            }
+            let mut cells = board.cells.clone();
+            for (x, row) in cells.iter_mut().enumerate() {
+                for (y, cell) in row.iter_mut().enumerate() {
+                    if (cell.value == CellValue::Mine) != (mines[x][y] > 0) {
+                        return Err("'mine_counts' don't match the cells".into());
+                    }
+                    cell.mines = mines[x][y];
+                    cell.flags = flags[x][y];
+                }
+            }
+            board = Field::with_grid(cells);
+            board.per_cell = number("mines_per_cell")?.clamp(1, 9) as u8;
+        }
+        // saves from before hex boards are all square and don't wrap
+        if let Some(topology) = json.get("topology") {
+            board.set_topology(topology.as_str().ok_or("'topology' isn't a string")?.parse()?);
+        }
+        if let Some(wrap) = json.get("wrap") {
+            board.set_wrap(wrap.as_bool().ok_or("'wrap' isn't a boolean")?);
+        }
+        if json.get("layers").is_some() {
+            board.set_layers(number("layers")? as u16).map_err(|e| e.to_string())?;
+        }
+        if !grid("numbers")?.iter().eq(board.numbers.iter()) {
+            return Err("'numbers' don't match the cells".into());
+        }
+        let zones = grid("zones")?;
+        if zones.len() != board.zones.len() || zones.iter().zip(board.zones.iter()).any(|(a, b)| a.len() != b.len()) {
+            return Err("'zones' don't match the size of the board".into());
+        }
+        board.zones = Grid::from(zones);
+        // saves from before crosssweeper boards don't have it
+        board.set_line_counts(json.get("line_counts").and_then(|b| b.as_bool()).unwrap_or(false));
+        Ok(Self {
+            field: board,
+            seed: field("seed")?.as_str().and_then(|seed| seed.parse().ok()).ok_or("'seed' isn't a number")?,
+            difficulty: field("difficulty")?.as_str().ok_or("'difficulty' isn't a string")?.parse()?,
+            elapsed: Duration::from_millis(number("elapsed_ms")?),
+            safe_start: flag("safe_start")?,
+            no_guess: flag("no_guess")?,
+            flagged: flag("flagged")?,
+            hints: number("hints")? as u32,
+            turns: number("turns")? as u32,
+            // saves from before clicks were counted start from none
+            clicks: match json.get("clicks") {
+                Some(clicks) => clicks.as_str().ok_or("'clicks' isn't a string")?.parse()?,
+                None => Clicks::default(),
+            },
+        })
+    }
+}
+
+/// Checks a name for a file in the data directory. Names are limited to
+/// letters, digits, `-` and `_` so they can't point elsewhere.
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid name '{}', use letters, digits, - and _", name));
+    }
+    Ok(())
+}
+
+/// Where the game saved as `name` goes, in `saves` in the data directory
+/// of `profile`.
+pub fn save_path(profile: &Profile, name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    let dir = data_dir(profile).ok_or("No data directory to save games in")?;
+    Ok(dir.join("saves").join(format!("{}.json", name)))
+}
+
+/// Writes `game` under `name` and returns where it went.
+pub fn save(profile: &Profile, name: &str, game: &SavedGame) -> Result<PathBuf, String> {
+    let path = save_path(profile, name)?;
+    let error = |e: std::io::Error| format!("Can't write {}: {}", path.display(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    fs::write(&path, game.to_json() + "\n").map_err(error)?;
+    Ok(path)
+}
+
+pub fn load(profile: &Profile, name: &str) -> Result<SavedGame, String> {
+    let path = save_path(profile, name)?;
+    let text = fs::read_to_string(&path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    SavedGame::from_json(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Difficulty, Field, Topology};
+    use crate::save::{save_path, SavedGame};
+    use crate::stats::{Clicks, Profile};
+    use std::time::Duration;
+
+    fn game() -> SavedGame {
+        let mut field = Field::parse("oo.*\nf*..\n*...").unwrap();
+        field.split_stages(2);
+        field.set_line_counts(true);
+        SavedGame {
+            field,
+            seed: u64::MAX,
+            difficulty: Difficulty::Custom(4, 3, 3),
+            elapsed: Duration::from_millis(83_250),
+            safe_start: false,
+            no_guess: true,
+            flagged: true,
+            hints: 1,
+            turns: 7,
+            clicks: Clicks { opens: 4, flags: 2, chords: 1 },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = game().to_json();
+        let loaded = SavedGame::from_json(&json).unwrap();
+        assert_eq!(game().field.to_board_file(), loaded.field.to_board_file());
+        assert_eq!(game().field.zones, loaded.field.zones);
+        assert_eq!(1, loaded.field.flag_count());
+        assert!(loaded.field.has_line_counts());
+        assert_eq!((u64::MAX, Difficulty::Custom(4, 3, 3)), (loaded.seed, loaded.difficulty));
+        assert_eq!(Duration::from_millis(83_250), loaded.elapsed);
+        assert!(!loaded.safe_start && loaded.no_guess && loaded.flagged);
+        assert_eq!((1, 7), (loaded.hints, loaded.turns));
+        assert_eq!(Clicks { opens: 4, flags: 2, chords: 1 }, loaded.clicks);
+        assert_eq!(json, loaded.to_json());
+        let mut hex = game();
+        hex.field.set_topology(Topology::Hex);
+        hex.field.set_wrap(true);
+        let loaded = SavedGame::from_json(&hex.to_json()).unwrap().field;
+        assert_eq!((Topology::Hex, true), (loaded.topology(), loaded.wraps()));
+        let mut multimine = game();
+        multimine.field = Field::parse("3.\n..").unwrap();
+        multimine.field.flag(0, 0).unwrap();
+        multimine.field.flag(0, 0).unwrap();
+        let loaded = SavedGame::from_json(&multimine.to_json()).unwrap().field;
+        assert_eq!((3, 3, 2), (loaded.mines_per_cell(), loaded.mine_count(), loaded.flag_count()));
+    }
+
+    #[test]
+    fn invalid() {
+        let json = game().to_json();
+        assert_eq!(Err("missing 'seed'".into()), SavedGame::from_json(&json.replace("\"seed\"", "\"sees\"")).map(|_| ()));
+        assert_eq!(Err("'numbers' don't match the cells".into()), SavedGame::from_json(&json.replace("[1,1,", "[1,2,")).map(|_| ()));
+        assert!(SavedGame::from_json("{").is_err());
+        assert!(save_path(&Profile::default(), "../games").is_err());
+        assert!(save_path(&Profile::default(), "expert-1").is_ok());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::Difficulty;
+use crate::stats::{data_dir, Profile};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Number of places in the high score table of a difficulty.
+pub const TABLE_SIZE: usize = 10;
+
+/// The presets that have a high score table. Custom boards come in too many
+/// sizes to compare.
+pub const RANKED: [Difficulty; 3] = [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Expert];
+
+/// A won game in the high score file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Score {
+    pub difficulty: Difficulty,
+    pub time: Duration,
+    /// Seconds since the Unix epoch when the game ended.
+    pub finished: u64,
+    pub name: String,
+}
+
+impl Score {
+    /// Writes the score as a tab separated line: difficulty, time in
+    /// milliseconds, end time and name.
+    pub fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}", self.difficulty, self.time.as_millis(), self.finished, self.name)
+    }
+
+    pub fn parse_line(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.splitn(4, '\t').collect();
+        if fields.len() != 4 {
+            return Err(format!("expected 4 fields, found {}", fields.len()));
+        }
+        let number = |field: &str| field.parse::<u64>().map_err(|_| format!("invalid number '{}'", field));
+        Ok(Self {
+            difficulty: fields[0].parse()?,
+            time: Duration::from_millis(number(fields[1])?),
+            finished: number(fields[2])?,
+            name: fields[3].into(),
+        })
+    }
+}
+
+/// Turns what the player typed into a name that fits in the file: tabs and
+/// line breaks become spaces and an empty name becomes `default`.
+pub fn clean_name(name: &str, default: &str) -> String {
+    let name: String = name.trim().chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    if name.is_empty() {
+        default.into()
+    } else {
+        name
+    }
+}
+
+/// The high score table of `difficulty`, fastest first. Earlier scores win
+/// ties.
+pub fn table(scores: &[Score], difficulty: Difficulty) -> Vec<&Score> {
+    let mut table: Vec<&Score> = scores.iter().filter(|score| score.difficulty == difficulty).collect();
+    table.sort_by_key(|score| (score.time, score.finished));
+    table.truncate(TABLE_SIZE);
+    table
+}
+
+/// The 0-based place a win on `difficulty` in `time` takes in the table,
+/// `None` if it doesn't make it in.
+pub fn rank(scores: &[Score], difficulty: Difficulty, time: Duration) -> Option<usize> {
+    if !RANKED.contains(&difficulty) {
+        return None;
+    }
+    let place = table(scores, difficulty).iter().filter(|score| score.time <= time).count();
+    if place < TABLE_SIZE {
+        Some(place)
+    } else {
+        None
+    }
+}
+
+/// The high score file, next to the stats file in `data_dir`.
+pub fn scores_path(profile: &Profile) -> Option<PathBuf> {
+    data_dir(profile).map(|dir| dir.join("scores.tsv"))
+}
+
+/// Reads all scores from the file at `path`. A missing file holds no scores
+/// yet.
+pub fn load(path: &Path) -> Result<Vec<Score>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("Can't read {}: {}", path.display(), e)),
+    };
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(line_no, line)| {
+            Score::parse_line(line).map_err(|e| format!("{} line {}: {}", path.display(), line_no + 1, e))
+        })
+        .collect()
+}
+
+/// Appends `score` to the file at `path`, creating it if needed.
+pub fn append(path: &Path, score: &Score) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Can't write {}: {}", path.display(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(error)?;
+    writeln!(file, "{}", score.to_line()).map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::scores::{append, clean_name, load, rank, table, Score, TABLE_SIZE};
+    use std::time::Duration;
+
+    fn score(name: &str, secs: u64) -> Score {
+        Score {
+            difficulty: Difficulty::Beginner,
+            time: Duration::from_secs(secs),
+            finished: 1_700_000_000 + secs,
+            name: name.into(),
+        }
+    }
+
+    #[test]
+    fn line() {
+        let line = score("Ada Lovelace", 12).to_line();
+        assert_eq!("beginner\t12000\t1700000012\tAda Lovelace", line);
+        assert_eq!(Ok(score("Ada Lovelace", 12)), Score::parse_line(&line));
+        assert!(Score::parse_line("beginner\t12000").is_err());
+        assert_eq!("a b", clean_name(" a\tb\n", "anonymous"));
+        assert_eq!("anonymous", clean_name("  ", "anonymous"));
+    }
+
+    #[test]
+    fn ranking() {
+        let mut scores: Vec<Score> = (0..TABLE_SIZE as u64).map(|i| score("bob", 20 + 2 * i)).collect();
+        scores.push(Score { difficulty: Difficulty::Expert, ..score("eve", 1) });
+        assert_eq!(Some(0), rank(&scores, Difficulty::Beginner, Duration::from_secs(5)));
+        assert_eq!(Some(2), rank(&scores, Difficulty::Beginner, Duration::from_secs(23)));
+        assert_eq!(None, rank(&scores, Difficulty::Beginner, Duration::from_secs(100)));
+        assert_eq!(Some(1), rank(&scores, Difficulty::Expert, Duration::from_secs(100)));
+        assert_eq!(None, rank(&scores, Difficulty::Custom(9, 9, 10), Duration::from_secs(1)));
+        scores.push(score("ann", 21));
+        let beginner = table(&scores, Difficulty::Beginner);
+        assert_eq!(TABLE_SIZE, beginner.len());
+        assert_eq!("ann", beginner[1].name);
+    }
+
+    #[test]
+    fn file() {
+        let path = std::env::temp_dir().join(format!("minesweep_scores_{}.tsv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Ok(vec![]), load(&path));
+        append(&path, &score("ann", 30)).unwrap();
+        append(&path, &score("bob", 25)).unwrap();
+        let scores = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vec![score("ann", 30), score("bob", 25)], scores);
+    }
+}
@@ -0,0 +1,539 @@
+use crate::{Difficulty, MinesError};
+use crate::game::Game;
+use crate::json::Json;
+use crate::lobby::{Lobby, OpenGame};
+use crate::tournament::{Request, Response};
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Most games kept at once, the oldest one goes when another is started.
+pub const MAX_GAMES: usize = 256;
+/// Most cells of a served board.
+pub const MAX_CELLS: usize = 250_000;
+/// Where clients of the lobby switch to the WebSocket protocol.
+pub const SOCKET_PATH: &str = "/lobby/socket";
+
+/// A game as answered to clients, with the `id` it is kept under.
+fn game_json(game: &Game, id: u64) -> Json {
+    let (cols, rows) = game.field.size();
+    Json::Object(vec![
+        ("id".into(), Json::Number(id as f64)),
+        ("difficulty".into(), Json::String(game.difficulty.to_string())),
+        ("seed".into(), Json::String(game.seed.to_string())),
+        ("rows".into(), Json::Number(rows as f64)),
+        ("cols".into(), Json::Number(cols as f64)),
+        ("mines".into(), Json::Number(game.field.mine_count() as f64)),
+        ("mines_left".into(), Json::Number(game.field.mines_left() as f64)),
+        ("state".into(), Json::String(game.state.name().into())),
+        ("board".into(), Json::Array(game.board().into_iter().map(Json::String).collect())),
+    ])
+}
+
+/// A game of the lobby as answered to clients: who sits in it, whether
+/// they are ready and, once started, the seed of the board they all play.
+fn lobby_json(game: &OpenGame) -> Json {
+    let seats = game.seats.iter()
+        .map(|seat| Json::Object(vec![
+            ("name".into(), Json::String(seat.name.clone())),
+            ("ready".into(), Json::Bool(seat.ready)),
+        ]))
+        .collect();
+    Json::Object(vec![
+        ("code".into(), Json::String(game.code.clone())),
+        ("difficulty".into(), Json::String(game.difficulty.to_string())),
+        ("players".into(), Json::Number(game.capacity as f64)),
+        ("seats".into(), Json::Array(seats)),
+        ("seed".into(), game.started.map_or(Json::Null, |seed| Json::String(seed.to_string()))),
+    ])
+}
+
+/// Reads the board of a request: a `"difficulty"` or a `"width"` and a
+/// `"height"` with an optional number of `"mines"`, beginner without any.
+fn difficulty_of(json: &Json) -> Result<Difficulty, Response> {
+    let bad = |message: &str| Response::error(400, message);
+    let number = |key: &str| json.get(key).and_then(|n| n.as_u64()).filter(|&n| n <= u16::MAX as u64).map(|n| n as u16);
+    let difficulty = match (json.get("difficulty").and_then(|d| d.as_str()), number("width"), number("height")) {
+        (Some(name), None, None) => name.parse().map_err(|e: String| bad(&e))?,
+        (None, Some(width), Some(height)) => match number("mines") {
+            Some(mines) => Difficulty::Custom(width, height, mines),
+            None => Difficulty::custom(width, height),
+        },
+        (None, None, None) => Difficulty::Beginner,
+        _ => return Err(bad("expected a 'difficulty' or a 'width' and a 'height'")),
+    };
+    let (width, height, _) = difficulty.dimensions();
+    if width as usize * height as usize > MAX_CELLS {
+        return Err(bad(&format!("boards have at most {} cells", MAX_CELLS)));
+    }
+    Ok(difficulty)
+}
+
+/// The body of a request as JSON, an empty object for an empty body.
+fn body_json(request: &Request) -> Result<Json, Response> {
+    match request.body.trim() {
+        "" => Ok(Json::Object(vec![])),
+        body => Json::parse(body).map_err(|e| Response::error(400, &e)),
+    }
+}
+
+/// Serves games for web and mobile clients. Rows and columns count from 0
+/// and every answer about a game is its state, see `game::Game::board`.
+///
+/// * `POST /games` with a body like `{"difficulty": "expert"}` or
+///   `{"width": 20, "height": 10, "mines": 30}` starts a game, a `"seed"`
+///   string or number makes the board repeatable.
+/// * `GET /games/<id>` is the state of a game.
+/// * `POST /games/<id>/open`, `/flag` and `/chord` with a body like
+///   `{"row": 3, "col": 4}` play a move.
+/// * `DELETE /games/<id>` ends a game.
+///
+/// Players find each other in the lobby, see `lobby::Lobby`:
+///
+/// * `GET /lobby` lists the games that can still be joined.
+/// * `POST /lobby` with a body like `{"name": "ada", "players": 2}` and the
+///   board as for `POST /games` creates a game, `GET /lobby/<code>` shows it.
+/// * `POST /lobby/<code>/join` with `{"name": "bo"}` takes a seat.
+/// * `POST /lobby/<code>/ready` with `{"token": "...", "ready": true}`,
+///   `/leave` with `{"token": "..."}` and, for the host, `/start` with
+///   `{"token": "..."}` once everybody is ready.
+///
+/// Creating and joining answer with the seat and a token only its player
+/// knows, which the other requests of the player take. A started game has
+/// a seed, and every player starts the same board with it at `POST /games`.
+///
+/// Clients that want to hear of changes instead of asking for them switch
+/// to the WebSocket protocol at `SOCKET_PATH`, see `Server::message`.
+#[derive(Default)]
+pub struct Server {
+    games: BTreeMap<u64, Game>,
+    next_id: u64,
+    lobby: Lobby,
+    /// The tokens of the seats of every game in the lobby, by its code.
+    tokens: HashMap<String, Vec<String>>,
+    sockets: HashMap<u64, Socket>,
+    next_socket: u64,
+}
+
+/// A client of the lobby on a WebSocket, see `Server::connect`.
+struct Socket {
+    events: Sender<Json>,
+    /// The code of the game and the token of the seat the client plays.
+    seat: Option<(String, String)>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle(&mut self, request: &Request) -> Response {
+        let path: Vec<&str> = request.path.split('?').next().unwrap_or("").split('/').filter(|part| !part.is_empty()).collect();
+        let result = match (request.method.as_str(), path.as_slice()) {
+            ("POST", ["games"]) => self.start(request),
+            ("GET", ["games", id]) => self.game(id).map(|(id, game)| game_json(game, id)),
+            ("DELETE", ["games", id]) => self.game(id).map(|(id, _)| id).map(|id| {
+                self.games.remove(&id);
+                Json::Object(vec![("id".into(), Json::Number(id as f64))])
+            }),
+            ("POST", ["games", id, action @ ("open" | "flag" | "chord")]) => self.play(id, action, request),
+            ("GET", ["lobby"]) => Ok(self.open_games()),
+            ("POST", ["lobby"]) => body_json(request).and_then(|json| self.create(&json)),
+            ("GET", ["lobby", "socket"]) => Err(Response::error(400, "expected a WebSocket upgrade")),
+            ("GET", ["lobby", code]) => self.lobby.game(code).map(lobby_json).ok_or_else(|| Response::error(404, "unknown game")),
+            ("POST", ["lobby", code, "join"]) => body_json(request).and_then(|json| self.join(code, &json)),
+            ("POST", ["lobby", code, action @ ("ready" | "leave" | "start")]) => body_json(request).and_then(|json| {
+                let token = json.get("token").and_then(|t| t.as_str()).ok_or_else(|| Response::error(401, "expected the 'token' of a seat"))?;
+                self.seat_action(code, action, token, &json)
+            }),
+            _ => Err(Response::error(404, "unknown endpoint")),
+        };
+        match result {
+            Ok(json) => Response::json(json),
+            Err(response) => response,
+        }
+    }
+
+    fn start(&mut self, request: &Request) -> Result<Json, Response> {
+        let bad = |message: &str| Response::error(400, message);
+        let json = body_json(request)?;
+        let difficulty = difficulty_of(&json)?;
+        let seed = match json.get("seed") {
+            Some(Json::String(seed)) => seed.parse().map_err(|_| bad("'seed' isn't a number"))?,
+            Some(seed) => seed.as_u64().ok_or_else(|| bad("'seed' isn't a number"))?,
+            None => rand::thread_rng().gen(),
+        };
+        let game = Game::new(difficulty, seed).map_err(|e| bad(&e.to_string()))?;
+        if self.games.len() >= MAX_GAMES {
+            let oldest = *self.games.keys().next().unwrap();
+            self.games.remove(&oldest);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let json = game_json(&game, id);
+        self.games.insert(id, game);
+        Ok(json)
+    }
+
+    /// Creates a game in the lobby, closing the oldest one if there are
+    /// `MAX_GAMES` already.
+    fn create(&mut self, json: &Json) -> Result<Json, Response> {
+        let name = name_of(json)?;
+        let difficulty = difficulty_of(json)?;
+        let players = json.get("players").and_then(|n| n.as_u64()).unwrap_or(2) as usize;
+        if self.lobby.len() >= MAX_GAMES {
+            if let Some(code) = self.lobby.close_oldest() {
+                self.tokens.remove(&code);
+                self.notify(&code);
+            }
+        }
+        let code = self.lobby.create(&name, difficulty, players, &mut rand::thread_rng()).map_err(|e| Response::error(400, &e))?;
+        self.tokens.insert(code.clone(), vec![]);
+        let seated = self.seated(&code, 0);
+        self.notify(&code);
+        Ok(seated)
+    }
+
+    fn join(&mut self, code: &str, json: &Json) -> Result<Json, Response> {
+        let name = name_of(json)?;
+        let seat = self.lobby.join(code, &name).map_err(|e| Response::error(400, &e))?;
+        let seated = self.seated(code, seat);
+        self.notify(code);
+        Ok(seated)
+    }
+
+    /// Draws the token of a player who just took `seat` and answers with it.
+    fn seated(&mut self, code: &str, seat: usize) -> Json {
+        let game = self.lobby.game(code).unwrap();
+        let token = format!("{:016x}", rand::thread_rng().gen::<u64>());
+        self.tokens.entry(game.code.clone()).or_default().push(token.clone());
+        Json::Object(vec![
+            ("seat".into(), Json::Number(seat as f64)),
+            ("token".into(), Json::String(token)),
+            ("game".into(), lobby_json(game)),
+        ])
+    }
+
+    /// Plays `ready`, `leave` or `start` for the player with `token`.
+    fn seat_action(&mut self, code: &str, action: &str, token: &str, json: &Json) -> Result<Json, Response> {
+        let code = self.lobby.game(code).ok_or_else(|| Response::error(404, "unknown game"))?.code.clone();
+        let seat = self.tokens.get(&code)
+            .and_then(|tokens| tokens.iter().position(|t| t == token))
+            .ok_or_else(|| Response::error(401, "not a seat of this game"))?;
+        let bad = |e: String| Response::error(400, &e);
+        match action {
+            "ready" => {
+                let ready = json.get("ready").and_then(|r| r.as_bool()).unwrap_or(true);
+                self.lobby.ready(&code, seat, ready).map_err(bad)?;
+            },
+            "leave" => {
+                self.lobby.leave(&code, seat).map_err(bad)?;
+                match self.lobby.game(&code) {
+                    Some(_) => {
+                        self.tokens.get_mut(&code).unwrap().remove(seat);
+                    },
+                    None => {
+                        self.tokens.remove(&code);
+                        self.notify(&code);
+                        return Ok(Json::Object(vec![("code".into(), Json::String(code))]));
+                    },
+                }
+            },
+            _ if seat != 0 => return Err(Response::error(400, "only the host starts the game")),
+            _ => {
+                self.lobby.start(&code, &mut rand::thread_rng()).map_err(bad)?;
+            },
+        }
+        self.notify(&code);
+        Ok(lobby_json(self.lobby.game(&code).unwrap()))
+    }
+
+    fn open_games(&self) -> Json {
+        Json::Array(self.lobby.open_games().into_iter().map(lobby_json).collect())
+    }
+
+    /// Opens a socket to the lobby, which is sent the open games right
+    /// away. Returns its id and the events to push to the client.
+    pub fn connect(&mut self) -> (u64, Receiver<Json>) {
+        let (events, receiver) = mpsc::channel();
+        let _ = events.send(event("list", self.open_games()));
+        let id = self.next_socket;
+        self.next_socket += 1;
+        self.sockets.insert(id, Socket { events, seat: None });
+        (id, receiver)
+    }
+
+    /// Plays a message of the client on socket `id`, a JSON object with an
+    /// `"action"`:
+    ///
+    /// * `list` sends the open games again.
+    /// * `create` and `join` take the rest of the body of `POST /lobby` and
+    ///   `POST /lobby/<code>/join`, with the `"code"` of the game to join,
+    ///   and are answered with a `seated` event like their answers.
+    /// * `ready`, `leave` and `start` play for the seat taken on the socket,
+    ///   or for the seat of a `"code"` and a `"token"` taken before.
+    ///
+    /// Clients without a seat are sent a `list` event whenever the open
+    /// games change, and players a `game` event whenever their game does,
+    /// `start` once it started. Anything turned down is an `error` event.
+    pub fn message(&mut self, id: u64, text: &str) {
+        if let Err(response) = self.socket_action(id, text) {
+            let error = Json::parse(&response.body).unwrap_or(Json::Null);
+            self.send(id, event("error", error));
+        }
+    }
+
+    fn socket_action(&mut self, id: u64, text: &str) -> Result<(), Response> {
+        let bad = |message: &str| Response::error(400, message);
+        let json = Json::parse(text).map_err(|e| bad(&e))?;
+        let code = json.get("code").and_then(|c| c.as_str()).map(str::to_uppercase);
+        let action = json.get("action").and_then(|a| a.as_str()).unwrap_or("");
+        match action {
+            "list" => {
+                let games = self.open_games();
+                self.send(id, event("list", games));
+            },
+            "create" | "join" => {
+                let seated = match (action, &code) {
+                    ("create", _) => self.create(&json)?,
+                    (_, Some(code)) => self.join(code, &json)?,
+                    _ => return Err(bad("expected the 'code' of a game")),
+                };
+                let code = seated.get("game").and_then(|g| g.get("code")).and_then(|c| c.as_str()).unwrap_or_default().to_string();
+                let token = seated.get("token").and_then(|t| t.as_str()).unwrap_or_default().to_string();
+                self.send(id, event("seated", seated));
+                if let Some(socket) = self.sockets.get_mut(&id) {
+                    socket.seat = Some((code, token));
+                }
+            },
+            "ready" | "leave" | "start" => {
+                let token = json.get("token").and_then(|t| t.as_str());
+                let (code, token) = match (code, token) {
+                    (Some(code), Some(token)) => (code, token.to_string()),
+                    _ => self.sockets.get(&id).and_then(|socket| socket.seat.clone()).ok_or_else(|| Response::error(401, "take a seat first"))?,
+                };
+                // seated before playing, so the socket hears of the change
+                let before = self.sockets.get_mut(&id).and_then(|socket| socket.seat.replace((code.clone(), token.clone())));
+                if let Err(response) = self.seat_action(&code, action, &token, &json) {
+                    if let Some(socket) = self.sockets.get_mut(&id) {
+                        socket.seat = before;
+                    }
+                    return Err(response);
+                }
+            },
+            _ => return Err(bad("expected an 'action' of list, create, join, ready, leave or start")),
+        }
+        Ok(())
+    }
+
+    /// Closes socket `id`. A player who goes away before their game
+    /// started gives up their seat.
+    pub fn disconnect(&mut self, id: u64) {
+        if let Some((code, token)) = self.sockets.remove(&id).and_then(|socket| socket.seat) {
+            if self.lobby.game(&code).is_some_and(|game| game.started.is_none()) {
+                let _ = self.seat_action(&code, "leave", &token, &Json::Null);
+            }
+        }
+    }
+
+    fn send(&mut self, id: u64, event: Json) {
+        if let Some(socket) = self.sockets.get(&id) {
+            let _ = socket.events.send(event);
+        }
+    }
+
+    /// Pushes the game with `code` to the sockets of its players, and the
+    /// open games to the sockets without a seat. Players whose seat went
+    /// away are back without one. Sockets whose client is gone are dropped.
+    fn notify(&mut self, code: &str) {
+        let code = code.to_uppercase();
+        let game = self.lobby.game(&code);
+        let name = if game.is_some_and(|game| game.started.is_some()) { "start" } else { "game" };
+        let game = game.map(lobby_json);
+        let games = self.open_games();
+        let tokens = self.tokens.get(&code);
+        self.sockets.retain(|_, socket| {
+            let event = match &socket.seat {
+                Some((seat_code, token)) if *seat_code == code => match (&game, tokens) {
+                    (Some(game), Some(tokens)) if tokens.contains(token) => event(name, Json::Object(vec![("game".into(), game.clone())])),
+                    _ => {
+                        socket.seat = None;
+                        event("list", games.clone())
+                    },
+                },
+                Some(_) => return true,
+                None => event("list", games.clone()),
+            };
+            socket.events.send(event).is_ok()
+        });
+    }
+
+    fn game(&self, id: &str) -> Result<(u64, &Game), Response> {
+        id.parse().ok()
+            .and_then(|id| self.games.get(&id).map(|game| (id, game)))
+            .ok_or_else(|| Response::error(404, "unknown game"))
+    }
+
+    fn play(&mut self, id: &str, action: &str, request: &Request) -> Result<Json, Response> {
+        let (id, _) = self.game(id)?;
+        let bad = |message: &str| Response::error(400, message);
+        let json = Json::parse(&request.body).map_err(|e| bad(&e))?;
+        let number = |key: &str| json.get(key).and_then(|n| n.as_u64()).filter(|&n| n <= u16::MAX as u64).map(|n| n as u16);
+        let (row, col) = number("row").zip(number("col")).ok_or_else(|| bad("expected a 'row' and a 'col'"))?;
+        let game = self.games.get_mut(&id).unwrap();
+        game.play(action, row, col).map_err(|e| match e {
+            // rows and columns the way clients send them, counted from 0
+            MinesError::OutOfBounds(row, col) => bad(&format!("row {} col {} is outside the board", row, col)),
+            MinesError::ZoneLocked(_, _) => bad("the cell lies in a stage that isn't unlocked yet"),
+            MinesError::TooManyMines => bad("there is no free cell to move the mine under the first move to"),
+            e => bad(&e.to_string()),
+        })?;
+        Ok(game_json(game, id))
+    }
+}
+
+/// What the lobby pushes to a socket: an object with its `"event"` first
+/// and the pairs of `json`.
+fn event(name: &str, json: Json) -> Json {
+    let mut pairs = vec![("event".into(), Json::String(name.into()))];
+    match json {
+        Json::Object(rest) => pairs.extend(rest),
+        json => pairs.push(("games".into(), json)),
+    }
+    Json::Object(pairs)
+}
+
+/// The `"name"` a player goes by in the lobby.
+fn name_of(json: &Json) -> Result<String, Response> {
+    json.get("name").and_then(|n| n.as_str())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty() && name.len() <= 32)
+        .ok_or_else(|| Response::error(400, "expected a 'name' of up to 32 characters"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::Json;
+    use crate::server::{Server, MAX_GAMES};
+    use crate::tournament::{Request, Response};
+    use std::sync::mpsc::Receiver;
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        let text = format!("{} {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", method, path, body.len(), body);
+        Request::read(&mut text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn games() {
+        let mut server = Server::new();
+        let response = server.handle(&request("POST", "/games", "{\"width\":5,\"height\":4,\"mines\":3,\"seed\":\"7\"}"));
+        assert_eq!(200, response.status);
+        let game = Json::parse(&response.body).unwrap();
+        assert_eq!((Some(0), Some(4), Some(5)), (game.get("id").and_then(|i| i.as_u64()), game.get("rows").and_then(|r| r.as_u64()), game.get("cols").and_then(|c| c.as_u64())));
+        assert_eq!(Some("....."), game.get("board").and_then(|b| b.as_array()).and_then(|b| b[0].as_str()));
+
+        let response = server.handle(&request("POST", "/games/0/open", "{\"row\":1,\"col\":2}"));
+        let game = Json::parse(&response.body).unwrap();
+        let board = game.get("board").and_then(|b| b.as_array()).unwrap();
+        assert!(board[1].as_str().unwrap().as_bytes()[2].is_ascii_digit());
+        assert_ne!(Some("lost"), game.get("state").and_then(|s| s.as_str()));
+        assert_eq!(response.body, server.handle(&request("GET", "/games/0", "")).body);
+
+        let outside = server.handle(&request("POST", "/games/0/flag", "{\"row\":9,\"col\":0}"));
+        assert_eq!((400, "{\"error\":\"row 9 col 0 is outside the board\"}"), (outside.status, outside.body.as_str()));
+        assert_eq!(400, server.handle(&request("POST", "/games/0/flag", "{}")).status);
+        assert_eq!(400, server.handle(&request("POST", "/games", "{\"difficulty\":\"hard\"}")).status);
+        assert_eq!(400, server.handle(&request("POST", "/games/0/open", &"[".repeat(200_000))).status);
+        assert_eq!(404, server.handle(&request("POST", "/games/0/dig", "{}")).status);
+        assert_eq!(200, server.handle(&request("DELETE", "/games/0", "")).status);
+        assert_eq!(404, server.handle(&request("GET", "/games/0", "")).status);
+    }
+
+    #[test]
+    fn lobby() {
+        let mut server = Server::new();
+        let field = |response: &Response, key: &str| Json::parse(&response.body).unwrap().get(key).cloned().unwrap();
+        let created = server.handle(&request("POST", "/lobby", "{\"name\":\"ada\",\"difficulty\":\"expert\"}"));
+        assert_eq!(200, created.status);
+        let host = field(&created, "token").as_str().unwrap().to_string();
+        let code = field(&created, "game").get("code").and_then(|c| c.as_str()).unwrap().to_string();
+        let listed = Json::parse(&server.handle(&request("GET", "/lobby", "")).body).unwrap();
+        assert_eq!(1, listed.as_array().unwrap().len());
+
+        let joined = server.handle(&request("POST", &format!("/lobby/{}/join", code), "{\"name\":\"bo\"}"));
+        assert_eq!(Some(1), field(&joined, "seat").as_u64());
+        let guest = field(&joined, "token").as_str().unwrap().to_string();
+        assert_eq!(400, server.handle(&request("POST", &format!("/lobby/{}/join", code), "{\"name\":\"cy\"}")).status);
+        let action = |action: &str, token: &str| request("POST", &format!("/lobby/{}/{}", code, action), &format!("{{\"token\":\"{}\"}}", token));
+        assert_eq!(401, server.handle(&action("ready", "guess")).status);
+        assert_eq!(200, server.handle(&action("ready", &host)).status);
+        assert_eq!(400, server.handle(&action("start", &host)).status);
+        assert_eq!(200, server.handle(&action("ready", &guest)).status);
+        assert_eq!(400, server.handle(&action("start", &guest)).status);
+        let started = server.handle(&action("start", &host));
+        let seed = field(&started, "seed").as_str().unwrap().to_string();
+
+        let game = server.handle(&request("POST", "/games", &format!("{{\"difficulty\":\"expert\",\"seed\":\"{}\"}}", seed)));
+        assert_eq!(Some(99), Json::parse(&game.body).unwrap().get("mines").and_then(|m| m.as_u64()));
+        assert_eq!(started.body, server.handle(&request("GET", &format!("/lobby/{}", code.to_lowercase()), "")).body);
+        assert_eq!(404, server.handle(&request("GET", "/lobby/ZZZZ", "")).status);
+    }
+
+    #[test]
+    fn sockets() {
+        let mut server = Server::new();
+        let names = |events: &Receiver<Json>| events.try_iter()
+            .map(|event| event.get("event").and_then(|e| e.as_str()).unwrap().to_string())
+            .collect::<Vec<_>>();
+        let (host, host_events) = server.connect();
+        let (watcher, watcher_events) = server.connect();
+        assert_eq!(vec!["list"], names(&host_events));
+        server.message(host, "{\"action\":\"create\",\"name\":\"ada\"}");
+        let code = server.lobby.open_games()[0].code.clone();
+        assert_eq!(vec!["list", "seated"], names(&host_events));
+        assert_eq!(vec!["list", "list"], names(&watcher_events));
+
+        let joined = server.handle(&request("POST", &format!("/lobby/{}/join", code.to_lowercase()), "{\"name\":\"bo\"}"));
+        let guest = Json::parse(&joined.body).unwrap().get("token").and_then(|t| t.as_str()).unwrap().to_string();
+        assert_eq!(vec!["game"], names(&host_events));
+        let listed = watcher_events.try_recv().unwrap();
+        assert_eq!(Some(0), listed.get("games").and_then(|g| g.as_array()).map(|g| g.len()));
+
+        server.message(host, "{\"action\":\"start\"}");
+        server.message(host, "{\"action\":\"ready\"}");
+        assert_eq!(vec!["error", "game"], names(&host_events));
+        server.handle(&request("POST", &format!("/lobby/{}/ready", code), &format!("{{\"token\":\"{}\"}}", guest)));
+        server.message(host, "{\"action\":\"start\"}");
+        let events: Vec<Json> = host_events.try_iter().collect();
+        assert_eq!(2, events.len());
+        assert!(events[1].get("game").and_then(|g| g.get("seed")).and_then(|s| s.as_str()).is_some());
+        assert_eq!(Some("start"), events[1].get("event").and_then(|e| e.as_str()));
+
+        watcher_events.try_iter().for_each(drop);
+        server.message(watcher, "{\"action\":\"dance\"}");
+        server.message(watcher, &"[".repeat(200_000));
+        server.message(watcher, "{\"action\":\"ready\"}");
+        assert_eq!(vec!["error"; 3], names(&watcher_events));
+    }
+
+    #[test]
+    fn sockets_leave_when_gone() {
+        let mut server = Server::new();
+        let (host, _events) = server.connect();
+        server.message(host, "{\"action\":\"create\",\"name\":\"ada\"}");
+        assert_eq!(1, server.lobby.len());
+        server.disconnect(host);
+        assert!(server.lobby.is_empty());
+        assert_eq!(400, server.handle(&request("POST", "/lobby", &"{\"a\":".repeat(200_000))).status);
+    }
+
+    #[test]
+    fn oldest_game_goes() {
+        let mut server = Server::new();
+        for _ in 0..=MAX_GAMES {
+            assert_eq!(200, server.handle(&request("POST", "/games", "")).status);
+        }
+        assert_eq!(404, server.handle(&request("GET", "/games/0", "")).status);
+        assert_eq!(200, server.handle(&request("GET", &format!("/games/{}", MAX_GAMES), "")).status);
+    }
+}
@@ -0,0 +1,424 @@
+//! A game on one screen, played alone or by a relay of players taking
+//! turns, with everything that happens to the board between reading a move
+//! and showing it: the safe first move, undo and redo, hints, stages, the
+//! owners of flags and the record of the moves. Frontends read the moves
+//! and show the outcome.
+
+use crate::Field;
+use crate::Difficulty;
+use crate::MinesError;
+use crate::handicap::Handicap;
+use crate::history::History;
+use crate::relay::Relay;
+use crate::replay::{Action, Recorder};
+use crate::save::SavedGame;
+use crate::scores;
+use crate::solver::{self, Deduction};
+use crate::stats::{self, Clicks, GameRecord};
+use crate::timer::Timer;
+use rand::rngs::StdRng;
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// How a game is played, picked before it starts.
+#[derive(Clone, Debug)]
+pub struct Rules {
+    /// Whether moves can be taken back, along with a mine opened in a game
+    /// that isn't ranked.
+    pub undo: bool,
+    /// Whether flagging a flag turns it into a question mark.
+    pub marks: bool,
+    /// Time added for every hint in ranked games.
+    pub hint_penalty: Duration,
+    /// A puzzle has no hints, every cell can be worked out.
+    pub puzzle: bool,
+    /// Number of stages the board is cleared in, timed one by one.
+    pub stages: u8,
+    /// Whether every recorded move comes with the solver's view of it.
+    pub annotate: bool,
+    /// Bytes the boards kept for undo may take up.
+    pub memory_cap: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            undo: true,
+            marks: false,
+            hint_penalty: Duration::from_secs(20),
+            puzzle: false,
+            stages: 1,
+            annotate: false,
+            memory_cap: 1 << 30,
+        }
+    }
+}
+
+/// What came of a move.
+#[derive(Debug, PartialEq)]
+pub enum Turn {
+    /// The turn of the relay player ran out before the move, which wasn't
+    /// played. The next player is up.
+    TimeUp,
+    /// A mine was opened before and left standing, which ends the game.
+    Over,
+    Played(Played),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Played {
+    pub result: Result<(), MinesError>,
+    /// Cells the move opened.
+    pub opened: usize,
+    /// The number of the stage the move cleared, the last one not counted
+    /// as it ends the game.
+    pub stage: Option<usize>,
+    /// Whether the relay went on to the next player.
+    pub handed_over: bool,
+}
+
+pub struct Session {
+    pub field: Field,
+    pub seed: u64,
+    /// What the board is played as, custom for board files.
+    pub difficulty: Difficulty,
+    pub no_guess: bool,
+    pub timer: Timer,
+    pub turns: u32,
+    pub clicks: Clicks,
+    pub used_flags: bool,
+    pub hints: u32,
+    pub relay: Option<Relay>,
+    /// The relay player who placed each flag.
+    pub flag_owners: HashMap<(u16, u16), usize>,
+    pub recorder: Option<Recorder<Box<dyn Write>>>,
+    rules: Rules,
+    rng: StdRng,
+    safe_start: bool,
+    history: History,
+    /// A mine opened in a casual game can still be undone before the next
+    /// move.
+    mine_hit: bool,
+}
+
+impl Session {
+    /// A game on `field`, played as `difficulty`, with `rng` moving a mine
+    /// away from the first opened cell if `safe_start` is set. Hints only
+    /// cost time where times are compared.
+    pub fn new(field: Field, difficulty: Difficulty, seed: u64, rng: StdRng, safe_start: bool, mut rules: Rules) -> Self {
+        if !scores::RANKED.contains(&difficulty) {
+            rules.hint_penalty = Duration::default();
+        }
+        // big boards only keep as many moves as fit in a quarter of the memory cap
+        let (cols, rows) = field.size();
+        let history = History::with_limit((rules.memory_cap / 4 / Field::memory_estimate(rows, cols)).max(1));
+        Self {
+            field,
+            seed,
+            difficulty,
+            no_guess: false,
+            timer: Timer::new(),
+            turns: 0,
+            clicks: Clicks::default(),
+            used_flags: false,
+            hints: 0,
+            relay: None,
+            flag_owners: HashMap::new(),
+            recorder: None,
+            rules,
+            rng,
+            safe_start,
+            history,
+            mine_hit: false,
+        }
+    }
+
+    /// Picks up a saved game where it was left.
+    pub fn resume(game: SavedGame, rng: StdRng, rules: Rules) -> Self {
+        let mut session = Self::new(game.field, game.difficulty, game.seed, rng, game.safe_start, rules);
+        session.no_guess = game.no_guess;
+        session.timer = Timer::resumed(game.elapsed);
+        session.turns = game.turns;
+        session.clicks = game.clicks;
+        session.used_flags = game.flagged;
+        session.hints = game.hints;
+        session
+    }
+
+    /// How the game went, for the stats.
+    pub fn record(&self, won: bool) -> GameRecord {
+        GameRecord {
+            finished: stats::now(),
+            difficulty: self.difficulty,
+            won,
+            time: self.timer.elapsed(Instant::now()),
+            no_guess: self.no_guess,
+            flagged: self.used_flags,
+            hints: self.hints,
+            clicks: self.clicks,
+            three_bv: self.field.three_bv(),
+        }
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Whether the board is cleared, which a chord opening the last safe
+    /// cells together with a mine doesn't do.
+    pub fn is_won(&self) -> bool {
+        self.field.is_won() && !self.mine_hit
+    }
+
+    /// Whether the first opened cell is still to be made safe.
+    pub fn safe_start(&self) -> bool {
+        self.safe_start
+    }
+
+    /// Whether a mine was opened that undo can still take back.
+    pub fn mine_hit(&self) -> bool {
+        self.mine_hit
+    }
+
+    /// Gives a relay player a head start, see `Relay::handicap`.
+    pub fn handicap(&mut self, player: usize, handicap: &Handicap) -> Result<(), String> {
+        let relay = self.relay.as_mut().ok_or("Handicaps even out games of several players")?;
+        relay.handicap(player, handicap, &mut self.field, &mut self.rng)?;
+        // the board is no longer untouched for the first move to rearrange
+        self.safe_start &= handicap.extra_reveals == 0;
+        Ok(())
+    }
+
+    fn write(&mut self, action: Action, x: u16, y: u16, annotate: bool) -> Result<(), String> {
+        let time = self.timer.elapsed(Instant::now());
+        let annotation = if annotate && self.rules.annotate {
+            Some(solver::annotate(&self.field, x, y, action == Action::Flag))
+        } else {
+            None
+        };
+        match self.recorder.as_mut() {
+            Some(recorder) => recorder.record(action, x, y, time, annotation.as_ref()).map_err(|e| format!("Can't record the game: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Finds a cell that can be worked out, preferring one to open, and
+    /// charges the hint penalty for it. Tells why there is none otherwise.
+    pub fn hint(&mut self) -> Result<Deduction, String> {
+        // single-cell logic, which doesn't know several mines per cell
+        let found = if self.field.mines_per_cell() > 1 { vec![] } else { solver::deductions(&self.field) };
+        let deduction = match found.iter().find(|d| matches!(d, Deduction::Safe(_, _))).or_else(|| found.first()) {
+            _ if self.rules.puzzle => return Err("No hints in a puzzle, every cell can be worked out".into()),
+            _ if self.field.mines_per_cell() > 1 => return Err("No hints with several mines per cell".into()),
+            Some(&deduction) => deduction,
+            None => return Err("No logical move available".into()),
+        };
+        self.hints += 1;
+        self.timer.penalize(self.rules.hint_penalty);
+        let (Deduction::Safe(x, y) | Deduction::Mine(x, y)) = deduction;
+        self.write(Action::Hint, x, y, false)?;
+        Ok(deduction)
+    }
+
+    /// Takes back the last move, or the last one taken back with `redo`.
+    /// Tells why nothing changed otherwise.
+    pub fn undo(&mut self, redo: bool) -> Result<(), String> {
+        if !self.rules.undo {
+            return Err("Undo is turned off".into());
+        }
+        let done = if redo { self.history.redo(&mut self.field) } else { self.history.undo(&mut self.field) };
+        match (done, redo) {
+            (false, false) => return Err("Nothing to undo".into()),
+            (false, true) => return Err("Nothing to redo".into()),
+            _ => {},
+        }
+        if self.mine_hit {
+            // the fatal move is gone for good
+            self.history.forget_redo();
+            self.mine_hit = false;
+        }
+        self.write(if redo { Action::Redo } else { Action::Undo }, 0, 0, false)
+    }
+
+    /// Plays `action`, one of open, flag or chord, on the cell at `x` and
+    /// `y`, for the relay player whose turn it is.
+    pub fn play(&mut self, action: Action, x: u16, y: u16) -> Result<Turn, String> {
+        if self.mine_hit {
+            return Ok(Turn::Over);
+        }
+        if matches!(action, Action::Hint | Action::Undo | Action::Redo) {
+            return Err(format!("{:?} isn't a move", action));
+        }
+        if self.relay.as_mut().is_some_and(|relay| relay.expire(Instant::now())) {
+            return Ok(Turn::TimeUp);
+        }
+        // a move off the board or into a locked stage changes nothing, so it
+        // isn't counted, recorded or used up from a relay turn
+        let refused = match self.field.cell(x, y) {
+            Err(e) => Some(e),
+            Ok(_) if action == Action::Open && self.field.is_locked_with(x, y, self.field.unlocked_zone()) => Some(MinesError::ZoneLocked(x, y)),
+            Ok(_) => None,
+        };
+        if let Some(e) = refused {
+            return Ok(Turn::Played(Played { result: Err(e), opened: 0, stage: None, handed_over: false }));
+        }
+        self.turns += 1;
+        match action {
+            Action::Open => self.clicks.opens += 1,
+            Action::Flag => self.clicks.flags += 1,
+            _ => self.clicks.chords += 1,
+        }
+        self.write(action, x, y, true)?;
+        let opened_before = self.field.opened_count();
+        let stage_before = if self.rules.stages > 1 { self.field.unlocked_zone() } else { 0 };
+        if action != Action::Flag {
+            self.timer.start(Instant::now());
+        }
+        // opening a hole doesn't use up the safe start
+        if action == Action::Open && self.safe_start && self.field.cell(x, y).is_ok_and(|cell| !cell.is_hole()) {
+            let _ = self.field.ensure_safe(x, y, &mut self.rng);
+            self.safe_start = false;
+        }
+        // without undo there is no need to copy the board, which is most of
+        // the work of a move on a big one
+        let before = if self.rules.undo { Some(self.field.clone()) } else { None };
+        let result = match action {
+            Action::Flag => {
+                self.used_flags = true;
+                if self.rules.marks {
+                    self.field.cycle_mark(x, y)
+                } else {
+                    self.field.flag(x, y)
+                }
+            },
+            Action::Chord => self.field.chord(x, y),
+            _ => self.field.open(x, y),
+        };
+        if let Some(before) = before {
+            self.history.record(before, &self.field);
+        }
+        let opened = self.field.opened_count().saturating_sub(opened_before);
+        if result == Err(MinesError::MineOpened) {
+            if !self.rules.undo || scores::RANKED.contains(&self.difficulty) {
+                // the game is over, with the turn of whoever opened the mine
+                return Ok(Turn::Played(Played { result, opened, stage: None, handed_over: false }));
+            }
+            self.mine_hit = true;
+        }
+        if let (Some(relay), Action::Flag, Ok(())) = (self.relay.as_mut(), action, &result) {
+            if self.field.cell(x, y).is_ok_and(|cell| cell.is_flagged()) {
+                relay.record_flag();
+                self.flag_owners.insert((x, y), relay.current);
+            } else {
+                self.flag_owners.remove(&(x, y));
+            }
+        }
+        // the last stage ends with the game
+        let stage = if self.rules.stages > 1 && self.field.unlocked_zone() > stage_before && !self.field.is_won() {
+            self.timer.split(Instant::now());
+            Some(self.timer.splits().len())
+        } else {
+            None
+        };
+        let handed_over = self.relay.as_mut().is_some_and(|relay| relay.record_move(opened, Instant::now()));
+        if self.is_won() {
+            self.timer.stop(Instant::now());
+        }
+        Ok(Turn::Played(Played { result, opened, stage, handed_over }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Difficulty, Field, MinesError};
+    use crate::handicap::Handicap;
+    use crate::relay::Relay;
+    use crate::replay::Action;
+    use crate::session::{Played, Rules, Session, Turn};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use std::time::{Duration, Instant};
+
+    fn session(board: &str, difficulty: Difficulty, rules: Rules) -> Session {
+        Session::new(Field::parse(board).unwrap(), difficulty, 1, StdRng::seed_from_u64(1), false, rules)
+    }
+
+    #[test]
+    fn undo_a_mine_in_casual_games() {
+        let mut casual = session("*..\n...\n..*", Difficulty::Custom(3, 3, 2), Rules::default());
+        match casual.play(Action::Open, 0, 0) {
+            Ok(Turn::Played(played)) => assert_eq!(Err(MinesError::MineOpened), played.result),
+            other => panic!("not played: {:?}", other),
+        }
+        assert!(casual.mine_hit());
+        casual.undo(false).unwrap();
+        assert!(!casual.mine_hit() && casual.field.opened_mine().is_none());
+        assert_eq!(Err("Nothing to redo".into()), casual.undo(true));
+        casual.play(Action::Open, 0, 0).unwrap();
+        assert_eq!(Ok(Turn::Over), casual.play(Action::Open, 1, 1));
+
+        let rules = Rules { undo: false, ..Rules::default() };
+        let mut strict = session("*..\n...\n..*", Difficulty::Custom(3, 3, 2), rules);
+        strict.play(Action::Open, 0, 0).unwrap();
+        assert!(!strict.mine_hit());
+        assert_eq!(Err("Undo is turned off".into()), strict.undo(false));
+    }
+
+    #[test]
+    fn hints() {
+        let mut game = session("*..\n...\n...", Difficulty::Beginner, Rules::default());
+        assert_eq!(Err("No logical move available".into()), game.hint());
+        game.play(Action::Open, 2, 2).unwrap();
+        assert!(game.hint().is_ok());
+        assert_eq!(1, game.hints);
+        assert!(game.timer.elapsed(Instant::now()) >= Duration::from_secs(20));
+        let rules = Rules { puzzle: true, ..Rules::default() };
+        assert!(session("*..\n...\n...", Difficulty::Beginner, rules).hint().is_err());
+        // hints are free where times aren't compared
+        let mut casual = session("*..\n...\n...", Difficulty::Custom(3, 3, 1), Rules::default());
+        casual.play(Action::Open, 2, 2).unwrap();
+        casual.hint().unwrap();
+        assert!(casual.timer.elapsed(Instant::now()) < Duration::from_secs(20));
+    }
+
+    #[test]
+    fn relay() {
+        let mut game = session("*....\n.....\n....*", Difficulty::Custom(5, 3, 2), Rules::default());
+        game.relay = Some(Relay::new(2, Some(1), None, Instant::now()));
+        game.handicap(1, &Handicap { extra_reveals: 1, ..Handicap::default() }).unwrap();
+        let opened = game.field.opened_count();
+        assert_eq!(opened, game.relay.as_ref().unwrap().stats[1].cells_opened);
+        assert!(matches!(game.play(Action::Flag, 0, 0), Ok(Turn::Played(Played { handed_over: true, .. }))));
+        assert_eq!(Some(&0), game.flag_owners.get(&(0, 0)));
+        game.play(Action::Flag, 2, 4).unwrap();
+        assert_eq!(Some(&1), game.flag_owners.get(&(2, 4)));
+        assert_eq!(1, game.relay.as_ref().unwrap().stats[1].flags);
+    }
+
+    #[test]
+    fn no_history_without_undo() {
+        let mut game = session("*..\n...", Difficulty::Custom(3, 2, 1), Rules { undo: false, ..Rules::default() });
+        game.play(Action::Open, 1, 2).unwrap();
+        let mut field = game.field.clone();
+        assert!(!game.history.undo(&mut field));
+    }
+
+    #[test]
+    fn refused_moves_dont_count() {
+        let mut game = session("*..\n...", Difficulty::Custom(3, 2, 1), Rules::default());
+        assert!(game.play(Action::Hint, 0, 0).is_err());
+        let started = Instant::now() - Duration::from_secs(5);
+        game.relay = Some(Relay::new(2, None, Some(Duration::from_secs(1)), started));
+        assert_eq!(Ok(Turn::TimeUp), game.play(Action::Open, 1, 2));
+        assert_eq!((0, 0, 0, 0), (game.turns, game.clicks.opens, game.clicks.flags, game.clicks.chords));
+        assert_eq!(1, game.relay.as_ref().unwrap().current);
+
+        // a typo off the board, with a move per turn that would hand over
+        game.relay = Some(Relay::new(2, Some(1), None, Instant::now()));
+        let off = game.play(Action::Open, 98, 98).unwrap();
+        assert_eq!(Turn::Played(Played { result: Err(MinesError::OutOfBounds(98, 98)), opened: 0, stage: None, handed_over: false }), off);
+        assert_eq!((0, 0), (game.turns, game.clicks.opens));
+        assert_eq!(0, game.relay.as_ref().unwrap().current);
+    }
+}
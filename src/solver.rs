@@ -0,0 +1,976 @@
+use crate::{CancelToken, Field, MinesError};
+use rand::Rng;
+use std::collections::HashMap;
+
+/// A move that follows from the visible board without guessing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Deduction {
+    /// The cell can't hide a mine.
+    Safe(u16, u16),
+    /// The cell has to hide a mine.
+    Mine(u16, u16),
+}
+
+/// Finds all closed, unflagged cells that follow from single opened numbers:
+/// the neighbours of a number are safe once it has all its mines around it,
+/// and they are mines once the closed neighbours are needed to reach the
+/// number, with the mines found this way counting for the numbers around
+/// them in turn. Flags don't count, so a wrong one can't make a mine look
+/// safe. On boards with line counts, every row and column counts like a
+/// number as well.
+pub fn deductions(field: &Field) -> Vec<Deduction> {
+    let known = proven(field);
+    let mut found = vec![];
+    for (x, col) in field.cells.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            if cell.opened || cell.is_flagged() {
+                continue;
+            }
+            match known[x][y] {
+                Some(true) => found.push(Deduction::Mine(x as u16, y as u16)),
+                Some(false) => found.push(Deduction::Safe(x as u16, y as u16)),
+                None => {},
+            }
+        }
+    }
+    found
+}
+
+/// What the opened numbers and line counts prove about every cell, `true`
+/// for a mine and `false` for a safe cell, without looking at flags. Every
+/// number is checked again whenever a cell around it is settled. When cells
+/// hold several mines the closed neighbours a number needs may share its
+/// mines, so only safe cells are proven then.
+fn proven(field: &Field) -> Vec<Vec<Option<bool>>> {
+    let single = field.mines_per_cell() == 1;
+    let mut known: Vec<Vec<Option<bool>>> = field.cells.iter()
+        .map(|col| col.iter().map(|cell| if cell.opened { Some(cell.is_mine()) } else { None }).collect())
+        .collect();
+    let mut rules: Vec<(usize, Vec<(u16, u16)>)> = vec![];
+    for (x, col) in field.cells.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            if cell.opened && !cell.is_mine() {
+                let neighbours = field.neighbours(x as u16, y as u16);
+                // a number without closed cells around it has nothing to tell
+                if neighbours.iter().any(|&(nx, ny)| !field.cells[nx as usize][ny as usize].opened) {
+                    rules.push((field.numbers[x][y] as usize, neighbours));
+                }
+            }
+        }
+    }
+    if field.has_line_counts() {
+        let (cols, rows) = field.size();
+        for (x, mines) in field.row_mines().into_iter().enumerate() {
+            rules.push((mines, (0..cols).map(|y| (x as u16, y)).collect()));
+        }
+        for (y, mines) in field.col_mines().into_iter().enumerate() {
+            rules.push((mines, (0..rows).map(|x| (x, y as u16)).collect()));
+        }
+    }
+    let mut rules_of: Vec<Vec<Vec<usize>>> = field.cells.iter().map(|col| vec![vec![]; col.len()]).collect();
+    for (rule, (_, cells)) in rules.iter().enumerate() {
+        for &(x, y) in cells {
+            rules_of[x as usize][y as usize].push(rule);
+        }
+    }
+    let mut pending: Vec<usize> = (0..rules.len()).collect();
+    while let Some(rule) = pending.pop() {
+        let (mines, cells) = &rules[rule];
+        let mut found = 0;
+        let mut open = vec![];
+        for &(x, y) in cells {
+            match known[x as usize][y as usize] {
+                // an opened mine holds all its mines, a proven one at least one
+                Some(true) => found += (field.cells[x as usize][y as usize].opened as usize
+                    * field.cells[x as usize][y as usize].mine_count() as usize).max(1),
+                Some(false) => {},
+                None => open.push((x, y)),
+            }
+        }
+        let mine = if open.is_empty() {
+            continue;
+        } else if *mines == found {
+            false
+        } else if single && *mines == found + open.len() {
+            true
+        } else {
+            continue;
+        };
+        for (x, y) in open {
+            known[x as usize][y as usize] = Some(mine);
+            pending.extend(rules_of[x as usize][y as usize].iter().copied());
+        }
+    }
+    known
+}
+
+/// Rough mine probability of every closed, unflagged cell. Cells next to
+/// opened numbers take the most pessimistic share of their numbers' missing
+/// mines, all other cells share the mines that are left on the board.
+pub fn mine_estimates(field: &Field) -> Vec<((u16, u16), f64)> {
+    let mut unknown = vec![];
+    let mut flagged = 0;
+    let unlocked = field.unlocked_zone();
+    for (x, col) in field.cells.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            if cell.is_flagged() {
+                flagged += 1;
+            } else if !cell.opened && !field.is_locked_with(x as u16, y as u16, unlocked) {
+                unknown.push((x as u16, y as u16));
+            }
+        }
+    }
+    let left = field.mine_count().saturating_sub(flagged) as f64;
+    let global = if unknown.is_empty() { 0.0 } else { (left / unknown.len() as f64).min(1.0) };
+    unknown.into_iter().map(|(x, y)| {
+        let mut estimate: Option<f64> = None;
+        for (nx, ny) in field.neighbours(x, y) {
+            if !field.cells[nx as usize][ny as usize].opened {
+                continue;
+            }
+            let mut missing = field.numbers[nx as usize][ny as usize] as f64;
+            let mut closed = 0.0;
+            for (ox, oy) in field.neighbours(nx, ny) {
+                let other = &field.cells[ox as usize][oy as usize];
+                if other.is_flagged() {
+                    missing -= 1.0;
+                } else if !other.opened {
+                    closed += 1.0;
+                }
+            }
+            let local = (missing.max(0.0) / closed).min(1.0);
+            estimate = Some(estimate.map_or(local, |e| e.max(local)));
+        }
+        ((x, y), estimate.unwrap_or(global))
+    }).collect()
+}
+
+/// Mine probability of every closed, unflagged cell, with the certain
+/// deductions settled at 0 and 1 and everything else estimated.
+pub fn probabilities(field: &Field) -> Vec<((u16, u16), f64)> {
+    let found = deductions(field);
+    let mut estimates = mine_estimates(field);
+    for (cell, p) in estimates.iter_mut() {
+        if found.contains(&Deduction::Safe(cell.0, cell.1)) {
+            *p = 0.0;
+        } else if found.contains(&Deduction::Mine(cell.0, cell.1)) {
+            *p = 1.0;
+        }
+    }
+    estimates
+}
+
+/// A cell with the probability that it hides a mine.
+pub type Chance = ((u16, u16), f64);
+
+/// Frontier groups with more cells than this are sampled instead of
+/// enumerated.
+const EXACT_LIMIT: usize = 20;
+
+/// Assignments drawn for a frontier group too big to enumerate.
+const SAMPLES: usize = 2_000;
+
+/// Enumerated groups a `SolverCache` keeps before it starts over.
+const CACHE_LIMIT: usize = 4_096;
+
+/// Closed cells next to opened numbers that are linked by sharing numbers,
+/// with the numbers as lists of cell indices and the mines they still need.
+struct Group {
+    cells: Vec<(u16, u16)>,
+    numbers: Vec<(Vec<usize>, usize)>,
+}
+
+/// Assignments of a group by their number of mines: how many were found and
+/// how often every cell is a mine in them.
+#[derive(Clone)]
+struct Tally {
+    count: Vec<f64>,
+    mines: Vec<Vec<f64>>,
+}
+
+impl Tally {
+    fn new(cells: usize) -> Self {
+        Self { count: vec![0.0; cells + 1], mines: vec![vec![0.0; cells]; cells + 1] }
+    }
+
+    fn add(&mut self, assignment: &[bool]) {
+        let k = assignment.iter().filter(|&&mine| mine).count();
+        self.count[k] += 1.0;
+        for (total, &mine) in self.mines[k].iter_mut().zip(assignment) {
+            if mine {
+                *total += 1.0;
+            }
+        }
+    }
+}
+
+/// The size and numbers of a group, all that its tally depends on.
+type GroupKey = (usize, Vec<(Vec<usize>, usize)>);
+
+/// Enumerations of frontier groups for `mine_probabilities_cached`, keyed
+/// by the group's size and numbers. Where on the board a group lies doesn't
+/// matter, so the same pattern in another place is a hit as well.
+#[derive(Default)]
+pub struct SolverCache {
+    tallies: HashMap<GroupKey, Tally>,
+    hits: usize,
+}
+
+impl SolverCache {
+    /// Groups taken from the cache instead of enumerated again.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Groups kept.
+    pub fn len(&self) -> usize {
+        self.tallies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tallies.is_empty()
+    }
+}
+
+/// Backtracking over the cells of a group, keeping track of the mines every
+/// number still needs and the cells it has left to place them.
+struct Search<'a> {
+    group: &'a Group,
+    cancel: &'a CancelToken,
+    /// Numbers every cell is part of.
+    numbers_of: Vec<Vec<usize>>,
+    assignment: Vec<bool>,
+    needed: Vec<isize>,
+    left: Vec<usize>,
+}
+
+impl<'a> Search<'a> {
+    fn new(group: &'a Group, cancel: &'a CancelToken) -> Self {
+        let mut numbers_of = vec![vec![]; group.cells.len()];
+        for (n, (cells, _)) in group.numbers.iter().enumerate() {
+            for &cell in cells {
+                numbers_of[cell].push(n);
+            }
+        }
+        Self {
+            group,
+            cancel,
+            numbers_of,
+            assignment: vec![false; group.cells.len()],
+            needed: group.numbers.iter().map(|(_, mines)| *mines as isize).collect(),
+            left: group.numbers.iter().map(|(cells, _)| cells.len()).collect(),
+        }
+    }
+
+    /// Places or clears a mine at `cell` and tells whether every number can
+    /// still be satisfied.
+    fn set(&mut self, cell: usize, mine: bool) -> bool {
+        self.assignment[cell] = mine;
+        let mut possible = true;
+        for &n in self.numbers_of[cell].iter() {
+            self.left[n] -= 1;
+            if mine {
+                self.needed[n] -= 1;
+            }
+            possible &= self.needed[n] >= 0 && self.needed[n] as usize <= self.left[n];
+        }
+        possible
+    }
+
+    fn unset(&mut self, cell: usize) {
+        for &n in self.numbers_of[cell].iter() {
+            self.left[n] += 1;
+            if self.assignment[cell] {
+                self.needed[n] += 1;
+            }
+        }
+        self.assignment[cell] = false;
+    }
+
+    /// Adds every assignment of the cells from `cell` on to `tally`, or
+    /// some of them if `cancel` is cancelled meanwhile.
+    fn enumerate(&mut self, cell: usize, tally: &mut Tally) {
+        if cell == self.group.cells.len() {
+            tally.add(&self.assignment);
+            return;
+        }
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        for &mine in [false, true].iter() {
+            if self.set(cell, mine) {
+                self.enumerate(cell + 1, tally);
+            }
+            self.unset(cell);
+        }
+    }
+
+    /// Finds one assignment of the cells from `cell` on, trying mines and
+    /// water in random order. Gives up once `steps` run out.
+    fn sample<R: Rng>(&mut self, cell: usize, rng: &mut R, steps: &mut usize) -> bool {
+        if cell == self.group.cells.len() {
+            return true;
+        }
+        if *steps == 0 {
+            return false;
+        }
+        *steps -= 1;
+        let first = rng.gen::<bool>();
+        for &mine in [first, !first].iter() {
+            let found = self.set(cell, mine) && self.sample(cell + 1, rng, steps);
+            if found {
+                return true;
+            }
+            self.unset(cell);
+        }
+        false
+    }
+}
+
+/// Splits the closed cells next to opened numbers into groups that don't
+/// share a number.
+fn frontier_groups(field: &Field) -> Vec<Group> {
+    let mut numbers: Vec<(Vec<(u16, u16)>, usize)> = vec![];
+    for (x, col) in field.cells.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            if !cell.opened {
+                continue;
+            }
+            let mut flagged = 0;
+            let mut closed = vec![];
+            for (nx, ny) in field.neighbours(x as u16, y as u16) {
+                let neighbour = &field.cells[nx as usize][ny as usize];
+                if neighbour.is_flagged() {
+                    flagged += 1;
+                } else if !neighbour.opened {
+                    closed.push((nx, ny));
+                }
+            }
+            if !closed.is_empty() {
+                numbers.push((closed, (field.numbers[x][y] as usize).saturating_sub(flagged)));
+            }
+        }
+    }
+    let mut groups: Vec<Group> = vec![];
+    for (cells, mines) in numbers {
+        // merge every group this number shares a cell with
+        let (mut linked, rest): (Vec<Group>, Vec<Group>) = groups.into_iter()
+            .partition(|group| cells.iter().any(|cell| group.cells.contains(cell)));
+        groups = rest;
+        let mut group = linked.pop().unwrap_or(Group { cells: vec![], numbers: vec![] });
+        for other in linked {
+            let offset = group.cells.len();
+            group.cells.extend(other.cells);
+            group.numbers.extend(other.numbers.into_iter()
+                .map(|(indices, mines)| (indices.into_iter().map(|i| i + offset).collect(), mines)));
+        }
+        let indices = cells.iter().map(|cell| match group.cells.iter().position(|c| c == cell) {
+            Some(i) => i,
+            None => {
+                group.cells.push(*cell);
+                group.cells.len() - 1
+            },
+        }).collect();
+        group.numbers.push((indices, mines));
+        groups.push(group);
+    }
+    groups
+}
+
+/// Counts the assignments of `group`, as shares of all of them. Stops with
+/// `MinesError::Cancelled` once `cancel` is cancelled.
+fn group_tally<R: Rng>(group: &Group, rng: &mut R, cancel: &CancelToken) -> Result<Tally, MinesError> {
+    let mut tally = Tally::new(group.cells.len());
+    let mut search = Search::new(group, cancel);
+    if group.cells.len() <= EXACT_LIMIT {
+        search.enumerate(0, &mut tally);
+    } else {
+        for _ in 0..SAMPLES {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let mut steps = 100 * group.cells.len();
+            if search.sample(0, rng, &mut steps) {
+                tally.add(&search.assignment);
+            }
+            search = Search::new(group, cancel);
+        }
+    }
+    // a tally cut short is wrong, and mustn't end up in a cache
+    if cancel.is_cancelled() {
+        return Err(MinesError::Cancelled);
+    }
+    // only the shares matter, and unscaled counts of many groups overflow
+    let total: f64 = tally.count.iter().sum();
+    if total > 0.0 {
+        tally.count.iter_mut().for_each(|count| *count /= total);
+        tally.mines.iter_mut().flatten().for_each(|mines| *mines /= total);
+    }
+    Ok(tally)
+}
+
+/// Mine probability of every closed, unflagged cell, from the assignments
+/// of mines that fit the opened numbers and the mines left. Groups of up to
+/// `EXACT_LIMIT` cells are enumerated, bigger ones are sampled, which makes
+/// their probabilities estimates. Line counts aren't taken into account.
+/// Falls back to `probabilities` when nothing fits, e.g. after wrong flags.
+pub fn mine_probabilities<R: Rng>(field: &Field, rng: &mut R) -> Vec<((u16, u16), f64)> {
+    mine_probabilities_cached(field, rng, &mut SolverCache::default())
+}
+
+/// Like `mine_probabilities`, reusing the enumerations of frontier groups
+/// found in `cache` and keeping the new ones there. A move only changes the
+/// groups around it, so asking again after every move mostly hits the cache.
+pub fn mine_probabilities_cached<R: Rng>(field: &Field, rng: &mut R, cache: &mut SolverCache) -> Vec<((u16, u16), f64)> {
+    mine_probabilities_until(field, rng, cache, &CancelToken::new()).expect("nothing cancels it")
+}
+
+/// Like `mine_probabilities_cached`, but stops with `MinesError::Cancelled`
+/// once `cancel` is cancelled, also in the middle of enumerating a group.
+pub fn mine_probabilities_until<R: Rng>(field: &Field, rng: &mut R, cache: &mut SolverCache, cancel: &CancelToken) -> Result<Vec<Chance>, MinesError> {
+    let groups = frontier_groups(field);
+    let tallies = groups.iter().map(|group| {
+        if group.cells.len() > EXACT_LIMIT {
+            // samples differ every time, so they aren't worth keeping
+            return group_tally(group, rng, cancel);
+        }
+        let key = (group.cells.len(), group.numbers.clone());
+        if let Some(tally) = cache.tallies.get(&key) {
+            cache.hits += 1;
+            return Ok(tally.clone());
+        }
+        let tally = group_tally(group, rng, cancel)?;
+        if cache.tallies.len() >= CACHE_LIMIT {
+            cache.tallies.clear();
+        }
+        cache.tallies.insert(key, tally.clone());
+        Ok(tally)
+    }).collect::<Result<Vec<Tally>, MinesError>>()?;
+    let (cols, rows) = field.size();
+    let mut found: Vec<Vec<Option<f64>>> = vec![vec![None; cols as usize]; rows as usize];
+    let mut interior = vec![];
+    let mut flagged = 0;
+    for group in groups.iter() {
+        for &(x, y) in group.cells.iter() {
+            found[x as usize][y as usize] = Some(0.0);
+        }
+    }
+    for (x, col) in field.cells.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            if cell.is_flagged() {
+                flagged += 1;
+            } else if !cell.opened && found[x][y].is_none() {
+                interior.push((x as u16, y as u16));
+            }
+        }
+    }
+    let left = field.mine_count() as isize - flagged as isize;
+    let others = interior.len() as isize;
+    // the ways to place the other mines in the interior when the groups hold
+    // t of them, relative to the fewest t that leaves few enough, in logs
+    // to stay in range
+    let max_mines: usize = groups.iter().map(|group| group.cells.len()).sum();
+    let log_weights: Vec<Option<f64>> = (0..=max_mines as isize).scan(None, |log: &mut Option<f64>, t| {
+        let rest = left - t;
+        *log = if rest < 0 || rest > others {
+            None
+        } else {
+            Some(log.map_or(0.0, |log| log + ((rest + 1) as f64).ln() - ((others - rest) as f64).ln()))
+        };
+        Some(*log)
+    }).collect();
+    let top = log_weights.iter().flatten().cloned().fold(f64::MIN, f64::max);
+    let weight = |t: usize| log_weights.get(t).cloned().flatten().map_or(0.0, |log| (log - top).exp());
+    let convolve = |a: &[f64], b: &[f64]| {
+        let mut sum = vec![0.0; a.len() + b.len() - 1];
+        for (i, x) in a.iter().enumerate() {
+            for (j, y) in b.iter().enumerate() {
+                sum[i + j] += x * y;
+            }
+        }
+        sum
+    };
+    let all = tallies.iter().fold(vec![1.0], |dist, tally| convolve(&dist, &tally.count));
+    let total: f64 = all.iter().enumerate().map(|(t, p)| p * weight(t)).sum();
+    if total <= 0.0 || !total.is_finite() {
+        return Ok(probabilities(field));
+    }
+    for (g, (group, tally)) in groups.iter().zip(tallies.iter()).enumerate() {
+        let rest = tallies.iter().enumerate()
+            .filter(|&(other, _)| other != g)
+            .fold(vec![1.0], |dist, (_, tally)| convolve(&dist, &tally.count));
+        for (i, &(x, y)) in group.cells.iter().enumerate() {
+            let mut p = 0.0;
+            for (k, mines) in tally.mines.iter().enumerate() {
+                for (j, share) in rest.iter().enumerate() {
+                    p += mines[i] * share * weight(k + j);
+                }
+            }
+            found[x as usize][y as usize] = Some(p / total);
+        }
+    }
+    if others > 0 {
+        let expected: f64 = all.iter().enumerate().map(|(t, p)| p * weight(t) * (left - t as isize) as f64).sum();
+        for &(x, y) in interior.iter() {
+            found[x as usize][y as usize] = Some(expected / total / others as f64);
+        }
+    }
+    let mut probabilities = vec![];
+    let unlocked = field.unlocked_zone();
+    for (x, row) in found.into_iter().enumerate() {
+        for (y, p) in row.into_iter().enumerate() {
+            match p {
+                Some(p) if !field.is_locked_with(x as u16, y as u16, unlocked) => probabilities.push(((x as u16, y as u16), p)),
+                _ => {},
+            }
+        }
+    }
+    Ok(probabilities)
+}
+
+/// The solver's view of a move, taken before the move is played.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    /// Whether the move follows from the board without guessing.
+    pub forced: bool,
+    /// Estimated chance that the cell hides a mine.
+    pub mine_probability: f64,
+    /// Up to three other cells that were safest to open instead, with their
+    /// estimated mine probability.
+    pub alternatives: Vec<((u16, u16), f64)>,
+}
+
+/// Evaluates opening (or with `flag` flagging) the cell at `(x, y)`.
+pub fn annotate(field: &Field, x: u16, y: u16, flag: bool) -> Annotation {
+    let found = deductions(field);
+    let mut estimates = probabilities(field);
+    let mine_probability = estimates.iter()
+        .find(|(cell, _)| *cell == (x, y))
+        .map_or(0.0, |(_, p)| *p);
+    let forced = if flag {
+        found.contains(&Deduction::Mine(x, y))
+    } else {
+        found.contains(&Deduction::Safe(x, y))
+    };
+    estimates.retain(|(cell, _)| *cell != (x, y));
+    estimates.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+    estimates.truncate(3);
+    Annotation {
+        forced,
+        mine_probability,
+        alternatives: estimates,
+    }
+}
+
+/// Plays every certain move on `field` until nothing follows anymore.
+/// Returns `false` if a deduction opened a mine. Flags are left as they
+/// are, a wrong one just stays in place.
+pub fn apply_deductions(field: &mut Field) -> bool {
+    apply_deductions_until(field, &CancelToken::new()) == Ok(true)
+}
+
+/// Like `apply_deductions`, but checks `cancel` between rounds and stops
+/// with `MinesError::Cancelled`.
+pub fn apply_deductions_until(field: &mut Field, cancel: &CancelToken) -> Result<bool, MinesError> {
+    loop {
+        if cancel.is_cancelled() {
+            return Err(MinesError::Cancelled);
+        }
+        let found = deductions(field);
+        if found.is_empty() {
+            return Ok(true);
+        }
+        for deduction in found {
+            let result = match deduction {
+                Deduction::Safe(x, y) => field.open(x, y),
+                Deduction::Mine(x, y) => {
+                    if field.cells[x as usize][y as usize].is_flagged() {
+                        Ok(())
+                    } else {
+                        field.flag(x, y)
+                    }
+                },
+            };
+            if let Err(MinesError::MineOpened) = result {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// Whether the board can be cleared from `(x, y)` without a single guess.
+pub fn solvable_from(field: &Field, x: u16, y: u16) -> bool {
+    solvable_from_until(field, x, y, &CancelToken::new()) == Ok(true)
+}
+
+/// Like `solvable_from`, but stops with `MinesError::Cancelled` once
+/// `cancel` is cancelled.
+pub fn solvable_from_until(field: &Field, x: u16, y: u16, cancel: &CancelToken) -> Result<bool, MinesError> {
+    let mut field = field.clone();
+    if field.open(x, y).is_err() {
+        return Ok(false);
+    }
+    Ok(apply_deductions_until(&mut field, cancel)? && field.is_won())
+}
+
+/// Closed, unflagged cells that hide exactly `mines` mines between them.
+struct Constraint {
+    /// Sorted, so that subsets can be found by binary search.
+    cells: Vec<(u16, u16)>,
+    mines: usize,
+}
+
+/// The constraints of every opened number, of the line counts if the board
+/// has them and of the mines left on the board.
+fn constraints(field: &Field) -> Vec<Constraint> {
+    let unknown = |&(x, y): &(u16, u16)| {
+        let cell = &field.cells[x as usize][y as usize];
+        !cell.opened && !cell.is_flagged()
+    };
+    let flagged = |cells: &[(u16, u16)]| cells.iter().filter(|&&(x, y)| field.cells[x as usize][y as usize].is_flagged()).count();
+    let mut lines: Vec<(usize, Vec<(u16, u16)>)> = vec![];
+    for (x, col) in field.cells.iter().enumerate() {
+        for (y, cell) in col.iter().enumerate() {
+            if cell.opened {
+                lines.push((field.numbers[x][y] as usize, field.neighbours(x as u16, y as u16)));
+            }
+        }
+    }
+    let (cols, rows) = field.size();
+    if field.has_line_counts() {
+        for (x, mines) in field.row_mines().into_iter().enumerate() {
+            lines.push((mines, (0..cols).map(|y| (x as u16, y)).collect()));
+        }
+        for (y, mines) in field.col_mines().into_iter().enumerate() {
+            lines.push((mines, (0..rows).map(|x| (x, y as u16)).collect()));
+        }
+    }
+    let all: Vec<(u16, u16)> = (0..rows).flat_map(|x| (0..cols).map(move |y| (x, y))).collect();
+    // the mines left say nothing about the unlocked cells while later stages hide some
+    let unlocked = field.unlocked_zone();
+    if !all.iter().any(|&(x, y)| field.is_locked_with(x, y, unlocked)) {
+        lines.push((field.mine_count(), all));
+    }
+    lines.into_iter()
+        .filter_map(|(mines, cells)| {
+            let mines = mines.checked_sub(flagged(&cells))?;
+            let mut cells: Vec<(u16, u16)> = cells.into_iter().filter(unknown).collect();
+            cells.sort_unstable();
+            if cells.is_empty() {
+                None
+            } else {
+                Some(Constraint { cells, mines })
+            }
+        })
+        .collect()
+}
+
+/// Finds the cells that follow from comparing constraints: when the cells
+/// of one constraint are all part of another, the rest of the other one
+/// hides the difference of their mines. Catches what `deductions` misses,
+/// like the 1-2-1 and 1-2-2-1 patterns and the last few mines of a board.
+/// Like `deductions` it finds only safe cells when cells hold several mines.
+pub fn constraint_deductions(field: &Field) -> Vec<Deduction> {
+    let constraints = constraints(field);
+    let single = field.mines_per_cell() == 1;
+    let mut found = vec![];
+    let mut conclude = |cells: Vec<(u16, u16)>, mines: usize| {
+        let deduction: fn(u16, u16) -> Deduction = if mines == 0 {
+            Deduction::Safe
+        } else if single && mines == cells.len() {
+            Deduction::Mine
+        } else {
+            return;
+        };
+        for (x, y) in cells {
+            let d = deduction(x, y);
+            if !found.contains(&d) {
+                found.push(d);
+            }
+        }
+    };
+    for a in constraints.iter() {
+        conclude(a.cells.clone(), a.mines);
+        for b in constraints.iter() {
+            if a.cells.len() >= b.cells.len() || b.mines < a.mines
+                || !a.cells.iter().all(|cell| b.cells.binary_search(cell).is_ok()) {
+                continue;
+            }
+            let rest = b.cells.iter().filter(|cell| a.cells.binary_search(cell).is_err()).copied().collect();
+            conclude(rest, b.mines - a.mines);
+        }
+    }
+    found
+}
+
+/// How `solve` played a board.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Solution {
+    /// Whether the board was cleared, `false` when a guess hit a mine.
+    pub cleared: bool,
+    /// Moves played, the first one and flags included.
+    pub moves: u32,
+    /// Moves that didn't follow from the board, the one that hit a mine
+    /// included.
+    pub guesses: u32,
+    /// Moves played before the first guess, all of them without one.
+    pub certain_moves: u32,
+}
+
+impl Solution {
+    /// Whether the board was cleared without a single guess, which makes it
+    /// solvable from the first cell without guessing.
+    pub fn without_guessing(&self) -> bool {
+        self.cleared && self.guesses == 0
+    }
+}
+
+/// Plays `field` to the end, starting by opening `(x, y)`. Every move comes
+/// from `deductions` and then from `constraint_deductions`. When nothing
+/// follows the solver guesses at the cell least likely to hide a mine,
+/// going by `probabilities`, and stops with the board not cleared when the
+/// guess hits one. Fails with `MinesError::MineOpened` if `(x, y)` hides a
+/// mine or wrong flags lead to one.
+pub fn solve(field: &mut Field, x: u16, y: u16) -> Result<Solution, MinesError> {
+    let mut solution = Solution { moves: 1, ..Solution::default() };
+    field.open(x, y)?;
+    // cells in locked stages can't be played yet, and a batch may have opened cells already
+    let playable = |field: &Field, d: &Deduction, unlocked: u8| match *d {
+        Deduction::Safe(x, y) => !field.cells[x as usize][y as usize].opened && !field.is_locked_with(x, y, unlocked),
+        Deduction::Mine(x, y) => !field.cells[x as usize][y as usize].is_flagged(),
+    };
+    while !field.is_won() {
+        // a stage cleared during a round unlocks the next one in the round after
+        let unlocked = field.unlocked_zone();
+        let mut found = deductions(field);
+        found.retain(|d| playable(field, d, unlocked));
+        if found.is_empty() {
+            found = constraint_deductions(field);
+            found.retain(|d| playable(field, d, unlocked));
+        }
+        if found.is_empty() {
+            let guess = probabilities(field).into_iter()
+                .filter(|&((x, y), _)| playable(field, &Deduction::Safe(x, y), unlocked))
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+            let (x, y) = match guess {
+                Some((cell, _)) => cell,
+                None => return Ok(solution),
+            };
+            if solution.guesses == 0 {
+                solution.certain_moves = solution.moves;
+            }
+            solution.guesses += 1;
+            solution.moves += 1;
+            match field.open(x, y) {
+                Err(MinesError::MineOpened) => return Ok(solution),
+                result => result?,
+            }
+            continue;
+        }
+        for deduction in found {
+            if !playable(field, &deduction, unlocked) {
+                continue;
+            }
+            solution.moves += 1;
+            match deduction {
+                Deduction::Safe(x, y) => field.open(x, y)?,
+                Deduction::Mine(x, y) => field.flag(x, y)?,
+            }
+        }
+    }
+    if solution.guesses == 0 {
+        solution.certain_moves = solution.moves;
+    }
+    solution.cleared = true;
+    Ok(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancelToken, Field, MinesError};
+    use crate::solver::{annotate, constraint_deductions, deductions, mine_estimates, mine_probabilities, mine_probabilities_cached, mine_probabilities_until, probabilities, solvable_from, solvable_from_until, solve, Deduction::*, SolverCache};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn forced_mine() {
+        let field = Field::parse("o*\noo").unwrap();
+        assert_eq!(vec![Mine(0, 1)], deductions(&field));
+    }
+
+    #[test]
+    fn satisfied_number() {
+        let field = Field::parse("oF.\noo.\n...").unwrap();
+        let found = deductions(&field);
+        assert!(found.contains(&Safe(0, 2)));
+        assert!(found.contains(&Safe(1, 2)));
+        assert!(!found.iter().any(|d| matches!(d, Mine(_, _))));
+    }
+
+    #[test]
+    fn wrong_flag() {
+        // the flag is on water, so the mine below is still unknown
+        let field = Field::parse("of\n*.").unwrap();
+        assert!(deductions(&field).is_empty());
+        // a mine proven by one number satisfies the next
+        let field = Field::parse("oo.\n*o.\nooo").unwrap();
+        assert!(deductions(&field).contains(&Safe(0, 2)));
+    }
+
+    #[test]
+    fn solvable() {
+        let field = Field::parse("*..\n...\n...").unwrap();
+        assert!(solvable_from(&field, 2, 2));
+        assert!(!solvable_from(&field, 0, 0));
+    }
+
+    #[test]
+    fn cancelled() {
+        let field = Field::parse("*..\n...\n...").unwrap();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert_eq!(Err(MinesError::Cancelled), solvable_from_until(&field, 2, 2, &cancel));
+    }
+
+    #[test]
+    fn line_counts() {
+        let mut field = Field::parse("o*.\n...\n..*").unwrap();
+        assert!(!deductions(&field).contains(&Safe(1, 1)));
+        field.set_line_counts(true);
+        let found = deductions(&field);
+        // the middle row and the first column are free of mines
+        assert!(found.contains(&Safe(1, 1)) && found.contains(&Safe(2, 0)));
+        let mut field = Field::parse("*.\noo").unwrap();
+        field.set_line_counts(true);
+        // the only closed cell left in the first column, and a free column
+        assert_eq!(vec![Mine(0, 0), Safe(0, 1)], deductions(&field));
+    }
+
+    #[test]
+    fn one_two_one() {
+        let field = Field::parse("*.*\nooo").unwrap();
+        assert!(deductions(&field).is_empty());
+        let found = constraint_deductions(&field);
+        assert!(found.contains(&Mine(0, 0)) && found.contains(&Mine(0, 2)));
+        assert!(!found.contains(&Mine(0, 1)));
+    }
+
+    #[test]
+    fn multimine() {
+        // the 2 may be both mines of the first cell, so the last one isn't a mine
+        let field = Field::parse("2o.").unwrap();
+        assert!(deductions(&field).is_empty());
+        assert!(constraint_deductions(&field).is_empty());
+        let mut field = Field::parse("2o.").unwrap();
+        solve(&mut field, 0, 1).unwrap();
+        assert!(!field.cells[0][2].is_flagged());
+    }
+
+    #[test]
+    fn full_solve() {
+        let mut field = Field::parse("*..\n...\n...").unwrap();
+        let solution = solve(&mut field, 2, 2).unwrap();
+        assert!(solution.without_guessing() && field.is_won());
+        // the 50/50 is guessed at its first cell, wherever the mine is
+        let mut field = Field::parse(".*\noo").unwrap();
+        let solution = solve(&mut field, 1, 0).unwrap();
+        assert!(solution.cleared && field.is_won());
+        assert_eq!((1, 1, false), (solution.guesses, solution.certain_moves, solution.without_guessing()));
+        let mut field = Field::parse("*.\noo").unwrap();
+        let solution = solve(&mut field, 1, 0).unwrap();
+        assert!(!solution.cleared && !field.is_won());
+        assert_eq!((2, 1), (solution.moves, solution.guesses));
+        let mut field = Field::parse("*..\n...").unwrap();
+        assert_eq!(Err(MinesError::MineOpened), solve(&mut field, 0, 0));
+    }
+
+    #[test]
+    fn needs_guess() {
+        let field = Field::parse("*.\n.*\n..\n..").unwrap();
+        assert!(!solvable_from(&field, 3, 0));
+    }
+
+    #[test]
+    fn estimates() {
+        let field = Field::parse("o..\n.*.\n...\n...").unwrap();
+        let estimates = mine_estimates(&field);
+        assert_eq!(11, estimates.len());
+        let of = |cell| estimates.iter().find(|(c, _)| *c == cell).unwrap().1;
+        assert!((of((0, 1)) - 1.0 / 3.0).abs() < 1e-9);
+        assert!((of((3, 2)) - 1.0 / 11.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn exact_probabilities() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let of = |probabilities: &[((u16, u16), f64)], cell| probabilities.iter().find(|(c, _)| *c == cell).unwrap().1;
+        let field = Field::parse("*.*\nooo").unwrap();
+        let found = mine_probabilities(&field, &mut rng);
+        assert_eq!((1.0, 0.0, 1.0), (of(&found, (0, 0)), of(&found, (0, 1)), of(&found, (0, 2))));
+        // one mine between the numbers or one on each side, with the other
+        // mine in one of three cells in the first case
+        let field = Field::parse(".o*o...*").unwrap();
+        let found = mine_probabilities(&field, &mut rng);
+        assert_eq!(6, found.len());
+        assert!((of(&found, (0, 2)) - 0.75).abs() < 1e-9);
+        assert!((of(&found, (0, 0)) - 0.25).abs() < 1e-9);
+        assert!((of(&found, (0, 6)) - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cached_probabilities() {
+        let mut cache = SolverCache::default();
+        let field = Field::parse(".o*o.....o*o..").unwrap();
+        let found = mine_probabilities_cached(&field, &mut StdRng::seed_from_u64(0), &mut cache);
+        // both ends make the same group, enumerated once
+        assert_eq!((1, 1), (cache.len(), cache.hits()));
+        assert_eq!(found, mine_probabilities(&field, &mut StdRng::seed_from_u64(0)));
+        assert_eq!(found, mine_probabilities_cached(&field, &mut StdRng::seed_from_u64(0), &mut cache));
+        assert_eq!((1, 3), (cache.len(), cache.hits()));
+    }
+
+    #[test]
+    fn cancelled_probabilities() {
+        let mut cache = SolverCache::default();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let field = Field::parse(".o*o.....o*o..").unwrap();
+        let found = mine_probabilities_until(&field, &mut StdRng::seed_from_u64(0), &mut cache, &cancel);
+        assert_eq!(Err(MinesError::Cancelled), found);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn sampled_probabilities() {
+        // 25 cells that alternate, with only the mine count to tell which way
+        let field = Field::parse(&format!("*{}", "o.o*".repeat(12))).unwrap();
+        let found = mine_probabilities(&field, &mut StdRng::seed_from_u64(0));
+        assert_eq!(25, found.len());
+        assert!(found.iter().all(|&((_, y), p)| p == if y % 4 == 0 { 1.0 } else { 0.0 }));
+    }
+
+    #[test]
+    fn settled_probabilities() {
+        let field = Field::parse("o*.\noo.\n...").unwrap();
+        let probabilities = probabilities(&field);
+        let of = |cell| probabilities.iter().find(|(c, _)| *c == cell).unwrap().1;
+        assert_eq!(1.0, of((0, 1)));
+        assert!(of((2, 2)) < 1.0);
+    }
+
+    #[test]
+    fn annotations() {
+        let field = Field::parse("o*.\noo.\n...").unwrap();
+        let annotation = annotate(&field, 0, 1, true);
+        assert!(annotation.forced);
+        assert_eq!(1.0, annotation.mine_probability);
+        assert_eq!(3, annotation.alternatives.len());
+        assert!(!annotate(&field, 0, 1, false).forced);
+    }
+
+    #[test]
+    fn nothing_certain() {
+        let field = Field::parse("o..\n.*.\n...").unwrap();
+        assert!(deductions(&field).is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+//! The board in plain sentences for players who can't see it, read out by
+//! a screen reader: what is in a cell, a row or a column, how the game
+//! stands and what a move did, like "Row 3, column 5: 2 adjacent mines".
+//! Rows and columns are counted from 1 the way they are typed.
+
+use crate::{Field, Mark};
+use crate::replay::Action;
+
+/// Where the cell at `(x, y)` is, like "Row 3, column 5", with the layer
+/// first on a three-dimensional board.
+pub fn place(field: &Field, x: u16, y: u16) -> String {
+    if field.layers() > 1 {
+        let height = field.layer_height();
+        format!("Layer {}, row {}, column {}", x / height + 1, x % height + 1, y + 1)
+    } else {
+        format!("Row {}, column {}", x + 1, y + 1)
+    }
+}
+
+/// What the player knows about the cell at `(x, y)`, like "closed",
+/// "flagged" or "2 adjacent mines".
+pub fn state(field: &Field, x: u16, y: u16) -> String {
+    let cell = match field.cell(x, y) {
+        Ok(cell) => cell,
+        Err(_) => return "off the board".into(),
+    };
+    if cell.is_hole() {
+        return "a hole".into();
+    }
+    if cell.is_opened() {
+        return match (cell.mine_count(), field.number(x, y).unwrap_or(0)) {
+            (0, 0) => "no adjacent mines".into(),
+            (0, 1) => "1 adjacent mine".into(),
+            (0, number) => format!("{} adjacent mines", number),
+            (1, _) => "a mine".into(),
+            (mines, _) => format!("{} mines", mines),
+        };
+    }
+    match (cell.mark(), cell.flags()) {
+        (Mark::Flag, flags) if flags > 1 => format!("{} flags", flags),
+        (Mark::Flag, _) => "flagged".into(),
+        (Mark::Question, _) => "marked with a question mark".into(),
+        _ if field.is_locked(x, y) => "closed, in a later stage".into(),
+        _ => "closed".into(),
+    }
+}
+
+/// The cell at `(x, y)` in a sentence, like "Row 3, column 5: closed."
+pub fn cell(field: &Field, x: u16, y: u16) -> String {
+    format!("{}: {}.", place(field, x, y), state(field, x, y))
+}
+
+/// The cells of row `x` from left to right, runs of cells alike read
+/// together: "Row 2: columns 1 to 4, closed; column 5, flagged."
+pub fn row(field: &Field, x: u16) -> String {
+    let (cols, _) = field.size();
+    let name = place(field, x, 0);
+    let name = &name[..name.rfind(", column").unwrap_or(name.len())];
+    let states: Vec<String> = (0..cols).map(|y| state(field, x, y)).collect();
+    format!("{}: {}.", name, runs(&states, "column"))
+}
+
+/// The cells of column `y` in the layer starting at row `top`, from top to
+/// bottom, like `row`.
+pub fn column(field: &Field, y: u16, top: u16) -> String {
+    let states: Vec<String> = (top..top + field.layer_height()).map(|x| state(field, x, y)).collect();
+    let name = if field.layers() > 1 {
+        format!("Layer {}, column {}", top / field.layer_height() + 1, y + 1)
+    } else {
+        format!("Column {}", y + 1)
+    };
+    format!("{}: {}.", name, runs(&states, "row"))
+}
+
+/// `states` numbered from 1 with runs of the same state read at once:
+/// "columns 1 to 4, closed; column 5, flagged".
+fn runs(states: &[String], unit: &str) -> String {
+    let mut parts = vec![];
+    let mut start = 0;
+    for end in 1..=states.len() {
+        if end < states.len() && states[end] == states[start] {
+            continue;
+        }
+        parts.push(match end - start {
+            1 => format!("{} {}, {}", unit, start + 1, states[start]),
+            2 => format!("{}s {} and {}, {}", unit, start + 1, end, states[start]),
+            _ => format!("{}s {} to {}, {}", unit, start + 1, end, states[start]),
+        });
+        start = end;
+    }
+    parts.join("; ")
+}
+
+/// How the game stands: the size of the board, the mines left to flag and
+/// the cells still closed.
+pub fn board(field: &Field) -> String {
+    let (cols, rows) = field.size();
+    let layers = if field.layers() > 1 {
+        format!(" in {} layers", field.layers())
+    } else {
+        String::new()
+    };
+    let rows = rows / field.layers();
+    let closed = field.cell_count() - field.opened_count();
+    format!(
+        "{} columns by {} row{}{}, {} mines left to flag, {} of {} cells closed.",
+        cols, rows, if rows == 1 { "" } else { "s" }, layers, field.mines_left(), closed, field.cell_count(),
+    )
+}
+
+/// What `action` on the cell at `(x, y)` did to the board, from `before`
+/// to `after`.
+pub fn outcome(before: &Field, after: &Field, action: Action, x: u16, y: u16) -> String {
+    let hit: Vec<(u16, u16)> = after.opened_mines().into_iter().filter(|cell| !before.opened_mines().contains(cell)).collect();
+    let opened = after.opened_count() - before.opened_count().min(after.opened_count());
+    match action {
+        Action::Flag => cell(after, x, y),
+        Action::Chord if !hit.is_empty() => {
+            let places: Vec<String> = hit.iter().map(|&(mx, my)| place(after, mx, my).to_lowercase()).collect();
+            format!("Chord on {} opened a mine at {}.", place(after, x, y).to_lowercase(), places.join(" and "))
+        },
+        Action::Chord => match opened {
+            0 => format!("Chord on {} opened nothing.", place(after, x, y).to_lowercase()),
+            1 => format!("Chord on {} opened 1 cell.", place(after, x, y).to_lowercase()),
+            _ => format!("Chord on {} opened {} cells.", place(after, x, y).to_lowercase(), opened),
+        },
+        _ if opened > 1 && hit.is_empty() => format!("{}: {}, {} cells opened.", place(after, x, y), state(after, x, y), opened),
+        _ => cell(after, x, y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Field;
+    use crate::replay::Action;
+    use crate::speech::{board, cell, column, outcome, row};
+
+    #[test]
+    fn cells() {
+        let mut field = Field::parse("o.*\nf..\n...\n").unwrap();
+        field.open(2, 0).unwrap();
+        assert_eq!("Row 1, column 2: closed.", cell(&field, 0, 1));
+        assert_eq!("Row 1, column 1: no adjacent mines.", cell(&field, 0, 0));
+        assert_eq!("Row 2, column 1: flagged.", cell(&field, 1, 0));
+        assert_eq!("Row 2: column 1, flagged; columns 2 and 3, 1 adjacent mine.", row(&field, 1));
+        assert_eq!("Column 1: row 1, no adjacent mines; row 2, flagged; row 3, no adjacent mines.", column(&field, 0, 0));
+        assert_eq!("Row 3: columns 1 to 3, no adjacent mines.", row(&field, 2));
+        assert_eq!("3 columns by 3 rows, 0 mines left to flag, 3 of 9 cells closed.", board(&field));
+
+        field.set_layers(3).unwrap();
+        assert_eq!("Layer 2, row 1, column 3: 1 adjacent mine.", cell(&field, 1, 2));
+        assert_eq!("Layer 3, row 1: columns 1 to 3, no adjacent mines.", row(&field, 2));
+        assert_eq!("Layer 2, column 3: row 1, 1 adjacent mine.", column(&field, 2, 1));
+        assert_eq!("3 columns by 1 row in 3 layers, 0 mines left to flag, 3 of 9 cells closed.", board(&field));
+    }
+
+    #[test]
+    fn outcomes() {
+        let before = Field::parse("..*\n...\n...\n").unwrap();
+        let mut after = before.clone();
+        after.open(2, 0).unwrap();
+        assert_eq!("Row 3, column 1: no adjacent mines, 8 cells opened.", outcome(&before, &after, Action::Open, 2, 0));
+
+        let before = Field::parse("o.*\n.f.\n...\n").unwrap();
+        let mut after = before.clone();
+        after.open(0, 1).unwrap();
+        assert_eq!("Row 1, column 2: 1 adjacent mine.", outcome(&before, &after, Action::Open, 0, 1));
+        let before = after.clone();
+        let _ = after.chord(0, 1);
+        assert_eq!("Chord on row 1, column 2 opened a mine at row 1, column 3.", outcome(&before, &after, Action::Chord, 0, 1));
+    }
+}
@@ -0,0 +1,401 @@
+use crate::Difficulty;
+use crate::save::check_name;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One finished game as stored in the stats file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    /// Seconds since the Unix epoch when the game ended.
+    pub finished: u64,
+    pub difficulty: Difficulty,
+    pub won: bool,
+    pub time: Duration,
+    /// Whether the board was generated to be solvable without guessing.
+    pub no_guess: bool,
+    /// Whether any flag was placed during the game.
+    pub flagged: bool,
+    /// Number of hints taken, already paid for in `time` where they cost.
+    pub hints: u32,
+    pub clicks: Clicks,
+    /// The 3BV of the board, 0 for games from before it was recorded.
+    pub three_bv: u32,
+}
+
+impl GameRecord {
+    /// Writes the record as a tab separated line: end time, result,
+    /// difficulty, time in milliseconds, `no-guess` and `flags` or `-`, the
+    /// number of hints, the clicks and the 3BV.
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.finished,
+            if self.won { "won" } else { "lost" },
+            self.difficulty,
+            self.time.as_millis(),
+            if self.no_guess { "no-guess" } else { "-" },
+            if self.flagged { "flags" } else { "-" },
+            self.hints,
+            self.clicks,
+            self.three_bv,
+        )
+    }
+
+    /// Reads a line written by `to_line`. Lines from before hints were
+    /// counted lack the last three fields and count as games without hints,
+    /// lines from before clicks were counted lack the last two.
+    pub fn parse_line(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if ![6, 7, 9].contains(&fields.len()) {
+            return Err(format!("expected 9 fields, found {}", fields.len()));
+        }
+        let number = |field: &str| field.parse::<u64>().map_err(|_| format!("invalid number '{}'", field));
+        Ok(Self {
+            finished: number(fields[0])?,
+            won: match fields[1] {
+                "won" => true,
+                "lost" => false,
+                other => return Err(format!("unknown result '{}'", other)),
+            },
+            difficulty: fields[2].parse()?,
+            time: Duration::from_millis(number(fields[3])?),
+            no_guess: fields[4] == "no-guess",
+            flagged: fields[5] == "flags",
+            hints: match fields.get(6) {
+                Some(hints) => number(hints)? as u32,
+                None => 0,
+            },
+            clicks: match fields.get(7) {
+                Some(clicks) => clicks.parse()?,
+                None => Clicks::default(),
+            },
+            three_bv: match fields.get(8) {
+                Some(three_bv) => number(three_bv)? as u32,
+                None => 0,
+            },
+        })
+    }
+
+    /// 3BV per click of a won game, see `Clicks::efficiency`.
+    pub fn efficiency(&self) -> Option<f64> {
+        if self.won {
+            self.clicks.efficiency(self.three_bv)
+        } else {
+            None
+        }
+    }
+}
+
+/// The moves of a game by kind, the clicks a mouse player would need.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Clicks {
+    pub opens: u32,
+    pub flags: u32,
+    pub chords: u32,
+}
+
+impl Clicks {
+    pub fn total(&self) -> u32 {
+        self.opens + self.flags + self.chords
+    }
+
+    /// The 3BV of a board cleared with these clicks per click, 1 for
+    /// clearing it with the fewest clicks possible without flags and more
+    /// with chords. `None` without clicks or 3BV.
+    pub fn efficiency(&self, three_bv: u32) -> Option<f64> {
+        if self.total() == 0 || three_bv == 0 {
+            None
+        } else {
+            Some(three_bv as f64 / self.total() as f64)
+        }
+    }
+}
+
+/// Written as opens, flags and chords separated by `/`.
+impl fmt::Display for Clicks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.opens, self.flags, self.chords)
+    }
+}
+
+impl FromStr for Clicks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let counts = s.split('/')
+            .map(|count| count.parse::<u32>().map_err(|_| format!("invalid clicks '{}'", s)))
+            .collect::<Result<Vec<_>, _>>()?;
+        match counts[..] {
+            [opens, flags, chords] => Ok(Clicks { opens, flags, chords }),
+            _ => Err(format!("invalid clicks '{}'", s)),
+        }
+    }
+}
+
+/// Totals over all recorded games.
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub played: u32,
+    pub won: u32,
+    /// Fastest win on every board size that was won at least once, in the
+    /// order the sizes were first played.
+    pub best: Vec<(Difficulty, Duration)>,
+    /// Mean efficiency of the won games that recorded their clicks.
+    pub efficiency: Option<f64>,
+}
+
+impl Summary {
+    pub fn of(records: &[GameRecord]) -> Self {
+        let mut best: Vec<(Difficulty, Duration)> = vec![];
+        for record in records.iter().filter(|record| record.won) {
+            match best.iter_mut().find(|(difficulty, _)| difficulty.dimensions() == record.difficulty.dimensions()) {
+                Some((_, time)) => *time = (*time).min(record.time),
+                None => best.push((record.difficulty, record.time)),
+            }
+        }
+        let efficiencies: Vec<f64> = records.iter().filter_map(GameRecord::efficiency).collect();
+        Self {
+            played: records.len() as u32,
+            won: records.iter().filter(|record| record.won).count() as u32,
+            best,
+            efficiency: if efficiencies.is_empty() {
+                None
+            } else {
+                Some(efficiencies.iter().sum::<f64>() / efficiencies.len() as f64)
+            },
+        }
+    }
+
+    /// Share of games won, 0 without any games.
+    pub fn win_rate(&self) -> f64 {
+        if self.played == 0 {
+            0.0
+        } else {
+            self.won as f64 / self.played as f64
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Number of the local calendar day that `secs` since the Unix epoch fall on.
+pub fn local_day(secs: u64) -> i64 {
+    (secs as i64 + utc_offset(secs)).div_euclid(24 * 60 * 60)
+}
+
+/// Offset of the local time zone from UTC in seconds.
+fn utc_offset(secs: u64) -> i64 {
+    #[cfg(unix)]
+    {
+        let time = secs as libc::time_t;
+        let mut local: libc::tm = unsafe { std::mem::zeroed() };
+        if unsafe { libc::localtime_r(&time, &mut local) }.is_null() {
+            return 0;
+        }
+        local.tm_gmtoff as i64
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = secs;
+        0
+    }
+}
+
+/// Name of the shared profile, the files outside `profiles`.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Whose stats, high scores, saves, macros and goals `data_dir` and
+/// `config_dir` point to. A named profile keeps its files in
+/// `profiles/<name>` of the data and config directories so people sharing
+/// a machine don't mix their records. The default is the shared one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    name: Option<String>,
+}
+
+impl Profile {
+    /// The profile called `name`, the shared one for `DEFAULT_PROFILE`.
+    pub fn named(name: &str) -> Result<Self, String> {
+        check_name(name)?;
+        Ok(Self { name: Some(name.to_string()).filter(|name| name != DEFAULT_PROFILE) })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.name.is_none()
+    }
+
+    /// `dir` for this profile.
+    pub(crate) fn dir(&self, dir: PathBuf) -> PathBuf {
+        match self.name.as_ref() {
+            Some(name) => dir.join("profiles").join(name),
+            None => dir,
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Base directory for files that outlive a game, `$XDG_DATA_HOME/minesweep_rs`
+/// or the platform's usual place.
+fn shared_data_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME") {
+        return Some(PathBuf::from(dir).join("minesweep_rs"));
+    }
+    if let Some(dir) = std::env::var_os("APPDATA") {
+        return Some(PathBuf::from(dir).join("minesweep_rs"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/minesweep_rs"))
+}
+
+/// Directory for the files of `profile` that outlive a game.
+pub fn data_dir(profile: &Profile) -> Option<PathBuf> {
+    shared_data_dir().map(|dir| profile.dir(dir))
+}
+
+/// The shared profile and the profiles that have a directory, by name.
+pub fn profiles() -> Vec<Profile> {
+    let mut names: Vec<String> = match shared_data_dir().and_then(|dir| fs::read_dir(dir.join("profiles")).ok()) {
+        Some(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect(),
+        None => vec![],
+    };
+    names.sort();
+    std::iter::once(Profile::default())
+        .chain(names.iter().filter_map(|name| Profile::named(name).ok()).filter(|profile| !profile.is_shared()))
+        .collect()
+}
+
+/// The stats file in `data_dir`.
+pub fn stats_path(profile: &Profile) -> Option<PathBuf> {
+    data_dir(profile).map(|dir| dir.join("games.tsv"))
+}
+
+/// Reads all records from the stats file at `path`. A missing file holds no
+/// games yet.
+pub fn load(path: &Path) -> Result<Vec<GameRecord>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("Can't read {}: {}", path.display(), e)),
+    };
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(line_no, line)| {
+            GameRecord::parse_line(line).map_err(|e| format!("{} line {}: {}", path.display(), line_no + 1, e))
+        })
+        .collect()
+}
+
+/// Appends `record` to the stats file at `path`, creating it if needed.
+pub fn append(path: &Path, record: &GameRecord) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Can't write {}: {}", path.display(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(error)?;
+    writeln!(file, "{}", record.to_line()).map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::stats::{append, load, local_day, Clicks, GameRecord, Profile, Summary, DEFAULT_PROFILE};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn record() -> GameRecord {
+        GameRecord {
+            finished: 1_700_000_000,
+            difficulty: Difficulty::Custom(20, 10, 30),
+            won: true,
+            time: Duration::from_millis(61_500),
+            no_guess: false,
+            flagged: true,
+            hints: 2,
+            clicks: Clicks { opens: 40, flags: 25, chords: 15 },
+            three_bv: 60,
+        }
+    }
+
+    #[test]
+    fn line() {
+        let line = record().to_line();
+        assert_eq!("1700000000\twon\tcustom 20x10/30\t61500\t-\tflags\t2\t40/25/15\t60", line);
+        assert_eq!(Ok(record()), GameRecord::parse_line(&line));
+        let old = GameRecord { hints: 0, clicks: Clicks::default(), three_bv: 0, ..record() };
+        assert_eq!(Ok(old.clone()), GameRecord::parse_line("1700000000\twon\tcustom 20x10/30\t61500\t-\tflags"));
+        assert_eq!(Ok(GameRecord { hints: 2, ..old }), GameRecord::parse_line("1700000000\twon\tcustom 20x10/30\t61500\t-\tflags\t2"));
+        assert!(GameRecord::parse_line("1700000000\twon\tbeginner\t0\t-\t-\t0\t1/2\t0").is_err());
+        assert!(GameRecord::parse_line("1700000000\tdraw\tbeginner\t0\t-\t-").is_err());
+        assert!(GameRecord::parse_line("1700000000\twon").is_err());
+    }
+
+    #[test]
+    fn file() {
+        let path = std::env::temp_dir().join(format!("minesweep_stats_{}.tsv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Ok(vec![]), load(&path));
+        append(&path, &record()).unwrap();
+        append(&path, &GameRecord { won: false, ..record() }).unwrap();
+        let records = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(2, records.len());
+        assert!(!records[1].won);
+    }
+
+    #[test]
+    fn summary() {
+        let records = vec![
+            record(),
+            GameRecord { won: false, time: Duration::from_secs(5), ..record() },
+            GameRecord { time: Duration::from_secs(40), ..record() },
+            GameRecord { difficulty: Difficulty::Beginner, time: Duration::from_secs(9), ..record() },
+            GameRecord { difficulty: Difficulty::Expert, won: false, ..record() },
+        ];
+        let summary = Summary::of(&records);
+        assert_eq!((5, 3), (summary.played, summary.won));
+        assert_eq!(0.6, summary.win_rate());
+        assert_eq!(vec![
+            (Difficulty::Custom(20, 10, 30), Duration::from_secs(40)),
+            (Difficulty::Beginner, Duration::from_secs(9)),
+        ], summary.best);
+        assert_eq!(0.0, Summary::of(&[]).win_rate());
+        assert_eq!(Some(0.75), summary.efficiency);
+        assert_eq!(None, Summary::of(&records[1..2]).efficiency);
+    }
+
+    #[test]
+    fn days() {
+        assert_eq!(local_day(1_700_000_000) + 1, local_day(1_700_000_000 + 24 * 60 * 60));
+    }
+
+    #[test]
+    fn profile_names() {
+        assert!(Profile::named("../anna").is_err());
+        assert!(Profile::named("").is_err());
+        assert!(Profile::named(DEFAULT_PROFILE).unwrap().is_shared());
+        let anna = Profile::named("anna").unwrap();
+        assert_eq!(("anna", false), (anna.name(), anna.is_shared()));
+        assert_eq!(PathBuf::from("data/profiles/anna"), anna.dir(PathBuf::from("data")));
+        assert_eq!(PathBuf::from("data"), Profile::default().dir(PathBuf::from("data")));
+    }
+}
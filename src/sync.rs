@@ -0,0 +1,372 @@
+use crate::replay::Action;
+use crate::{Field, MinesError};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How long a player who lost the connection may take to come back before
+/// forfeiting.
+pub const GRACE: Duration = Duration::from_secs(60);
+
+/// A move sent by one of several players sharing a board over a link.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Move {
+    pub player: usize,
+    /// Counts the moves of `player`, so confirmations can be matched to the
+    /// predicted moves.
+    pub seq: u32,
+    /// How many moves the player had seen confirmed when making this one.
+    pub seen: u64,
+    pub action: Action,
+    pub x: u16,
+    pub y: u16,
+}
+
+/// What the authority made of a move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// The move was played, possibly opening a mine.
+    Applied,
+    /// Another player changed the cell after the mover last saw it, so the
+    /// move was made on a stale board and is dropped.
+    Conflict,
+    /// Not a move on the board or outside of it.
+    Rejected,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Applied => write!(f, "applied"),
+            Outcome::Conflict => write!(f, "conflict"),
+            Outcome::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "applied" => Ok(Outcome::Applied),
+            "conflict" => Ok(Outcome::Conflict),
+            "rejected" => Ok(Outcome::Rejected),
+            other => Err(format!("unknown outcome '{}'", other)),
+        }
+    }
+}
+
+/// A move as ordered by the authority, sent back to every player.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Applied {
+    /// Position in the authority's order, counting from 1.
+    pub order: u64,
+    pub mv: Move,
+    pub outcome: Outcome,
+}
+
+fn play(field: &mut Field, action: Action, x: u16, y: u16) -> Result<(), MinesError> {
+    match action {
+        Action::Open => field.open(x, y),
+        Action::Flag => field.flag(x, y),
+        Action::Chord => field.chord(x, y),
+        _ => Ok(()),
+    }
+}
+
+/// Everything a player who lost their state needs to carry on.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub field: Field,
+    /// Moves ordered so far.
+    pub order: u64,
+    /// The last of the player's own moves the authority got.
+    pub seq: u32,
+}
+
+/// Keeps the one true board of a shared game. Moves count in the order
+/// they arrive; two moves on the same cell are settled by that order, and
+/// the later one only goes through if its player had already seen the
+/// earlier.
+pub struct Authority {
+    field: Field,
+    /// Order and player of the last move that changed each cell.
+    touched: Vec<Vec<(u64, usize)>>,
+    order: u64,
+    log: Vec<Applied>,
+    /// When players lost their connection, for those not back yet.
+    absent: HashMap<usize, Instant>,
+    pub grace: Duration,
+}
+
+impl Authority {
+    pub fn new(field: Field) -> Self {
+        let (cols, rows) = field.size();
+        Self {
+            field,
+            touched: vec![vec![(0, 0); cols as usize]; rows as usize],
+            order: 0,
+            log: vec![],
+            absent: HashMap::new(),
+            grace: GRACE,
+        }
+    }
+
+    pub fn field(&self) -> &Field {
+        &self.field
+    }
+
+    /// The moves ordered after the first `seen`, for a player catching up
+    /// after a reconnect. `None` if `seen` is ahead of the authority.
+    pub fn since(&self, seen: u64) -> Option<&[Applied]> {
+        self.log.get(seen as usize..)
+    }
+
+    /// The board as it is now, for `player` to resume from.
+    pub fn snapshot(&self, player: usize) -> Snapshot {
+        let seq = self.log.iter().rev()
+            .find(|applied| applied.mv.player == player)
+            .map_or(0, |applied| applied.mv.seq);
+        Snapshot { field: self.field.clone(), order: self.order, seq }
+    }
+
+    /// Notes that `player` lost their connection at `now`.
+    pub fn leave(&mut self, player: usize, now: Instant) {
+        self.absent.entry(player).or_insert(now);
+    }
+
+    /// Takes `player` back if they return within the grace period. Returns
+    /// `false` if they already forfeited.
+    pub fn rejoin(&mut self, player: usize, now: Instant) -> bool {
+        if self.forfeited(now).contains(&player) {
+            return false;
+        }
+        self.absent.remove(&player);
+        true
+    }
+
+    /// Players gone for longer than the grace period, in order.
+    pub fn forfeited(&self, now: Instant) -> Vec<usize> {
+        let mut players: Vec<usize> = self.absent.iter()
+            .filter(|(_, &left)| now.duration_since(left) > self.grace)
+            .map(|(&player, _)| player)
+            .collect();
+        players.sort_unstable();
+        players
+    }
+
+    /// Orders a move, plays it if it doesn't conflict and returns what to
+    /// send to the players.
+    pub fn apply(&mut self, mv: Move) -> Applied {
+        let outcome = match mv.action {
+            Action::Open | Action::Flag | Action::Chord if self.field.cell(mv.x, mv.y).is_ok() => {
+                let (order, player) = self.touched[mv.x as usize][mv.y as usize];
+                if order > mv.seen && player != mv.player {
+                    Outcome::Conflict
+                } else {
+                    Outcome::Applied
+                }
+            },
+            _ => Outcome::Rejected,
+        };
+        self.order += 1;
+        if outcome == Outcome::Applied {
+            let before = self.field.cells.clone();
+            let _ = play(&mut self.field, mv.action, mv.x, mv.y);
+            for (x, row) in self.field.cells.iter().enumerate() {
+                for (y, cell) in row.iter().enumerate() {
+                    if *cell != before[x][y] {
+                        self.touched[x][y] = (self.order, mv.player);
+                    }
+                }
+            }
+        }
+        self.log(mv, outcome)
+    }
+
+    /// Orders a move without playing it, for moves turned down by rules
+    /// of the game the board doesn't know, and returns what to send to the
+    /// players.
+    pub fn reject(&mut self, mv: Move) -> Applied {
+        self.order += 1;
+        self.log(mv, Outcome::Rejected)
+    }
+
+    fn log(&mut self, mv: Move, outcome: Outcome) -> Applied {
+        let applied = Applied { order: self.order, mv, outcome };
+        self.log.push(applied);
+        applied
+    }
+}
+
+/// One player's view of a shared game. Own moves show up at once on a
+/// predicted board; once the authority's order comes back the board is
+/// rolled back to the confirmed one and the moves still on their way are
+/// played again on top.
+pub struct Predictor {
+    player: usize,
+    confirmed: Field,
+    seen: u64,
+    predicted: Field,
+    pending: Vec<Move>,
+    seq: u32,
+}
+
+impl Predictor {
+    pub fn new(player: usize, field: Field) -> Self {
+        Self::resume(player, Snapshot { field, order: 0, seq: 0 })
+    }
+
+    /// Starts over from the authority's snapshot, when the player's own
+    /// state is gone.
+    pub fn resume(player: usize, snapshot: Snapshot) -> Self {
+        Self {
+            player,
+            predicted: snapshot.field.clone(),
+            confirmed: snapshot.field,
+            seen: snapshot.order,
+            pending: vec![],
+            seq: snapshot.seq,
+        }
+    }
+
+    /// How many ordered moves this player has seen, to ask the authority
+    /// for the rest after a reconnect.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// The board to show the player.
+    pub fn field(&self) -> &Field {
+        &self.predicted
+    }
+
+    /// The board with only the moves the authority ordered.
+    pub fn confirmed(&self) -> &Field {
+        &self.confirmed
+    }
+
+    /// Own moves not confirmed yet.
+    pub fn pending(&self) -> &[Move] {
+        &self.pending
+    }
+
+    /// Plays a move of this player on the predicted board and returns it
+    /// for sending to the authority.
+    pub fn local(&mut self, action: Action, x: u16, y: u16) -> Move {
+        self.seq += 1;
+        let mv = Move { player: self.player, seq: self.seq, seen: self.seen, action, x, y };
+        let _ = play(&mut self.predicted, action, x, y);
+        self.pending.push(mv);
+        mv
+    }
+
+    /// Takes a move ordered by the authority. Returns `true` if one of this
+    /// player's own moves was dropped, so the board shown went back on it.
+    pub fn confirm(&mut self, applied: &Applied) -> bool {
+        self.seen = applied.order;
+        if applied.outcome == Outcome::Applied {
+            let _ = play(&mut self.confirmed, applied.mv.action, applied.mv.x, applied.mv.y);
+        }
+        let own = applied.mv.player == self.player;
+        if own {
+            self.pending.retain(|mv| mv.seq != applied.mv.seq);
+        }
+        self.predicted = self.confirmed.clone();
+        for mv in &self.pending {
+            let _ = play(&mut self.predicted, mv.action, mv.x, mv.y);
+        }
+        own && applied.outcome != Outcome::Applied
+    }
+
+    /// Catches up on the moves missed while disconnected and returns the
+    /// own moves the authority never got, to send again.
+    pub fn catch_up(&mut self, missed: &[Applied]) -> Vec<Move> {
+        for applied in missed {
+            self.confirm(applied);
+        }
+        self.pending.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Field;
+    use crate::replay::Action;
+    use crate::sync::{Authority, Outcome, Predictor, GRACE};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn conflicts() {
+        let field = Field::parse("...\n...\n..*").unwrap();
+        let mut authority = Authority::new(field.clone());
+        let mut alice = Predictor::new(0, field.clone());
+        let mut bob = Predictor::new(1, field);
+        let flag = alice.local(Action::Flag, 1, 1);
+        let open = bob.local(Action::Open, 1, 1);
+        assert!(alice.field().cell(1, 1).unwrap().is_flagged());
+        assert!(bob.field().cell(1, 1).unwrap().opened);
+
+        let first = authority.apply(flag);
+        let second = authority.apply(open);
+        assert_eq!(Outcome::Applied, first.outcome);
+        assert_eq!(Outcome::Conflict, second.outcome);
+        for applied in &[first, second] {
+            assert!(!alice.confirm(applied));
+        }
+        assert!(!bob.confirm(&first));
+        assert!(bob.confirm(&second));
+        assert!(bob.pending().is_empty());
+        assert!(bob.field().cell(1, 1).unwrap().is_flagged());
+        assert!(authority.field().cells == bob.field().cells);
+
+        // having seen the flag, bob may take it away again
+        let unflag = bob.local(Action::Flag, 1, 1);
+        assert_eq!(Outcome::Applied, authority.apply(unflag).outcome);
+    }
+
+    #[test]
+    fn reconnect() {
+        let field = Field::parse("...\n...\n..*").unwrap();
+        let mut authority = Authority::new(field.clone());
+        let mut alice = Predictor::new(0, field.clone());
+        let mut bob = Predictor::new(1, field);
+        let now = Instant::now();
+
+        // bob drops while a first move is on its way and the second never leaves
+        let sent = bob.local(Action::Flag, 2, 2);
+        authority.leave(1, now);
+        bob.local(Action::Open, 0, 0);
+        let applied = authority.apply(sent);
+        alice.confirm(&applied);
+        let open = alice.local(Action::Open, 0, 2);
+        alice.confirm(&authority.apply(open));
+
+        let rejected = authority.reject(alice.local(Action::Flag, 0, 0));
+        assert_eq!((Outcome::Rejected, 3), (rejected.outcome, rejected.order));
+        alice.confirm(&rejected);
+        assert!(!alice.field().cell(0, 0).unwrap().is_flagged());
+        assert_eq!(Ok(Outcome::Conflict), Outcome::Conflict.to_string().parse());
+
+        assert!(authority.rejoin(1, now + GRACE / 2));
+        let resend = bob.catch_up(authority.since(bob.seen()).unwrap());
+        assert_eq!(1, resend.len());
+        for mv in resend {
+            bob.confirm(&authority.apply(mv));
+        }
+        assert!(bob.pending().is_empty());
+        assert!(authority.field().cells == bob.field().cells);
+
+        // a fresh client picks up where the board is
+        let carol = Predictor::resume(1, authority.snapshot(1));
+        assert!(authority.field().cells == carol.field().cells);
+        assert_eq!(4, carol.seen());
+
+        authority.leave(0, now);
+        assert_eq!(vec![0], authority.forfeited(now + GRACE + Duration::from_secs(1)));
+        assert!(!authority.rejoin(0, now + GRACE + Duration::from_secs(1)));
+        assert_eq!(None, authority.since(10));
+    }
+}
@@ -0,0 +1,1461 @@
+use crate::{CellValue, Field, Mark, RevealStyle, Topology};
+use crate::replay::Event;
+use crate::solver::Deduction;
+use crate::speech;
+use colored::*;
+use rand::Rng;
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+/// Colors telling the players of a relay apart.
+pub const PLAYER_COLORS: [Color; 6] = [Color::BrightBlue, Color::BrightRed, Color::BrightGreen, Color::BrightMagenta, Color::BrightYellow, Color::BrightCyan];
+
+/// Number of terminal columns a single rendered cell takes up.
+const CELL_WIDTH: usize = 2;
+
+/// Whether a board with `cols` columns only fits a terminal `width`
+/// characters wide without the space between cells.
+pub fn needs_compact(cols: usize, width: usize) -> bool {
+    cols * CELL_WIDTH > width
+}
+
+/// Which way boards are drawn: as they are, turned with their rows as
+/// columns, or turned when that fits the terminal better, see
+/// `View::transposed`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    Wide,
+    Tall,
+    Auto,
+}
+
+impl Orientation {
+    /// Whether a board with `cols` columns and `rows` rows is drawn turned on
+    /// a terminal `width` by `height` characters. Automatically that is
+    /// when the board is too wide for the terminal but fits turned.
+    pub fn transposed(self, cols: usize, rows: usize, width: usize, height: usize) -> bool {
+        match self {
+            Orientation::Wide => false,
+            Orientation::Tall => true,
+            // the rows take up the width and the columns plus the mine counter the height
+            Orientation::Auto => cols > rows && cols * CELL_WIDTH > width && rows * CELL_WIDTH <= width && cols < height,
+        }
+    }
+}
+
+impl FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wide" => Ok(Orientation::Wide),
+            "tall" => Ok(Orientation::Tall),
+            "auto" => Ok(Orientation::Auto),
+            _ => Err(format!("Unknown orientation '{}', expected wide, tall or auto", s)),
+        }
+    }
+}
+
+/// The frame drawn around the board, which comes with the numbers of the
+/// rows and columns as moves take them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Border {
+    None,
+    Ascii,
+    Unicode,
+}
+
+impl Border {
+    /// The top left corner, the top and bottom edge, the top right corner,
+    /// the sides and the bottom corners.
+    fn chars(self) -> Option<[char; 6]> {
+        match self {
+            Border::None => None,
+            Border::Ascii => Some(['+', '-', '+', '|', '+', '+']),
+            Border::Unicode => Some(['┌', '─', '┐', '│', '└', '┘']),
+        }
+    }
+}
+
+impl FromStr for Border {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Border::None),
+            "ascii" => Ok(Border::Ascii),
+            "unicode" => Ok(Border::Unicode),
+            _ => Err(format!("Unknown border '{}', expected none, ascii or unicode", s)),
+        }
+    }
+}
+
+/// How the board is displayed: the horizontal window that is shown when it
+/// doesn't fit the terminal, the colors used for it, the highlighted
+/// cell in full-screen mode, whether the mines left in every quadrant
+/// are shown, the cell pointed out by a hint and whether cells are drawn
+/// without space between them.
+pub struct View {
+    pub first_col: u16,
+    pub theme: Theme,
+    pub cursor: Option<(u16, u16)>,
+    pub informed: bool,
+    pub hint: Option<Deduction>,
+    pub compact: bool,
+    /// The mine that ended the game. Once set, it is highlighted and flags
+    /// on cells without a mine show as wrong.
+    pub exploded: Option<(u16, u16)>,
+    /// The player who placed each flag in a relay, drawn in their color
+    /// from `PLAYER_COLORS`.
+    pub flag_owners: HashMap<(u16, u16), usize>,
+    /// The player whose turn it is in a relay, shown in their color next to
+    /// the mines left.
+    pub turn: Option<usize>,
+    /// How mine chances are grouped in the probability overlay.
+    pub buckets: Buckets,
+    /// Whether the board is drawn turned, its rows as columns, so wide
+    /// boards fit narrow terminals. Coordinates typed or clicked are those
+    /// on screen, see `screen_cell`.
+    pub transposed: bool,
+    /// Whether `print` draws the board over the previous one at the top of
+    /// the terminal instead of after the last line written.
+    pub reprint: bool,
+    /// Whether `print` tells how the game stands in a sentence for screen
+    /// readers instead of drawing the board, see `speech`.
+    pub spoken: bool,
+    /// The frame around the board, with the numbers of its rows on the left
+    /// and of its columns above.
+    pub border: Border,
+}
+
+impl View {
+    /// A view of a whole board in `theme`, as printed after the moves of a
+    /// game: no cursor, overlays, frame or players, flat and at its full
+    /// width. Frontends set what they show on top.
+    pub fn new(theme: Theme) -> Self {
+        Self {
+            first_col: 0,
+            theme,
+            cursor: None,
+            informed: false,
+            hint: None,
+            compact: false,
+            exploded: None,
+            flag_owners: HashMap::new(),
+            turn: None,
+            buckets: Buckets::default(),
+            transposed: false,
+            reprint: false,
+            spoken: false,
+            border: Border::None,
+        }
+    }
+
+    /// Whether `field` is drawn turned. Only flat boards with square cells
+    /// and without line counts are turned.
+    pub fn is_transposed(&self, field: &Field) -> bool {
+        self.transposed && field.topology() == Topology::Square && field.layers() == 1 && !field.has_line_counts()
+    }
+
+    /// The columns and rows of `field` on screen.
+    fn screen_size(&self, field: &Field) -> (usize, usize) {
+        let (cols, rows) = (field.cells.first().map_or(0, |col| col.len()), field.cells.len());
+        if self.is_transposed(field) { (rows, cols) } else { (cols, rows) }
+    }
+
+    /// The cell drawn at a 0-based screen column and row, or the other way
+    /// around, the screen column and row of a cell: both swap the two on
+    /// a turned board.
+    pub fn screen_cell(&self, field: &Field, x: u16, y: u16) -> (u16, u16) {
+        if self.is_transposed(field) { (y, x) } else { (x, y) }
+    }
+
+    /// The coordinates of the cell at `(x, y)` as the player sees them.
+    pub fn coords(&self, field: &Field, x: u16, y: u16) -> String {
+        let (x, y) = self.screen_cell(field, x, y);
+        field.coords(x, y)
+    }
+
+    /// The number of the row drawn `row` rows from the top, counted from 1
+    /// within its layer, as moves take it.
+    fn row_number(&self, field: &Field, row: usize) -> usize {
+        if field.layers() > 1 { row % field.layer_height() as usize + 1 } else { row + 1 }
+    }
+
+    /// Characters the row numbers and the frame take up next to the board,
+    /// none without a border.
+    pub fn frame_width(&self, field: &Field) -> usize {
+        if self.border == Border::None {
+            return 0;
+        }
+        let (_, rows) = self.screen_size(field);
+        let rows = if field.layers() > 1 { field.layer_height() as usize } else { rows };
+        // the numbers, a space and the sides
+        rows.to_string().len() + 3
+    }
+
+    fn cell_width(&self) -> usize {
+        if self.compact && !self.theme.glyphs.wide { 1 } else { CELL_WIDTH }
+    }
+
+    /// Returns the range of visible columns for a board with `cols` columns
+    /// on a terminal that is `width` characters wide.
+    fn visible_cols(&self, cols: usize, width: usize) -> (usize, usize) {
+        let first = (self.first_col as usize).min(cols.saturating_sub(1));
+        // leave room for the truncation markers on both sides
+        let fitting = (width.saturating_sub(2) / self.cell_width()).max(1);
+        (first, cols.min(first + fitting))
+    }
+
+    /// Renders the counter of remaining mines and the board below it into
+    /// lines that fit a terminal `width` characters wide.
+    pub fn render(&self, field: &Field, width: usize) -> Vec<String> {
+        self.render_with(field, width, &[])
+    }
+
+    /// Like `render`, but closed cells show the bucket of their mine
+    /// probability from `probabilities`, see `Buckets`. Certain cells show
+    /// the hint glyphs.
+    pub fn render_probabilities(&self, field: &Field, probabilities: &[((u16, u16), f64)], width: usize) -> Vec<String> {
+        self.render_with(field, width, probabilities)
+    }
+
+    /// The line explaining the probability overlay.
+    pub fn probability_legend(&self) -> String {
+        let glyphs = &self.theme.glyphs;
+        let mut parts: Vec<String> = (1..=3)
+            .map(|bucket| format!("{} below {}%", bucket, self.buckets.0[bucket - 1]))
+            .collect();
+        parts.push("4 more".to_string());
+        parts.push(format!("{} safe", glyphs.safe_hint));
+        parts.push(format!("{} mine", glyphs.mine_hint));
+        format!("Mine chance: {}", parts.join(", "))
+    }
+
+    fn render_with(&self, field: &Field, width: usize, probabilities: &[((u16, u16), f64)]) -> Vec<String> {
+        let mut overlay = vec![vec![None; field.cells.first().map_or(0, |col| col.len())]; field.cells.len()];
+        for &((x, y), p) in probabilities {
+            overlay[x as usize][y as usize] = Some(p);
+        }
+        let (cols, rows) = self.screen_size(field);
+        let (first, last) = self.visible_cols(cols, width.saturating_sub(self.frame_width(field)));
+        let unlocked = field.unlocked_zone();
+        let mut header = format!("Mines left: {}", field.mines_left());
+        if self.informed {
+            for (name, left) in ["NW", "NE", "SW", "SE"].iter().zip(field.quadrant_mines_left().iter()) {
+                header.push_str(&format!("  {} {}", name, left));
+            }
+        }
+        if let Some(player) = self.turn {
+            let turn = format!("Player {} to move", player + 1);
+            header.push_str(&format!("  {}", turn.color(PLAYER_COLORS[player % PLAYER_COLORS.len()]).bold()));
+        }
+        let mut lines = vec![header];
+        let gap = if self.compact || self.theme.glyphs.wide { "" } else { " " };
+        let height = field.layer_height() as usize;
+        let frame = self.border.chars();
+        // the row numbers and the left side come before every line of the board
+        let margin = " ".repeat(self.frame_width(field).saturating_sub(1));
+        let row_width = |row: usize| (first > 0) as usize + shifted(field, self, row) as usize + (last - first) * self.cell_width() + (last < cols) as usize;
+        let inner = (0..rows).map(row_width).max().unwrap_or(0);
+        if frame.is_some() {
+            let numbers: Vec<usize> = (first + 1..=last).collect();
+            lines.extend(col_counts(&numbers, first > 0, &" ".repeat(self.cell_width() - 1)).into_iter().map(|line| format!("{}{}", margin, line)));
+        }
+        let edge = |left: char, right: char, line: char| format!("{}{}{}{}", &margin[1..], left, line.to_string().repeat(inner), right);
+        for row in 0..rows {
+            if field.layers() > 1 && row.is_multiple_of(height) {
+                if let (Some(frame), true) = (frame, row > 0) {
+                    lines.push(edge(frame[4], frame[5], frame[1]));
+                }
+                lines.push(format!("Layer {}", row / height + 1));
+            }
+            if let (Some(frame), true) = (frame, row == 0 || field.layers() > 1 && row.is_multiple_of(height)) {
+                lines.push(edge(frame[0], frame[2], frame[1]));
+            }
+            let mut line = String::new();
+            if first > 0 {
+                line.push('…');
+            }
+            if shifted(field, self, row) {
+                line.push(' ');
+            }
+            for column in first..last {
+                let (x, y) = self.screen_cell(field, row as u16, column as u16);
+                let (x, y) = (x as usize, y as usize);
+                let cell = &field.cells[x][y];
+                let glyphs = &self.theme.glyphs;
+                let glyph = if cell.is_hole() {
+                    if glyphs.wide { "  " } else { " " }.to_string()
+                } else if self.hint == Some(Deduction::Safe(x as u16, y as u16)) {
+                    format!("{}", glyphs.safe_hint.to_string().bold())
+                } else if self.hint == Some(Deduction::Mine(x as u16, y as u16)) {
+                    format!("{}", glyphs.mine_hint.to_string().bold())
+                } else if cell.is_flagged() && self.exploded.is_some() && cell.value == CellValue::Water {
+                    self.theme.mine.paint(&glyphs.wrong_flag.to_string())
+                } else if let (true, Some(&owner)) = (cell.is_flagged(), self.flag_owners.get(&(x as u16, y as u16))) {
+                    glyphs.flag.to_string().color(PLAYER_COLORS[owner % PLAYER_COLORS.len()]).bold().to_string()
+                } else if cell.is_flagged() {
+                    self.theme.flag.paint(&glyphs.flag.to_string())
+                } else if let (false, Some(p)) = (cell.opened, overlay[x][y]) {
+                    self.buckets.glyph(p, glyphs)
+                } else if cell.mark() == Mark::Question {
+                    glyphs.question.to_string()
+                } else if !cell.opened && field.zones[x][y] > unlocked {
+                    self.theme.locked.paint(&glyphs.locked.to_string())
+                } else if !cell.opened {
+                    glyphs.closed.to_string()
+                } else {
+                    match cell.value {
+                        CellValue::Mine if self.exploded == Some((x as u16, y as u16)) => self.theme.mine.paint(&glyphs.exploded.to_string()),
+                        CellValue::Mine => self.theme.mine.paint(&glyphs.mine.to_string()),
+                        // no room for a second digit
+                        CellValue::Water if field.numbers[x][y] > 9 && self.cell_width() == 1 => self.theme.numbers[6].paint("+"),
+                        CellValue::Water => self.theme.number(field.numbers[x][y]),
+                    }
+                };
+                // the space after a triangle is the edge it shares with the next
+                let gap = match field.topology() {
+                    Topology::Triangle if !gap.is_empty() && Topology::points_up((x % height) as u16, y as u16) => "\\",
+                    Topology::Triangle if !gap.is_empty() => "/",
+                    _ => gap,
+                };
+                // two digit numbers and the count of several flags take up the space after a cell
+                let two_digits = cell.opened && cell.value == CellValue::Water && field.numbers[x][y] > 9 && !glyphs.wide;
+                let gap = if gap.is_empty() || two_digits {
+                    String::new()
+                } else if cell.is_hole() {
+                    " ".to_string()
+                } else if cell.flags() > 1 && self.exploded.is_none() {
+                    cell.flags().to_string()
+                } else {
+                    gap.to_string()
+                };
+                // without colors the hit mine has a glyph of its own and output is kept free of escapes
+                let highlighted = self.cursor == Some((x as u16, y as u16))
+                    || (self.exploded == Some((x as u16, y as u16)) && colored::control::SHOULD_COLORIZE.should_colorize());
+                if highlighted {
+                    line.push_str(&format!("\x1b[7m{}\x1b[0m{}", glyph, gap));
+                } else {
+                    line.push_str(&format!("{}{}", glyph, gap));
+                }
+            }
+            if last < cols {
+                line.push('…');
+            }
+            if let Some(frame) = frame {
+                let number = format!("{:>1$}", self.row_number(field, row), margin.len() - 2);
+                line = format!("{} {}{}{}{}", number, frame[3], line, " ".repeat(inner - row_width(row)), frame[3]);
+            }
+            if field.has_line_counts() {
+                line.push_str(&format!(" {}", field.row_mines()[row]));
+            }
+            lines.push(line);
+        }
+        if let Some(frame) = frame {
+            lines.push(edge(frame[4], frame[5], frame[1]));
+        }
+        if field.has_line_counts() {
+            lines.extend(col_counts(&field.col_mines()[first..last], first > 0, gap).into_iter().map(|line| format!("{}{}", margin, line)));
+        }
+        if first > 0 || last < cols {
+            lines.push("…more (use v <col> to shift view)".into());
+        }
+        lines
+    }
+
+    /// The cell drawn at a 0-based screen `column` and `row` when the board
+    /// is rendered at the top left of a terminal `width` characters wide.
+    pub fn cell_at(&self, field: &Field, width: usize, column: u16, row: u16) -> Option<(u16, u16)> {
+        let (cols, rows) = self.screen_size(field);
+        let (first, last) = self.visible_cols(cols, width.saturating_sub(self.frame_width(field)));
+        let framed = self.border != Border::None;
+        // skip the row numbers, the truncation marker, the mine counter and the column numbers
+        let column = (column as usize).checked_sub(self.frame_width(field).saturating_sub(1) + if first > 0 { 1 } else { 0 })?;
+        let row = row.checked_sub(1 + if framed { last.to_string().len() as u16 } else { 0 })?;
+        // every layer starts with its name and the top of its frame, and ends with the bottom
+        let row = if field.layers() > 1 {
+            let height = field.layer_height();
+            let block = height + 1 + 2 * framed as u16;
+            let within = (row % block).checked_sub(1 + framed as u16)?;
+            if within >= height {
+                return None;
+            }
+            row / block * height + within
+        } else {
+            row.checked_sub(framed as u16)?
+        };
+        let column = column.checked_sub(shifted(field, self, row as usize) as usize)?;
+        let y = first + column / self.cell_width();
+        if (row as usize) < rows && y < last {
+            Some(self.screen_cell(field, row, y as u16))
+        } else {
+            None
+        }
+    }
+
+    /// Shifts the view just enough for column `col` to be visible on a
+    /// terminal `width` characters wide.
+    pub fn scroll_to(&mut self, col: u16, cols: usize, width: usize) {
+        let (first, last) = self.visible_cols(cols, width);
+        if (col as usize) < first {
+            self.first_col = col;
+        } else if col as usize >= last {
+            self.first_col = (col as usize + 1 - (last - first)) as u16;
+        }
+    }
+
+    /// Prints the board and returns the number of lines written. A view
+    /// that reprints writes the board in one go over the previous one,
+    /// unless it doesn't fit the terminal with some lines left for what
+    /// comes after it.
+    pub fn print(&self, field: &Field) -> usize {
+        if self.spoken {
+            println!("{}", speech::board(field));
+            return 1;
+        }
+        let lines = self.render(field, terminal_width());
+        let mut out = io::stdout().lock();
+        if self.reprint && lines.len() + REPRINT_MARGIN <= terminal_height() {
+            let _ = out.write_all(in_place(&lines).as_bytes());
+        } else {
+            for line in lines.iter() {
+                let _ = writeln!(out, "{}", line);
+            }
+        }
+        let _ = out.flush();
+        lines.len()
+    }
+
+    /// Prints the board once more after the output of a move, set apart by
+    /// an empty line unless it is drawn over the previous one.
+    pub fn print_again(&self, field: &Field) -> usize {
+        if !self.reprint {
+            println!();
+        }
+        self.print(field)
+    }
+}
+
+/// Lines left free below a board drawn in place, for the status and the
+/// next move.
+const REPRINT_MARGIN: usize = 4;
+
+/// `lines` written from the top left corner of the terminal, each clearing
+/// what's left of the line it replaces, and clearing everything below.
+fn in_place(lines: &[String]) -> String {
+    let mut out = String::from("\x1b[H");
+    for line in lines {
+        out.push_str(line);
+        out.push_str("\x1b[K\n");
+    }
+    out.push_str("\x1b[J");
+    out
+}
+
+/// Whether row `x` is drawn half a cell to the right, like the odd rows of
+/// hex boards. Without space between cells there is no half a cell.
+fn shifted(field: &Field, view: &View, x: usize) -> bool {
+    field.topology() == Topology::Hex && x % field.layer_height() as usize % 2 == 1 && view.cell_width() > 1
+}
+
+/// Thresholds in percent that split the mine chances of the probability
+/// overlay into four buckets between the certain ones, like `5,20,50` for
+/// below 5%, below 20%, below 50% and more. Buckets show as `1` to `4`, a
+/// character per cell like every other glyph, and the legend tells which
+/// is which.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Buckets(pub [u8; 3]);
+
+impl Default for Buckets {
+    fn default() -> Self {
+        Buckets([5, 20, 50])
+    }
+}
+
+impl Buckets {
+    /// 0 for a safe cell, 5 for a mine and the buckets between.
+    fn bucket(&self, p: f64) -> usize {
+        if p <= 0.0 {
+            0
+        } else if p >= 1.0 {
+            5
+        } else {
+            1 + self.0.iter().take_while(|&&threshold| p * 100.0 >= threshold as f64).count()
+        }
+    }
+
+    fn glyph(&self, p: f64, glyphs: &Glyphs) -> String {
+        let bucket = self.bucket(p);
+        let label = bucket.to_string();
+        match bucket {
+            0 => glyphs.safe_hint.to_string().green().bold(),
+            1 => label.green(),
+            2 => label.yellow(),
+            3 => label.magenta(),
+            4 => label.red(),
+            _ => glyphs.mine_hint.to_string().red().bold(),
+        }.to_string()
+    }
+}
+
+impl FromStr for Buckets {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let thresholds = s.split(',')
+            .map(|part| part.trim().trim_end_matches('%').parse::<u8>().map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match thresholds[..] {
+            [a, b, c] if 0 < a && a < b && b < c && c < 100 => Ok(Buckets([a, b, c])),
+            [_, _, _] => Err("thresholds have to rise from above 0 to below 100".into()),
+            _ => Err("expected three thresholds, like 5,20,50".into()),
+        }
+    }
+}
+
+/// The mine counts of columns, written top to bottom below each column so
+/// they line up with the cells even when they have two digits. `indent`
+/// makes room for the truncation marker, `gap` is the space between cells.
+fn col_counts(counts: &[usize], indent: bool, gap: &str) -> Vec<String> {
+    let digits = counts.iter().map(|count| count.to_string().len()).max().unwrap_or(1);
+    (0..digits).map(|digit| {
+        let mut line = String::from(if indent { " " } else { "" });
+        for count in counts {
+            let count = format!("{:>1$}", count, digits);
+            line.push(count.as_bytes()[digit] as char);
+            line.push_str(gap);
+        }
+        line
+    }).collect()
+}
+
+/// Width of the terminal attached to stdout, falling back to `$COLUMNS` or 80.
+pub fn terminal_width() -> usize {
+    match terminal_size() {
+        Some((cols, _)) => cols,
+        None => std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).unwrap_or(80),
+    }
+}
+
+/// Height of the terminal attached to stdout, falling back to `$LINES` or 24.
+pub fn terminal_height() -> usize {
+    match terminal_size() {
+        Some((_, rows)) => rows,
+        None => std::env::var("LINES").ok().and_then(|l| l.parse().ok()).unwrap_or(24),
+    }
+}
+
+/// Columns and rows of the terminal attached to stdout. crossterm would ask
+/// the controlling terminal on unix even with stdout piped to a file.
+fn terminal_size() -> Option<(usize, usize)> {
+    #[cfg(unix)]
+    {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 && size.ws_row > 0 {
+            return Some((size.ws_col as usize, size.ws_row as usize));
+        }
+        None
+    }
+    #[cfg(not(unix))]
+    {
+        crossterm::terminal::size().ok()
+            .filter(|&(cols, rows)| cols > 0 && rows > 0)
+            .map(|(cols, rows)| (cols as usize, rows as usize))
+    }
+}
+
+/// Color capabilities of the terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorDepth {
+    Ansi8,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    pub fn detect() -> Self {
+        Self::from_env(std::env::var("COLORTERM").ok(), std::env::var("TERM").ok())
+    }
+
+    pub fn from_env(colorterm: Option<String>, term: Option<String>) -> Self {
+        let colorterm = colorterm.unwrap_or_default();
+        let term = term.unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" || term.ends_with("-direct") {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi8
+        }
+    }
+}
+
+/// A single theme color in the representation matching a `ColorDepth`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThemeColor {
+    Ansi(Color),
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+    Dimmed,
+    /// No color at all.
+    Plain,
+}
+
+impl ThemeColor {
+    pub fn paint(self, text: &str) -> String {
+        match self {
+            ThemeColor::Ansi(color) => text.color(color).to_string(),
+            ThemeColor::Rgb(r, g, b) => text.truecolor(r, g, b).to_string(),
+            ThemeColor::Dimmed => text.dimmed().to_string(),
+            ThemeColor::Plain => text.into(),
+            ThemeColor::Indexed(i) => {
+                if colored::control::SHOULD_COLORIZE.should_colorize() {
+                    format!("\x1b[38;5;{}m{}\x1b[0m", i, text)
+                } else {
+                    text.into()
+                }
+            },
+        }
+    }
+}
+
+/// Characters for the cell states that aren't numbers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyphs {
+    pub closed: char,
+    /// Closed cells of a stage that isn't unlocked yet.
+    pub locked: char,
+    pub flag: char,
+    pub question: char,
+    pub mine: char,
+    /// A flag on a cell without a mine, shown once the game is lost.
+    pub wrong_flag: char,
+    /// The mine that ended the game.
+    pub exploded: char,
+    /// Opened cells without mines around, `None` to print the `0`.
+    pub zero: Option<char>,
+    /// A cell a hint showed to be safe or a mine.
+    pub safe_hint: char,
+    pub mine_hint: char,
+    /// Whether the glyphs take up two columns, like emoji. Cells then go
+    /// without a space between them and numbers are drawn full width.
+    pub wide: bool,
+}
+
+impl Glyphs {
+    /// The usual look, which leaves some of the work to colors.
+    pub const CLASSIC: Glyphs = Glyphs {
+        closed: '_',
+        locked: '#',
+        flag: 'F',
+        question: '?',
+        mine: 'X',
+        wrong_flag: 'x',
+        exploded: 'X',
+        zero: None,
+        safe_hint: 'o',
+        mine_hint: '!',
+        wide: false,
+    };
+
+    /// Every state has a shape of its own: closed cells are solid so empty
+    /// cells stand out as `.`, and locked cells are `~` rather than dimmed.
+    pub const PLAIN: Glyphs = Glyphs {
+        closed: '#',
+        locked: '~',
+        flag: 'F',
+        question: '?',
+        mine: '*',
+        wrong_flag: 'x',
+        exploded: '@',
+        zero: Some('.'),
+        safe_hint: 'o',
+        mine_hint: '!',
+        wide: false,
+    };
+
+    /// Big and friendly, for the simple mode.
+    pub const EMOJI: Glyphs = Glyphs {
+        closed: '🟦',
+        locked: '⬛',
+        flag: '🚩',
+        question: '❓',
+        mine: '💣',
+        wrong_flag: '❌',
+        exploded: '💥',
+        zero: Some('⬜'),
+        safe_hint: '🟢',
+        mine_hint: '❗',
+        wide: true,
+    };
+}
+
+/// Number, mine and flag colors: the usual ones or ones players with a
+/// kind of color blindness can tell apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Palette {
+    Classic,
+    /// For players who can't tell green from red, avoiding both.
+    Deuteranopia,
+    /// For players who see red dark, along with green.
+    Protanopia,
+    /// For players who mix up blue with green and yellow with violet,
+    /// built on reds and blue greens instead.
+    Tritanopia,
+}
+
+/// The colors of a palette other than the classic one: the numbers 0 to 6,
+/// mines and flags in true color and in the 8 color fallback.
+struct PaletteColors {
+    numbers: [(u8, u8, u8); 7],
+    mine: (u8, u8, u8),
+    flag: (u8, u8, u8),
+    ansi: [Color; 7],
+    ansi_mine: Color,
+    ansi_flag: Color,
+}
+
+impl Palette {
+    fn colors(self) -> Option<PaletteColors> {
+        match self {
+            Palette::Classic => None,
+            Palette::Deuteranopia => Some(PaletteColors {
+                numbers: [(0, 114, 178), (86, 180, 233), (230, 159, 0), (240, 228, 66), (204, 121, 167), (213, 94, 0), (240, 240, 240)],
+                mine: (213, 94, 0),
+                flag: (86, 180, 233),
+                ansi: [Color::Blue, Color::BrightCyan, Color::Yellow, Color::BrightYellow, Color::Magenta, Color::BrightMagenta, Color::BrightWhite],
+                ansi_mine: Color::BrightYellow,
+                ansi_flag: Color::BrightCyan,
+            }),
+            Palette::Protanopia => Some(PaletteColors {
+                numbers: [(0, 114, 178), (86, 180, 233), (240, 228, 66), (230, 159, 0), (204, 121, 167), (120, 94, 240), (240, 240, 240)],
+                mine: (240, 228, 66),
+                flag: (86, 180, 233),
+                ansi: [Color::Blue, Color::BrightCyan, Color::BrightYellow, Color::Yellow, Color::Magenta, Color::BrightBlue, Color::BrightWhite],
+                ansi_mine: Color::BrightYellow,
+                ansi_flag: Color::BrightCyan,
+            }),
+            Palette::Tritanopia => Some(PaletteColors {
+                numbers: [(0, 158, 158), (0, 200, 200), (220, 50, 50), (255, 130, 170), (150, 0, 60), (130, 130, 130), (240, 240, 240)],
+                mine: (255, 40, 40),
+                flag: (0, 200, 200),
+                ansi: [Color::Cyan, Color::BrightCyan, Color::Red, Color::BrightRed, Color::Magenta, Color::BrightBlack, Color::BrightWhite],
+                ansi_mine: Color::BrightRed,
+                ansi_flag: Color::BrightCyan,
+            }),
+        }
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(Palette::Classic),
+            "deuteranopia" => Ok(Palette::Deuteranopia),
+            "protanopia" => Ok(Palette::Protanopia),
+            "tritanopia" => Ok(Palette::Tritanopia),
+            _ => Err(format!("Unknown palette '{}', expected classic, deuteranopia, protanopia or tritanopia", s)),
+        }
+    }
+}
+
+/// The color of the 256 color cube closest to `(r, g, b)`.
+fn cube_index((r, g, b): (u8, u8, u8)) -> u8 {
+    let level = |c: u8| (c as u16 * 5 + 127) / 255;
+    (16 + 36 * level(r) + 6 * level(g) + level(b)) as u8
+}
+
+/// Colors and glyphs used to render the board.
+pub struct Theme {
+    /// Colors for the numbers 0 to 5, everything above uses the last entry.
+    pub numbers: [ThemeColor; 7],
+    pub mine: ThemeColor,
+    pub flag: ThemeColor,
+    pub locked: ThemeColor,
+    pub glyphs: Glyphs,
+}
+
+impl Theme {
+    pub fn for_depth(depth: ColorDepth) -> Self {
+        match depth {
+            ColorDepth::Ansi8 => Self {
+                numbers: [
+                    ThemeColor::Ansi(Color::Blue),
+                    ThemeColor::Ansi(Color::BrightGreen),
+                    ThemeColor::Ansi(Color::Green),
+                    ThemeColor::Ansi(Color::Yellow),
+                    ThemeColor::Ansi(Color::BrightRed),
+                    ThemeColor::Ansi(Color::Red),
+                    ThemeColor::Ansi(Color::Magenta),
+                ],
+                mine: ThemeColor::Ansi(Color::Red),
+                flag: ThemeColor::Plain,
+                locked: ThemeColor::Dimmed,
+                glyphs: Glyphs::CLASSIC,
+            },
+            ColorDepth::Ansi256 => Self {
+                numbers: [
+                    ThemeColor::Indexed(27),
+                    ThemeColor::Indexed(82),
+                    ThemeColor::Indexed(34),
+                    ThemeColor::Indexed(220),
+                    ThemeColor::Indexed(203),
+                    ThemeColor::Indexed(160),
+                    ThemeColor::Indexed(165),
+                ],
+                mine: ThemeColor::Indexed(196),
+                flag: ThemeColor::Plain,
+                locked: ThemeColor::Dimmed,
+                glyphs: Glyphs::CLASSIC,
+            },
+            ColorDepth::TrueColor => Self {
+                numbers: [
+                    ThemeColor::Rgb(60, 110, 230),
+                    ThemeColor::Rgb(90, 220, 90),
+                    ThemeColor::Rgb(40, 160, 60),
+                    ThemeColor::Rgb(230, 200, 40),
+                    ThemeColor::Rgb(255, 110, 90),
+                    ThemeColor::Rgb(200, 30, 30),
+                    ThemeColor::Rgb(200, 60, 220),
+                ],
+                mine: ThemeColor::Rgb(255, 40, 40),
+                flag: ThemeColor::Plain,
+                locked: ThemeColor::Dimmed,
+                glyphs: Glyphs::CLASSIC,
+            },
+        }
+    }
+
+    /// The colors of `palette` at `depth` where colors are shown, otherwise
+    /// the monochrome theme: with `NO_COLOR` set or output that doesn't go
+    /// to a terminal, unless `CLICOLOR_FORCE` asks for colors anyway.
+    pub fn for_output(depth: ColorDepth, palette: Palette) -> Self {
+        if colored::control::SHOULD_COLORIZE.should_colorize() {
+            Self::with_palette(depth, palette)
+        } else {
+            Self::monochrome()
+        }
+    }
+
+    /// The colors of `palette` at `depth`.
+    pub fn with_palette(depth: ColorDepth, palette: Palette) -> Self {
+        let colors = match palette.colors() {
+            Some(colors) => colors,
+            None => return Self::for_depth(depth),
+        };
+        let color = |rgb: (u8, u8, u8), ansi: Color| match depth {
+            ColorDepth::Ansi8 => ThemeColor::Ansi(ansi),
+            ColorDepth::Ansi256 => ThemeColor::Indexed(cube_index(rgb)),
+            ColorDepth::TrueColor => ThemeColor::Rgb(rgb.0, rgb.1, rgb.2),
+        };
+        let mut numbers = [ThemeColor::Plain; 7];
+        for (number, (&rgb, &ansi)) in numbers.iter_mut().zip(colors.numbers.iter().zip(colors.ansi.iter())) {
+            *number = color(rgb, ansi);
+        }
+        Self {
+            numbers,
+            mine: color(colors.mine, colors.ansi_mine),
+            flag: color(colors.flag, colors.ansi_flag),
+            locked: ThemeColor::Dimmed,
+            glyphs: Glyphs::CLASSIC,
+        }
+    }
+
+    /// Tells cell states apart by glyph alone, for monochrome screens and
+    /// printers and for players who can't rely on colors.
+    pub fn monochrome() -> Self {
+        Self {
+            numbers: [ThemeColor::Plain; 7],
+            mine: ThemeColor::Plain,
+            flag: ThemeColor::Plain,
+            locked: ThemeColor::Plain,
+            glyphs: Glyphs::PLAIN,
+        }
+    }
+
+    pub fn number(&self, num: u8) -> String {
+        let color = self.numbers[(num as usize).min(self.numbers.len() - 1)];
+        match self.glyphs.zero {
+            Some(zero) if num == 0 => color.paint(&zero.to_string()),
+            // the full width digits start at U+FF10
+            // two digits take up as much room as a full width one
+            _ if self.glyphs.wide && num <= 9 => color.paint(&std::char::from_u32(0xff10 + num as u32).unwrap_or('？').to_string()),
+            _ => color.paint(&format!("{}", num)),
+        }
+    }
+}
+
+/// Renders the mine probabilities from `solver::probabilities` as a grid:
+/// closed cells show their chance in percent, opened cells their number and
+/// flagged cells `F`. Holes are left blank.
+pub fn probability_map(field: &Field, probabilities: &[((u16, u16), f64)]) -> Vec<String> {
+    field.cells.iter().enumerate().map(|(x, col)| {
+        let cells: Vec<String> = col.iter().enumerate().map(|(y, cell)| {
+            if cell.is_flagged() {
+                "   F".to_string()
+            } else if cell.is_hole() {
+                "    ".to_string()
+            } else if cell.opened {
+                format!("{:>4}", field.numbers[x][y])
+            } else {
+                match probabilities.iter().find(|(c, _)| *c == (x as u16, y as u16)) {
+                    Some((_, p)) => format!("{:>3.0}%", 100.0 * p),
+                    None => "   _".to_string(),
+                }
+            }
+        }).collect();
+        cells.join(" ")
+    }).collect()
+}
+
+/// Number of characters `text` takes up on screen, ignoring color codes.
+pub fn visible_len(text: &str) -> usize {
+    let mut len = 0;
+    let mut in_escape = false;
+    for c in text.chars() {
+        if in_escape {
+            in_escape = c != 'm';
+        } else if c == '\x1b' {
+            in_escape = true;
+        } else {
+            len += 1;
+        }
+    }
+    len
+}
+
+/// Joins two blocks of lines column-wise, padding the left block to `width`.
+pub fn side_by_side(left: &[String], right: &[String], width: usize) -> Vec<String> {
+    (0..left.len().max(right.len())).map(|i| {
+        let l = left.get(i).map_or("", |l| l.as_str());
+        let r = right.get(i).map_or("", |r| r.as_str());
+        format!("{}{}{}", l, " ".repeat(width.saturating_sub(visible_len(l))), r)
+    }).collect()
+}
+
+/// A bar over the moves of a replay with `events` for every frame, at most
+/// `width` characters wide: `?` marks the first guess, `+` a big cascade,
+/// `X` an opened mine and `!` the win. The frame at `current` is inverted.
+/// Frames share a character when there are more of them than fit.
+pub fn timeline(events: &[Option<Event>], current: usize, width: usize) -> String {
+    let slots = events.len().min(width.saturating_sub(2)).max(1);
+    let mut marks = vec!['-'; slots];
+    let rank = |mark: char| "-+?!X".find(mark).unwrap_or(0);
+    for (i, event) in events.iter().enumerate() {
+        let mark = match event {
+            Some(Event::FirstGuess) => '?',
+            Some(Event::Cascade(_)) => '+',
+            Some(Event::MineOpened) => 'X',
+            Some(Event::Won) => '!',
+            None => continue,
+        };
+        let slot = &mut marks[i * slots / events.len()];
+        if rank(mark) > rank(*slot) {
+            *slot = mark;
+        }
+    }
+    let at = current.min(events.len().saturating_sub(1)) * slots / events.len().max(1);
+    let bar: String = marks.iter().enumerate()
+        .map(|(slot, mark)| if slot == at { format!("\x1b[7m{}\x1b[0m", mark) } else { mark.to_string() })
+        .collect();
+    format!("[{}]", bar)
+}
+
+/// A boxed card with a title and aligned label/value rows.
+pub struct SummaryCard {
+    pub title: String,
+    pub rows: Vec<(String, String)>,
+}
+
+impl SummaryCard {
+    pub fn render(&self) -> Vec<String> {
+        let inner = self.rows.iter()
+            .map(|(label, value)| label.chars().count() + value.chars().count() + 3)
+            .chain(std::iter::once(self.title.chars().count() + 2))
+            .max()
+            .unwrap_or(0);
+        let mut lines = vec![format!("┌{}┐", "─".repeat(inner))];
+        let pad = inner - self.title.chars().count();
+        lines.push(format!("│{}{}{}│", " ".repeat(pad / 2), self.title, " ".repeat(pad - pad / 2)));
+        lines.push(format!("├{}┤", "─".repeat(inner)));
+        for (label, value) in self.rows.iter() {
+            let gap = inner - 2 - label.chars().count() - value.chars().count();
+            lines.push(format!("│ {}{}{} │", label, " ".repeat(gap), value));
+        }
+        lines.push(format!("└{}┘", "─".repeat(inner)));
+        lines
+    }
+}
+
+/// Plays a short ASCII fireworks animation in a `width` x `height` area.
+pub fn fireworks<R: Rng>(rng: &mut R, width: usize, height: usize) {
+    const SPARKS: [(char, Color); 4] = [('.', Color::Yellow), ('*', Color::BrightRed), ('o', Color::BrightMagenta), ('+', Color::Cyan)];
+    let mut canvas = vec![vec![' '; width]; height];
+    for frame in 0..8 {
+        if frame > 0 {
+            print!("\x1b[{}A", height);
+        }
+        for row in canvas.iter_mut() {
+            for c in row.iter_mut() {
+                *c = match *c {
+                    '*' => 'o',
+                    'o' => '.',
+                    _ => ' ',
+                };
+            }
+        }
+        for _ in 0..3 {
+            let (cx, cy) = (rng.gen_range(0, width), rng.gen_range(0, height));
+            for (dx, dy) in &[(0i32, 0i32), (-2, 0), (2, 0), (0, -1), (0, 1), (-1, -1), (1, 1), (1, -1), (-1, 1)] {
+                let x = cx as i32 + dx;
+                let y = cy as i32 + dy;
+                if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+                    canvas[y as usize][x as usize] = if (*dx, *dy) == (0, 0) { '+' } else { '*' };
+                }
+            }
+        }
+        for row in canvas.iter() {
+            let line: String = row.iter().map(|&c| match SPARKS.iter().find(|(spark, _)| *spark == c) {
+                Some((spark, color)) => spark.to_string().color(*color).to_string(),
+                None => c.to_string(),
+            }).collect();
+            println!("{}", line);
+        }
+        let _ = io::stdout().flush();
+        thread::sleep(Duration::from_millis(150));
+    }
+}
+
+pub fn stdout_is_tty() -> bool {
+    io::stdout().is_terminal()
+}
+
+pub fn stdin_is_tty() -> bool {
+    io::stdin().is_terminal()
+}
+
+/// Uncovers all mines frame by frame, redrawing the board in place when
+/// stdout is a terminal.
+pub fn reveal_mines(field: &mut Field, view: &View, style: RevealStyle) {
+    // a screen reader would read every frame, and the board before the mines
+    let animate = stdout_is_tty() && !view.spoken;
+    let mut lines = if view.spoken { 0 } else { view.print(field) };
+    for frame in field.mine_reveal_order(style) {
+        for (x, y) in frame {
+            field.reveal(x, y);
+        }
+        if animate {
+            thread::sleep(style.frame_delay());
+            print!("\x1b[{}A", lines);
+            lines = view.print(field);
+            let _ = io::stdout().flush();
+        }
+    }
+    if !animate {
+        println!();
+        view.print(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod view {
+        use crate::term::{needs_compact, Border, Buckets, ColorDepth, Glyphs, Theme, View};
+
+        fn with_first_col(first_col: u16) -> View {
+            View { first_col, ..View::new(Theme::for_depth(ColorDepth::Ansi8)) }
+        }
+
+        #[test]
+        fn fits_terminal() {
+            let view = with_first_col(0);
+            assert_eq!((0, 9), view.visible_cols(9, 80));
+        }
+
+        #[test]
+        fn compact() {
+            let mut field = crate::Field::parse("o.*.\noo..").unwrap();
+            field.set_line_counts(true);
+            let view = View { compact: true, ..View::new(Theme::monochrome()) };
+            let lines = view.render(&field, 80);
+            assert_eq!(vec!["Mines left: 1", ".### 1", ".1## 0", "0010"], lines);
+            assert_eq!(Some((1, 2)), view.cell_at(&field, 80, 2, 2));
+            assert!(needs_compact(41, 80) && !needs_compact(40, 80));
+        }
+
+        #[test]
+        fn question_marks() {
+            let mut field = crate::Field::parse("q.\nF.").unwrap();
+            field.cycle_mark(0, 1).unwrap();
+            let view = View { theme: Theme::monochrome(), ..with_first_col(0) };
+            assert_eq!(vec!["Mines left: -1", "? F ", "F # "], view.render(&field, 80));
+        }
+
+        #[test]
+        fn wide_glyphs() {
+            let field = crate::Field::parse("o*\n..").unwrap();
+            let view = View { theme: Theme { glyphs: Glyphs::EMOJI, ..Theme::monochrome() }, compact: true, ..with_first_col(0) };
+            assert_eq!(vec!["Mines left: 1", "１🟦", "🟦🟦"], view.render(&field, 80));
+            assert_eq!(Some((1, 1)), view.cell_at(&field, 80, 3, 2));
+        }
+
+        #[test]
+        fn hex() {
+            let mut field = crate::Field::parse("o.*\n.o.").unwrap();
+            field.set_topology(crate::Topology::Hex);
+            let view = View { theme: Theme::monochrome(), ..with_first_col(0) };
+            assert_eq!(vec!["Mines left: 1", ". # # ", " # 1 # "], view.render(&field, 80));
+            assert_eq!(Some((1, 0)), view.cell_at(&field, 80, 1, 2));
+            assert_eq!(None, view.cell_at(&field, 80, 0, 2));
+        }
+
+        #[test]
+        fn triangle() {
+            let mut field = crate::Field::parse("o.*\n.o.").unwrap();
+            field.set_topology(crate::Topology::Triangle);
+            let view = View { theme: Theme::monochrome(), ..with_first_col(0) };
+            assert_eq!(vec!["Mines left: 1", "1\\#/#\\", "#/1\\#/"], view.render(&field, 80));
+            assert_eq!(Some((1, 2)), view.cell_at(&field, 80, 4, 2));
+        }
+
+        #[test]
+        fn layers() {
+            let mut field = crate::Field::parse("o.\n..\n.o\n.*").unwrap();
+            field.set_layers(2).unwrap();
+            let view = View { theme: Theme::monochrome(), ..with_first_col(0) };
+            assert_eq!(vec!["Mines left: 1", "Layer 1", "1 # ", "# # ", "Layer 2", "# 1 ", "# # "], view.render(&field, 80));
+            assert_eq!(Some((2, 1)), view.cell_at(&field, 80, 2, 5));
+            assert_eq!(None, view.cell_at(&field, 80, 2, 4));
+        }
+
+        #[test]
+        fn border() {
+            let field = crate::Field::parse("o.*\n.o.").unwrap();
+            let view = View { theme: Theme::monochrome(), border: Border::Ascii, ..with_first_col(0) };
+            assert_eq!(vec!["Mines left: 1", "   1 2 3 ", "  +------+", "1 |. # # |", "2 |# 1 # |", "  +------+"], view.render(&field, 80));
+            assert_eq!(Some((1, 1)), view.cell_at(&field, 80, 6, 4));
+            assert_eq!(None, view.cell_at(&field, 80, 2, 4));
+            assert_eq!(None, view.cell_at(&field, 80, 6, 2));
+
+            let mut field = crate::Field::parse("o.\n..\n.o\n.*").unwrap();
+            field.set_layers(2).unwrap();
+            let view = View { border: Border::Unicode, ..view };
+            assert_eq!(vec![
+                "Mines left: 1", "   1 2 ",
+                "Layer 1", "  ┌────┐", "1 │1 # │", "2 │# # │", "  └────┘",
+                "Layer 2", "  ┌────┐", "1 │# 1 │", "2 │# # │", "  └────┘",
+            ], view.render(&field, 80));
+            assert_eq!(Some((2, 1)), view.cell_at(&field, 80, 6, 9));
+            assert_eq!(None, view.cell_at(&field, 80, 6, 7));
+            assert_eq!(Ok(Border::Ascii), "ascii".parse());
+            assert!("round".parse::<Border>().is_err());
+        }
+
+        #[test]
+        fn multimine() {
+            let mut field = crate::Field::parse("99o.\n99..").unwrap();
+            field.flag(0, 0).unwrap();
+            field.flag(0, 0).unwrap();
+            field.flag(0, 1).unwrap();
+            let view = View { theme: Theme::monochrome(), ..with_first_col(0) };
+            assert_eq!(vec!["Mines left: 33", "F2F 18# ", "# # # # "], view.render(&field, 80));
+            let view = View { compact: true, ..view };
+            assert_eq!("FF+#", view.render(&field, 80)[1]);
+        }
+
+        #[test]
+        fn holes() {
+            let field = crate::Field::parse("-*-\n.o.").unwrap();
+            let view = View { theme: Theme::monochrome(), ..with_first_col(0) };
+            assert_eq!(vec!["Mines left: 1", "  #   ", "# 1 # "], view.render(&field, 80));
+            assert_eq!("        _     ", crate::term::probability_map(&field, &[])[0]);
+        }
+
+        #[test]
+        fn exploded() {
+            let mut field = crate::Field::parse("f*\n..").unwrap();
+            field.open(0, 1).unwrap_err();
+            let view = View { theme: Theme::monochrome(), ..with_first_col(0) };
+            assert_eq!("F * ", view.render(&field, 80)[1]);
+            let view = View { exploded: field.opened_mine(), ..view };
+            colored::control::set_override(true);
+            assert_eq!("x \x1b[7m@\x1b[0m ", view.render(&field, 80)[1]);
+            colored::control::set_override(false);
+            assert_eq!("x @ ", view.render(&field, 80)[1]);
+            colored::control::unset_override();
+        }
+
+        #[test]
+        fn probabilities() {
+            let field = crate::Field::parse("o.*.\noo..").unwrap();
+            let view = View { theme: Theme::monochrome(), ..with_first_col(0) };
+            let lines = view.render_probabilities(&field, &[((0, 1), 0.0), ((0, 2), 0.35), ((0, 3), 1.0)], 80);
+            let plain: String = lines[1].split('\x1b').enumerate()
+                .map(|(i, part)| if i == 0 { part } else { part.split_once('m').map_or("", |(_, rest)| rest) })
+                .collect();
+            assert_eq!(". o 3 ! ", plain);
+            assert_eq!("Mine chance: 1 below 5%, 2 below 20%, 3 below 50%, 4 more, o safe, ! mine", view.probability_legend());
+            assert_eq!(". 1 # # ", lines[2]);
+            let view = View { compact: true, ..view };
+            let lines = view.render_probabilities(&field, &[((0, 2), 0.02), ((0, 3), 0.6)], 80);
+            let plain: String = lines[1].split('\x1b').enumerate()
+                .map(|(i, part)| if i == 0 { part } else { part.split_once('m').map_or("", |(_, rest)| rest) })
+                .collect();
+            assert_eq!(".#14", plain);
+        }
+
+        #[test]
+        fn buckets() {
+            let buckets: Buckets = "10, 25%,60".parse().unwrap();
+            assert_eq!(Buckets([10, 25, 60]), buckets);
+            assert_eq!(vec![0, 1, 2, 2, 3, 4, 5], [0.0, 0.05, 0.1, 0.2, 0.3, 0.99, 1.0].iter().map(|&p| buckets.bucket(p)).collect::<Vec<_>>());
+            assert!("5,5,50".parse::<Buckets>().is_err());
+            assert!("5,20".parse::<Buckets>().is_err());
+            assert!("5,20,100".parse::<Buckets>().is_err());
+        }
+
+        #[test]
+        fn truncated() {
+            let view = with_first_col(0);
+            assert_eq!((0, 9), view.visible_cols(30, 20));
+            let view = with_first_col(25);
+            assert_eq!((25, 30), view.visible_cols(30, 20));
+        }
+
+        #[test]
+        fn shifted_past_end() {
+            let view = with_first_col(100);
+            assert_eq!((29, 30), view.visible_cols(30, 20));
+        }
+
+        #[test]
+        fn scroll_to_cursor() {
+            let mut view = with_first_col(0);
+            view.scroll_to(12, 30, 20);
+            assert_eq!((4, 13), view.visible_cols(30, 20));
+            view.scroll_to(2, 30, 20);
+            assert_eq!(2, view.first_col);
+        }
+
+        #[test]
+        fn cell_at() {
+            let field = crate::Field::parse(&"..........\n".repeat(3)).unwrap();
+            let view = with_first_col(0);
+            assert_eq!(Some((1, 0)), view.cell_at(&field, 80, 0, 2));
+            assert_eq!(Some((2, 3)), view.cell_at(&field, 80, 7, 3));
+            assert_eq!(None, view.cell_at(&field, 80, 20, 1));
+            assert_eq!(None, view.cell_at(&field, 80, 0, 4));
+            assert_eq!(None, view.cell_at(&field, 80, 0, 0));
+            let view = with_first_col(4);
+            assert_eq!(None, view.cell_at(&field, 8, 0, 1));
+            assert_eq!(Some((0, 4)), view.cell_at(&field, 8, 1, 1));
+            assert_eq!(Some((0, 6)), view.cell_at(&field, 8, 5, 1));
+        }
+
+        #[test]
+        fn transposed() {
+            let field = crate::Field::parse("o.*\n.o.").unwrap();
+            let view = View { theme: Theme::monochrome(), transposed: true, ..with_first_col(0) };
+            assert_eq!(vec!["Mines left: 1", ". # ", "# 1 ", "# # "], view.render(&field, 80));
+            // the third column is the third row on screen
+            assert_eq!(Some((0, 2)), view.cell_at(&field, 80, 0, 3));
+            assert_eq!(("2 3".to_string(), (2, 0)), (view.coords(&field, 1, 2), view.screen_cell(&field, 0, 2)));
+            let mut hex = field.clone();
+            hex.set_topology(crate::Topology::Hex);
+            assert!(view.is_transposed(&field) && !view.is_transposed(&hex));
+        }
+
+        #[test]
+        fn orientation() {
+            use crate::term::Orientation;
+            assert!(!Orientation::Auto.transposed(30, 16, 80, 24));
+            assert!(Orientation::Auto.transposed(30, 16, 40, 50));
+            assert!(!Orientation::Auto.transposed(30, 16, 40, 20));
+            assert!(Orientation::Tall.transposed(9, 9, 80, 24) && !Orientation::Wide.transposed(30, 16, 40, 50));
+            assert_eq!(Ok(Orientation::Auto), "auto".parse());
+            assert!("sideways".parse::<Orientation>().is_err());
+        }
+
+        #[test]
+        fn highlights_cursor() {
+            let field = crate::Field::parse("..\n.*").unwrap();
+            let mut view = with_first_col(0);
+            view.cursor = Some((1, 0));
+            assert_eq!(vec!["Mines left: 1".to_string(), "_ _ ".to_string(), "\x1b[7m_\x1b[0m _ ".to_string()], view.render(&field, 80));
+        }
+
+        #[test]
+        fn line_counts() {
+            let mut field = crate::Field::parse("*.*\n..*").unwrap();
+            field.set_line_counts(true);
+            let view = with_first_col(0);
+            assert_eq!(vec!["Mines left: 3", "_ _ _  2", "_ _ _  1", "1 0 2 "], view.render(&field, 80));
+            let mut field = crate::Field::parse(&"*.\n".repeat(10)).unwrap();
+            field.set_line_counts(true);
+            assert_eq!(vec!["1   ", "0 0 "], view.render(&field, 80)[11..].to_vec());
+        }
+
+        #[test]
+        fn marks_hint() {
+            let field = crate::Field::parse("o.\n.*").unwrap();
+            let mut view = with_first_col(0);
+            view.hint = Some(crate::solver::Deduction::Mine(1, 1));
+            colored::control::set_override(false);
+            let lines = view.render(&field, 80);
+            colored::control::unset_override();
+            assert_eq!(vec!["Mines left: 1", "1 _ ", "_ ! "], lines);
+        }
+
+        #[test]
+        fn mine_counter() {
+            let mut field = crate::Field::parse("*.\n.*").unwrap();
+            let view = with_first_col(0);
+            assert_eq!("Mines left: 2", view.render(&field, 80)[0]);
+            field.flag(0, 0).unwrap();
+            field.flag(0, 1).unwrap();
+            field.flag(1, 0).unwrap();
+            assert_eq!("Mines left: -1", view.render(&field, 80)[0]);
+            let view = View { informed: true, ..view };
+            assert_eq!("Mines left: -1  NW 0  NE -1  SW -1  SE 1", view.render(&field, 80)[0]);
+        }
+    }
+
+    mod theme {
+        use crate::term::ColorDepth::{self, *};
+        use crate::term::{cube_index, Glyphs, Palette, Theme, ThemeColor, View};
+
+        fn detect(colorterm: Option<&str>, term: Option<&str>) -> ColorDepth {
+            ColorDepth::from_env(colorterm.map(|s| s.into()), term.map(|s| s.into()))
+        }
+
+        #[test]
+        fn detect_depth() {
+            assert_eq!(Ansi8, detect(None, None));
+            assert_eq!(Ansi8, detect(None, Some("xterm")));
+            assert_eq!(Ansi256, detect(None, Some("xterm-256color")));
+            assert_eq!(TrueColor, detect(Some("truecolor"), Some("xterm-256color")));
+            assert_eq!(TrueColor, detect(Some("24bit"), None));
+            assert_eq!(TrueColor, detect(None, Some("xterm-direct")));
+        }
+
+        #[test]
+        fn without_colors() {
+            colored::control::set_override(false);
+            let theme = Theme::for_output(TrueColor, Palette::Tritanopia);
+            colored::control::set_override(true);
+            let colored = Theme::for_output(TrueColor, Palette::Tritanopia);
+            colored::control::unset_override();
+            assert_eq!((Glyphs::PLAIN, ThemeColor::Plain), (theme.glyphs, theme.mine));
+            assert_eq!((Glyphs::CLASSIC, ThemeColor::Rgb(255, 40, 40)), (colored.glyphs, colored.mine));
+        }
+
+        #[test]
+        fn palettes() {
+            assert_eq!(Ok(Palette::Deuteranopia), "deuteranopia".parse());
+            assert!("red-green".parse::<Palette>().is_err());
+            assert_eq!(Theme::for_depth(Ansi256).numbers, Theme::with_palette(Ansi256, Palette::Classic).numbers);
+
+            let theme = Theme::with_palette(TrueColor, Palette::Deuteranopia);
+            assert_eq!(ThemeColor::Rgb(86, 180, 233), theme.numbers[1]);
+            assert_eq!(ThemeColor::Rgb(86, 180, 233), theme.flag);
+            assert_eq!(ThemeColor::Plain, Theme::for_depth(TrueColor).flag);
+            assert_eq!((16, 231), (cube_index((0, 0, 0)), cube_index((255, 255, 255))));
+            assert_eq!(ThemeColor::Indexed(37), Theme::with_palette(Ansi256, Palette::Tritanopia).numbers[0]);
+        }
+
+        #[test]
+        fn monochrome() {
+            colored::control::set_override(true);
+            let mut field = crate::Field::parse("o.*.\noo..").unwrap();
+            field.split_stages(2);
+            field.flag(0, 1).unwrap();
+            field.reveal(0, 2);
+            let view = View::new(Theme::monochrome());
+            let lines = view.render(&field, 80);
+            colored::control::unset_override();
+            assert_eq!(vec!["Mines left: 0", ". F * ~ ", ". 1 ~ ~ "], lines);
+        }
+    }
+
+    mod layout {
+        use crate::replay::Event;
+        use crate::term::{side_by_side, timeline, visible_len};
+        use colored::*;
+
+        #[test]
+        fn visible_len_ignores_colors() {
+            colored::control::set_override(true);
+            assert_eq!(3, visible_len(&format!("{}b{}", "a".red(), "c".blue())));
+            colored::control::unset_override();
+        }
+
+        #[test]
+        fn joins_columns() {
+            let left = vec!["ab".to_string(), "abcd".to_string()];
+            let right = vec!["x".to_string()];
+            assert_eq!(vec!["ab    x", "abcd  "], side_by_side(&left, &right, 6));
+        }
+
+        #[test]
+        fn timelines() {
+            let events = [None, Some(Event::Cascade(12)), None, Some(Event::FirstGuess), Some(Event::MineOpened)];
+            assert_eq!("[\x1b[7m-\x1b[0m+-?X]", timeline(&events, 0, 80));
+            // up to two frames to a character
+            assert_eq!("[+?\x1b[7mX\x1b[0m]", timeline(&events, 4, 5));
+        }
+    }
+
+    mod probability_map {
+        use crate::Field;
+        use crate::solver::probabilities;
+        use crate::term::probability_map;
+
+        #[test]
+        fn grid() {
+            let field = Field::from_position("mines 1\n1_\n__").unwrap();
+            assert_eq!(vec![
+                "   1  33%".to_string(),
+                " 33%  33%".to_string(),
+            ], probability_map(&field, &probabilities(&field)));
+        }
+    }
+
+    mod in_place {
+        use crate::term::in_place;
+
+        #[test]
+        fn frame() {
+            let lines = vec!["1 2".to_string(), "_ *".to_string()];
+            assert_eq!("\x1b[H1 2\x1b[K\n_ *\x1b[K\n\x1b[J", in_place(&lines));
+        }
+    }
+
+    mod summary_card {
+        use crate::term::SummaryCard;
+
+        #[test]
+        fn render() {
+            let card = SummaryCard {
+                title: "You won!".into(),
+                rows: vec![
+                    ("Board".into(), "9 x 9".into()),
+                    ("Turns".into(), "12".into()),
+                ],
+            };
+            assert_eq!(vec![
+                "┌─────────────┐",
+                "│  You won!   │",
+                "├─────────────┤",
+                "│ Board 9 x 9 │",
+                "│ Turns    12 │",
+                "└─────────────┘",
+            ], card.render());
+        }
+    }
+}
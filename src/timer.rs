@@ -0,0 +1,192 @@
+use std::time::{Duration, Instant};
+
+/// Time spent on a game, counted from the first opened cell until the game
+/// is won or lost, without pauses and plus any penalties. Times are passed
+/// in so frontends and tests control the clock; they come from `Instant`,
+/// which only ever goes forward, so changes of the system clock don't count.
+/// The game time of moves in a record and of splits is taken from here too.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timer {
+    started: Option<Instant>,
+    stopped: Option<Instant>,
+    /// Penalties and the time of earlier sessions of a saved game.
+    added: Duration,
+    /// When the running pause began.
+    paused: Option<Instant>,
+    /// Time spent in pauses that are over.
+    pauses: Duration,
+    splits: Vec<Duration>,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A timer for a saved game that had already taken `elapsed`. It starts
+    /// again with the next opened cell.
+    pub fn resumed(elapsed: Duration) -> Self {
+        Self {
+            added: elapsed,
+            ..Self::default()
+        }
+    }
+
+    /// Starts the timer unless it is already running or stopped.
+    pub fn start(&mut self, now: Instant) {
+        if self.started.is_none() {
+            self.started = Some(now);
+        }
+    }
+
+    /// Stops the timer for good, starting it first if the game ended on the
+    /// first move. A running pause ends with it.
+    pub fn stop(&mut self, now: Instant) {
+        self.start(now);
+        self.resume(now);
+        if self.stopped.is_none() {
+            self.stopped = Some(now);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started.is_some() && self.stopped.is_none() && self.paused.is_none()
+    }
+
+    /// Stops counting until `resume`. Pausing a timer that isn't running
+    /// does nothing.
+    pub fn pause(&mut self, now: Instant) {
+        if self.is_running() {
+            self.paused = Some(now);
+        }
+    }
+
+    pub fn resume(&mut self, now: Instant) {
+        if let Some(paused) = self.paused.take() {
+            self.pauses += now.saturating_duration_since(paused);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Remembers the game time at `now`, e.g. when a stage is cleared.
+    pub fn split(&mut self, now: Instant) {
+        let time = self.elapsed(now);
+        self.splits.push(time);
+    }
+
+    /// The game times of every `split` so far.
+    pub fn splits(&self) -> &[Duration] {
+        &self.splits
+    }
+
+    /// Adds `time` to the game time, e.g. for a hint in a ranked game.
+    pub fn penalize(&mut self, time: Duration) {
+        self.added += time;
+    }
+
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        let played = match self.started {
+            Some(started) => {
+                let end = self.stopped.or(self.paused).unwrap_or(now);
+                end.saturating_duration_since(started).saturating_sub(self.pauses)
+            },
+            None => Duration::default(),
+        };
+        played + self.added
+    }
+}
+
+/// Formats a game time as `m:ss.t`.
+pub fn format_time(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+/// Reads a game time written as `m:ss.t` like `format_time` does, or as
+/// seconds.
+pub fn parse_time(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid time '{}', expected m:ss.t or seconds", text);
+    let (minutes, seconds) = match text.split_once(':') {
+        Some((minutes, seconds)) => (minutes.parse::<u64>().map_err(|_| invalid())?, seconds),
+        None => (0, text),
+    };
+    let seconds: f64 = seconds.parse().map_err(|_| invalid())?;
+    if !seconds.is_finite() || seconds < 0.0 || (minutes > 0 && seconds >= 60.0) {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timer::{format_time, parse_time, Timer};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn counts_from_start_to_stop() {
+        let now = Instant::now();
+        let secs = |s| now + Duration::from_secs(s);
+        let mut timer = Timer::new();
+        assert_eq!(Duration::from_secs(0), timer.elapsed(secs(5)));
+        timer.start(secs(1));
+        timer.start(secs(3));
+        assert!(timer.is_running());
+        assert_eq!(Duration::from_secs(4), timer.elapsed(secs(5)));
+        timer.stop(secs(11));
+        timer.stop(secs(12));
+        assert!(!timer.is_running());
+        assert_eq!(Duration::from_secs(10), timer.elapsed(secs(20)));
+    }
+
+    #[test]
+    fn penalties() {
+        let now = Instant::now();
+        let mut timer = Timer::new();
+        timer.penalize(Duration::from_secs(20));
+        assert_eq!(Duration::from_secs(20), timer.elapsed(now));
+        timer.start(now);
+        timer.penalize(Duration::from_secs(5));
+        timer.stop(now + Duration::from_secs(3));
+        assert_eq!(Duration::from_secs(28), timer.elapsed(now + Duration::from_secs(60)));
+        let mut timer = Timer::resumed(Duration::from_secs(90));
+        timer.start(now);
+        assert_eq!(Duration::from_secs(100), timer.elapsed(now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn pauses() {
+        let now = Instant::now();
+        let secs = |s| now + Duration::from_secs(s);
+        let mut timer = Timer::new();
+        timer.pause(secs(0));
+        assert!(!timer.is_paused());
+        timer.start(secs(0));
+        timer.pause(secs(5));
+        assert!(timer.is_paused() && !timer.is_running());
+        assert_eq!(Duration::from_secs(5), timer.elapsed(secs(50)));
+        timer.resume(secs(60));
+        timer.split(secs(62));
+        assert_eq!(Duration::from_secs(8), timer.elapsed(secs(63)));
+        timer.pause(secs(70));
+        timer.stop(secs(80));
+        assert_eq!(Duration::from_secs(15), timer.elapsed(secs(90)));
+        assert_eq!(&[Duration::from_secs(7)], timer.splits());
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!("0:00.0", format_time(Duration::from_millis(40)));
+        assert_eq!("1:05.3", format_time(Duration::from_millis(65_300)));
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Ok(Duration::from_millis(65_300)), parse_time("1:05.3"));
+        assert_eq!(Ok(Duration::from_millis(12_500)), parse_time("12.5"));
+        assert!(parse_time("1:75").is_err());
+        assert!(parse_time("fast").is_err());
+    }
+}
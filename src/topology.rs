@@ -0,0 +1,157 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How the cells of a board touch each other, which decides the neighbours
+/// counted by the numbers, flood filled by openings and opened by chords.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Topology {
+    /// Square cells with eight neighbours.
+    #[default]
+    Square,
+    /// Hexagons with six neighbours. Odd rows are shifted right by half a
+    /// cell, so a cell touches two cells of the rows above and below.
+    Hex,
+    /// Triangles pointing up and down in turn, with twelve neighbours: the
+    /// three cells sharing an edge and the nine more touching a corner.
+    Triangle,
+    /// Square cells whose neighbours are the eight cells a chess knight
+    /// jumps to.
+    Knight,
+}
+
+impl Topology {
+    /// Cells adjacent to `(x, y)` on a board of `rows` rows and `cols`
+    /// columns, row by row. With `wrap` the board is a torus: the first
+    /// and last rows touch, as do the first and last columns.
+    pub fn neighbours(self, x: u16, y: u16, rows: u16, cols: u16, wrap: bool) -> Vec<(u16, u16)> {
+        const SQUARE: [(i32, i32); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+        const HEX_EVEN: [(i32, i32); 6] = [(-1, -1), (-1, 0), (0, -1), (0, 1), (1, -1), (1, 0)];
+        const HEX_ODD: [(i32, i32); 6] = [(-1, 0), (-1, 1), (0, -1), (0, 1), (1, 0), (1, 1)];
+        const UP: [(i32, i32); 12] = [
+            (-1, -1), (-1, 0), (-1, 1),
+            (0, -2), (0, -1), (0, 1), (0, 2),
+            (1, -2), (1, -1), (1, 0), (1, 1), (1, 2),
+        ];
+        const DOWN: [(i32, i32); 12] = [
+            (-1, -2), (-1, -1), (-1, 0), (-1, 1), (-1, 2),
+            (0, -2), (0, -1), (0, 1), (0, 2),
+            (1, -1), (1, 0), (1, 1),
+        ];
+        const KNIGHT: [(i32, i32); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+        let offsets: &[(i32, i32)] = match self {
+            Topology::Square => &SQUARE,
+            Topology::Hex if x.is_multiple_of(2) => &HEX_EVEN,
+            Topology::Hex => &HEX_ODD,
+            Topology::Triangle if Topology::points_up(x, y) => &UP,
+            Topology::Triangle => &DOWN,
+            Topology::Knight => &KNIGHT,
+        };
+        let (rows, cols) = (rows as i32, cols as i32);
+        let mut neighbours: Vec<(u16, u16)> = offsets.iter()
+            .map(|&(dx, dy)| (x as i32 + dx, y as i32 + dy))
+            .map(|(nx, ny)| if wrap { (nx.rem_euclid(rows), ny.rem_euclid(cols)) } else { (nx, ny) })
+            .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && nx < rows && ny < cols)
+            .map(|(nx, ny)| (nx as u16, ny as u16))
+            .collect();
+        if wrap {
+            // on narrow boards the way around meets the cell or another neighbour
+            neighbours.sort_unstable();
+            neighbours.dedup();
+            neighbours.retain(|&cell| cell != (x, y));
+        }
+        neighbours
+    }
+
+    /// Like `neighbours`, on a board of `layers` layers stacked as rows: the
+    /// first `rows / layers` rows are the top layer, the next ones the layer
+    /// below and so on. A cell also touches the cells of the layers right
+    /// above and below that are at or next to it, which makes 26 neighbours
+    /// for square cells. Only the edges of a layer wrap, the top and bottom
+    /// layers don't touch.
+    pub fn stacked_neighbours(self, x: u16, y: u16, rows: u16, cols: u16, wrap: bool, layers: u16) -> Vec<(u16, u16)> {
+        if layers <= 1 {
+            return self.neighbours(x, y, rows, cols, wrap);
+        }
+        let height = rows / layers;
+        let (layer, row) = (x / height, x % height);
+        let flat = self.neighbours(row, y, height, cols, wrap);
+        let mut neighbours = vec![];
+        for z in layer.saturating_sub(1)..=(layer + 1).min(layers - 1) {
+            if z != layer {
+                neighbours.push((z * height + row, y));
+            }
+            neighbours.extend(flat.iter().map(|&(nx, ny)| (z * height + nx, ny)));
+        }
+        neighbours.sort_unstable();
+        neighbours
+    }
+
+    /// Whether a board of `rows` rows and `cols` columns can wrap around
+    /// without breaking the pattern of the cells at the edges.
+    pub fn can_wrap(self, rows: u16, cols: u16) -> bool {
+        match self {
+            Topology::Square | Topology::Knight => true,
+            Topology::Hex => rows.is_multiple_of(2),
+            Topology::Triangle => rows.is_multiple_of(2) && cols.is_multiple_of(2),
+        }
+    }
+
+    /// Whether the triangle at `(x, y)` of a triangle board points up. The
+    /// top left one does.
+    pub fn points_up(x: u16, y: u16) -> bool {
+        (x + y).is_multiple_of(2)
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Topology::Square),
+            "hex" => Ok(Topology::Hex),
+            "triangle" => Ok(Topology::Triangle),
+            "knight" => Ok(Topology::Knight),
+            _ => Err(format!("Unknown grid '{}', expected square, hex, triangle or knight", s)),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Topology::Square => write!(f, "square"),
+            Topology::Hex => write!(f, "hex"),
+            Topology::Triangle => write!(f, "triangle"),
+            Topology::Knight => write!(f, "knight"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Topology;
+
+    #[test]
+    fn neighbours() {
+        assert_eq!(8, Topology::Square.neighbours(1, 1, 3, 3, false).len());
+        assert_eq!(vec![(0, 1), (1, 0), (1, 1)], Topology::Square.neighbours(0, 0, 3, 3, false));
+        assert_eq!(vec![(1, 0), (1, 1), (2, 0), (2, 2), (3, 0), (3, 1)], Topology::Hex.neighbours(2, 1, 5, 5, false));
+        assert_eq!(vec![(0, 1), (0, 2), (1, 0), (1, 2), (2, 1), (2, 2)], Topology::Hex.neighbours(1, 1, 3, 3, false));
+        assert_eq!(12, Topology::Triangle.neighbours(2, 2, 5, 5, false).len());
+        assert_eq!(12, Topology::Triangle.neighbours(2, 3, 5, 6, false).len());
+        assert_eq!(vec![(0, 1), (0, 2), (1, 0), (1, 1), (1, 2)], Topology::Triangle.neighbours(0, 0, 3, 3, false));
+        assert_eq!(vec![(0, 0), (0, 2), (1, 0), (1, 1), (1, 2)], Topology::Triangle.neighbours(0, 1, 3, 3, false));
+        assert_eq!(vec![(0, 1), (0, 2), (1, 0), (1, 1), (1, 2), (2, 0), (2, 1), (2, 2)], Topology::Square.neighbours(0, 0, 3, 3, true));
+        assert_eq!(vec![(0, 1), (1, 0), (1, 1)], Topology::Square.neighbours(0, 0, 2, 2, true));
+        assert_eq!(6, Topology::Hex.neighbours(0, 0, 4, 4, true).len());
+        assert!(!Topology::Triangle.can_wrap(4, 5));
+        assert_eq!(26, Topology::Square.stacked_neighbours(4, 1, 9, 3, false, 3).len());
+        assert_eq!(vec![(0, 1), (1, 0), (1, 1), (2, 0), (2, 1), (3, 0), (3, 1)], Topology::Square.stacked_neighbours(0, 0, 4, 2, false, 2));
+        assert_eq!(Ok(Topology::Hex), "hex".parse());
+        assert_eq!(Ok(Topology::Triangle), "triangle".parse());
+        assert_eq!(8, Topology::Knight.neighbours(2, 2, 5, 5, false).len());
+        assert_eq!(vec![(1, 2), (2, 1)], Topology::Knight.neighbours(0, 0, 5, 5, false));
+        assert_eq!(Ok(Topology::Knight), "knight".parse());
+    }
+}
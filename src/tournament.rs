@@ -0,0 +1,292 @@
+use crate::bracket::{Bracket, Outcome};
+use crate::json::Json;
+use rand::Rng;
+use std::io::{self, BufRead, Read, Write};
+use std::time::Duration;
+
+/// Largest request body taken, enough for the replays of a round.
+const MAX_BODY: usize = 4 << 20;
+/// Largest request line and headers taken together.
+const MAX_HEAD: usize = 16 << 10;
+
+/// Draws a secret token for every player of a bracket, handed out by the
+/// organizer so only the player can enter their results.
+pub fn new_tokens<R: Rng>(players: usize, rng: &mut R) -> Vec<String> {
+    (0..players).map(|_| format!("{:016x}", rng.gen::<u64>())).collect()
+}
+
+/// Writes the tokens as lines of player name and token, separated by a tab.
+pub fn format_tokens(bracket: &Bracket, tokens: &[String]) -> String {
+    bracket.players.iter().zip(tokens)
+        .map(|(name, token)| format!("{}\t{}\n", name, token))
+        .collect()
+}
+
+/// Reads tokens written by `format_tokens`, in the order of the players.
+pub fn parse_tokens(bracket: &Bracket, text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![None; bracket.players.len()];
+    for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let (name, token) = line.split_once('\t').ok_or_else(|| format!("line {} isn't a name and a token", i + 1))?;
+        let player = bracket.player(name).ok_or_else(|| format!("line {}: unknown player '{}'", i + 1, name))?;
+        tokens[player] = Some(token.trim().to_string());
+    }
+    tokens.into_iter()
+        .enumerate()
+        .map(|(player, token)| token.ok_or_else(|| format!("no token for {}", bracket.players[player])))
+        .collect()
+}
+
+/// An HTTP request, as much of it as the tournament server reads.
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Headers with lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// Reads a request with a body of `Content-Length` bytes, turning down
+    /// requests with a head of more than `MAX_HEAD` bytes.
+    pub fn read<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut left = MAX_HEAD;
+        let mut line = String::new();
+        read_head_line(reader, &mut line, &mut left)?;
+        let mut parts = line.split_whitespace();
+        let (method, path) = match (parts.next(), parts.next()) {
+            (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+            _ => return Err(invalid("not an HTTP request")),
+        };
+        let mut headers = vec![];
+        loop {
+            line.clear();
+            if read_head_line(reader, &mut line, &mut left)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+            }
+        }
+        let length = headers.iter()
+            .find(|(name, _)| name == "content-length")
+            .map_or(Ok(0), |(_, value)| value.parse().map_err(|_| invalid("invalid Content-Length")))?;
+        if length > MAX_BODY {
+            return Err(invalid("request too large"));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        let body = String::from_utf8(body).map_err(|_| invalid("body isn't UTF-8"))?;
+        Ok(Self { method, path, headers, body })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads a line of the head of a request into `line`, taking at most `left`
+/// bytes and counting them off.
+fn read_head_line<R: BufRead>(reader: &mut R, line: &mut String, left: &mut usize) -> io::Result<usize> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidData, "request head too large");
+    if *left == 0 {
+        return Err(too_large());
+    }
+    let read = Read::take(&mut *reader, *left as u64).read_line(line)?;
+    *left -= read;
+    if *left == 0 && !line.ends_with('\n') {
+        return Err(too_large());
+    }
+    Ok(read)
+}
+
+/// A JSON answer with its HTTP status.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    pub(crate) fn json(json: Json) -> Self {
+        Self { status: 200, body: json.to_string() }
+    }
+
+    pub(crate) fn error(status: u16, message: &str) -> Self {
+        Self { status, body: Json::Object(vec![("error".into(), Json::String(message.into()))]).to_string() }
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            409 => "Conflict",
+            _ => "Error",
+        };
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status, reason, self.body.len(), self.body,
+        )?;
+        out.flush()
+    }
+}
+
+/// Results a player entered, to be kept by the caller.
+#[derive(Debug, PartialEq)]
+pub struct Submission {
+    /// From 0.
+    pub round: usize,
+    pub player: usize,
+    /// Records of the games as written by `--record`, in the order played.
+    pub replays: Vec<String>,
+}
+
+/// Serves a bracket: anybody can look at it and the standings, and players
+/// enter the results of their rounds with their token.
+///
+/// * `GET /bracket` is the bracket as written by `Bracket::to_json`.
+/// * `GET /standings` lists the players from the first place on.
+/// * `POST /results` with `Authorization: Bearer <token>` and a body like
+///   `{"round": 1, "games": [83250, null], "replays": ["..."]}` enters the
+///   times in milliseconds of the player's boards, `null` for lost ones,
+///   and optionally the records of the games, once for every round.
+pub struct Server {
+    pub bracket: Bracket,
+    tokens: Vec<String>,
+}
+
+impl Server {
+    pub fn new(bracket: Bracket, tokens: Vec<String>) -> Self {
+        Self { bracket, tokens }
+    }
+
+    /// Answers a request. Entered results come back as a submission so the
+    /// bracket and the replays can be saved.
+    pub fn handle(&mut self, request: &Request) -> (Response, Option<Submission>) {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/bracket") => (Response { status: 200, body: self.bracket.to_json() }, None),
+            ("GET", "/standings") => (Response::json(self.standings()), None),
+            ("POST", "/results") => match self.submit(request) {
+                Ok(submission) => (Response::json(self.standings()), Some(submission)),
+                Err(response) => (response, None),
+            },
+            _ => (Response::error(404, "unknown endpoint"), None),
+        }
+    }
+
+    fn standings(&self) -> Json {
+        let champion = self.bracket.champion();
+        Json::Array(self.bracket.standings().iter()
+            .enumerate()
+            .map(|(place, standing)| Json::Object(vec![
+                ("place".into(), Json::Number((place + 1) as f64)),
+                ("player".into(), Json::String(self.bracket.players[standing.player].clone())),
+                ("round".into(), Json::Number(standing.reached as f64)),
+                ("out".into(), Json::Bool(standing.out)),
+                ("champion".into(), Json::Bool(champion == Some(standing.player))),
+                ("games_won".into(), Json::Number(standing.games_won as f64)),
+                ("time_ms".into(), Json::Number(standing.time.as_millis() as f64)),
+            ]))
+            .collect())
+    }
+
+    fn submit(&mut self, request: &Request) -> Result<Submission, Response> {
+        let token = request.header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Response::error(401, "missing token"))?;
+        let player = self.tokens.iter()
+            .position(|t| same_token(t, token.trim()))
+            .ok_or_else(|| Response::error(401, "unknown token"))?;
+        let bad = |message: &str| Response::error(400, message);
+        let json = Json::parse(&request.body).map_err(|e| bad(&e))?;
+        let round = json.get("round").and_then(|r| r.as_u64()).filter(|&r| r >= 1).ok_or_else(|| bad("'round' has to count from 1"))? as usize - 1;
+        let outcomes = json.get("games").and_then(|g| g.as_array()).ok_or_else(|| bad("missing 'games'"))?.iter()
+            .map(|game| match game {
+                Json::Null => Some(Outcome::Lost),
+                _ => game.as_u64().map(|ms| Outcome::Cleared(Duration::from_millis(ms))),
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| bad("'games' holds times in milliseconds or null"))?;
+        let replays = match json.get("replays") {
+            Some(replays) => replays.as_array()
+                .and_then(|replays| replays.iter().map(|r| r.as_str().map(String::from)).collect::<Option<Vec<_>>>())
+                .ok_or_else(|| bad("'replays' isn't an array of records"))?,
+            None => vec![],
+        };
+        // the first results count, so they can't be changed once the opponent's are known
+        if self.bracket.outcomes(round, player).is_some() {
+            return Err(Response::error(409, &format!("the results of round {} were entered already", round + 1)));
+        }
+        self.bracket.record(round, player, outcomes).map_err(|e| bad(&e))?;
+        Ok(Submission { round, player, replays })
+    }
+}
+
+/// Whether `a` and `b` are the same token, comparing every byte so the time
+/// taken doesn't tell how much of a guess was right.
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |differ, (x, y)| differ | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::bracket::{Bracket, Outcome};
+    use crate::tournament::{new_tokens, format_tokens, parse_tokens, Request, Server};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use std::time::Duration;
+
+    fn post(token: &str, body: &str) -> Request {
+        let text = format!(
+            "POST /results HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\n\r\n{}",
+            token, body.len(), body,
+        );
+        Request::read(&mut text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn results() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let bracket = Bracket::new(vec!["ada".into(), "bo".into()], 1, Difficulty::Beginner, &mut rng).unwrap();
+        let tokens = new_tokens(2, &mut rng);
+        assert_eq!(tokens, parse_tokens(&bracket, &format_tokens(&bracket, &tokens)).unwrap());
+        let mut server = Server::new(bracket, tokens.clone());
+
+        let (response, submission) = server.handle(&post("guess", "{\"round\":1,\"games\":[1000]}"));
+        assert_eq!((401, None), (response.status, submission));
+        let (response, submission) = server.handle(&post(&tokens[1], "{\"round\":1,\"games\":[83250],\"replays\":[\"{}\"]}"));
+        assert_eq!(200, response.status);
+        let submission = submission.unwrap();
+        assert_eq!((0, 1, vec!["{}".to_string()]), (submission.round, submission.player, submission.replays));
+        assert_eq!(Some(&[Outcome::Cleared(Duration::from_millis(83_250))][..]), server.bracket.outcomes(0, 1));
+        // entered once, not again after seeing the opponent's
+        let (response, submission) = server.handle(&post(&tokens[1], "{\"round\":1,\"games\":[1000]}"));
+        assert_eq!((409, None), (response.status, submission));
+        assert_eq!(Some(&[Outcome::Cleared(Duration::from_millis(83_250))][..]), server.bracket.outcomes(0, 1));
+        assert_eq!(401, server.handle(&post(&tokens[1][..15], "{\"round\":1,\"games\":[1000]}")).0.status);
+        assert_eq!(400, server.handle(&post(&tokens[0], "{\"round\":2,\"games\":[null]}")).0.status);
+        let (response, submission) = server.handle(&post(&tokens[0], &"[".repeat(200_000)));
+        assert_eq!((400, None), (response.status, submission));
+
+        server.handle(&post(&tokens[0], "{\"round\":1,\"games\":[null]}"));
+        let standings = Request::read(&mut "GET /standings HTTP/1.1\r\n\r\n".as_bytes()).unwrap();
+        let (response, _) = server.handle(&standings);
+        assert!(response.body.starts_with("[{\"place\":1,\"player\":\"bo\""));
+        assert!(response.body.contains("\"champion\":true"));
+    }
+
+    #[test]
+    fn long_head() {
+        let long = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", "a".repeat(20_000));
+        assert!(Request::read(&mut long.as_bytes()).is_err());
+        let many = format!("GET / HTTP/1.1\r\n{}\r\n", "X: y\r\n".repeat(5_000));
+        assert!(Request::read(&mut many.as_bytes()).is_err());
+        let fits = format!("GET / HTTP/1.1\r\nCookie: {}\r\n\r\n", "a".repeat(1_000));
+        assert_eq!(1, Request::read(&mut fits.as_bytes()).unwrap().headers.len());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::bot::Step;
+use crate::json::Json;
+use crate::Field;
+
+/// Value of a closed cell in `visible_state`.
+pub const CLOSED: i8 = -1;
+/// Value of a flagged cell in `visible_state`.
+pub const FLAGGED: i8 = -2;
+/// Value of a hole of a shaped board in `visible_state`.
+pub const HOLE: i8 = -3;
+
+/// What a player sees of `field`, row by row: the number of every opened
+/// cell, `CLOSED` for closed cells, `FLAGGED` for flags and `HOLE` for
+/// holes. Question marks count as closed.
+pub fn visible_state(field: &Field) -> Vec<i8> {
+    field.cells.iter().enumerate()
+        .flat_map(|(x, row)| row.iter().enumerate().map(move |(y, cell)| {
+            if cell.is_flagged() {
+                FLAGGED
+            } else if cell.is_hole() {
+                HOLE
+            } else if cell.opened {
+                field.numbers[x][y].min(i8::MAX as u8) as i8
+            } else {
+                CLOSED
+            }
+        }))
+        .collect()
+}
+
+/// One training example as a line of JSON: the board a bot saw, the move it
+/// made on it and whether that move was right, like
+///
+/// ```text
+/// {"rows":2,"cols":3,"mines_left":1,"state":[-1,1,-1,-1,1,-1],
+///  "action":"open","row":0,"col":2,"guess":true,"correct":true,
+///  "mines":[1,0,0,0,0,0]}
+/// ```
+///
+/// `state` is `visible_state`, `mines` the number of mines below every cell
+/// in the same order, the answer for the closed ones. `action` is `open` or
+/// `flag` and `guess` tells if the bot had to guess. A move is correct if
+/// it opens a cell without a mine or flags one with a mine. Returns `None`
+/// for a step that isn't a move, like the end of a won game.
+pub fn example(before: &Field, step: Step) -> Option<String> {
+    let (action, x, y, guess) = match step {
+        Step::Flagged(x, y) => ("flag", x, y, false),
+        Step::Opened(x, y) => ("open", x, y, false),
+        Step::Guessed(x, y) | Step::Lost(x, y) => ("open", x, y, true),
+        Step::Won => return None,
+    };
+    let mine = before.cell(x, y).ok()?.is_mine();
+    let number = |n: f64| Json::Number(n);
+    let (cols, rows) = before.size();
+    Some(Json::Object(vec![
+        ("rows".into(), number(rows as f64)),
+        ("cols".into(), number(cols as f64)),
+        ("mines_left".into(), number(before.mines_left() as f64)),
+        ("state".into(), Json::Array(visible_state(before).into_iter().map(|v| number(v as f64)).collect())),
+        ("action".into(), Json::String(action.into())),
+        ("row".into(), number(x as f64)),
+        ("col".into(), number(y as f64)),
+        ("guess".into(), Json::Bool(guess)),
+        ("correct".into(), Json::Bool(mine == (action == "flag"))),
+        ("mines".into(), Json::Array(before.cells.iter().flatten().map(|cell| number(cell.mine_count() as f64)).collect())),
+    ]).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Field;
+    use crate::bot::Step;
+    use crate::training::{example, visible_state};
+
+    #[test]
+    fn examples() {
+        let field = Field::parse("*o.\nFo.").unwrap();
+        assert_eq!(vec![-1, 2, -1, -2, 2, -1], visible_state(&field));
+        let line = example(&field, Step::Guessed(0, 2)).unwrap();
+        assert_eq!(
+            "{\"rows\":2,\"cols\":3,\"mines_left\":1,\"state\":[-1,2,-1,-2,2,-1],\"action\":\"open\",\"row\":0,\"col\":2,\
+             \"guess\":true,\"correct\":true,\"mines\":[1,0,0,1,0,0]}",
+            line,
+        );
+        assert!(example(&field, Step::Flagged(0, 0)).unwrap().contains("\"correct\":true"));
+        assert!(example(&field, Step::Lost(0, 0)).unwrap().contains("\"correct\":false"));
+        assert_eq!(None, example(&field, Step::Won));
+    }
+}
@@ -0,0 +1,327 @@
+use crate::term::terminal_width;
+use crossterm::cursor::{Hide, Show};
+use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Least time between two frames, which caps redraws at about 60 a second.
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// A key press decoded from terminal input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+    Backspace,
+    /// Ctrl-C, which doesn't raise a signal in raw mode.
+    Interrupt,
+    Char(char),
+    /// A mouse button pressed at a 0-based screen column and row.
+    Click(Button, u16, u16),
+    /// The mouse moved onto a 0-based screen column and row.
+    Hover(u16, u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Button {
+    Left,
+    Middle,
+    Right,
+}
+
+/// The key or mouse action of a terminal event. Releases, the wheel and
+/// resizes are dropped, and so are the key releases Windows reports.
+fn key_of(event: Event) -> Option<Key> {
+    match event {
+        Event::Key(key) if key.kind != KeyEventKind::Release => Some(match key.code {
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Enter => Key::Enter,
+            KeyCode::Esc => Key::Escape,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Key::Interrupt,
+            KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => Key::Char(c),
+            _ => return None,
+        }),
+        Event::Mouse(mouse) => match mouse.kind {
+            MouseEventKind::Down(button) => Some(Key::Click(match button {
+                MouseButton::Left => Button::Left,
+                MouseButton::Middle => Button::Middle,
+                MouseButton::Right => Button::Right,
+            }, mouse.column, mouse.row)),
+            // motion, with or without a button held
+            MouseEventKind::Moved | MouseEventKind::Drag(_) => Some(Key::Hover(mouse.column, mouse.row)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The highlighted cell in full-screen mode, `x` being the row like on `Field`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Cursor {
+    pub x: u16,
+    pub y: u16,
+    /// Whether the digits act on the cells around it, see `numpad`.
+    pub numpad: bool,
+}
+
+impl Cursor {
+    /// Moves the cursor for an arrow key, staying on a board with `rows`
+    /// rows and `cols` columns. Returns `false` for keys that don't move it.
+    pub fn apply(&mut self, key: Key, rows: u16, cols: u16) -> bool {
+        match key {
+            Key::Up => self.x = self.x.saturating_sub(1),
+            Key::Down => self.x = (self.x + 1).min(rows.saturating_sub(1)),
+            Key::Left => self.y = self.y.saturating_sub(1),
+            Key::Right => self.y = (self.y + 1).min(cols.saturating_sub(1)),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Whether stdin has input waiting to be read, without blocking, for the
+/// line mode. Full-screen mode asks `RawTerminal::input_pending`.
+pub fn input_pending() -> bool {
+    #[cfg(unix)]
+    {
+        let mut stdin = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut stdin, 1, 0) > 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Catches Ctrl-C, which ends the program otherwise, until it is dropped.
+/// For the line mode, where the terminal sends it as a signal; full-screen
+/// mode reads it as `Key::Interrupt`.
+pub struct Interrupts {
+    #[cfg(unix)]
+    previous: libc::sighandler_t,
+}
+
+impl Interrupts {
+    pub fn catch() -> Self {
+        INTERRUPTED.store(false, Ordering::Relaxed);
+        #[cfg(unix)]
+        {
+            extern "C" fn interrupt(_: libc::c_int) {
+                INTERRUPTED.store(true, Ordering::Relaxed);
+            }
+            let handler = interrupt as extern "C" fn(libc::c_int);
+            Self { previous: unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) } }
+        }
+        #[cfg(not(unix))]
+        {
+            Self {}
+        }
+    }
+
+    /// Whether Ctrl-C was pressed since `catch`.
+    pub fn caught(&self) -> bool {
+        INTERRUPTED.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Interrupts {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::signal(libc::SIGINT, self.previous);
+        }
+    }
+}
+
+/// The output that turns a screen showing `previous` into one showing
+/// `next`, rewriting only the lines that changed.
+pub fn redraw(previous: &[String], next: &[String]) -> String {
+    let mut out = String::new();
+    for (row, line) in next.iter().enumerate() {
+        if previous.get(row) != Some(line) {
+            out.push_str(&format!("\x1b[{};1H{}\x1b[K", row + 1, line));
+        }
+    }
+    if next.len() < previous.len() {
+        out.push_str(&format!("\x1b[{};1H\x1b[J", next.len() + 1));
+    }
+    out
+}
+
+/// Switches the terminal to raw mode on the alternate screen with mouse
+/// reporting and restores it when dropped, so a panic doesn't leave the
+/// terminal broken.
+pub struct RawTerminal {
+    /// The lines on the screen and the terminal width they were drawn for.
+    screen: Vec<String>,
+    width: usize,
+    last_frame: Option<Instant>,
+    /// Times of the frames in the last second and the lines the last frame
+    /// rewrote, kept while the fps overlay is shown.
+    frames: Option<(VecDeque<Instant>, usize)>,
+}
+
+impl RawTerminal {
+    pub fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        if let Err(e) = execute!(io::stdout(), EnterAlternateScreen, Hide, EnableMouseCapture) {
+            let _ = terminal::disable_raw_mode();
+            return Err(e);
+        }
+        Ok(Self {
+            screen: vec![],
+            width: 0,
+            last_frame: None,
+            frames: None,
+        })
+    }
+
+    /// Shows the frames drawn in the last second and the lines the last
+    /// frame rewrote below every frame.
+    pub fn with_fps_overlay(mut self) -> Self {
+        self.frames = Some((VecDeque::new(), 0));
+        self
+    }
+
+    /// Whether a key or mouse action is waiting to be read.
+    pub fn input_pending(&self) -> bool {
+        event::poll(Duration::ZERO).unwrap_or(false)
+    }
+
+    /// Blocks until the terminal reports something and returns the keys
+    /// among it and whatever else is waiting, none for a resize.
+    pub fn read_keys(&self) -> io::Result<Vec<Key>> {
+        let mut keys: Vec<Key> = key_of(event::read()?).into_iter().collect();
+        while event::poll(Duration::ZERO)? {
+            keys.extend(key_of(event::read()?));
+        }
+        Ok(keys)
+    }
+
+    /// Like `read_keys`, but gives up after `timeout` without a key.
+    pub fn poll_keys(&self, timeout: Duration) -> io::Result<Vec<Key>> {
+        if !event::poll(timeout)? {
+            return Ok(vec![]);
+        }
+        self.read_keys()
+    }
+
+    /// Draws `lines` over the previous frame, rewriting the lines that
+    /// changed. Waits if the previous frame is less than `FRAME_INTERVAL`
+    /// ago.
+    pub fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        if let Some(since) = self.last_frame.map(|at| at.elapsed()) {
+            if since < FRAME_INTERVAL {
+                thread::sleep(FRAME_INTERVAL - since);
+            }
+        }
+        let now = Instant::now();
+        let mut lines = lines.to_vec();
+        if let Some((times, rewritten)) = self.frames.as_mut() {
+            times.retain(|&at| now.duration_since(at) < Duration::from_secs(1));
+            times.push_back(now);
+            lines.push(format!("{} fps, {} lines redrawn", times.len(), rewritten));
+        }
+        // wrapped lines of a resized terminal are all over the place
+        let width = terminal_width();
+        if width != self.width {
+            self.width = width;
+            self.screen = vec![];
+            print!("\x1b[2J");
+        }
+        let screen = &self.screen;
+        if let Some((_, rewritten)) = self.frames.as_mut() {
+            *rewritten = lines.iter().enumerate().filter(|&(row, line)| screen.get(row) != Some(line)).count();
+        }
+        let mut out = io::stdout();
+        write!(out, "{}", redraw(&self.screen, &lines))?;
+        self.screen = lines;
+        self.last_frame = Some(now);
+        out.flush()
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), DisableMouseCapture, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tui::{key_of, redraw, Button, Cursor, Key::*};
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+        Event::Key(KeyEvent::new(code, modifiers))
+    }
+
+    fn mouse(kind: MouseEventKind, column: u16, row: u16) -> Event {
+        Event::Mouse(MouseEvent { kind, column, row, modifiers: KeyModifiers::NONE })
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(Some(Up), key_of(key(KeyCode::Up, KeyModifiers::NONE)));
+        assert_eq!(Some(Char('F')), key_of(key(KeyCode::Char('F'), KeyModifiers::SHIFT)));
+        assert_eq!(Some(Interrupt), key_of(key(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+        assert_eq!(Some(Backspace), key_of(key(KeyCode::Backspace, KeyModifiers::NONE)));
+        assert_eq!(None, key_of(key(KeyCode::Char('x'), KeyModifiers::ALT)));
+        let release = KeyEvent::new_with_kind(KeyCode::Enter, KeyModifiers::NONE, KeyEventKind::Release);
+        assert_eq!(None, key_of(Event::Key(release)));
+        assert_eq!(None, key_of(Event::Resize(80, 24)));
+    }
+
+    #[test]
+    fn mouse_actions() {
+        assert_eq!(Some(Click(Button::Left, 4, 0)), key_of(mouse(MouseEventKind::Down(MouseButton::Left), 4, 0)));
+        assert_eq!(Some(Click(Button::Middle, 9, 9)), key_of(mouse(MouseEventKind::Down(MouseButton::Middle), 9, 9)));
+        assert_eq!(None, key_of(mouse(MouseEventKind::Up(MouseButton::Left), 4, 0)));
+        assert_eq!(None, key_of(mouse(MouseEventKind::ScrollUp, 0, 0)));
+        // motion without a button, then dragging with the left one
+        assert_eq!(Some(Hover(2, 6)), key_of(mouse(MouseEventKind::Moved, 2, 6)));
+        assert_eq!(Some(Hover(3, 6)), key_of(mouse(MouseEventKind::Drag(MouseButton::Left), 3, 6)));
+    }
+
+    #[test]
+    fn cursor() {
+        let mut cursor = Cursor::default();
+        assert!(cursor.apply(Up, 3, 4));
+        assert_eq!(Cursor { x: 0, y: 0, numpad: false }, cursor);
+        for _ in 0..5 {
+            cursor.apply(Right, 3, 4);
+            cursor.apply(Down, 3, 4);
+        }
+        assert_eq!(Cursor { x: 2, y: 3, numpad: false }, cursor);
+        assert!(!cursor.apply(Char('f'), 3, 4));
+    }
+
+    #[test]
+    fn changed_lines() {
+        let lines = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+        assert_eq!("\x1b[1;1Ha\x1b[K\x1b[2;1Hb\x1b[K", redraw(&[], &lines(&["a", "b"])));
+        assert_eq!("\x1b[2;1Hc\x1b[K", redraw(&lines(&["a", "b"]), &lines(&["a", "c"])));
+        assert_eq!("", redraw(&lines(&["a", "b"]), &lines(&["a", "b"])));
+        assert_eq!("\x1b[2;1H\x1b[J", redraw(&lines(&["a", "b", "c"]), &lines(&["a"])));
+    }
+}
@@ -0,0 +1,172 @@
+//! The engine for a frontend in the browser. Built with
+//!
+//! ```text
+//! cargo rustc --lib --crate-type cdylib --target wasm32-unknown-unknown \
+//!     --no-default-features --features wasm
+//! ```
+//!
+//! the module exports plain functions that JavaScript can call without any
+//! glue:
+//!
+//! ```text
+//! const game = exports.minesweep_new(9, 9, 10, seed);
+//! exports.minesweep_open(game, 4, 4);      // 0 on, 1 lost, 2 won
+//! exports.minesweep_cell(game, 0, 0);      // what the cell shows
+//! exports.minesweep_free(game);
+//! ```
+//!
+//! Rows and columns are 0-based. Every function but `minesweep_new` takes
+//! the pointer it returned and must not be called after `minesweep_free`.
+
+use crate::Difficulty;
+use crate::game::{Game, State};
+
+/// `cell` of a closed cell.
+pub const CLOSED: i32 = -1;
+/// `cell` of a flagged cell.
+pub const FLAGGED: i32 = -2;
+/// `cell` of a hole in the board.
+pub const HOLE: i32 = -3;
+/// `cell` of an opened mine, and of every mine once the game is lost.
+pub const MINE: i32 = -4;
+/// `cell` of a cell outside the board.
+pub const OUTSIDE: i32 = -5;
+
+/// Plays `action` like `Game::play`. A move outside the board changes
+/// nothing, so all there is to answer is where the game stands.
+fn play(game: &mut Game, action: &str, row: u32, col: u32) -> i32 {
+    let (row, col) = (row.min(u16::MAX as u32) as u16, col.min(u16::MAX as u32) as u16);
+    game.play(action, row, col).unwrap_or(game.state) as i32
+}
+
+/// What the cell shows: the number of neighbouring mines once opened, or
+/// `CLOSED`, `FLAGGED`, `HOLE`, `MINE` or `OUTSIDE`.
+pub fn cell(game: &Game, row: u16, col: u16) -> i32 {
+    match game.field.cell(row, col) {
+        Err(_) => OUTSIDE,
+        Ok(cell) if cell.is_hole() => HOLE,
+        Ok(cell) if cell.is_mine() && (cell.opened || game.state == State::Lost) => MINE,
+        Ok(cell) if cell.is_flagged() => FLAGGED,
+        Ok(cell) if cell.opened => game.field.numbers[row as usize][col as usize] as i32,
+        Ok(_) => CLOSED,
+    }
+}
+
+/// Starts a game on a board made from `seed`, since there is no randomness
+/// to draw from in the browser without further dependencies. Returns null
+/// if the board can't be made, like with more mines than cells.
+#[no_mangle]
+pub extern "C" fn minesweep_new(width: u32, height: u32, mines: u32, seed: u32) -> *mut Game {
+    let fits = |n: u32| n <= u16::MAX as u32;
+    if !fits(width) || !fits(height) || !fits(mines) {
+        return std::ptr::null_mut();
+    }
+    match Game::new(Difficulty::Custom(width as u16, height as u16, mines as u16), seed as u64) {
+        Ok(game) => Box::into_raw(Box::new(game)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `game` has to come from `minesweep_new` and can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn minesweep_free(game: *mut Game) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+/// # Safety
+///
+/// `game` has to come from `minesweep_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn minesweep_open(game: *mut Game, row: u32, col: u32) -> i32 {
+    play(&mut *game, "open", row, col)
+}
+
+/// # Safety
+///
+/// `game` has to come from `minesweep_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn minesweep_flag(game: *mut Game, row: u32, col: u32) -> i32 {
+    play(&mut *game, "flag", row, col)
+}
+
+/// # Safety
+///
+/// `game` has to come from `minesweep_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn minesweep_chord(game: *mut Game, row: u32, col: u32) -> i32 {
+    play(&mut *game, "chord", row, col)
+}
+
+/// # Safety
+///
+/// `game` has to come from `minesweep_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn minesweep_cell(game: *const Game, row: u32, col: u32) -> i32 {
+    cell(&*game, row.min(u16::MAX as u32) as u16, col.min(u16::MAX as u32) as u16)
+}
+
+/// # Safety
+///
+/// `game` has to come from `minesweep_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn minesweep_state(game: *const Game) -> i32 {
+    (*game).state as i32
+}
+
+/// # Safety
+///
+/// `game` has to come from `minesweep_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn minesweep_mines_left(game: *const Game) -> i32 {
+    (*game).field.mines_left() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Difficulty;
+    use crate::game::{Game, State};
+    use crate::wasm::{cell, minesweep_cell, minesweep_chord, minesweep_flag, minesweep_free, minesweep_mines_left, minesweep_new, minesweep_open, minesweep_state, CLOSED, FLAGGED, MINE, OUTSIDE};
+
+    #[test]
+    fn game() {
+        let game = Game::new(Difficulty::Beginner, 4).unwrap();
+        assert_eq!((CLOSED, OUTSIDE), (cell(&game, 0, 0), cell(&game, 9, 0)));
+        let game = Box::into_raw(Box::new(game));
+        unsafe {
+            assert_ne!(State::Lost as i32, minesweep_open(game, 4, 4));
+            assert!(minesweep_cell(game, 4, 4) >= 0);
+            let closed = (0..81).map(|i| (i / 9, i % 9)).find(|&(x, y)| minesweep_cell(game, x, y) == CLOSED).unwrap();
+            minesweep_flag(game, closed.0, closed.1);
+            assert_eq!(FLAGGED, minesweep_cell(game, closed.0, closed.1));
+            let mine = (0..81).map(|i| (i / 9, i % 9))
+                .find(|&(x, y)| (*game).field.cell(x as u16, y as u16).unwrap().is_mine() && minesweep_cell(game, x, y) == CLOSED)
+                .unwrap();
+            assert_eq!(State::Lost as i32, minesweep_open(game, mine.0, mine.1));
+            assert_eq!(MINE, minesweep_cell(game, mine.0, mine.1));
+            let opened = (*game).field.opened_count();
+            for (x, y) in (0..81).map(|i| (i / 9, i % 9)) {
+                assert_eq!(State::Lost as i32, minesweep_open(game, x, y));
+                assert_eq!(State::Lost as i32, minesweep_chord(game, x, y));
+            }
+            assert_eq!(opened, (*game).field.opened_count());
+            minesweep_free(game);
+        }
+    }
+
+    #[test]
+    fn exported() {
+        assert!(minesweep_new(3, 3, 10, 0).is_null());
+        let game = minesweep_new(5, 5, 0, 1);
+        unsafe {
+            assert_eq!(0, minesweep_mines_left(game));
+            assert_eq!(State::Playing as i32, minesweep_open(game, 9, 9));
+            assert_eq!(State::Won as i32, minesweep_open(game, 2, 2));
+            assert_eq!((0, 2), (minesweep_cell(game, 0, 0), minesweep_state(game)));
+            minesweep_free(game);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::tournament::{Request, Response};
+use std::io::{self, Read, Write};
+
+/// Largest message taken from a client, its fragments together.
+const MAX_MESSAGE: usize = 64 << 10;
+/// Appended to the key of a client before hashing it, see RFC 6455.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// A message read from a client.
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Text(String),
+    /// Answered with a pong carrying the same bytes.
+    Ping(Vec<u8>),
+    /// The client closed the connection, or went away without saying so.
+    Close,
+}
+
+/// Whether `request` asks to switch to the WebSocket protocol.
+pub fn is_upgrade(request: &Request) -> bool {
+    request.method == "GET" && request.header("upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+/// The head that switches the connection of `request` to the WebSocket
+/// protocol, or the error to answer with instead.
+pub fn handshake(request: &Request) -> Result<String, Response> {
+    if !is_upgrade(request) {
+        return Err(Response::error(400, "expected a WebSocket upgrade"));
+    }
+    if request.header("sec-websocket-version") != Some("13") {
+        return Err(Response::error(400, "only version 13 of the WebSocket protocol is spoken"));
+    }
+    let key = request.header("sec-websocket-key").ok_or_else(|| Response::error(400, "missing Sec-WebSocket-Key"))?;
+    Ok(format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key),
+    ))
+}
+
+/// The `Sec-WebSocket-Accept` answering the `Sec-WebSocket-Key` of a client.
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+/// Reads the messages of a client, joining fragments and turning down
+/// unmasked frames, binary messages and messages of more than `MAX_MESSAGE`
+/// bytes.
+pub struct MessageReader<R> {
+    reader: R,
+    /// The fragments of a text message read so far, kept over pings sent
+    /// in between.
+    text: Vec<u8>,
+}
+
+impl<R: Read> MessageReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, text: vec![] }
+    }
+
+    pub fn read(&mut self) -> io::Result<Message> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        loop {
+            let mut head = [0; 2];
+            match self.reader.read_exact(&mut head) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Message::Close),
+                result => result?,
+            }
+            let fin = head[0] & 0x80 != 0;
+            let opcode = head[0] & 0x0f;
+            if head[1] & 0x80 == 0 {
+                return Err(invalid("frames of clients have to be masked"));
+            }
+            let length = match head[1] & 0x7f {
+                126 => {
+                    let mut length = [0; 2];
+                    self.reader.read_exact(&mut length)?;
+                    u16::from_be_bytes(length) as u64
+                },
+                127 => {
+                    let mut length = [0; 8];
+                    self.reader.read_exact(&mut length)?;
+                    u64::from_be_bytes(length)
+                },
+                length => length as u64,
+            };
+            if length > (MAX_MESSAGE - self.text.len()) as u64 {
+                return Err(invalid("message too large"));
+            }
+            let mut mask = [0; 4];
+            self.reader.read_exact(&mut mask)?;
+            let mut payload = vec![0; length as usize];
+            self.reader.read_exact(&mut payload)?;
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+            match opcode {
+                0x8 => return Ok(Message::Close),
+                0x9 => return Ok(Message::Ping(payload)),
+                0xa => continue,
+                0x1 | 0x0 => {
+                    self.text.extend_from_slice(&payload);
+                    if fin {
+                        let text = std::mem::take(&mut self.text);
+                        return String::from_utf8(text).map(Message::Text).map_err(|_| invalid("message isn't UTF-8"));
+                    }
+                },
+                _ => return Err(invalid("only text messages are taken")),
+            }
+        }
+    }
+}
+
+/// Sends `text` as a single unmasked frame, as servers do.
+pub fn write_text<W: Write>(out: &mut W, text: &str) -> io::Result<()> {
+    write_frame(out, 0x1, text.as_bytes())
+}
+
+pub fn write_pong<W: Write>(out: &mut W, payload: &[u8]) -> io::Result<()> {
+    write_frame(out, 0xa, payload)
+}
+
+pub fn write_close<W: Write>(out: &mut W) -> io::Result<()> {
+    write_frame(out, 0x8, &[])
+}
+
+fn write_frame<W: Write>(out: &mut W, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        },
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        },
+    }
+    frame.extend_from_slice(payload);
+    out.write_all(&frame)?;
+    out.flush()
+}
+
+/// The SHA-1 digest of `data`, only used for the handshake.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(LETTERS[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tournament::Request;
+    use crate::websocket::{accept_key, handshake, write_text, Message, MessageReader};
+
+    /// A frame as a client sends it, masked.
+    fn masked(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frame = vec![first, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+        frame
+    }
+
+    #[test]
+    fn handshakes() {
+        // the example of RFC 6455
+        assert_eq!("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=", accept_key("dGhlIHNhbXBsZSBub25jZQ=="));
+        let upgrade = "GET /lobby/socket HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+        let head = handshake(&Request::read(&mut upgrade.as_bytes()).unwrap()).unwrap();
+        assert!(head.starts_with("HTTP/1.1 101 "));
+        let plain = Request::read(&mut "GET /lobby/socket HTTP/1.1\r\n\r\n".as_bytes()).unwrap();
+        assert_eq!(400, handshake(&plain).unwrap_err().status);
+    }
+
+    #[test]
+    fn messages() {
+        let mut frames = masked(0x01, b"Hel");
+        frames.extend(masked(0x89, b"hi"));
+        frames.extend(masked(0x80, b"lo"));
+        frames.extend(masked(0x88, b""));
+        let mut reader = MessageReader::new(&frames[..]);
+        assert_eq!(Message::Ping(b"hi".to_vec()), reader.read().unwrap());
+        assert_eq!(Message::Text("Hello".into()), reader.read().unwrap());
+        assert_eq!(Message::Close, reader.read().unwrap());
+        assert_eq!(Message::Close, reader.read().unwrap());
+
+        assert!(MessageReader::new(&[0x81, 0x02, b'h', b'i'][..]).read().is_err());
+        assert!(MessageReader::new(&masked(0x82, b"hi")[..]).read().is_err());
+        let mut large = vec![0x81, 0xff];
+        large.extend_from_slice(&(1u64 << 40).to_be_bytes());
+        assert!(MessageReader::new(&large[..]).read().is_err());
+
+        let mut out = vec![];
+        write_text(&mut out, "hi").unwrap();
+        assert_eq!(vec![0x81, 0x02, b'h', b'i'], out);
+    }
+}